@@ -0,0 +1,208 @@
+//! Cost accounting
+//!
+//! Prices the token usage of every request with the configured per-model
+//! prices, records it per chat and enforces an optional daily budget shared by
+//! all chats, so a public bot cannot run up an unbounded bill.
+
+use crate::i18n::Messages;
+use chrono::{DateTime, Utc};
+use rustclaw_persistence::{PersistenceService, UsageTotals};
+use rustclaw_types::{ModelPrice, TokenUsage};
+use std::collections::HashMap;
+use tracing::{debug, error, warn};
+
+/// Per-model prices and the daily spending cap
+#[derive(Debug, Default)]
+pub(crate) struct CostTracker {
+    prices: HashMap<String, ModelPrice>,
+    /// Maximum spend in USD per UTC day across all chats (`0` = no cap)
+    daily_budget: f64,
+}
+
+impl CostTracker {
+    pub(crate) fn new(prices: HashMap<String, ModelPrice>, daily_budget: f64) -> Self {
+        Self {
+            prices,
+            daily_budget,
+        }
+    }
+
+    /// Cost in USD of `usage` on `model`; models without a price are free
+    pub(crate) fn cost(&self, model: &str, usage: &TokenUsage) -> f64 {
+        match self.prices.get(model) {
+            Some(price) => price.cost(usage),
+            None => {
+                debug!("No price configured for model '{}'", model);
+                0.0
+            }
+        }
+    }
+
+    /// Whether today's spend has reached the daily budget
+    pub(crate) async fn budget_reached(&self, persistence: &PersistenceService) -> bool {
+        if self.daily_budget <= 0.0 {
+            return false;
+        }
+
+        match persistence
+            .get_usage_totals(None, Some(start_of_day()))
+            .await
+        {
+            Ok(today) if today.cost >= self.daily_budget => {
+                warn!(
+                    "Daily budget of ${:.2} reached (spent ${:.4})",
+                    self.daily_budget, today.cost
+                );
+                true
+            }
+            Ok(_) => false,
+            Err(e) => {
+                error!("Failed to load today's usage: {}", e);
+                false
+            }
+        }
+    }
+
+    /// Price and store the usage of a completed request
+    pub(crate) async fn record(
+        &self,
+        persistence: &PersistenceService,
+        chat_id: i64,
+        model: &str,
+        usage: &TokenUsage,
+    ) {
+        let cost = self.cost(model, usage);
+        debug!(
+            "Chat {} used {} prompt + {} completion tokens on {} (${:.6})",
+            chat_id, usage.prompt_tokens, usage.completion_tokens, model, cost
+        );
+        if let Err(e) = persistence.record_usage(chat_id, model, usage, cost).await {
+            error!("Failed to record usage: {}", e);
+        }
+    }
+
+    /// Usage report for `/stats`
+    pub(crate) async fn stats(
+        &self,
+        persistence: &PersistenceService,
+        chat_id: i64,
+        messages: &Messages,
+    ) -> String {
+        let totals = async {
+            Ok::<_, anyhow::Error>((
+                persistence.get_usage_totals(Some(chat_id), None).await?,
+                persistence
+                    .get_usage_totals(None, Some(start_of_day()))
+                    .await?,
+                persistence.get_usage_totals(None, None).await?,
+            ))
+        };
+        let (chat, today, all) = match totals.await {
+            Ok(totals) => totals,
+            Err(e) => {
+                error!("Failed to load usage: {}", e);
+                return format!("{} {}", messages.usage_not_loaded, e);
+            }
+        };
+
+        let mut report = format!(
+            "{}\n\n{} {}\n{} {}\n{} {}",
+            messages.usage_report,
+            messages.usage_chat,
+            format_totals(&chat, messages),
+            messages.usage_today,
+            format_totals(&today, messages),
+            messages.usage_total,
+            format_totals(&all, messages)
+        );
+        if self.daily_budget > 0.0 {
+            report.push_str(&format!(
+                "\n\n{} ${:.2} (${:.2} {})",
+                messages.daily_budget,
+                self.daily_budget,
+                (self.daily_budget - today.cost).max(0.0),
+                messages.budget_left
+            ));
+        }
+        report
+    }
+}
+
+/// Midnight UTC of the current day
+fn start_of_day() -> DateTime<Utc> {
+    Utc::now()
+        .date_naive()
+        .and_hms_opt(0, 0, 0)
+        .map_or_else(Utc::now, |midnight| midnight.and_utc())
+}
+
+fn format_totals(totals: &UsageTotals, messages: &Messages) -> String {
+    format!(
+        "{} {}, {} {} / {} {}, ${:.4}",
+        totals.requests,
+        messages.requests,
+        totals.prompt_tokens,
+        messages.tokens_in,
+        totals.completion_tokens,
+        messages.tokens_out,
+        totals.cost
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cost() {
+        let mut prices = HashMap::new();
+        prices.insert(
+            "gpt-4o-mini".to_string(),
+            ModelPrice {
+                input: 0.15,
+                output: 0.60,
+            },
+        );
+        let tracker = CostTracker::new(prices, 1.0);
+
+        let usage = TokenUsage::new(1_000_000, 500_000);
+        assert!((tracker.cost("gpt-4o-mini", &usage) - 0.45).abs() < 1e-9);
+        assert_eq!(tracker.cost("unpriced", &usage), 0.0);
+    }
+
+    #[tokio::test]
+    async fn test_budget_reached() {
+        let path =
+            std::env::temp_dir().join(format!("rustclaw-budget-{}.db", uuid::Uuid::new_v4()));
+        let persistence = PersistenceService::new(path.to_str().unwrap())
+            .await
+            .unwrap();
+        let mut prices = HashMap::new();
+        prices.insert(
+            "gpt-4o".to_string(),
+            ModelPrice {
+                input: 2.5,
+                output: 10.0,
+            },
+        );
+        let tracker = CostTracker::new(prices, 1.0);
+        let usage = TokenUsage::new(200_000, 20_000);
+
+        assert!(!tracker.budget_reached(&persistence).await);
+        tracker.record(&persistence, 1, "gpt-4o", &usage).await;
+        assert!(!tracker.budget_reached(&persistence).await);
+        // The budget is shared, so another chat's spend counts too
+        tracker.record(&persistence, 2, "gpt-4o", &usage).await;
+        assert!(tracker.budget_reached(&persistence).await);
+
+        // Without a budget nothing is refused
+        assert!(
+            !CostTracker::new(HashMap::new(), 0.0)
+                .budget_reached(&persistence)
+                .await
+        );
+
+        drop(persistence);
+        let _ = std::fs::remove_file(&path);
+    }
+}
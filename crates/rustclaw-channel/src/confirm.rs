@@ -0,0 +1,199 @@
+//! Confirmation of tool calls
+//!
+//! Built-in tools refuse destructive or sensitive operations until the user
+//! confirms them. The agentic loop pauses on such refusals; the paused calls
+//! are kept here per chat while the user is asked with inline Yes/No buttons,
+//! and handed back to resume the loop once the user who sent the request
+//! answers.
+
+use crate::i18n::Messages;
+use rustclaw_provider::PendingConfirmation;
+use std::collections::HashMap;
+use std::sync::{Mutex, PoisonError};
+use teloxide::types::{InlineKeyboardButton, InlineKeyboardMarkup};
+
+/// Callback data of the "Yes" button
+pub(crate) const CONFIRM_YES: &str = "confirm:yes";
+/// Callback data of the "No" button
+pub(crate) const CONFIRM_NO: &str = "confirm:no";
+
+/// The user's reply recorded when they press "Yes"
+pub(crate) const CONFIRMED_PROMPT: &str = "Yes, go ahead.";
+
+/// Longest tool argument text shown in a confirmation question
+const MAX_ARGS_PREVIEW: usize = 500;
+
+/// Paused tool calls and the user allowed to confirm them
+#[derive(Debug)]
+struct Pending {
+    user_id: i64,
+    confirmations: Vec<PendingConfirmation>,
+}
+
+/// Paused tool calls awaiting confirmation, one round per chat
+#[derive(Debug, Default)]
+pub(crate) struct Confirmations {
+    pending: Mutex<HashMap<i64, Pending>>,
+}
+
+impl Confirmations {
+    /// Keep `confirmations` until `user_id` answers, replacing any earlier
+    /// ones in the chat, and return the question to ask
+    pub(crate) fn ask(
+        &self,
+        chat_id: i64,
+        user_id: i64,
+        confirmations: Vec<PendingConfirmation>,
+        messages: &Messages,
+    ) -> String {
+        let question = question(&confirmations, messages);
+        self.pending
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(
+                chat_id,
+                Pending {
+                    user_id,
+                    confirmations,
+                },
+            );
+        question
+    }
+
+    /// Whether calls are pending in a chat that `user_id` may confirm
+    pub(crate) fn awaits(&self, chat_id: i64, user_id: i64) -> bool {
+        self.pending
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(&chat_id)
+            .is_some_and(|p| p.user_id == user_id)
+    }
+
+    /// Take the calls pending in a chat if `user_id` may answer them
+    pub(crate) fn take(&self, chat_id: i64, user_id: i64) -> Option<Vec<PendingConfirmation>> {
+        let mut pending = self.pending.lock().unwrap_or_else(PoisonError::into_inner);
+        if pending.get(&chat_id)?.user_id != user_id {
+            return None;
+        }
+        pending.remove(&chat_id).map(|p| p.confirmations)
+    }
+
+    /// Drop the calls pending in a chat, if any
+    pub(crate) fn clear(&self, chat_id: i64) {
+        self.pending
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .remove(&chat_id);
+    }
+}
+
+/// Yes/No buttons attached to a confirmation question
+pub(crate) fn keyboard(messages: &Messages) -> InlineKeyboardMarkup {
+    InlineKeyboardMarkup::new([[
+        InlineKeyboardButton::callback(messages.confirm_yes, CONFIRM_YES),
+        InlineKeyboardButton::callback(messages.confirm_no, CONFIRM_NO),
+    ]])
+}
+
+/// Describe the paused calls for the user
+fn question(confirmations: &[PendingConfirmation], messages: &Messages) -> String {
+    let calls: Vec<String> = confirmations
+        .iter()
+        .map(|confirmation| describe(confirmation, messages))
+        .collect();
+    format!("{}\n\n{}", calls.join("\n\n"), messages.confirm_question)
+}
+
+/// Describe one paused call
+fn describe(confirmation: &PendingConfirmation, messages: &Messages) -> String {
+    let action = match confirmation.confirmation_type.as_str() {
+        "destructive" => messages.confirm_destructive,
+        "sensitive_file" => messages.confirm_sensitive_file,
+        "overwrite" => messages.confirm_overwrite,
+        "delete" => messages.confirm_delete,
+        _ => messages.confirm_other,
+    };
+
+    let function = &confirmation.call.function;
+    let args = if function.arguments.chars().count() > MAX_ARGS_PREVIEW {
+        let preview: String = function.arguments.chars().take(MAX_ARGS_PREVIEW).collect();
+        format!("{}...", preview)
+    } else {
+        function.arguments.clone()
+    };
+
+    format!("⚠️ {}\n\n{} {}", action, function.name, args)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::i18n::Language;
+    use rustclaw_types::{FunctionCall, ToolCall};
+
+    fn confirmation() -> Vec<PendingConfirmation> {
+        vec![delete("notes.txt")]
+    }
+
+    fn delete(path: &str) -> PendingConfirmation {
+        PendingConfirmation {
+            call: ToolCall {
+                id: "call_1_confirmed".to_string(),
+                call_type: "function".to_string(),
+                function: FunctionCall {
+                    name: "delete_file".to_string(),
+                    arguments: format!(r#"{{"path":"{}","confirm_delete":true}}"#, path),
+                },
+            },
+            confirmation_type: "delete".to_string(),
+            reason: format!("This will delete {}", path),
+        }
+    }
+
+    #[test]
+    fn test_question() {
+        let messages = Language::En.messages();
+        let text = question(&confirmation(), messages);
+        assert!(text.starts_with("⚠️ This will delete a file."));
+        assert!(text.contains("delete_file"));
+        assert!(text.contains("notes.txt"));
+        assert!(text.ends_with("Do you want to proceed?"));
+
+        // Every call paused in a round is asked about at once
+        let text = question(&[delete("a.txt"), delete("b.txt")], messages);
+        assert_eq!(text.matches("This will delete a file.").count(), 2);
+        assert!(text.contains("a.txt") && text.contains("b.txt"));
+
+        let text = question(&confirmation(), Language::De.messages());
+        assert!(text.starts_with("⚠️ Dies löscht eine Datei."));
+        assert!(text.ends_with("Möchtest du fortfahren?"));
+    }
+
+    #[test]
+    fn test_only_requester_confirms() {
+        let confirmations = Confirmations::default();
+        confirmations.ask(1, 42, confirmation(), Language::En.messages());
+
+        // Other users in a group cannot answer someone else's confirmation
+        assert!(!confirmations.awaits(1, 7));
+        assert!(confirmations.take(1, 7).is_none());
+        assert!(confirmations.take(2, 42).is_none());
+
+        assert!(confirmations.awaits(1, 42));
+        let taken = confirmations.take(1, 42).unwrap();
+        assert_eq!(taken[0].call.function.name, "delete_file");
+        assert!(!confirmations.awaits(1, 42));
+        assert!(confirmations.take(1, 42).is_none());
+    }
+
+    #[test]
+    fn test_clear() {
+        let confirmations = Confirmations::default();
+        confirmations.ask(1, 42, confirmation(), Language::En.messages());
+        confirmations.ask(2, 42, confirmation(), Language::En.messages());
+
+        confirmations.clear(1);
+        assert!(confirmations.take(1, 42).is_none());
+        assert!(confirmations.take(2, 42).is_some());
+    }
+}
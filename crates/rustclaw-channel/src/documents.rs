@@ -0,0 +1,206 @@
+//! Received documents
+//!
+//! Builds the prompt for a file the user sent. The content of small
+//! text-like files (source code, logs, CSV, JSON, ...) is put into the prompt
+//! directly; other files are only referenced by the path they were saved to,
+//! for the model to examine with `read_file` or `bash`. Files whose name
+//! matches the sensitive-file patterns are never inlined.
+
+use std::path::Path;
+
+/// Most bytes of a text file put into the prompt
+const MAX_INLINE_BYTES: usize = 32 * 1024;
+
+/// MIME types outside `text/*` that hold text
+const TEXT_MIME_TYPES: &[&str] = &[
+    "application/json",
+    "application/xml",
+    "application/yaml",
+    "application/x-yaml",
+    "application/toml",
+    "application/javascript",
+    "application/x-sh",
+    "application/sql",
+];
+
+/// Extensions of text files sent with a generic MIME type
+const TEXT_EXTENSIONS: &[&str] = &[
+    "txt", "md", "csv", "tsv", "log", "json", "jsonl", "yaml", "yml", "toml", "xml", "ini", "cfg",
+    "conf", "html", "css", "js", "ts", "py", "rs", "go", "java", "c", "h", "cpp", "sh", "sql",
+];
+
+/// What the model gets to see of a received file
+#[derive(Debug, PartialEq)]
+pub(crate) enum DocumentText {
+    /// The file's text, cut at [`MAX_INLINE_BYTES`] if `truncated`
+    Inline { content: String, truncated: bool },
+    /// Only the path: the file is binary, unreadable or not UTF-8
+    Path,
+    /// Only the path: the file's name suggests it holds secrets
+    Sensitive,
+}
+
+impl DocumentText {
+    /// Read the text of the file saved at `path`, if it may be inlined
+    pub(crate) async fn read(path: &Path, file_name: &str, mime_type: Option<&str>) -> Self {
+        if crate::is_sensitive_path(file_name) {
+            return DocumentText::Sensitive;
+        }
+        if !is_text_like(file_name, mime_type) {
+            return DocumentText::Path;
+        }
+        match tokio::fs::read(path).await {
+            Ok(bytes) => Self::from_bytes(&bytes),
+            Err(_) => DocumentText::Path,
+        }
+    }
+
+    /// Text of the first [`MAX_INLINE_BYTES`] of `bytes`
+    fn from_bytes(bytes: &[u8]) -> Self {
+        let truncated = bytes.len() > MAX_INLINE_BYTES;
+        let head = &bytes[..bytes.len().min(MAX_INLINE_BYTES)];
+        let content = match std::str::from_utf8(head) {
+            Ok(text) => text,
+            // The cut may split the last character
+            Err(e) if truncated && e.error_len().is_none() => {
+                std::str::from_utf8(&head[..e.valid_up_to()]).unwrap_or_default()
+            }
+            Err(_) => return DocumentText::Path,
+        };
+        DocumentText::Inline {
+            content: content.to_string(),
+            truncated,
+        }
+    }
+}
+
+/// Whether a file is text, judged by its MIME type or extension
+fn is_text_like(file_name: &str, mime_type: Option<&str>) -> bool {
+    if let Some(mime) = mime_type {
+        let mime = mime.split(';').next().unwrap_or(mime).trim();
+        if mime.starts_with("text/") || TEXT_MIME_TYPES.contains(&mime) {
+            return true;
+        }
+    }
+    Path::new(file_name)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| TEXT_EXTENSIONS.contains(&ext.to_lowercase().as_str()))
+}
+
+/// Prompt telling the model about a file the user sent
+pub(crate) fn document_prompt(
+    file_name: &str,
+    mime_type: Option<&str>,
+    size: u32,
+    caption: Option<&str>,
+    path: &Path,
+    text: &DocumentText,
+) -> String {
+    let mut prompt = format!(
+        "The user sent a file.\n\
+         Filename: {}\n\
+         MIME type: {}\n\
+         Size: {} bytes\n\
+         Caption: {}\n\
+         Saved at: {}\n\n",
+        file_name,
+        mime_type.unwrap_or("unknown"),
+        size,
+        caption.unwrap_or("[No caption]"),
+        path.display()
+    );
+
+    match text {
+        DocumentText::Inline { content, truncated } => {
+            if *truncated {
+                prompt.push_str(&format!(
+                    "First {} bytes of the file (use read_file for the rest):\n",
+                    MAX_INLINE_BYTES
+                ));
+            } else {
+                prompt.push_str("Content of the file:\n");
+            }
+            prompt.push_str(&format!("```\n{}\n```\n\n", content));
+            prompt.push_str("Do what the caption asks, or ask the user what to do with it.");
+        }
+        DocumentText::Path => prompt.push_str(
+            "Use available tools (like read_file, bash) to examine the file if it's a text-based format. \
+             Ask the user what they want you to do with it.",
+        ),
+        DocumentText::Sensitive => prompt.push_str(
+            "The file name suggests it contains secrets (a key, credential or password), so its \
+             content was not shared. Do not read it unless the user confirms they want you to.",
+        ),
+    }
+
+    prompt
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_text_like() {
+        assert!(is_text_like("notes", Some("text/plain; charset=utf-8")));
+        assert!(is_text_like("data", Some("application/json")));
+        assert!(is_text_like("main.RS", Some("application/octet-stream")));
+        assert!(is_text_like("report.csv", None));
+        assert!(!is_text_like("photo.jpg", Some("image/jpeg")));
+        assert!(!is_text_like("archive.zip", None));
+    }
+
+    #[test]
+    fn test_from_bytes() {
+        assert_eq!(
+            DocumentText::from_bytes(b"hello"),
+            DocumentText::Inline {
+                content: "hello".to_string(),
+                truncated: false
+            }
+        );
+        assert_eq!(DocumentText::from_bytes(&[0xff, 0xfe]), DocumentText::Path);
+
+        // A multi-byte character split by the cut is dropped
+        let mut bytes = vec![b'a'; MAX_INLINE_BYTES - 1];
+        bytes.extend_from_slice("é".as_bytes());
+        match DocumentText::from_bytes(&bytes) {
+            DocumentText::Inline { content, truncated } => {
+                assert!(truncated);
+                assert_eq!(content.len(), MAX_INLINE_BYTES - 1);
+            }
+            other => panic!("Expected inline text, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_read() {
+        let dir = std::env::temp_dir().join(format!("rustclaw-doc-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("notes.md");
+        std::fs::write(&path, "# Notes").unwrap();
+
+        let text = DocumentText::read(&path, "notes.md", None).await;
+        assert_eq!(
+            text,
+            DocumentText::Inline {
+                content: "# Notes".to_string(),
+                truncated: false
+            }
+        );
+        let prompt = document_prompt("notes.md", None, 7, Some("Summarize"), &path, &text);
+        assert!(prompt.contains("```\n# Notes\n```"));
+        assert!(prompt.contains("Caption: Summarize"));
+
+        // Sensitive files are never inlined, whatever their type
+        let env = dir.join(".env");
+        std::fs::write(&env, "API_KEY=secret").unwrap();
+        let text = DocumentText::read(&env, ".env", Some("text/plain")).await;
+        assert_eq!(text, DocumentText::Sensitive);
+        let prompt = document_prompt(".env", None, 14, None, &env, &text);
+        assert!(!prompt.contains("API_KEY=secret"));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
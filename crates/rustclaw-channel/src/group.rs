@@ -0,0 +1,160 @@
+//! Group chat addressing
+//!
+//! In group chats the bot can be configured to only answer messages that are
+//! explicitly addressed to it, either by an `@mention` or a command prefix.
+//! Private chats are always answered.
+
+use serde::Deserialize;
+use std::ops::Range;
+use teloxide::types::{Message, MessageEntityKind, UserId};
+
+/// How the bot decides which group chat messages to answer
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GroupMode {
+    /// Answer every message (default)
+    #[default]
+    All,
+    /// Answer only when mentioned or when the message starts with the prefix
+    Mention,
+}
+
+/// Group chat filter shared with the message handlers
+#[derive(Debug, Clone, Default)]
+pub(crate) struct GroupFilter {
+    mode: GroupMode,
+    /// Optional prefix (e.g. `!ask`) that addresses the bot
+    prefix: Option<String>,
+    /// Bot username without the leading `@`
+    bot_username: Option<String>,
+    /// Bot user ID, for mentions of bots without a username
+    bot_id: Option<UserId>,
+}
+
+impl GroupFilter {
+    pub(crate) fn new(mode: GroupMode, prefix: Option<String>) -> Self {
+        Self {
+            mode,
+            prefix: prefix.filter(|p| !p.trim().is_empty()),
+            bot_username: None,
+            bot_id: None,
+        }
+    }
+
+    /// Set the bot identity used for mention detection
+    pub(crate) fn with_bot(mut self, username: Option<String>, id: UserId) -> Self {
+        self.bot_username = username;
+        self.bot_id = Some(id);
+        self
+    }
+
+    /// Get the prompt from a message's text (or caption), or `None` if the
+    /// bot should not answer it
+    pub(crate) fn prompt_for(&self, msg: &Message) -> Option<String> {
+        let (text, entities) = match msg.text() {
+            Some(text) => (text, msg.parse_entities()),
+            None => (msg.caption().unwrap_or(""), msg.parse_caption_entities()),
+        };
+
+        if self.mode == GroupMode::All || msg.chat.is_private() {
+            return Some(text.to_string());
+        }
+
+        let mentions: Vec<Range<usize>> = entities
+            .unwrap_or_default()
+            .iter()
+            .filter(|entity| match entity.kind() {
+                MessageEntityKind::Mention => self.is_bot_mention(entity.text()),
+                MessageEntityKind::TextMention { user } => Some(user.id) == self.bot_id,
+                _ => false,
+            })
+            .map(|entity| entity.range())
+            .collect();
+
+        self.strip_address(text, &mentions)
+    }
+
+    /// Check whether an `@name` mention refers to this bot
+    fn is_bot_mention(&self, mention: &str) -> bool {
+        match (mention.strip_prefix('@'), &self.bot_username) {
+            (Some(name), Some(username)) => name.eq_ignore_ascii_case(username),
+            _ => false,
+        }
+    }
+
+    /// Remove the prefix or bot mentions (given as byte ranges) from addressed text
+    fn strip_address(&self, text: &str, mentions: &[Range<usize>]) -> Option<String> {
+        if let Some(prefix) = &self.prefix {
+            if let Some(rest) = text.trim_start().strip_prefix(prefix.as_str()) {
+                // Require a word boundary so `!ask` doesn't match `!asking`
+                if rest.is_empty() || rest.starts_with(char::is_whitespace) {
+                    return Some(rest.trim().to_string());
+                }
+            }
+        }
+
+        if mentions.is_empty() {
+            return None;
+        }
+
+        let mut stripped = text.to_string();
+        let mut ranges = mentions.to_vec();
+        ranges.sort_by_key(|r| std::cmp::Reverse(r.start));
+        for range in ranges {
+            if stripped.get(range.clone()).is_some() {
+                stripped.replace_range(range, "");
+            }
+        }
+
+        Some(
+            stripped
+                .trim_start_matches(|c: char| c.is_whitespace() || c == ',' || c == ':')
+                .trim()
+                .to_string(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mention_filter() -> GroupFilter {
+        GroupFilter::new(GroupMode::Mention, Some("!ask".to_string()))
+            .with_bot(Some("rustclaw_bot".to_string()), UserId(42))
+    }
+
+    #[test]
+    fn test_prefix_addresses_bot() {
+        let filter = mention_filter();
+        assert_eq!(
+            filter.strip_address("!ask what time is it?", &[]),
+            Some("what time is it?".to_string())
+        );
+    }
+
+    #[test]
+    fn test_mention_is_stripped() {
+        let filter = mention_filter();
+        let mention = 0..13;
+        assert_eq!(
+            filter.strip_address("@RustClaw_Bot, what time is it?", &[mention]),
+            Some("what time is it?".to_string())
+        );
+    }
+
+    #[test]
+    fn test_unaddressed_message_ignored() {
+        let filter = mention_filter();
+        assert_eq!(filter.strip_address("just chatting", &[]), None);
+        assert_eq!(filter.strip_address("!asking around", &[]), None);
+    }
+
+    #[test]
+    fn test_is_bot_mention_case_insensitive() {
+        let filter = mention_filter();
+        assert!(filter.is_bot_mention("@RUSTCLAW_BOT"));
+        assert!(!filter.is_bot_mention("@someone_else"));
+        assert!(!filter.is_bot_mention("rustclaw_bot"));
+    }
+}
@@ -0,0 +1,422 @@
+//! Localized bot messages
+//!
+//! The bot's own replies (welcome text, busy and budget notices, error
+//! prefixes, ...) in the language set with `[telegram] language`. Answers of
+//! the model are not affected; it replies in the language the user writes in.
+
+use serde::Deserialize;
+
+/// Language of the bot's own messages
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Language {
+    /// English (default)
+    #[default]
+    En,
+    /// German
+    De,
+}
+
+impl Language {
+    /// The message catalog of this language
+    pub(crate) fn messages(self) -> &'static Messages {
+        match self {
+            Language::En => &EN,
+            Language::De => &DE,
+        }
+    }
+}
+
+/// Catalog of the bot's own messages in one language
+#[derive(Debug)]
+pub(crate) struct Messages {
+    /// Reply to `/start`
+    pub(crate) welcome: &'static str,
+    /// Reply to `/clear`, followed by the number of deleted messages
+    pub(crate) history_cleared: &'static str,
+    /// Prefix of error replies, followed by the error
+    pub(crate) error: &'static str,
+    /// Reply sent when too many requests are already being handled
+    pub(crate) busy: &'static str,
+    /// Reply sent instead of calling the model once the daily budget is spent
+    pub(crate) budget_reached: &'static str,
+    /// Note appended when the model's response was cut off by its output limit
+    pub(crate) truncated: &'static str,
+    /// Sent while a received photo is downloaded
+    pub(crate) processing_image: &'static str,
+    /// Reply to a photo when the model had nothing to say
+    pub(crate) image_processed: &'static str,
+    /// Reply to a document when the model had nothing to say
+    pub(crate) file_processed: &'static str,
+    /// Reply to `/retry` in a chat without messages
+    pub(crate) nothing_to_retry: &'static str,
+    /// Reply to `/retry` when the last message was not text
+    pub(crate) retry_text_only: &'static str,
+    /// Reply to `/retry` with arguments that are not a temperature
+    pub(crate) retry_usage: &'static str,
+    /// Reply to `/summarize` for a short conversation
+    pub(crate) too_short_to_summarize: &'static str,
+    /// Reply to `/memory` without remembered facts
+    pub(crate) nothing_remembered: &'static str,
+    /// Reply to `/forget` without remembered facts
+    pub(crate) nothing_to_forget: &'static str,
+    /// Reply sent when the user presses "No"
+    pub(crate) cancelled: &'static str,
+    /// Reply sent when a button is pressed for a confirmation that is gone
+    pub(crate) nothing_to_confirm: &'static str,
+    /// Status shown while tools run, followed by their names
+    pub(crate) running_tools: &'static str,
+    /// Reply when the provider rejected the API key
+    pub(crate) provider_auth_failed: &'static str,
+    /// Reply when the provider account has no quota left
+    pub(crate) provider_quota_exceeded: &'static str,
+    /// Reply when the provider rate-limited the request
+    pub(crate) provider_rate_limited: &'static str,
+    /// Reply when the provider could not be reached
+    pub(crate) provider_unavailable: &'static str,
+    /// Reply to an admin command sent by someone else
+    pub(crate) admins_only: &'static str,
+    /// Sent in place of a file Telegram refused, followed by its name
+    pub(crate) file_not_sent: &'static str,
+    /// Reply to `/help`
+    pub(crate) help: &'static str,
+    /// Reply to `/tools` when no tools are offered
+    pub(crate) no_tools: &'static str,
+    /// Heading of `/tools`, followed by the number of tools in parentheses
+    pub(crate) available_tools: &'static str,
+    /// Note closing `/tools`
+    pub(crate) sensitive_files_note: &'static str,
+    /// Label of the models or providers to choose from, followed by them
+    pub(crate) available: &'static str,
+    /// Marks the configured default model or provider
+    pub(crate) default_marker: &'static str,
+    /// Reply to `/model`, followed by the chat's model
+    pub(crate) current_model: &'static str,
+    /// Reply to `/model` with a model not allowed, followed by it
+    pub(crate) model_not_allowed: &'static str,
+    /// Reply to `/model` with a model, followed by it
+    pub(crate) model_set: &'static str,
+    /// Reply when the chat's model could not be saved, followed by the error
+    pub(crate) model_not_set: &'static str,
+    /// Reply to `/provider`, followed by the chat's provider
+    pub(crate) current_provider: &'static str,
+    /// Reply to `/provider` with an unknown provider, followed by it
+    pub(crate) provider_not_available: &'static str,
+    /// Reply to `/provider` with a provider, followed by it
+    pub(crate) provider_set: &'static str,
+    /// Joins a provider and its model
+    pub(crate) with_model: &'static str,
+    /// Reply when the chat's provider could not be saved, followed by the error
+    pub(crate) provider_not_set: &'static str,
+    /// Heading of `/stats`
+    pub(crate) usage_report: &'static str,
+    /// Label of the chat's usage in `/stats`
+    pub(crate) usage_chat: &'static str,
+    /// Label of today's usage in `/stats`
+    pub(crate) usage_today: &'static str,
+    /// Label of the total usage in `/stats`
+    pub(crate) usage_total: &'static str,
+    /// Unit of a number of requests
+    pub(crate) requests: &'static str,
+    /// Unit of a number of prompt tokens
+    pub(crate) tokens_in: &'static str,
+    /// Unit of a number of completion tokens
+    pub(crate) tokens_out: &'static str,
+    /// Label of the daily budget in `/stats`, followed by it
+    pub(crate) daily_budget: &'static str,
+    /// Follows the part of the daily budget not spent yet
+    pub(crate) budget_left: &'static str,
+    /// Reply when usage could not be loaded, followed by the error
+    pub(crate) usage_not_loaded: &'static str,
+    /// Reply to `/mcp` without connected servers
+    pub(crate) no_mcp_servers: &'static str,
+    /// Reply to `/mcp`, followed by the connected servers
+    pub(crate) mcp_servers: &'static str,
+    /// Closes the reply to `/mcp`
+    pub(crate) mcp_describe_hint: &'static str,
+    /// Reply to `/mcp describe` with an unknown server, followed by it
+    pub(crate) unknown_mcp_server: &'static str,
+    /// Label of the connected MCP servers, followed by them
+    pub(crate) connected: &'static str,
+    /// Reply to `/mcp` with unknown arguments
+    pub(crate) mcp_usage: &'static str,
+    /// Reply to `/summarize`, followed by the number of messages summarized
+    pub(crate) summarized: &'static str,
+    /// Follows the estimated number of tokens a summary saves
+    pub(crate) tokens_saved: &'static str,
+    /// Reply when summarizing failed, followed by the error
+    pub(crate) summary_failed: &'static str,
+    /// Heading of `/memory`, followed by the facts
+    pub(crate) remembered_facts: &'static str,
+    /// Reply when remembered facts could not be loaded, followed by the error
+    pub(crate) memory_not_loaded: &'static str,
+    /// Reply to `/forget` with a key, followed by it
+    pub(crate) forgot_key: &'static str,
+    /// Reply to `/forget`, followed by the number of facts forgotten
+    pub(crate) forgot_facts: &'static str,
+    /// Reply when forgetting failed, followed by the error
+    pub(crate) forget_failed: &'static str,
+    /// Reply to `/reload-skills`, followed by the number of skills
+    pub(crate) skills_reloaded: &'static str,
+    /// Label of the number of skills before reloading, followed by it
+    pub(crate) previously: &'static str,
+    /// Heading of the skills that failed to load
+    pub(crate) skills_failed: &'static str,
+    /// Reply when reloading skills failed, followed by the error
+    pub(crate) skills_not_reloaded: &'static str,
+    /// Label of the latest tool output in the progress message
+    pub(crate) tool_output: &'static str,
+    /// Reply when a photo could not be downloaded, followed by the error
+    pub(crate) image_not_downloaded: &'static str,
+    /// Sent while a document is downloaded, followed by its name and size
+    pub(crate) downloading: &'static str,
+    /// Unit of a file size
+    pub(crate) bytes: &'static str,
+    /// Reply when a document could not be downloaded, followed by the error
+    pub(crate) file_not_downloaded: &'static str,
+    /// Closes a confirmation question
+    pub(crate) confirm_question: &'static str,
+    /// Button confirming paused tool calls
+    pub(crate) confirm_yes: &'static str,
+    /// Button declining paused tool calls
+    pub(crate) confirm_no: &'static str,
+    /// Asked before a command that deletes files
+    pub(crate) confirm_destructive: &'static str,
+    /// Asked before a sensitive file is accessed
+    pub(crate) confirm_sensitive_file: &'static str,
+    /// Asked before a file is overwritten
+    pub(crate) confirm_overwrite: &'static str,
+    /// Asked before a file is deleted
+    pub(crate) confirm_delete: &'static str,
+    /// Asked before other calls that need confirmation
+    pub(crate) confirm_other: &'static str,
+}
+
+const EN: Messages = Messages {
+    welcome: "🦀 Welcome to RustClaw!\n\nI'm your AI assistant powered by Rust. \
+              Send me a message to start chatting.\n\n\
+              /help - Show commands\n/tools - Show available tools",
+    history_cleared: "🗑️ Conversation history cleared. Messages deleted:",
+    error: "Error",
+    busy: "⏳ I'm busy with other requests right now. Please try again in a moment.",
+    budget_reached:
+        "💸 Daily budget reached. Please try again tomorrow (budgets reset at 00:00 UTC).",
+    truncated: "✂️ [Response truncated: the model reached its output limit]",
+    processing_image: "📷 Processing image...",
+    image_processed: "✅ Image processed. What would you like me to do with it?",
+    file_processed: "✅ File processed. What would you like me to do with it?",
+    nothing_to_retry: "🤷 There is no message to retry yet.",
+    retry_text_only: "🤷 Only text messages can be retried. Please send the file again.",
+    retry_usage: "Usage: /retry [temperature from 0 to 2]",
+    too_short_to_summarize: "🤷 The conversation is too short to summarize yet.",
+    nothing_remembered: "🧠 Nothing remembered yet.",
+    nothing_to_forget: "🤷 Nothing to forget.",
+    cancelled: "❌ Cancelled.",
+    nothing_to_confirm: "⌛ Nothing to confirm. Please send your request again.",
+    running_tools: "⚙️ Running tool:",
+    provider_auth_failed: "🔑 The AI provider rejected the API key. \
+                           Please ask the bot's administrator to check its configuration.",
+    provider_quota_exceeded: "💳 The AI provider account has no quota left. \
+                              Please ask the bot's administrator to check its billing.",
+    provider_rate_limited: "⏳ The AI provider is receiving too many requests. \
+                            Please try again in a moment.",
+    provider_unavailable: "📡 The AI provider can't be reached right now. \
+                           Please try again later.",
+    admins_only: "⛔ Only the bot's administrators can do that.",
+    file_not_sent: "Could not send the file:",
+    help: "📖 Commands:\n\n\
+           /start - Start the bot\n\
+           /help - Show this help\n\
+           /clear - Clear conversation history\n\
+           /tools - Show available tools\n\
+           /model [name|default] - Show or set the model for this chat\n\
+           /provider [name|default] - Show or set the provider for this chat\n\
+           /stats - Show token usage and cost\n\
+           /retry [temperature] - Answer your last message again\n\
+           /mcp [describe <server>] - Show MCP servers\n\
+           /summarize - Summarize older messages to keep the context small\n\
+           /memory - List the facts remembered about this chat\n\
+           /forget [key] - Forget remembered facts\n\
+           /reload-skills - Discover skills again (admins only)",
+    no_tools: "🔧 No tools are available.",
+    available_tools: "🔧 Available tools",
+    sensitive_files_note: "⚠️ Sensitive files (SSH keys, passwords) require your confirmation.",
+    available: "Available:",
+    default_marker: "(default)",
+    current_model: "🤖 Current model:",
+    model_not_allowed: "❌ This model is not allowed:",
+    model_set: "✅ Model for this chat set to",
+    model_not_set: "❌ Failed to set model:",
+    current_provider: "🔌 Current provider:",
+    provider_not_available: "❌ This provider is not available:",
+    provider_set: "✅ Provider for this chat set to",
+    with_model: "with model",
+    provider_not_set: "❌ Failed to set provider:",
+    usage_report: "📊 Usage",
+    usage_chat: "This chat:",
+    usage_today: "Today (all chats):",
+    usage_total: "Total (all chats):",
+    requests: "requests",
+    tokens_in: "tokens in",
+    tokens_out: "out",
+    daily_budget: "Daily budget:",
+    budget_left: "left",
+    usage_not_loaded: "❌ Failed to load usage:",
+    no_mcp_servers: "🔌 No MCP servers are connected.",
+    mcp_servers: "🔌 MCP servers:",
+    mcp_describe_hint: "Use /mcp describe <server> for details.",
+    unknown_mcp_server: "❌ Unknown MCP server:",
+    connected: "Connected:",
+    mcp_usage: "Usage: /mcp [describe <server>]",
+    summarized: "🗜️ Older messages summarized:",
+    tokens_saved: "tokens saved",
+    summary_failed: "❌ Failed to summarize:",
+    remembered_facts: "🧠 Remembered facts:",
+    memory_not_loaded: "❌ Failed to load memory:",
+    forgot_key: "🧹 Forgot:",
+    forgot_facts: "🧹 Facts forgotten:",
+    forget_failed: "❌ Failed to forget:",
+    skills_reloaded: "🔄 Skills reloaded. Available:",
+    previously: "before:",
+    skills_failed: "⚠️ Failed to load:",
+    skills_not_reloaded: "❌ Failed to reload skills:",
+    tool_output: "⏳ Output:",
+    image_not_downloaded: "❌ Failed to download image:",
+    downloading: "📄 Downloading:",
+    bytes: "bytes",
+    file_not_downloaded: "❌ Failed to download file:",
+    confirm_question: "Do you want to proceed?",
+    confirm_yes: "✅ Yes",
+    confirm_no: "❌ No",
+    confirm_destructive: "This command will delete files.",
+    confirm_sensitive_file: "This may access sensitive files (keys, passwords, secrets).",
+    confirm_overwrite: "This will overwrite an existing file.",
+    confirm_delete: "This will delete a file.",
+    confirm_other: "This action needs your confirmation.",
+};
+
+const DE: Messages = Messages {
+    welcome: "🦀 Willkommen bei RustClaw!\n\nIch bin dein KI-Assistent, gebaut mit Rust. \
+              Schick mir eine Nachricht, um loszulegen.\n\n\
+              /help - Befehle anzeigen\n/tools - Verfügbare Werkzeuge anzeigen",
+    history_cleared: "🗑️ Gesprächsverlauf gelöscht. Gelöschte Nachrichten:",
+    error: "Fehler",
+    busy: "⏳ Ich bearbeite gerade andere Anfragen. Bitte versuche es gleich noch einmal.",
+    budget_reached: "💸 Tagesbudget erreicht. Bitte versuche es morgen wieder \
+                     (Budgets werden um 00:00 UTC zurückgesetzt).",
+    truncated: "✂️ [Antwort gekürzt: das Modell hat sein Ausgabelimit erreicht]",
+    processing_image: "📷 Bild wird verarbeitet...",
+    image_processed: "✅ Bild verarbeitet. Was soll ich damit machen?",
+    file_processed: "✅ Datei verarbeitet. Was soll ich damit machen?",
+    nothing_to_retry: "🤷 Es gibt noch keine Nachricht, die wiederholt werden kann.",
+    retry_text_only: "🤷 Nur Textnachrichten können wiederholt werden. \
+                      Bitte schick die Datei noch einmal.",
+    retry_usage: "Verwendung: /retry [Temperatur von 0 bis 2]",
+    too_short_to_summarize: "🤷 Das Gespräch ist noch zu kurz für eine Zusammenfassung.",
+    nothing_remembered: "🧠 Noch nichts gemerkt.",
+    nothing_to_forget: "🤷 Nichts zu vergessen.",
+    cancelled: "❌ Abgebrochen.",
+    nothing_to_confirm: "⌛ Nichts zu bestätigen. Bitte sende deine Anfrage erneut.",
+    running_tools: "⚙️ Werkzeug läuft:",
+    provider_auth_failed: "🔑 Der KI-Anbieter hat den API-Schlüssel abgelehnt. \
+                           Bitte lass die Konfiguration vom Administrator des Bots prüfen.",
+    provider_quota_exceeded: "💳 Das Kontingent beim KI-Anbieter ist aufgebraucht. \
+                              Bitte lass die Abrechnung vom Administrator des Bots prüfen.",
+    provider_rate_limited: "⏳ Der KI-Anbieter erhält gerade zu viele Anfragen. \
+                            Bitte versuche es gleich noch einmal.",
+    provider_unavailable: "📡 Der KI-Anbieter ist gerade nicht erreichbar. \
+                           Bitte versuche es später noch einmal.",
+    admins_only: "⛔ Das dürfen nur die Administratoren des Bots.",
+    file_not_sent: "Die Datei konnte nicht gesendet werden:",
+    help: "📖 Befehle:\n\n\
+           /start - Bot starten\n\
+           /help - Diese Hilfe anzeigen\n\
+           /clear - Gesprächsverlauf löschen\n\
+           /tools - Verfügbare Werkzeuge anzeigen\n\
+           /model [Name|default] - Modell für diesen Chat anzeigen oder setzen\n\
+           /provider [Name|default] - Anbieter für diesen Chat anzeigen oder setzen\n\
+           /stats - Token-Verbrauch und Kosten anzeigen\n\
+           /retry [Temperatur] - Deine letzte Nachricht noch einmal beantworten\n\
+           /mcp [describe <Server>] - MCP-Server anzeigen\n\
+           /summarize - Ältere Nachrichten zusammenfassen, um den Kontext klein zu halten\n\
+           /memory - Die über diesen Chat gemerkten Fakten auflisten\n\
+           /forget [Schlüssel] - Gemerkte Fakten vergessen\n\
+           /reload-skills - Skills neu einlesen (nur Administratoren)",
+    no_tools: "🔧 Es sind keine Werkzeuge verfügbar.",
+    available_tools: "🔧 Verfügbare Werkzeuge",
+    sensitive_files_note:
+        "⚠️ Sensible Dateien (SSH-Schlüssel, Passwörter) erfordern deine Bestätigung.",
+    available: "Verfügbar:",
+    default_marker: "(Standard)",
+    current_model: "🤖 Aktuelles Modell:",
+    model_not_allowed: "❌ Dieses Modell ist nicht erlaubt:",
+    model_set: "✅ Modell für diesen Chat:",
+    model_not_set: "❌ Das Modell konnte nicht gesetzt werden:",
+    current_provider: "🔌 Aktueller Anbieter:",
+    provider_not_available: "❌ Dieser Anbieter ist nicht verfügbar:",
+    provider_set: "✅ Anbieter für diesen Chat:",
+    with_model: "mit Modell",
+    provider_not_set: "❌ Der Anbieter konnte nicht gesetzt werden:",
+    usage_report: "📊 Verbrauch",
+    usage_chat: "Dieser Chat:",
+    usage_today: "Heute (alle Chats):",
+    usage_total: "Gesamt (alle Chats):",
+    requests: "Anfragen",
+    tokens_in: "Tokens rein",
+    tokens_out: "raus",
+    daily_budget: "Tagesbudget:",
+    budget_left: "übrig",
+    usage_not_loaded: "❌ Der Verbrauch konnte nicht geladen werden:",
+    no_mcp_servers: "🔌 Es sind keine MCP-Server verbunden.",
+    mcp_servers: "🔌 MCP-Server:",
+    mcp_describe_hint: "Details mit /mcp describe <server>.",
+    unknown_mcp_server: "❌ Unbekannter MCP-Server:",
+    connected: "Verbunden:",
+    mcp_usage: "Verwendung: /mcp [describe <server>]",
+    summarized: "🗜️ Ältere Nachrichten zusammengefasst:",
+    tokens_saved: "Tokens gespart",
+    summary_failed: "❌ Die Zusammenfassung ist fehlgeschlagen:",
+    remembered_facts: "🧠 Gemerkte Fakten:",
+    memory_not_loaded: "❌ Das Gedächtnis konnte nicht geladen werden:",
+    forgot_key: "🧹 Vergessen:",
+    forgot_facts: "🧹 Vergessene Fakten:",
+    forget_failed: "❌ Das Vergessen ist fehlgeschlagen:",
+    skills_reloaded: "🔄 Skills neu geladen. Verfügbar:",
+    previously: "vorher:",
+    skills_failed: "⚠️ Konnten nicht geladen werden:",
+    skills_not_reloaded: "❌ Die Skills konnten nicht neu geladen werden:",
+    tool_output: "⏳ Ausgabe:",
+    image_not_downloaded: "❌ Das Bild konnte nicht heruntergeladen werden:",
+    downloading: "📄 Wird heruntergeladen:",
+    bytes: "Bytes",
+    file_not_downloaded: "❌ Die Datei konnte nicht heruntergeladen werden:",
+    confirm_question: "Möchtest du fortfahren?",
+    confirm_yes: "✅ Ja",
+    confirm_no: "❌ Nein",
+    confirm_destructive: "Dieser Befehl löscht Dateien.",
+    confirm_sensitive_file:
+        "Dies greift möglicherweise auf sensible Dateien zu (Schlüssel, Passwörter, Geheimnisse).",
+    confirm_overwrite: "Dies überschreibt eine vorhandene Datei.",
+    confirm_delete: "Dies löscht eine Datei.",
+    confirm_other: "Diese Aktion erfordert deine Bestätigung.",
+};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_language() {
+        let parse = |s: &str| serde_json::from_str::<Language>(s).unwrap();
+        assert_eq!(parse(r#""en""#), Language::En);
+        assert_eq!(parse(r#""de""#), Language::De);
+        assert!(serde_json::from_str::<Language>(r#""xx""#).is_err());
+
+        assert_eq!(Language::default().messages().error, "Error");
+        assert_eq!(Language::De.messages().error, "Fehler");
+        assert_ne!(
+            Language::En.messages().welcome,
+            Language::De.messages().welcome
+        );
+    }
+}
@@ -1,22 +1,84 @@
 use anyhow::{anyhow, Result};
-use rustclaw_persistence::PersistenceService;
-use rustclaw_provider::{EchoTool, ProviderService, ToolFunction, ToolRegistry};
+use rustclaw_mcp::MCPToolRegistry;
+use rustclaw_persistence::{ChatSummary, PersistenceService};
+use rustclaw_provider::context::ConversationTurn;
+use rustclaw_provider::error::{ProviderErrorKind, UsageError};
+use rustclaw_provider::{
+    lossy_utf8, truncate_utf8, AgenticResponse, ConversationMetadata, EchoTool,
+    PendingConfirmation, ProviderService, ToolCapabilities, ToolFunction, ToolOutputSink,
+    ToolProgressSink, ToolRegistry, TurnRecorder,
+};
+use rustclaw_skills::{Skill, SkillsRegistry};
 use rustclaw_types::{
-    DocumentContent, ImageContent, Message as RustClawMessage, MessageContent, Tool, User,
+    DocumentContent, ImageContent, Message as RustClawMessage, MessageContent, ModelPrice,
+    ResponseImage, ResponsePart, Tool, User,
 };
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
 use std::path::PathBuf;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::Duration;
 use teloxide::net::Download;
+use teloxide::types::InputFile;
 use teloxide::{error_handlers::LoggingErrorHandler, prelude::*, utils::command::BotCommands};
 use tokio::sync::RwLock;
-use tracing::{error, info};
-
+use tracing::{error, info, instrument, warn, Instrument};
+use uuid::Uuid;
+
+mod budget;
+mod confirm;
+mod documents;
+mod group;
+mod i18n;
+mod limiter;
+mod memory;
+mod pages;
+mod progress;
 mod utils;
+mod web;
+use budget::CostTracker;
+use confirm::Confirmations;
+use documents::DocumentText;
+use group::GroupFilter;
+pub use group::GroupMode;
+pub use i18n::Language;
+use i18n::Messages;
+use limiter::RequestLimiter;
+pub use pages::PageIndicator;
+use progress::ToolStatus;
 pub use utils::{format_for_telegram, format_for_telegram_truncated};
+pub use web::WebReadTool;
 
 /// Maximum message length for Telegram (4096 chars, but we use less to be safe)
 const MAX_MESSAGE_LENGTH: usize = 4000;
 
+/// Seconds a bash command may run when the call sets no timeout
+const DEFAULT_COMMAND_TIMEOUT_SECS: u64 = 30;
+
+/// Longest timeout a bash call may set, in seconds
+const MAX_COMMAND_TIMEOUT_SECS: u64 = 120;
+
+/// How often a running bash command is checked for exit or timeout
+const COMMAND_POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+/// How long a killed bash command's output pipes may take to close
+const PIPE_CLOSE_GRACE: Duration = Duration::from_secs(1);
+
+/// Maximum bytes of command output streamed while a bash command runs
+const MAX_STREAMED_BYTES: usize = 64 * 1024;
+
+/// Largest file `read_file` reads by default (1 MiB)
+pub const DEFAULT_MAX_READ_BYTES: u64 = 1024 * 1024;
+
+/// Bytes at the start of a file checked for binary content
+const BINARY_SNIFF_BYTES: usize = 8 * 1024;
+
+/// Characters of a tool's description shown by /tools
+const TOOL_SUMMARY_CHARS: usize = 80;
+
+/// Most messages loaded when summarizing a chat's history
+const SUMMARY_SOURCE_MESSAGES: i32 = 200;
+
 /// Sensitive file patterns that require user confirmation
 const SENSITIVE_PATTERNS: &[&str] = &[
     ".ssh/",
@@ -36,8 +98,34 @@ const SENSITIVE_PATTERNS: &[&str] = &[
     ".aws/",
     ".kube/",
     ".docker/",
+    "authorized_keys",
+    ".bashrc",
+    ".bash_profile",
+    ".zshrc",
+    ".profile",
 ];
 
+/// Whether a path matches one of the [`SENSITIVE_PATTERNS`]
+fn is_sensitive_path(path: &str) -> bool {
+    let lower_path = path.to_lowercase();
+    SENSITIVE_PATTERNS
+        .iter()
+        .any(|pattern| lower_path.contains(&pattern.to_lowercase()))
+}
+
+/// Telegram users allowed to run admin commands such as `/reload-skills`,
+/// and `/forget` in groups
+#[derive(Debug, Default)]
+struct AdminUsers(HashSet<i64>);
+
+impl AdminUsers {
+    fn contains(&self, msg: &Message) -> bool {
+        msg.from
+            .as_ref()
+            .is_some_and(|user| self.0.contains(&(user.id.0 as i64)))
+    }
+}
+
 /// Telegram channel service
 pub struct TelegramService {
     bot: Bot,
@@ -45,6 +133,28 @@ pub struct TelegramService {
     provider: Arc<RwLock<ProviderService>>,
     /// Directory to store downloaded files (relative to workspace)
     downloads_dir: PathBuf,
+    /// Which group chat messages the bot answers
+    group_filter: GroupFilter,
+    /// Message sent to an admin chat once the bot is up
+    admin_notice: Option<(ChatId, String)>,
+    /// Users allowed to run admin commands
+    admins: Arc<AdminUsers>,
+    /// Skills that can be activated with `/skill-name`
+    skills: Arc<RwLock<SkillsRegistry>>,
+    /// Limit on concurrently handled messages
+    limiter: Arc<RequestLimiter>,
+    /// Model prices and the daily budget
+    costs: Arc<CostTracker>,
+    /// Marker for the parts of replies split into several messages
+    pages: Arc<PageIndicator>,
+    /// Tool calls waiting for the user's confirmation
+    confirmations: Arc<Confirmations>,
+    /// Connected MCP servers, for `/mcp`
+    mcp: Arc<MCPToolRegistry>,
+    /// The bot's own messages in the configured language
+    messages: &'static Messages,
+    /// Show the tools the agentic loop runs in a status message
+    show_tool_progress: bool,
 }
 
 /// Bot commands
@@ -59,6 +169,27 @@ enum Command {
     Clear,
     #[command(description = "Show available tools")]
     Tools,
+    #[command(description = "Show or set the model for this chat: /model [name|default]")]
+    Model(String),
+    #[command(description = "Show or set the provider for this chat: /provider [name|default]")]
+    Provider(String),
+    #[command(description = "Show token usage and cost")]
+    Stats,
+    #[command(description = "Answer your last message again: /retry [temperature]")]
+    Retry(String),
+    #[command(description = "Show MCP servers: /mcp [describe <server>]")]
+    Mcp(String),
+    #[command(description = "Summarize older messages to keep the context small")]
+    Summarize,
+    #[command(description = "List the facts remembered about this chat")]
+    Memory,
+    #[command(description = "Forget remembered facts: /forget [key]")]
+    Forget(String),
+    #[command(
+        rename = "reload-skills",
+        description = "Discover skills again (admins only)"
+    )]
+    ReloadSkills,
 }
 
 impl TelegramService {
@@ -75,6 +206,17 @@ impl TelegramService {
             persistence: Arc::new(RwLock::new(persistence)),
             provider: Arc::new(RwLock::new(provider)),
             downloads_dir,
+            group_filter: GroupFilter::default(),
+            admin_notice: None,
+            admins: Arc::new(AdminUsers::default()),
+            skills: Arc::new(RwLock::new(SkillsRegistry::new())),
+            limiter: Arc::new(RequestLimiter::default()),
+            costs: Arc::new(CostTracker::default()),
+            pages: Arc::new(PageIndicator::default()),
+            confirmations: Arc::new(Confirmations::default()),
+            mcp: Arc::new(MCPToolRegistry::new()),
+            messages: Language::default().messages(),
+            show_tool_progress: false,
         }
     }
 
@@ -96,17 +238,117 @@ impl TelegramService {
             persistence: Arc::new(RwLock::new(persistence)),
             provider: Arc::new(RwLock::new(provider)),
             downloads_dir,
+            group_filter: GroupFilter::default(),
+            admin_notice: None,
+            admins: Arc::new(AdminUsers::default()),
+            skills: Arc::new(RwLock::new(SkillsRegistry::new())),
+            limiter: Arc::new(RequestLimiter::default()),
+            costs: Arc::new(CostTracker::default()),
+            pages: Arc::new(PageIndicator::default()),
+            confirmations: Arc::new(Confirmations::default()),
+            mcp: Arc::new(MCPToolRegistry::new()),
+            messages: Language::default().messages(),
+            show_tool_progress: false,
         }
     }
 
+    /// Only answer group messages that mention the bot or start with `prefix`
+    /// when `mode` is [`GroupMode::Mention`]; private chats are always answered
+    pub fn with_group_mode(mut self, mode: GroupMode, prefix: Option<String>) -> Self {
+        self.group_filter = GroupFilter::new(mode, prefix);
+        self
+    }
+
+    /// Set the skills that can be activated with `/skill-name <request>`
+    pub fn with_skills(mut self, skills: SkillsRegistry) -> Self {
+        self.skills = Arc::new(RwLock::new(skills));
+        self
+    }
+
+    /// Handle at most `max` messages concurrently, answering any others with
+    /// a "busy" reply (`0` = unlimited)
+    pub fn with_max_concurrent_requests(mut self, max: usize) -> Self {
+        self.limiter = Arc::new(RequestLimiter::new(max));
+        self
+    }
+
+    /// Price requests with `prices` (per model, USD per million tokens) and
+    /// stop answering once `daily_budget` USD are spent in a UTC day
+    /// (`0` = no cap)
+    pub fn with_pricing(mut self, prices: HashMap<String, ModelPrice>, daily_budget: f64) -> Self {
+        self.costs = Arc::new(CostTracker::new(prices, daily_budget));
+        self
+    }
+
+    /// Mark the parts of replies that are split into several messages with
+    /// `pages` (e.g. `(1/3)`), or not at all with [`PageIndicator::Off`]
+    pub fn with_page_indicator(mut self, pages: PageIndicator) -> Self {
+        self.pages = Arc::new(pages);
+        self
+    }
+
+    /// Send the bot's own messages (welcome text, busy and budget notices,
+    /// errors, ...) in `language`; the model still answers in the user's language
+    pub fn with_language(mut self, language: Language) -> Self {
+        self.messages = language.messages();
+        self
+    }
+
+    /// Show which tool the agentic loop is running in a status message that
+    /// is edited as the loop goes on and replaced by the final answer
+    pub fn with_tool_progress(mut self, enabled: bool) -> Self {
+        self.show_tool_progress = enabled;
+        self
+    }
+
+    /// Set the MCP servers shown by `/mcp`
+    pub fn with_mcp(mut self, registry: Arc<MCPToolRegistry>) -> Self {
+        self.mcp = registry;
+        self
+    }
+
+    /// Let the model keep facts about each chat with the `remember` and
+    /// `recall` tools; remembered facts are added to the system prompt
+    pub fn with_memory(self) -> Self {
+        match self.provider.try_read() {
+            Ok(provider) => {
+                for tool in memory::memory_tools(self.persistence.clone()) {
+                    provider.add_tool(tool);
+                }
+                info!("Memory tools enabled");
+            }
+            Err(_) => warn!("Provider is in use, memory tools not registered"),
+        }
+        self
+    }
+
+    /// Send `notice` to the admin chat once the bot has started
+    /// (e.g. to report MCP servers that failed to start)
+    pub fn with_admin_notice(mut self, chat_id: i64, notice: impl Into<String>) -> Self {
+        self.admin_notice = Some((ChatId(chat_id), notice.into()));
+        self
+    }
+
+    /// Allow the users with these Telegram ids to run admin commands
+    pub fn with_admin_users(mut self, user_ids: impl IntoIterator<Item = i64>) -> Self {
+        self.admins = Arc::new(AdminUsers(user_ids.into_iter().collect()));
+        self
+    }
+
+    /// The tool definitions sent to the model, including tools added by the
+    /// channel (e.g. memory), as JSON
+    pub async fn tool_manifest(&self) -> Result<serde_json::Value> {
+        self.provider.read().await.tool_manifest()
+    }
+
     /// Validate the bot token by making a test API call
-    pub async fn validate_token(&self) -> Result<()> {
+    pub async fn validate_token(&self) -> Result<teloxide::types::Me> {
         info!("Validating Telegram bot token...");
 
         match self.bot.get_me().await {
-            Ok(_me) => {
+            Ok(me) => {
                 info!("Telegram bot token is valid");
-                Ok(())
+                Ok(me)
             }
             Err(teloxide::RequestError::Api(teloxide::ApiError::InvalidToken)) => Err(anyhow!(
                 "Invalid Telegram bot token. Please check TELEGRAM_BOT_TOKEN environment variable \
@@ -119,7 +361,12 @@ impl TelegramService {
     /// Run the Telegram service (this is a blocking call)
     pub async fn run(self) -> Result<()> {
         // Validate token first
-        self.validate_token().await?;
+        let me = self.validate_token().await?;
+        let group_filter = Arc::new(
+            self.group_filter
+                .clone()
+                .with_bot(me.user.username.clone(), me.user.id),
+        );
 
         info!("Starting Telegram bot...");
 
@@ -129,37 +376,76 @@ impl TelegramService {
             .map_err(|e| anyhow!("Failed to create downloads directory: {}", e))?;
         info!("Downloads directory: {:?}", self.downloads_dir);
 
+        if let Some((chat_id, notice)) = &self.admin_notice {
+            if let Err(e) = self.bot.send_message(*chat_id, notice).await {
+                warn!("Failed to send admin notice to chat {}: {}", chat_id, e);
+            }
+        }
+
         let persistence = self.persistence.clone();
         let provider = self.provider.clone();
         let downloads_dir = self.downloads_dir.clone();
         let bot_for_download = self.bot.clone();
+        let skills = self.skills.clone();
+        let limiter = self.limiter.clone();
+        let costs = self.costs.clone();
+        let pages = self.pages.clone();
+        let confirmations = self.confirmations.clone();
+        let mcp = self.mcp.clone();
+        let admins = self.admins.clone();
+        let status = ToolStatus::new(self.show_tool_progress, self.messages);
 
         // Use Dispatcher with multiple message type handlers
-        let handler = Update::filter_message()
+        let messages = Update::filter_message()
+            .branch(
+                dptree::entry()
+                    .filter_command::<Command>()
+                    .filter(|cmd: Command| matches!(cmd, Command::Retry(_)))
+                    .endpoint(Self::handle_retry),
+            )
             .branch(
                 dptree::entry()
                     .filter_command::<Command>()
                     .endpoint(Self::handle_command),
             )
             .branch(
-                dptree::filter(|msg: Message| msg.text().is_some())
-                    .endpoint(Self::handle_text_message),
+                dptree::filter(|msg: Message, group: Arc<GroupFilter>| {
+                    msg.text().is_some() && group.prompt_for(&msg).is_some()
+                })
+                .endpoint(Self::handle_text_message),
             )
             .branch(
-                dptree::filter(|msg: Message| msg.photo().is_some())
-                    .endpoint(Self::handle_photo_message),
+                dptree::filter(|msg: Message, group: Arc<GroupFilter>| {
+                    msg.photo().is_some() && group.prompt_for(&msg).is_some()
+                })
+                .endpoint(Self::handle_photo_message),
             )
             .branch(
-                dptree::filter(|msg: Message| msg.document().is_some())
-                    .endpoint(Self::handle_document_message),
+                dptree::filter(|msg: Message, group: Arc<GroupFilter>| {
+                    msg.document().is_some() && group.prompt_for(&msg).is_some()
+                })
+                .endpoint(Self::handle_document_message),
             );
+        let handler = dptree::entry()
+            .branch(messages)
+            .branch(Update::filter_callback_query().endpoint(Self::handle_confirmation));
 
         let mut dispatcher = Dispatcher::builder(self.bot.clone(), handler)
             .dependencies(dptree::deps![
                 persistence,
                 provider,
                 downloads_dir,
-                bot_for_download
+                bot_for_download,
+                group_filter,
+                skills,
+                limiter,
+                costs,
+                pages,
+                confirmations,
+                mcp,
+                admins,
+                status,
+                self.messages
             ])
             .error_handler(LoggingErrorHandler::with_custom_text(
                 "An error has occurred in the dispatcher",
@@ -172,73 +458,446 @@ impl TelegramService {
         Ok(())
     }
 
-    /// Split a message into chunks that fit Telegram's limits
-    fn split_message(text: &str) -> Vec<String> {
-        if text.len() <= MAX_MESSAGE_LENGTH {
-            return vec![text.to_string()];
+    /// Run the agentic loop, persisting each intermediate tool round as it
+    /// happens and relaying tool output to the chat while it runs
+    ///
+    /// The tokens used are recorded with their cost once the loop finishes.
+    #[allow(clippy::too_many_arguments)]
+    async fn complete_and_record(
+        bot: &Bot,
+        provider: &ProviderService,
+        persistence: Arc<RwLock<PersistenceService>>,
+        costs: &CostTracker,
+        status: ToolStatus,
+        chat_id: i64,
+        history: &[RustClawMessage],
+        prompt: &str,
+        metadata: &ConversationMetadata,
+    ) -> Result<AgenticResponse> {
+        let metadata = &Self::with_chat_context(&persistence, chat_id, history, metadata).await;
+        let provider = &*Self::chat_provider(&persistence, provider, chat_id).await;
+        Self::record_run(
+            bot,
+            provider,
+            persistence,
+            costs,
+            status,
+            chat_id,
+            async |recorder, model, output, progress| {
+                provider
+                    .complete_agentic_recorded(
+                        history,
+                        prompt,
+                        recorder,
+                        model,
+                        Some(metadata),
+                        Some(output),
+                        Some(progress),
+                    )
+                    .await
+            },
+        )
+        .await
+    }
+
+    /// Run a tool call the user confirmed and continue the agentic loop,
+    /// recording it like [`Self::complete_and_record`]
+    #[allow(clippy::too_many_arguments)]
+    async fn resume_and_record(
+        bot: &Bot,
+        provider: &ProviderService,
+        persistence: Arc<RwLock<PersistenceService>>,
+        costs: &CostTracker,
+        status: ToolStatus,
+        chat_id: i64,
+        history: &[RustClawMessage],
+        confirmations: &[PendingConfirmation],
+        metadata: &ConversationMetadata,
+    ) -> Result<AgenticResponse> {
+        let metadata = &Self::with_chat_context(&persistence, chat_id, history, metadata).await;
+        let provider = &*Self::chat_provider(&persistence, provider, chat_id).await;
+        Self::record_run(
+            bot,
+            provider,
+            persistence,
+            costs,
+            status,
+            chat_id,
+            async |recorder, model, output, progress| {
+                provider
+                    .resume_confirmed(
+                        history,
+                        confirm::CONFIRMED_PROMPT,
+                        confirmations,
+                        recorder,
+                        model,
+                        Some(metadata),
+                        Some(output),
+                        Some(progress),
+                    )
+                    .await
+            },
+        )
+        .await
+    }
+
+    /// Run `run` with the chat's model, persisting each tool round it
+    /// records, relaying tool output (and, if `status` is enabled, the
+    /// running tools) to the chat and recording token usage
+    #[allow(clippy::too_many_arguments)]
+    async fn record_run(
+        bot: &Bot,
+        provider: &ProviderService,
+        persistence: Arc<RwLock<PersistenceService>>,
+        costs: &CostTracker,
+        status: ToolStatus,
+        chat_id: i64,
+        run: impl AsyncFnOnce(
+            &TurnRecorder,
+            Option<&str>,
+            &ToolOutputSink,
+            &ToolProgressSink,
+        ) -> Result<AgenticResponse>,
+    ) -> Result<AgenticResponse> {
+        let model = Self::chat_model(&persistence, provider, chat_id).await;
+        let usage_persistence = persistence.clone();
+        let (recorder, mut turns) = tokio::sync::mpsc::unbounded_channel::<ConversationTurn>();
+
+        let writer = tokio::spawn(async move {
+            while let Some(turn) = turns.recv().await {
+                let persistence = persistence.write().await;
+                if let Err(e) = persistence
+                    .save_chat_message(chat_id, &turn.to_chat_message())
+                    .await
+                {
+                    error!("Failed to save tool round: {}", e);
+                }
+            }
+        });
+
+        let (output, progress, relay) =
+            progress::spawn_output_relay(bot.clone(), ChatId(chat_id), status);
+
+        let response = run(&recorder, model.as_deref(), &output, &progress).await;
+
+        // Close the channels so the tasks drain remaining items and exit
+        drop(recorder);
+        drop(output);
+        drop(progress);
+        if let Err(e) = writer.await {
+            error!("Tool round persistence task failed: {}", e);
+        }
+        if let Err(e) = relay.await {
+            error!("Tool output relay task failed: {}", e);
+        }
+
+        // Failed runs still pay for the requests made before the failure
+        let usage = match &response {
+            Ok(response) => Some(response.usage),
+            Err(e) => Some(UsageError::usage_of(e)).filter(|usage| usage.total_tokens() > 0),
+        };
+        if let Some(usage) = usage {
+            let model = model.as_deref().unwrap_or(provider.default_model());
+            costs
+                .record(&*usage_persistence.read().await, chat_id, model, &usage)
+                .await;
+        }
+
+        response
+    }
+
+    /// Reply telling the user why their request failed
+    ///
+    /// Problems the user can't fix, like a rejected API key, get a plain
+    /// explanation instead of the provider's error, which may contain
+    /// configuration details.
+    fn error_reply(messages: &Messages, error: &anyhow::Error) -> String {
+        match ProviderErrorKind::of(error) {
+            ProviderErrorKind::Authentication => messages.provider_auth_failed.to_string(),
+            ProviderErrorKind::QuotaExceeded => messages.provider_quota_exceeded.to_string(),
+            ProviderErrorKind::RateLimited => messages.provider_rate_limited.to_string(),
+            ProviderErrorKind::Unavailable => messages.provider_unavailable.to_string(),
+            ProviderErrorKind::Other => format!("❌ {}: {}", messages.error, error),
+        }
+    }
+
+    /// Describe the message sender for the system prompt
+    fn conversation_metadata(msg: &Message) -> ConversationMetadata {
+        let user = msg.from.as_ref();
+        ConversationMetadata {
+            user_name: user.map(|u| u.first_name.clone()),
+            username: user.and_then(|u| u.username.clone()),
+            locale: user.and_then(|u| u.language_code.clone()),
+            ..Default::default()
+        }
+    }
+
+    /// Load the skill named by a leading `/skill-name` in the message,
+    /// returning it together with the rest of the message
+    async fn activate_skill(
+        skills: &RwLock<SkillsRegistry>,
+        text: &str,
+    ) -> Option<(Skill, String)> {
+        let rest = text.trim_start().strip_prefix('/')?;
+        let (command, request) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+        // Commands may be addressed as `/name@botname` in groups
+        let name = command.split('@').next().unwrap_or(command);
+
+        let mut skills = skills.write().await;
+        skills.get(name)?;
+        match skills.load_skill(name) {
+            Ok(skill) => {
+                info!("Activated skill: {}", name);
+                Some((skill.clone(), request.trim().to_string()))
+            }
+            Err(e) => {
+                warn!("Failed to load skill '{}': {}", name, e);
+                None
+            }
+        }
+    }
+
+    /// Build the prompt for a skill invocation
+    fn skill_prompt(skill: &Skill, request: &str) -> String {
+        let mut prompt = format!(
+            "Use the '{}' skill to handle this request.\n\nSkill instructions:\n{}\n\nRequest: {}",
+            skill.name(),
+            skill.instructions().unwrap_or_default(),
+            request
+        );
+
+        if let Some(schema) = skill.output_schema() {
+            prompt.push_str(&format!(
+                "\n\nRespond with only a JSON document (no other text) that matches this JSON schema:\n{}",
+                schema
+            ));
+        }
+
+        prompt
+    }
+
+    /// Run a skill invocation, validating the final response against the
+    /// skill's output schema and re-prompting once if it doesn't match
+    #[allow(clippy::too_many_arguments)]
+    async fn complete_with_skill(
+        bot: &Bot,
+        provider: &ProviderService,
+        persistence: Arc<RwLock<PersistenceService>>,
+        costs: &CostTracker,
+        status: ToolStatus,
+        chat_id: i64,
+        history: &[RustClawMessage],
+        skill: &Skill,
+        request: &str,
+        metadata: &ConversationMetadata,
+    ) -> Result<AgenticResponse> {
+        let prompt = Self::skill_prompt(skill, request);
+        let response = Self::complete_and_record(
+            bot,
+            provider,
+            persistence.clone(),
+            costs,
+            status,
+            chat_id,
+            history,
+            &prompt,
+            metadata,
+        )
+        .await?;
+
+        let Err(e) = skill.validate_output(&response.content) else {
+            return Ok(response);
+        };
+
+        warn!(
+            "Skill '{}' response failed validation, re-prompting: {}",
+            skill.name(),
+            e
+        );
+        let retry_prompt = format!(
+            "{}\n\nYour previous response was rejected: {}\n\nPrevious response:\n{}\n\n\
+             Respond again with only JSON that matches the schema.",
+            prompt, e, response.content
+        );
+        let mut response = Self::complete_and_record(
+            bot,
+            provider,
+            persistence,
+            costs,
+            status,
+            chat_id,
+            history,
+            &retry_prompt,
+            metadata,
+        )
+        .await?;
+
+        if let Err(e) = skill.validate_output(&response.content) {
+            warn!(
+                "Skill '{}' response failed validation again: {}",
+                skill.name(),
+                e
+            );
+            response.content = format!("{}\n\n⚠️ {}", response.content, e);
+        }
+
+        Ok(response)
+    }
+
+    /// Count a finished skill activation towards the skill's ranking
+    ///
+    /// The run succeeded if it completed normally and its response matches
+    /// the skill's output schema. The system prompt lists the skills in
+    /// their new order from the next request on. The database write does not
+    /// delay the reply.
+    async fn record_skill_use(
+        skills: &RwLock<SkillsRegistry>,
+        provider: &ProviderService,
+        persistence: Arc<RwLock<PersistenceService>>,
+        skill: &Skill,
+        response: &Result<AgenticResponse>,
+    ) {
+        let mut skills = skills.write().await;
+        if !skills.tracks_usage() {
+            return;
         }
 
-        let mut chunks = Vec::new();
-        let mut current_chunk = String::new();
+        let success = response.as_ref().is_ok_and(|r| {
+            !r.is_truncated()
+                && r.finish_reason != "timeout"
+                && skill.validate_output(&r.content).is_ok()
+        });
+        skills.record_use(skill.name(), success);
+        provider.set_skill_summaries(skills.summaries());
+
+        let name = skill.name().to_string();
+        tokio::spawn(
+            async move {
+                if let Err(e) = persistence
+                    .read()
+                    .await
+                    .record_skill_use(&name, success)
+                    .await
+                {
+                    warn!("Failed to record use of skill '{}': {}", name, e);
+                }
+            }
+            .in_current_span(),
+        );
+    }
 
-        // Try to split on paragraph breaks first, then sentences, then words
-        for paragraph in text.split("\n\n") {
-            if current_chunk.len() + paragraph.len() + 2 > MAX_MESSAGE_LENGTH {
-                if !current_chunk.is_empty() {
-                    chunks.push(current_chunk.trim().to_string());
-                    current_chunk = String::new();
+    /// Note on the parts to send that the response was cut off
+    ///
+    /// Call this after saving the parts: the note is for the user only and
+    /// would be replayed to the model on later turns.
+    fn flag_truncation(
+        response: &AgenticResponse,
+        parts: &mut Vec<ResponsePart>,
+        messages: &Messages,
+    ) {
+        if !response.is_truncated() {
+            return;
+        }
+        warn!(
+            "Sending truncated response (finish_reason={})",
+            response.finish_reason
+        );
+        match parts.first_mut() {
+            Some(ResponsePart::Text(text)) => {
+                text.push_str("\n\n");
+                text.push_str(messages.truncated);
+            }
+            _ => parts.insert(0, ResponsePart::Text(messages.truncated.to_string())),
+        }
+    }
+
+    /// Text of response parts to save to the conversation, with a note in
+    /// place of each attachment
+    fn parts_text(parts: &[ResponsePart]) -> String {
+        parts
+            .iter()
+            .map(|part| match part {
+                ResponsePart::Text(text) => text.clone(),
+                ResponsePart::Image(image) => format!("[Sent image ({})]", image.mime_type),
+                ResponsePart::Document(document) => {
+                    format!("[Sent file {}]", document.file_name)
                 }
+                ResponsePart::Location {
+                    latitude,
+                    longitude,
+                } => format!("[Sent location {}, {}]", latitude, longitude),
+            })
+            .collect::<Vec<_>>()
+            .join("\n\n")
+    }
 
-                // If paragraph itself is too long, split by sentences
-                if paragraph.len() > MAX_MESSAGE_LENGTH {
-                    for sentence in paragraph.split(". ") {
-                        if current_chunk.len() + sentence.len() + 2 > MAX_MESSAGE_LENGTH {
-                            if !current_chunk.is_empty() {
-                                chunks.push(current_chunk.trim().to_string());
-                                current_chunk = String::new();
-                            }
-
-                            // If sentence is too long, split by words
-                            if sentence.len() > MAX_MESSAGE_LENGTH {
-                                for word in sentence.split_whitespace() {
-                                    if current_chunk.len() + word.len() + 1 > MAX_MESSAGE_LENGTH {
-                                        if !current_chunk.is_empty() {
-                                            chunks.push(current_chunk.trim().to_string());
-                                        }
-                                        current_chunk = word.to_string();
-                                    } else {
-                                        if !current_chunk.is_empty() {
-                                            current_chunk.push(' ');
-                                        }
-                                        current_chunk.push_str(word);
-                                    }
-                                }
-                            } else {
-                                current_chunk = sentence.to_string();
-                            }
-                        } else {
-                            if !current_chunk.is_empty() {
-                                current_chunk.push_str(". ");
-                            }
-                            current_chunk.push_str(sentence);
-                        }
+    /// Send response parts in order: text split into pages marked with
+    /// `pages`, images as photos, files as documents
+    async fn send_parts(
+        bot: &Bot,
+        chat_id: ChatId,
+        parts: &[ResponsePart],
+        pages: &PageIndicator,
+        messages: &Messages,
+    ) -> Result<(), teloxide::RequestError> {
+        for part in parts {
+            match part {
+                ResponsePart::Text(text) => Self::send_paged(bot, chat_id, text, pages).await?,
+                ResponsePart::Image(image) => {
+                    Self::send_image(bot, chat_id, image, messages).await?
+                }
+                ResponsePart::Document(document) => {
+                    let file = InputFile::memory(document.data.clone())
+                        .file_name(document.file_name.clone());
+                    if let Err(e) = bot.send_document(chat_id, file).await {
+                        warn!("Failed to send document {}: {}", document.file_name, e);
+                        let note = format!("⚠️ {} {}", messages.file_not_sent, document.file_name);
+                        Self::send_message_safe(bot, chat_id, &note).await?;
                     }
-                } else {
-                    current_chunk = paragraph.to_string();
                 }
-            } else {
-                if !current_chunk.is_empty() {
-                    current_chunk.push_str("\n\n");
+                ResponsePart::Location {
+                    latitude,
+                    longitude,
+                } => {
+                    if let Err(e) = bot.send_location(chat_id, *latitude, *longitude).await {
+                        warn!("Failed to send location: {}", e);
+                        let note = format!("📍 {}, {}", latitude, longitude);
+                        Self::send_message_safe(bot, chat_id, &note).await?;
+                    }
                 }
-                current_chunk.push_str(paragraph);
             }
         }
+        Ok(())
+    }
 
-        if !current_chunk.trim().is_empty() {
-            chunks.push(current_chunk.trim().to_string());
-        }
+    /// Send an image produced while answering as a photo
+    ///
+    /// Images Telegram refuses as photos (e.g. too large) are sent as a
+    /// document instead, and if that fails too, a note is sent in their place.
+    async fn send_image(
+        bot: &Bot,
+        chat_id: ChatId,
+        image: &ResponseImage,
+        messages: &Messages,
+    ) -> Result<(), teloxide::RequestError> {
+        let extension = image.mime_type.rsplit('/').next().unwrap_or("png");
+        let file_name = format!("image.{extension}");
+        let file = InputFile::memory(image.data.clone()).file_name(file_name.clone());
+        let Err(e) = bot.send_photo(chat_id, file).await else {
+            return Ok(());
+        };
+        warn!(
+            "Failed to send image as a photo, sending it as a document: {}",
+            e
+        );
 
-        chunks
+        let file = InputFile::memory(image.data.clone()).file_name(file_name.clone());
+        if let Err(e) = bot.send_document(chat_id, file).await {
+            warn!("Failed to send image as a document: {}", e);
+            let note = format!("⚠️ {} {}", messages.file_not_sent, file_name);
+            Self::send_message_safe(bot, chat_id, &note).await?;
+        }
+        Ok(())
     }
 
     /// Send a message, splitting if necessary
@@ -246,82 +905,614 @@ impl TelegramService {
         bot: &Bot,
         chat_id: ChatId,
         text: &str,
+    ) -> Result<(), teloxide::RequestError> {
+        Self::send_paged(bot, chat_id, text, &PageIndicator::default()).await
+    }
+
+    /// Send a message, splitting it if necessary and marking the parts with
+    /// `pages`
+    async fn send_paged(
+        bot: &Bot,
+        chat_id: ChatId,
+        text: &str,
+        pages: &PageIndicator,
     ) -> Result<(), teloxide::RequestError> {
         // Format text for Telegram (handle escaped newlines, etc.)
         let formatted = format_for_telegram(text);
-        let chunks = Self::split_message(&formatted);
-        for (i, chunk) in chunks.iter().enumerate() {
-            if chunks.len() > 1 {
-                bot.send_message(
-                    chat_id,
-                    format!("({}/{})\n\n{}", i + 1, chunks.len(), chunk),
-                )
-                .await?;
-            } else {
-                bot.send_message(chat_id, chunk).await?;
-            }
+        for chunk in pages::paginate(&formatted, MAX_MESSAGE_LENGTH, pages) {
+            bot.send_message(chat_id, chunk).await?;
+        }
+        Ok(())
+    }
+
+    /// Ask the user to confirm the tool calls a response paused on, with
+    /// Yes/No buttons, and save the question to the conversation
+    async fn ask_confirmation(
+        bot: &Bot,
+        chat_id: ChatId,
+        user_id: i64,
+        response: AgenticResponse,
+        persistence: &RwLock<PersistenceService>,
+        confirmations: &Confirmations,
+        messages: &Messages,
+    ) -> Result<(), teloxide::RequestError> {
+        if response.confirmations.is_empty() {
+            return Ok(());
+        }
+        let question = confirmations.ask(chat_id.0, user_id, response.confirmations, messages);
+        bot.send_message(chat_id, format_for_telegram(&question))
+            .reply_markup(confirm::keyboard(messages))
+            .await?;
+
+        let ai_msg = RustClawMessage::new(
+            chat_id.0,
+            User::new(0), // System/AI user
+            MessageContent::Text(question),
+        );
+        if let Err(e) = persistence.write().await.save_message(&ai_msg).await {
+            error!("Failed to save confirmation question: {}", e);
         }
         Ok(())
     }
 
     /// Handle bot commands
+    #[allow(clippy::too_many_arguments)]
+    #[instrument(name = "request", skip_all, fields(request_id = %Uuid::new_v4(), chat_id = msg.chat.id.0))]
     async fn handle_command(
         bot: Bot,
         msg: Message,
         cmd: Command,
+        persistence: Arc<RwLock<PersistenceService>>,
+        provider: Arc<RwLock<ProviderService>>,
+        costs: Arc<CostTracker>,
+        mcp: Arc<MCPToolRegistry>,
+        skills: Arc<RwLock<SkillsRegistry>>,
+        admins: Arc<AdminUsers>,
+        confirmations: Arc<Confirmations>,
+        messages: &'static Messages,
     ) -> Result<(), teloxide::RequestError> {
         let chat_id = msg.chat.id;
 
         match cmd {
             Command::Start => {
-                Self::send_message_safe(
-                    &bot,
-                    chat_id,
-                    "🦀 Welcome to RustClaw!\n\nI'm your AI assistant powered by Rust. \
-                     Send me a message to start chatting.\n\n\
-                     /help - Show commands\n/tools - Show available tools",
-                )
-                .await?;
+                Self::send_message_safe(&bot, chat_id, messages.welcome).await?;
             }
             Command::Help => {
-                Self::send_message_safe(&bot, chat_id, &Command::descriptions().to_string())
-                    .await?;
+                Self::send_message_safe(&bot, chat_id, messages.help).await?;
             }
             Command::Clear => {
-                Self::send_message_safe(&bot, chat_id, "🗑️ Conversation history cleared.").await?;
+                // A paused tool call belongs to the history being cleared
+                confirmations.clear(chat_id.0);
+                let deleted = persistence.read().await.delete_messages(chat_id.0).await;
+                let reply = match deleted {
+                    Ok(count) => format!("{} {}", messages.history_cleared, count),
+                    Err(e) => {
+                        error!("Failed to clear chat history: {}", e);
+                        format!("❌ {}: {}", messages.error, e)
+                    }
+                };
+                Self::send_message_safe(&bot, chat_id, &reply).await?;
             }
             Command::Tools => {
-                Self::send_message_safe(
-                    &bot,
-                    chat_id,
-                    "🔧 Available tools:\n\n\
-                     📁 **bash** - Execute bash commands (ls, cat, grep, curl, git, etc.)\n\
-                     📄 **read_file** - Read file contents\n\
-                     📂 **list_dir** - List directory contents\n\
-                     ⏰ **get_current_time** - Get current date/time\n\
-                     📢 **echo** - Echo back a message\n\n\
-                     ⚠️ Sensitive files (SSH keys, passwords) require your confirmation.",
+                let reply = Self::tools_reply(provider.read().await.tools(), messages);
+                Self::send_message_safe(&bot, chat_id, &reply).await?;
+            }
+            Command::Model(name) => {
+                let reply = Self::handle_model_command(
+                    &persistence,
+                    &provider,
+                    chat_id.0,
+                    name.trim(),
+                    messages,
                 )
-                .await?;
+                .await;
+                Self::send_message_safe(&bot, chat_id, &reply).await?;
+            }
+            Command::Provider(name) => {
+                let reply = Self::handle_provider_command(
+                    &persistence,
+                    &provider,
+                    chat_id.0,
+                    name.trim(),
+                    messages,
+                )
+                .await;
+                Self::send_message_safe(&bot, chat_id, &reply).await?;
+            }
+            Command::Stats => {
+                let report = costs
+                    .stats(&*persistence.read().await, chat_id.0, messages)
+                    .await;
+                Self::send_message_safe(&bot, chat_id, &report).await?;
             }
+            Command::Mcp(args) => {
+                let reply = Self::handle_mcp_command(&mcp, args.trim(), messages).await;
+                Self::send_message_safe(&bot, chat_id, &reply).await?;
+            }
+            Command::Summarize => {
+                if costs.budget_reached(&*persistence.read().await).await {
+                    return Self::send_message_safe(&bot, chat_id, messages.budget_reached).await;
+                }
+                let result = {
+                    let provider = provider.read().await;
+                    Self::summarize_chat(&provider, &persistence, &costs, chat_id.0).await
+                };
+                let reply = match result {
+                    Ok(Some((count, saved))) => format!(
+                        "{} {}, ~{} {}.",
+                        messages.summarized, count, saved, messages.tokens_saved
+                    ),
+                    Ok(None) => messages.too_short_to_summarize.to_string(),
+                    Err(e) => {
+                        error!("Failed to summarize chat {}: {}", chat_id, e);
+                        format!("{} {}", messages.summary_failed, e)
+                    }
+                };
+                Self::send_message_safe(&bot, chat_id, &reply).await?;
+            }
+            Command::Memory => {
+                let reply = match persistence.read().await.get_memory(chat_id.0).await {
+                    Ok(facts) if facts.is_empty() => messages.nothing_remembered.to_string(),
+                    Ok(facts) => {
+                        let lines: Vec<String> = facts
+                            .iter()
+                            .map(|(key, value)| format!("• {}: {}", key, value))
+                            .collect();
+                        format!("{}\n\n{}", messages.remembered_facts, lines.join("\n"))
+                    }
+                    Err(e) => {
+                        error!("Failed to load chat memory: {}", e);
+                        format!("{} {}", messages.memory_not_loaded, e)
+                    }
+                };
+                Self::send_message_safe(&bot, chat_id, &reply).await?;
+            }
+            // Facts of a group belong to all its members
+            Command::Forget(_) if !msg.chat.is_private() && !admins.contains(&msg) => {
+                Self::send_message_safe(&bot, chat_id, messages.admins_only).await?;
+            }
+            Command::Forget(key) => {
+                let key = Some(key.trim()).filter(|key| !key.is_empty());
+                let forgotten = persistence.read().await.forget(chat_id.0, key).await;
+                let reply = match (forgotten, key) {
+                    (Ok(0), _) => messages.nothing_to_forget.to_string(),
+                    (Ok(_), Some(key)) => format!("{} {}", messages.forgot_key, key),
+                    (Ok(count), None) => format!("{} {}", messages.forgot_facts, count),
+                    (Err(e), _) => {
+                        error!("Failed to clear chat memory: {}", e);
+                        format!("{} {}", messages.forget_failed, e)
+                    }
+                };
+                Self::send_message_safe(&bot, chat_id, &reply).await?;
+            }
+            Command::ReloadSkills => {
+                let reply = if admins.contains(&msg) {
+                    Self::reload_skills(&skills, &provider, messages).await
+                } else {
+                    messages.admins_only.to_string()
+                };
+                Self::send_message_safe(&bot, chat_id, &reply).await?;
+            }
+            // Answered by `handle_retry`, which has the dependencies it needs
+            Command::Retry(_) => {}
         }
 
         Ok(())
     }
 
+    /// Discover the skills again and list them in the system prompt
+    ///
+    /// Returns the reply with the new skill count and the skills that
+    /// failed to load.
+    async fn reload_skills(
+        skills: &RwLock<SkillsRegistry>,
+        provider: &RwLock<ProviderService>,
+        messages: &Messages,
+    ) -> String {
+        let (summaries, reply) = {
+            let mut skills = skills.write().await;
+            let before = skills.len();
+            if let Err(e) = skills.reload().await {
+                error!("Failed to reload skills: {}", e);
+                return format!("{} {}", messages.skills_not_reloaded, e);
+            }
+            info!("Reloaded skills: {} (was {})", skills.len(), before);
+
+            let mut reply = format!(
+                "{} {} ({} {})",
+                messages.skills_reloaded,
+                skills.len(),
+                messages.previously,
+                before
+            );
+            if !skills.failures().is_empty() {
+                reply.push_str(&format!("\n\n{}", messages.skills_failed));
+                for failure in skills.failures() {
+                    reply.push_str(&format!("\n• {}", failure));
+                }
+            }
+            (skills.summaries(), reply)
+        };
+        provider.read().await.set_skill_summaries(summaries);
+        reply
+    }
+
+    /// Fold the chat's older messages into its summary with the model
+    ///
+    /// Returns how many messages were summarized and the estimated tokens
+    /// saved, or `None` if the history is too short to summarize.
+    async fn summarize_chat(
+        provider: &ProviderService,
+        persistence: &RwLock<PersistenceService>,
+        costs: &CostTracker,
+        chat_id: i64,
+    ) -> Result<Option<(usize, usize)>> {
+        let (previous, messages) = {
+            let persistence = persistence.read().await;
+            (
+                persistence.get_chat_summary(chat_id).await?,
+                persistence
+                    .get_recent_messages(chat_id, SUMMARY_SOURCE_MESSAGES)
+                    .await?,
+            )
+        };
+
+        let provider = &*Self::chat_provider(persistence, provider, chat_id).await;
+        let model = Self::chat_model(persistence, provider, chat_id).await;
+        let previous = previous.map(|p| p.summary);
+        let Some(summary) = provider
+            .summarize_history(&messages, previous.as_deref(), model.as_deref())
+            .await?
+        else {
+            return Ok(None);
+        };
+        let Some(covers_until) = messages
+            .iter()
+            .filter(|m| summary.messages_covered.contains(&m.id))
+            .map(|m| m.timestamp)
+            .max()
+        else {
+            return Ok(None);
+        };
+
+        let persistence = persistence.read().await;
+        persistence
+            .save_chat_summary(
+                chat_id,
+                &ChatSummary {
+                    summary: summary.text,
+                    covers_until,
+                },
+            )
+            .await?;
+        let model = model.as_deref().unwrap_or(provider.default_model());
+        costs
+            .record(&persistence, chat_id, model, &summary.usage)
+            .await;
+
+        info!(
+            "Summarized {} messages of chat {}, saving about {} tokens",
+            summary.messages_covered.len(),
+            chat_id,
+            summary.tokens_saved
+        );
+        Ok(Some((summary.messages_covered.len(), summary.tokens_saved)))
+    }
+
+    /// Summarize the chat in the background once its history passes the
+    /// provider's automatic summary threshold
+    fn spawn_auto_summary(
+        provider: Arc<RwLock<ProviderService>>,
+        persistence: Arc<RwLock<PersistenceService>>,
+        costs: Arc<CostTracker>,
+        chat_id: i64,
+    ) {
+        tokio::spawn(
+            async move {
+                let messages = persistence
+                    .read()
+                    .await
+                    .get_recent_messages(chat_id, SUMMARY_SOURCE_MESSAGES)
+                    .await
+                    .unwrap_or_default();
+                let provider = provider.read().await;
+                if !provider.needs_summary(&messages) {
+                    return;
+                }
+                if costs.budget_reached(&*persistence.read().await).await {
+                    info!("Daily budget reached, not summarizing chat {}", chat_id);
+                    return;
+                }
+                if let Err(e) = Self::summarize_chat(&provider, &persistence, &costs, chat_id).await
+                {
+                    warn!("Failed to summarize chat {}: {}", chat_id, e);
+                }
+            }
+            .in_current_span(),
+        );
+    }
+
+    /// Add the chat's id, the summary of its older messages, its remembered
+    /// facts and the tool rounds saved since `history` began to the
+    /// conversation metadata
+    async fn with_chat_context(
+        persistence: &RwLock<PersistenceService>,
+        chat_id: i64,
+        history: &[RustClawMessage],
+        metadata: &ConversationMetadata,
+    ) -> ConversationMetadata {
+        let persistence = persistence.read().await;
+        let summary = persistence
+            .get_chat_summary(chat_id)
+            .await
+            .unwrap_or_else(|e| {
+                error!("Failed to load chat summary: {}", e);
+                None
+            });
+        let facts = persistence.get_memory(chat_id).await.unwrap_or_else(|e| {
+            error!("Failed to load chat memory: {}", e);
+            Vec::new()
+        });
+        let tool_rounds = match history.first() {
+            Some(first) => persistence
+                .get_tool_rounds(chat_id, first.timestamp)
+                .await
+                .unwrap_or_else(|e| {
+                    error!("Failed to load tool rounds: {}", e);
+                    Vec::new()
+                }),
+            None => Vec::new(),
+        };
+        ConversationMetadata {
+            summary: summary.map(|s| s.summary),
+            facts,
+            chat_id: Some(chat_id),
+            tool_rounds: tool_rounds
+                .into_iter()
+                .map(|saved| ConversationTurn::from_chat_message(saved.message, saved.timestamp))
+                .collect(),
+            ..metadata.clone()
+        }
+    }
+
+    /// List the tools currently offered to the model, built-in and MCP
+    /// alike, with the first line of each description
+    fn tools_reply(tools: &ToolRegistry, messages: &Messages) -> String {
+        let mut tools = tools.get_tools();
+        if tools.is_empty() {
+            return messages.no_tools.to_string();
+        }
+        tools.sort_by(|a, b| a.function.name.cmp(&b.function.name));
+
+        let mut reply = format!("{} ({}):\n", messages.available_tools, tools.len());
+        for tool in &tools {
+            let summary = tool.function.description.lines().next().unwrap_or_default();
+            let summary: String = summary.chars().take(TOOL_SUMMARY_CHARS).collect();
+            reply.push_str(&format!(
+                "\n• **{}** - {}",
+                tool.function.name,
+                summary.trim()
+            ));
+        }
+        reply.push_str(&format!("\n\n{}", messages.sensitive_files_note));
+        reply
+    }
+
+    /// List the MCP servers or describe one, returning the reply text
+    async fn handle_mcp_command(mcp: &MCPToolRegistry, args: &str, messages: &Messages) -> String {
+        let servers = mcp.server_names().await;
+        if servers.is_empty() {
+            return messages.no_mcp_servers.to_string();
+        }
+
+        let mut words = args.split_whitespace();
+        match (words.next(), words.next()) {
+            (None, _) => format!(
+                "{} {}\n\n{}",
+                messages.mcp_servers,
+                servers.join(", "),
+                messages.mcp_describe_hint
+            ),
+            (Some("describe"), Some(server)) => match mcp.describe(server).await {
+                Some(description) => format!("🔌 {}", description),
+                None => format!(
+                    "{} {}\n\n{} {}",
+                    messages.unknown_mcp_server,
+                    server,
+                    messages.connected,
+                    servers.join(", ")
+                ),
+            },
+            _ => messages.mcp_usage.to_string(),
+        }
+    }
+
+    /// Show or change the model for a chat, returning the reply text
+    async fn handle_model_command(
+        persistence: &RwLock<PersistenceService>,
+        provider: &RwLock<ProviderService>,
+        chat_id: i64,
+        name: &str,
+        messages: &Messages,
+    ) -> String {
+        let provider = provider.read().await;
+        let provider = Self::chat_provider(persistence, &provider, chat_id).await;
+        let default_model = provider.default_model();
+        let available = provider.allowed_models().join(", ");
+
+        if name.is_empty() {
+            let current = Self::chat_model(persistence, &provider, chat_id).await;
+            return match current {
+                Some(model) => format!(
+                    "{} {}\n\n{} {}",
+                    messages.current_model, model, messages.available, available
+                ),
+                None => format!(
+                    "{} {} {}\n\n{} {}",
+                    messages.current_model,
+                    default_model,
+                    messages.default_marker,
+                    messages.available,
+                    available
+                ),
+            };
+        }
+
+        // Selecting the default model clears the override
+        let model = (name != "default" && name != default_model).then_some(name);
+        if let Some(model) = model {
+            if !provider.is_model_allowed(model) {
+                return format!(
+                    "{} {}\n\n{} {}",
+                    messages.model_not_allowed, model, messages.available, available
+                );
+            }
+        }
+
+        let persistence = persistence.read().await;
+        match persistence.set_chat_model(chat_id, model).await {
+            Ok(()) => format!("{} {}", messages.model_set, model.unwrap_or(default_model)),
+            Err(e) => {
+                error!("Failed to save chat model: {}", e);
+                format!("{} {}", messages.model_not_set, e)
+            }
+        }
+    }
+
+    /// Show or change the provider for a chat, returning the reply text
+    async fn handle_provider_command(
+        persistence: &RwLock<PersistenceService>,
+        provider: &RwLock<ProviderService>,
+        chat_id: i64,
+        name: &str,
+        messages: &Messages,
+    ) -> String {
+        let provider = provider.read().await;
+        let default_provider = provider.default_provider();
+        let available = provider.providers().join(", ");
+
+        if name.is_empty() {
+            let current = Self::chat_provider(persistence, &provider, chat_id).await;
+            let current = current.default_provider();
+            let marker = if current == default_provider {
+                format!(" {}", messages.default_marker)
+            } else {
+                String::new()
+            };
+            return format!(
+                "{} {}{}\n\n{} {}",
+                messages.current_provider, current, marker, messages.available, available
+            );
+        }
+
+        // Selecting the default provider clears the override
+        let selected = (name != "default" && name != default_provider).then_some(name);
+        if let Some(selected) = selected {
+            if !provider.is_provider_allowed(selected) {
+                return format!(
+                    "{} {}\n\n{} {}",
+                    messages.provider_not_available, selected, messages.available, available
+                );
+            }
+        }
+
+        let persistence = persistence.read().await;
+        match persistence.set_chat_provider(chat_id, selected).await {
+            Ok(()) => {
+                let selected = provider.for_provider(selected);
+                format!(
+                    "{} {} {} {}",
+                    messages.provider_set,
+                    selected.default_provider(),
+                    messages.with_model,
+                    selected.default_model()
+                )
+            }
+            Err(e) => {
+                error!("Failed to save chat provider: {}", e);
+                format!("{} {}", messages.provider_not_set, e)
+            }
+        }
+    }
+
+    /// Get the service for the provider selected for a chat, ignoring
+    /// selections that are no longer allowed
+    async fn chat_provider<'a>(
+        persistence: &RwLock<PersistenceService>,
+        provider: &'a ProviderService,
+        chat_id: i64,
+    ) -> Cow<'a, ProviderService> {
+        let name = persistence
+            .read()
+            .await
+            .get_chat_provider(chat_id)
+            .await
+            .unwrap_or_else(|e| {
+                error!("Failed to load chat provider: {}", e);
+                None
+            });
+        provider.for_provider(name.as_deref())
+    }
+
+    /// Get the model selected for a chat, ignoring selections that are no
+    /// longer allowed
+    async fn chat_model(
+        persistence: &RwLock<PersistenceService>,
+        provider: &ProviderService,
+        chat_id: i64,
+    ) -> Option<String> {
+        let model = persistence
+            .read()
+            .await
+            .get_chat_model(chat_id)
+            .await
+            .unwrap_or_else(|e| {
+                error!("Failed to load chat model: {}", e);
+                None
+            })?;
+
+        if provider.is_model_allowed(&model) {
+            Some(model)
+        } else {
+            warn!(
+                "Chat {} selected model '{}' which is no longer allowed",
+                chat_id, model
+            );
+            None
+        }
+    }
+
     /// Handle text messages
+    #[allow(clippy::too_many_arguments)]
+    #[instrument(name = "request", skip_all, fields(request_id = %Uuid::new_v4(), chat_id = msg.chat.id.0))]
     async fn handle_text_message(
         bot: Bot,
         msg: Message,
         persistence: Arc<RwLock<PersistenceService>>,
         provider: Arc<RwLock<ProviderService>>,
+        group: Arc<GroupFilter>,
+        skills: Arc<RwLock<SkillsRegistry>>,
+        limiter: Arc<RequestLimiter>,
+        costs: Arc<CostTracker>,
+        status: ToolStatus,
+        pages: Arc<PageIndicator>,
+        confirmations: Arc<Confirmations>,
+        messages: &'static Messages,
     ) -> Result<(), teloxide::RequestError> {
-        let text = match msg.text() {
+        // Prompt with any group mention/prefix stripped
+        let text = match group.prompt_for(&msg) {
             Some(t) => t,
             None => return Ok(()),
         };
+        let text = text.as_str();
 
         let chat_id = msg.chat.id;
+        let Some(_permit) = limiter.try_acquire() else {
+            return Self::send_message_safe(&bot, chat_id, messages.busy).await;
+        };
+        if costs.budget_reached(&*persistence.read().await).await {
+            return Self::send_message_safe(&bot, chat_id, messages.budget_reached).await;
+        }
         let user_id = msg.from.as_ref().map(|u| u.id.0 as i64).unwrap_or(0);
         let user = User::new(user_id);
 
@@ -347,22 +1538,239 @@ impl TelegramService {
         };
 
         // Get AI response using agentic loop (handles tools automatically)
-        let response = {
-            let provider = provider.read().await;
-            provider
-                .complete_agentic_default(&recent_messages, text)
-                .await
+        let metadata = Self::conversation_metadata(&msg);
+        let response = Self::complete_text(
+            &bot,
+            &provider,
+            persistence.clone(),
+            &costs,
+            status,
+            &skills,
+            chat_id.0,
+            &recent_messages,
+            text,
+            &metadata,
+        )
+        .await;
+
+        let delivered = Self::deliver_response(
+            &bot,
+            chat_id,
+            user_id,
+            response,
+            &persistence,
+            &pages,
+            &confirmations,
+            messages,
+        )
+        .await;
+        Self::spawn_auto_summary(provider, persistence, costs, chat_id.0);
+        delivered
+    }
+
+    /// Re-run the last message the user sent in the chat, optionally with
+    /// another sampling temperature
+    #[allow(clippy::too_many_arguments)]
+    #[instrument(name = "request", skip_all, fields(request_id = %Uuid::new_v4(), chat_id = msg.chat.id.0))]
+    async fn handle_retry(
+        bot: Bot,
+        msg: Message,
+        cmd: Command,
+        persistence: Arc<RwLock<PersistenceService>>,
+        provider: Arc<RwLock<ProviderService>>,
+        skills: Arc<RwLock<SkillsRegistry>>,
+        limiter: Arc<RequestLimiter>,
+        costs: Arc<CostTracker>,
+        status: ToolStatus,
+        pages: Arc<PageIndicator>,
+        confirmations: Arc<Confirmations>,
+        messages: &'static Messages,
+    ) -> Result<(), teloxide::RequestError> {
+        let chat_id = msg.chat.id;
+        let Command::Retry(args) = cmd else {
+            return Ok(());
+        };
+        let Ok(temperature) = Self::retry_temperature(&args) else {
+            return Self::send_message_safe(&bot, chat_id, messages.retry_usage).await;
         };
+        let Some(_permit) = limiter.try_acquire() else {
+            return Self::send_message_safe(&bot, chat_id, messages.busy).await;
+        };
+        if costs.budget_reached(&*persistence.read().await).await {
+            return Self::send_message_safe(&bot, chat_id, messages.budget_reached).await;
+        }
+
+        // Only the user's own request, not a reply recorded for a button
+        let user_id = msg.from.as_ref().map(|u| u.id.0 as i64).unwrap_or(0);
+        let last = persistence
+            .read()
+            .await
+            .get_last_user_message(chat_id.0, user_id, confirm::CONFIRMED_PROMPT)
+            .await
+            .unwrap_or_else(|e| {
+                error!("Failed to load last message: {}", e);
+                None
+            });
+        let Some(last) = last else {
+            return Self::send_message_safe(&bot, chat_id, messages.nothing_to_retry).await;
+        };
+        let MessageContent::Text(text) = &last.content else {
+            return Self::send_message_safe(&bot, chat_id, messages.retry_text_only).await;
+        };
+        info!(
+            "Retrying last message in chat {} (temperature {:?})",
+            chat_id, temperature
+        );
+
+        // The context the message was first answered in, without the answer
+        let recent_messages: Vec<RustClawMessage> = persistence
+            .read()
+            .await
+            .get_recent_messages(chat_id.0, 10)
+            .await
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|m| m.timestamp < last.timestamp)
+            .collect();
+
+        let metadata = Self::conversation_metadata(&msg);
+        let retry_provider = match temperature {
+            Some(temperature) => Some(RwLock::new(
+                provider.read().await.clone().with_temperature(temperature),
+            )),
+            None => None,
+        };
+        let response = Self::complete_text(
+            &bot,
+            retry_provider.as_ref().unwrap_or(&provider),
+            persistence.clone(),
+            &costs,
+            status,
+            &skills,
+            chat_id.0,
+            &recent_messages,
+            text,
+            &metadata,
+        )
+        .await;
+
+        Self::deliver_response(
+            &bot,
+            chat_id,
+            user_id,
+            response,
+            &persistence,
+            &pages,
+            &confirmations,
+            messages,
+        )
+        .await
+    }
+
+    /// Parse the arguments of `/retry`: nothing, or a sampling temperature
+    /// from 0 to 2
+    fn retry_temperature(args: &str) -> Result<Option<f32>, ()> {
+        let args = args.trim();
+        if args.is_empty() {
+            return Ok(None);
+        }
+        match args.parse::<f32>() {
+            Ok(temperature) if (0.0..=2.0).contains(&temperature) => Ok(Some(temperature)),
+            _ => Err(()),
+        }
+    }
+
+    /// Answer a text message, activating the skill it names with a leading
+    /// `/skill-name`, if any
+    #[allow(clippy::too_many_arguments)]
+    async fn complete_text(
+        bot: &Bot,
+        provider: &RwLock<ProviderService>,
+        persistence: Arc<RwLock<PersistenceService>>,
+        costs: &CostTracker,
+        status: ToolStatus,
+        skills: &RwLock<SkillsRegistry>,
+        chat_id: i64,
+        history: &[RustClawMessage],
+        text: &str,
+        metadata: &ConversationMetadata,
+    ) -> Result<AgenticResponse> {
+        let skill = Self::activate_skill(skills, text).await;
+        let provider = provider.read().await;
+        match &skill {
+            Some((skill, request)) => {
+                let response = Self::complete_with_skill(
+                    bot,
+                    &provider,
+                    persistence.clone(),
+                    costs,
+                    status,
+                    chat_id,
+                    history,
+                    skill,
+                    request,
+                    metadata,
+                )
+                .await;
+                if !matches!(&response, Ok(r) if r.needs_confirmation()) {
+                    Self::record_skill_use(skills, &provider, persistence, skill, &response).await;
+                }
+                response
+            }
+            None => {
+                Self::complete_and_record(
+                    bot,
+                    &provider,
+                    persistence,
+                    costs,
+                    status,
+                    chat_id,
+                    history,
+                    text,
+                    metadata,
+                )
+                .await
+            }
+        }
+    }
 
+    /// Send the answer to a text message and save it to the conversation, or
+    /// ask for confirmation if the agentic loop paused on a tool call
+    #[allow(clippy::too_many_arguments)]
+    async fn deliver_response(
+        bot: &Bot,
+        chat_id: ChatId,
+        user_id: i64,
+        response: Result<AgenticResponse>,
+        persistence: &RwLock<PersistenceService>,
+        pages: &PageIndicator,
+        confirmations: &Confirmations,
+        messages: &Messages,
+    ) -> Result<(), teloxide::RequestError> {
         match response {
+            Ok(response) if response.needs_confirmation() => {
+                Self::ask_confirmation(
+                    bot,
+                    chat_id,
+                    user_id,
+                    response,
+                    persistence,
+                    confirmations,
+                    messages,
+                )
+                .await?;
+            }
             Ok(response) => {
-                Self::send_message_safe(&bot, chat_id, &response).await?;
+                let mut parts = response.parts();
+                let response_text = Self::parts_text(&parts);
+                Self::flag_truncation(&response, &mut parts, messages);
+                Self::send_parts(bot, chat_id, &parts, pages, messages).await?;
 
                 // Save AI response to context so follow-up questions work
                 let ai_msg = RustClawMessage::new(
                     chat_id.0,
                     User::new(0), // System/AI user
-                    MessageContent::Text(response.clone()),
+                    MessageContent::Text(response_text),
                 );
                 let persistence = persistence.write().await;
                 if let Err(e) = persistence.save_message(&ai_msg).await {
@@ -371,7 +1779,7 @@ impl TelegramService {
             }
             Err(e) => {
                 error!("Failed to get AI response: {}", e);
-                Self::send_message_safe(&bot, chat_id, &format!("❌ Error: {}", e)).await?;
+                Self::send_message_safe(bot, chat_id, &Self::error_reply(messages, &e)).await?;
             }
         }
 
@@ -379,6 +1787,8 @@ impl TelegramService {
     }
 
     /// Handle photo messages
+    #[allow(clippy::too_many_arguments)]
+    #[instrument(name = "request", skip_all, fields(request_id = %Uuid::new_v4(), chat_id = msg.chat.id.0))]
     async fn handle_photo_message(
         bot: Bot,
         msg: Message,
@@ -386,16 +1796,28 @@ impl TelegramService {
         provider: Arc<RwLock<ProviderService>>,
         downloads_dir: PathBuf,
         download_bot: Bot,
+        limiter: Arc<RequestLimiter>,
+        costs: Arc<CostTracker>,
+        status: ToolStatus,
+        pages: Arc<PageIndicator>,
+        confirmations: Arc<Confirmations>,
+        messages: &'static Messages,
     ) -> Result<(), teloxide::RequestError> {
         let photos = match msg.photo() {
             Some(p) if !p.is_empty() => p,
             _ => return Ok(()),
         };
+        let Some(_permit) = limiter.try_acquire() else {
+            return Self::send_message_safe(&bot, msg.chat.id, messages.busy).await;
+        };
+        if costs.budget_reached(&*persistence.read().await).await {
+            return Self::send_message_safe(&bot, msg.chat.id, messages.budget_reached).await;
+        }
 
         let chat_id = msg.chat.id;
 
         // Acknowledge receipt
-        bot.send_message(chat_id, "📷 Processing image...").await?;
+        bot.send_message(chat_id, messages.processing_image).await?;
 
         // Get the largest photo (highest quality)
         let photo = photos.last().unwrap();
@@ -412,7 +1834,7 @@ impl TelegramService {
 
         if let Err(e) = Self::download_file(&download_bot, &file_id.0, &local_path).await {
             error!("Failed to download photo: {}", e);
-            bot.send_message(chat_id, format!("❌ Failed to download image: {}", e))
+            bot.send_message(chat_id, format!("{} {}", messages.image_not_downloaded, e))
                 .await?;
             return Ok(());
         }
@@ -473,21 +1895,44 @@ impl TelegramService {
         };
 
         // Get AI response
+        let metadata = Self::conversation_metadata(&msg);
         let response = {
             let provider = provider.read().await;
-            provider
-                .complete_agentic_default(&recent_messages, &image_prompt)
-                .await
+            Self::complete_and_record(
+                &bot,
+                &provider,
+                persistence.clone(),
+                &costs,
+                status,
+                chat_id.0,
+                &recent_messages,
+                &image_prompt,
+                &metadata,
+            )
+            .await
         };
 
         match response {
+            Ok(response) if response.needs_confirmation() => {
+                Self::ask_confirmation(
+                    &bot,
+                    chat_id,
+                    user_id,
+                    response,
+                    &persistence,
+                    &confirmations,
+                    messages,
+                )
+                .await?;
+            }
             Ok(response) => {
-                let response_text = if response.trim().is_empty() {
-                    "✅ Image processed. What would you like me to do with it?".to_string()
-                } else {
-                    response.clone()
-                };
-                Self::send_message_safe(&bot, chat_id, &response_text).await?;
+                let mut parts = response.parts();
+                if parts.is_empty() {
+                    parts.push(ResponsePart::Text(messages.image_processed.to_string()));
+                }
+                let response_text = Self::parts_text(&parts);
+                Self::flag_truncation(&response, &mut parts, messages);
+                Self::send_parts(&bot, chat_id, &parts, &pages, messages).await?;
 
                 // Save AI response to context so follow-up questions work
                 let ai_msg = RustClawMessage::new(
@@ -502,7 +1947,7 @@ impl TelegramService {
             }
             Err(e) => {
                 error!("Failed to get AI response: {}", e);
-                Self::send_message_safe(&bot, chat_id, &format!("❌ Error: {}", e)).await?;
+                Self::send_message_safe(&bot, chat_id, &Self::error_reply(messages, &e)).await?;
             }
         }
 
@@ -510,6 +1955,8 @@ impl TelegramService {
     }
 
     /// Handle document messages
+    #[allow(clippy::too_many_arguments)]
+    #[instrument(name = "request", skip_all, fields(request_id = %Uuid::new_v4(), chat_id = msg.chat.id.0))]
     async fn handle_document_message(
         bot: Bot,
         msg: Message,
@@ -517,11 +1964,23 @@ impl TelegramService {
         provider: Arc<RwLock<ProviderService>>,
         downloads_dir: PathBuf,
         download_bot: Bot,
+        limiter: Arc<RequestLimiter>,
+        costs: Arc<CostTracker>,
+        status: ToolStatus,
+        pages: Arc<PageIndicator>,
+        confirmations: Arc<Confirmations>,
+        messages: &'static Messages,
     ) -> Result<(), teloxide::RequestError> {
         let doc = match msg.document() {
             Some(d) => d,
             None => return Ok(()),
         };
+        let Some(_permit) = limiter.try_acquire() else {
+            return Self::send_message_safe(&bot, msg.chat.id, messages.busy).await;
+        };
+        if costs.budget_reached(&*persistence.read().await).await {
+            return Self::send_message_safe(&bot, msg.chat.id, messages.budget_reached).await;
+        }
 
         let chat_id = msg.chat.id;
 
@@ -547,14 +2006,17 @@ impl TelegramService {
 
         bot.send_message(
             chat_id,
-            format!("📄 Downloading: {} ({} bytes)", filename, file_size),
+            format!(
+                "{} {} ({} {})",
+                messages.downloading, filename, file_size, messages.bytes
+            ),
         )
         .await?;
 
         // Download the document
         if let Err(e) = Self::download_file(&download_bot, &file_id.0, &local_path).await {
             error!("Failed to download document: {}", e);
-            bot.send_message(chat_id, format!("❌ Failed to download file: {}", e))
+            bot.send_message(chat_id, format!("{} {}", messages.file_not_downloaded, e))
                 .await?;
             return Ok(());
         }
@@ -595,36 +2057,57 @@ impl TelegramService {
                 .unwrap_or_default()
         };
 
-        // Build prompt with document context
-        let caption = msg.caption().unwrap_or("[No caption]");
-        let doc_prompt = format!(
-            "The user sent a file.\n\
-             Filename: {}\n\
-             MIME type: {:?}\n\
-             Size: {} bytes\n\
-             Caption: {}\n\
-             Saved at: {:?}\n\n\
-             Use available tools (like read_file, bash) to examine the file if it's a text-based format. \
-             Ask the user what they want you to do with it.",
-            filename, doc.mime_type, file_size, caption, local_path
+        // Build prompt with the file's text, or its path
+        let mime_type = doc.mime_type.as_ref().map(|m| m.to_string());
+        let text = DocumentText::read(&local_path, original_name, mime_type.as_deref()).await;
+        let doc_prompt = documents::document_prompt(
+            &filename,
+            mime_type.as_deref(),
+            file_size,
+            msg.caption(),
+            &local_path,
+            &text,
         );
 
         // Get AI response
+        let metadata = Self::conversation_metadata(&msg);
         let response = {
             let provider = provider.read().await;
-            provider
-                .complete_agentic_default(&recent_messages, &doc_prompt)
-                .await
+            Self::complete_and_record(
+                &bot,
+                &provider,
+                persistence.clone(),
+                &costs,
+                status,
+                chat_id.0,
+                &recent_messages,
+                &doc_prompt,
+                &metadata,
+            )
+            .await
         };
 
         match response {
+            Ok(response) if response.needs_confirmation() => {
+                Self::ask_confirmation(
+                    &bot,
+                    chat_id,
+                    user_id,
+                    response,
+                    &persistence,
+                    &confirmations,
+                    messages,
+                )
+                .await?;
+            }
             Ok(response) => {
-                let response_text = if response.trim().is_empty() {
-                    "✅ File processed. What would you like me to do with it?".to_string()
-                } else {
-                    response.clone()
-                };
-                Self::send_message_safe(&bot, chat_id, &response_text).await?;
+                let mut parts = response.parts();
+                if parts.is_empty() {
+                    parts.push(ResponsePart::Text(messages.file_processed.to_string()));
+                }
+                let response_text = Self::parts_text(&parts);
+                Self::flag_truncation(&response, &mut parts, messages);
+                Self::send_parts(&bot, chat_id, &parts, &pages, messages).await?;
 
                 // Save AI response to context so follow-up questions work
                 let ai_msg = RustClawMessage::new(
@@ -639,13 +2122,139 @@ impl TelegramService {
             }
             Err(e) => {
                 error!("Failed to get AI response: {}", e);
-                Self::send_message_safe(&bot, chat_id, &format!("❌ Error: {}", e)).await?;
+                Self::send_message_safe(&bot, chat_id, &Self::error_reply(messages, &e)).await?;
             }
         }
 
         Ok(())
     }
 
+    /// Handle a press of a confirmation button: run the paused tool calls
+    /// and continue the conversation, or cancel them
+    #[allow(clippy::too_many_arguments)]
+    #[instrument(name = "request", skip_all, fields(request_id = %Uuid::new_v4(), user_id = query.from.id.0))]
+    async fn handle_confirmation(
+        bot: Bot,
+        query: CallbackQuery,
+        persistence: Arc<RwLock<PersistenceService>>,
+        provider: Arc<RwLock<ProviderService>>,
+        limiter: Arc<RequestLimiter>,
+        costs: Arc<CostTracker>,
+        status: ToolStatus,
+        pages: Arc<PageIndicator>,
+        confirmations: Arc<Confirmations>,
+        messages: &'static Messages,
+    ) -> Result<(), teloxide::RequestError> {
+        bot.answer_callback_query(query.id.clone()).await?;
+
+        let Some(message) = &query.message else {
+            return Ok(());
+        };
+        let chat_id = message.chat().id;
+        let confirmed = match query.data.as_deref() {
+            Some(confirm::CONFIRM_YES) => true,
+            Some(confirm::CONFIRM_NO) => false,
+            _ => return Ok(()),
+        };
+
+        let user_id = query.from.id.0 as i64;
+        if !confirmations.awaits(chat_id.0, user_id) {
+            return Self::send_message_safe(&bot, chat_id, messages.nothing_to_confirm).await;
+        }
+
+        // Refusing to run leaves the calls and the buttons in place, so the
+        // user can press again
+        let permit = if confirmed {
+            let Some(permit) = limiter.try_acquire() else {
+                return Self::send_message_safe(&bot, chat_id, messages.busy).await;
+            };
+            if costs.budget_reached(&*persistence.read().await).await {
+                return Self::send_message_safe(&bot, chat_id, messages.budget_reached).await;
+            }
+            Some(permit)
+        } else {
+            None
+        };
+
+        let Some(pending) = confirmations.take(chat_id.0, user_id) else {
+            return Self::send_message_safe(&bot, chat_id, messages.nothing_to_confirm).await;
+        };
+        // Remove the buttons so the question cannot be answered twice
+        if let Err(e) = bot.edit_message_reply_markup(chat_id, message.id()).await {
+            warn!("Failed to remove confirmation buttons: {}", e);
+        }
+
+        let Some(_permit) = permit else {
+            for confirmation in &pending {
+                info!("User declined {}", confirmation.call.function.name);
+            }
+            let ai_msg = RustClawMessage::new(
+                chat_id.0,
+                User::new(0), // System/AI user
+                MessageContent::Text(messages.cancelled.to_string()),
+            );
+            if let Err(e) = persistence.write().await.save_message(&ai_msg).await {
+                error!("Failed to save AI response: {}", e);
+            }
+            return Self::send_message_safe(&bot, chat_id, messages.cancelled).await;
+        };
+
+        // Record the answer as the user's reply
+        let reply = RustClawMessage::new(
+            chat_id.0,
+            User::new(user_id),
+            MessageContent::Text(confirm::CONFIRMED_PROMPT.to_string()),
+        );
+        {
+            let persistence = persistence.write().await;
+            if let Err(e) = persistence.save_message(&reply).await {
+                error!("Failed to save message: {}", e);
+            }
+        }
+
+        let recent_messages = {
+            let persistence = persistence.read().await;
+            persistence
+                .get_recent_messages(chat_id.0, 10)
+                .await
+                .unwrap_or_default()
+        };
+
+        let metadata = ConversationMetadata {
+            user_name: Some(query.from.first_name.clone()),
+            username: query.from.username.clone(),
+            locale: query.from.language_code.clone(),
+            ..Default::default()
+        };
+        let response = {
+            let provider = provider.read().await;
+            Self::resume_and_record(
+                &bot,
+                &provider,
+                persistence.clone(),
+                &costs,
+                status,
+                chat_id.0,
+                &recent_messages,
+                &pending,
+                &metadata,
+            )
+            .await
+        };
+
+        Self::deliver_response(
+            &bot,
+            chat_id,
+            user_id,
+            response,
+            &persistence,
+            &pages,
+            &confirmations,
+            messages,
+        )
+        .await
+    }
+
     /// Download a file from Telegram
     async fn download_file(bot: &Bot, file_id: &str, local_path: &PathBuf) -> Result<()> {
         let file = bot
@@ -670,7 +2279,18 @@ impl TelegramService {
 // ============================================================================
 
 /// Tool for executing bash commands (safe subset)
-pub struct BashTool;
+///
+/// A disabled tool stays registered but refuses every command, so the model
+/// learns that shell access is unavailable instead of assuming success.
+pub struct BashTool {
+    enabled: bool,
+}
+
+impl Default for BashTool {
+    fn default() -> Self {
+        Self::new(true)
+    }
+}
 
 impl ToolFunction for BashTool {
     fn definition(&self) -> Tool {
@@ -723,17 +2343,104 @@ impl ToolFunction for BashTool {
         )
     }
 
-    fn execute(&self, args: serde_json::Value) -> Result<serde_json::Value> {
+    fn capabilities(&self) -> ToolCapabilities {
+        ToolCapabilities {
+            reads_files: true,
+            writes_files: true,
+            executes_commands: true,
+            network: true,
+        }
+    }
+
+    fn timeout_argument(&self) -> Option<&str> {
+        Some("timeout")
+    }
+
+    fn execute(&self, args: serde_json::Value) -> Result<serde_json::Value> {
+        self.execute_with(&args, None)
+    }
+
+    fn execute_streaming(
+        &self,
+        args: serde_json::Value,
+        output: &ToolOutputSink,
+    ) -> Result<serde_json::Value> {
+        self.execute_with(&args, Some(output))
+    }
+}
+
+/// Output of a command run by [`BashTool::run`]
+struct CommandRun {
+    stdout: Vec<u8>,
+    stderr: Vec<u8>,
+    status: std::process::ExitStatus,
+    /// Whether the command was killed at its timeout
+    timed_out: bool,
+}
+
+impl BashTool {
+    /// Create the tool, refusing all commands unless `enabled`
+    pub fn new(enabled: bool) -> Self {
+        Self { enabled }
+    }
+
+    /// Check and run a command, streaming its output to `output` if given
+    fn execute_with(
+        &self,
+        args: &serde_json::Value,
+        output: Option<&ToolOutputSink>,
+    ) -> Result<serde_json::Value> {
+        let (command, timeout) = match self.check_command(args)? {
+            Ok(command) => command,
+            Err(refusal) => return Ok(refusal),
+        };
+
+        match Self::run(command, timeout, output) {
+            Ok(run) => {
+                let mut result = Self::command_result(&run.stdout, &run.stderr, run.status);
+                if run.timed_out {
+                    warn!("Command timed out after {:?}: {}", timeout, command);
+                    result["success"] = serde_json::Value::Bool(false);
+                    result["timed_out"] = serde_json::Value::Bool(true);
+                    result["error"] = serde_json::Value::String(format!(
+                        "Command timed out after {}s and was killed; output up to then is shown",
+                        timeout.as_secs()
+                    ));
+                }
+                Ok(result)
+            }
+            Err(e) => Ok(serde_json::json!({
+                "success": false,
+                "error": format!("Failed to execute command: {}", e)
+            })),
+        }
+    }
+
+    /// Validate the arguments, returning the command to run with its
+    /// timeout or a refusal to report back to the model
+    fn check_command<'a>(
+        &self,
+        args: &'a serde_json::Value,
+    ) -> Result<std::result::Result<(&'a str, Duration), serde_json::Value>> {
         let command = args
             .get("command")
             .and_then(|c| c.as_str())
             .ok_or_else(|| anyhow!("Missing 'command' argument"))?;
 
-        let _timeout = args
+        if !self.enabled {
+            warn!("Blocked bash command (disabled by policy): {}", command);
+            return Ok(Err(serde_json::json!({
+                "success": false,
+                "blocked": true,
+                "error": "Shell execution is disabled by policy. Use the other available tools instead."
+            })));
+        }
+
+        let timeout = args
             .get("timeout")
             .and_then(|t| t.as_u64())
-            .unwrap_or(30)
-            .min(120);
+            .unwrap_or(DEFAULT_COMMAND_TIMEOUT_SECS)
+            .min(MAX_COMMAND_TIMEOUT_SECS);
 
         let confirm_destructive = args
             .get("confirm_destructive")
@@ -757,11 +2464,11 @@ impl ToolFunction for BashTool {
         ];
         for pattern in dangerous {
             if command.contains(pattern) {
-                return Ok(serde_json::json!({
+                return Ok(Err(serde_json::json!({
                     "success": false,
                     "blocked": true,
                     "error": format!("Command blocked: contains unsafe pattern '{}'", pattern.trim())
-                }));
+                })));
             }
         }
 
@@ -769,15 +2476,16 @@ impl ToolFunction for BashTool {
         if !confirm_sensitive {
             for pattern in SENSITIVE_PATTERNS {
                 if command.contains(pattern) {
-                    return Ok(serde_json::json!({
+                    return Ok(Err(serde_json::json!({
                         "success": false,
                         "needs_confirmation": true,
                         "confirmation_type": "sensitive_file",
+                        "confirm_arg": "confirm_sensitive",
                         "error": format!(
                             "⚠️ SENSITIVE FILE DETECTED: The command appears to access '{}' which may contain secrets, keys, or credentials.\n\nPlease ask the user: \"This command may access sensitive files. Do you want me to proceed?\"",
                             pattern
                         )
-                    }));
+                    })));
                 }
             }
         }
@@ -787,59 +2495,305 @@ impl ToolFunction for BashTool {
             let destructive_patterns = ["rm ", "rm -", "rmdir", "del ", "format ", "shred "];
             for pattern in destructive_patterns {
                 if command.contains(pattern) {
-                    return Ok(serde_json::json!({
+                    return Ok(Err(serde_json::json!({
                         "success": false,
                         "needs_confirmation": true,
                         "confirmation_type": "destructive",
+                        "confirm_arg": "confirm_destructive",
                         "error": format!(
                             "⚠️ DESTRUCTIVE COMMAND: '{}'\n\nThis will delete files. Please ask the user: \"This command will delete files. Are you sure you want to proceed?\"",
                             command
                         )
-                    }));
+                    })));
                 }
             }
         }
 
-        // Execute the command
-        let output = std::process::Command::new("bash")
-            .arg("-c")
+        Ok(Ok((command, Duration::from_secs(timeout))))
+    }
+
+    /// Run a command, killing it with everything it started once `timeout`
+    /// elapses
+    ///
+    /// Background processes that hold on to the command's output are
+    /// killed at the timeout too, even if the command itself exited.
+    ///
+    /// Stdout lines are sent to `output`, if given, as they arrive until
+    /// `MAX_STREAMED_BYTES` have been streamed.
+    fn run(
+        command: &str,
+        timeout: Duration,
+        output: Option<&ToolOutputSink>,
+    ) -> std::io::Result<CommandRun> {
+        use std::io::{BufRead, Read};
+        use std::process::Stdio;
+
+        let mut cmd = std::process::Command::new("bash");
+        cmd.arg("-c")
             .arg(command)
-            .output();
-
-        match output {
-            Ok(output) => {
-                let stdout = String::from_utf8_lossy(&output.stdout);
-                let stderr = String::from_utf8_lossy(&output.stderr);
-                let success = output.status.success();
-
-                // Truncate very long output
-                let stdout_str = if stdout.len() > 15000 {
-                    format!(
-                        "{}...\n\n[Output truncated: showing first 15KB of {} bytes total]",
-                        &stdout[..15000],
-                        stdout.len()
-                    )
-                } else {
-                    stdout.to_string()
-                };
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        // In a group of its own, so a timeout kills what it started too
+        #[cfg(unix)]
+        std::os::unix::process::CommandExt::process_group(&mut cmd, 0);
+        let mut child = cmd.spawn()?;
+
+        // Drain both pipes on other threads so a chatty command can't block
+        // on a full pipe. What they read is shared, so it can be taken even
+        // if a pipe is never closed.
+        let stderr = Arc::new(Mutex::new(Vec::new()));
+        let stderr_reader = child.stderr.take().map(|mut pipe| {
+            let stderr = stderr.clone();
+            std::thread::spawn(move || {
+                let mut buf = [0; 8192];
+                while let Ok(n @ 1..) = pipe.read(&mut buf) {
+                    stderr
+                        .lock()
+                        .unwrap_or_else(PoisonError::into_inner)
+                        .extend_from_slice(&buf[..n]);
+                }
+            })
+        });
+        let output = output.cloned();
+        let stdout = Arc::new(Mutex::new(Vec::new()));
+        let stdout_reader = child.stdout.take().map(|pipe| {
+            let stdout = stdout.clone();
+            std::thread::spawn(move || {
+                let mut reader = std::io::BufReader::new(pipe);
+                let mut line = Vec::new();
+                let mut streamed = 0;
+                while reader.read_until(b'\n', &mut line).unwrap_or(0) > 0 {
+                    if let Some(output) = output.as_ref().filter(|_| streamed < MAX_STREAMED_BYTES)
+                    {
+                        streamed += line.len();
+                        let text = String::from_utf8_lossy(&line);
+                        let mut sent = output
+                            .send(text.trim_end_matches(['\r', '\n']).to_string())
+                            .is_ok();
+                        if sent && streamed >= MAX_STREAMED_BYTES {
+                            sent = output
+                                .send(format!(
+                                    "[Streaming stopped after {} bytes; the command is still running]",
+                                    streamed
+                                ))
+                                .is_ok();
+                        }
+                        // Nobody is listening anymore: keep running, stop streaming
+                        if !sent {
+                            streamed = MAX_STREAMED_BYTES;
+                        }
+                    }
+                    stdout
+                        .lock()
+                        .unwrap_or_else(PoisonError::into_inner)
+                        .append(&mut line);
+                }
+            })
+        });
 
-                Ok(serde_json::json!({
-                    "success": success,
-                    "stdout": stdout_str,
-                    "stderr": stderr,
-                    "exit_code": output.status.code()
-                }))
+        let deadline = std::time::Instant::now() + timeout;
+        let (status, timed_out) = loop {
+            if let Some(status) = child.try_wait()? {
+                break (status, false);
             }
-            Err(e) => Ok(serde_json::json!({
-                "success": false,
-                "error": format!("Failed to execute command: {}", e)
-            })),
+            if std::time::Instant::now() >= deadline {
+                Self::kill(&mut child);
+                break (child.wait()?, true);
+            }
+            std::thread::sleep(COMMAND_POLL_INTERVAL);
+        };
+
+        // Processes the command left running in the background keep the
+        // pipes open after the shell exits: wait for them until the deadline
+        // only, then kill them
+        let readers = [stdout_reader, stderr_reader];
+        if !timed_out && !Self::wait_for(&readers, deadline) {
+            warn!("Killing processes left running by command: {}", command);
+            Self::kill(&mut child);
+        }
+        if !Self::wait_for(&readers, std::time::Instant::now() + PIPE_CLOSE_GRACE) {
+            warn!("Output of command still open after killing it: {}", command);
+        }
+
+        let take = |buf: &Mutex<Vec<u8>>| {
+            std::mem::take(&mut *buf.lock().unwrap_or_else(PoisonError::into_inner))
+        };
+        Ok(CommandRun {
+            stdout: take(&stdout),
+            stderr: take(&stderr),
+            status,
+            timed_out,
+        })
+    }
+
+    /// Wait until all `readers` finished or `deadline` passed, returning
+    /// whether they finished
+    fn wait_for(
+        readers: &[Option<std::thread::JoinHandle<()>>],
+        deadline: std::time::Instant,
+    ) -> bool {
+        loop {
+            if readers.iter().flatten().all(|reader| reader.is_finished()) {
+                return true;
+            }
+            if std::time::Instant::now() >= deadline {
+                return false;
+            }
+            std::thread::sleep(COMMAND_POLL_INTERVAL);
+        }
+    }
+
+    /// Kill a command's process group, or just its shell where there are no
+    /// process groups
+    fn kill(child: &mut std::process::Child) {
+        #[cfg(unix)]
+        {
+            let group = format!("-{}", child.id());
+            let killed = std::process::Command::new("kill")
+                .args(["-KILL", "--", &group])
+                .status()
+                .is_ok_and(|status| status.success());
+            if killed {
+                return;
+            }
+        }
+        if let Err(e) = child.kill() {
+            warn!("Failed to kill command: {}", e);
+        }
+    }
+
+    /// Build the tool result for a finished command
+    fn command_result(
+        stdout: &[u8],
+        stderr: &[u8],
+        status: std::process::ExitStatus,
+    ) -> serde_json::Value {
+        let (stdout, stdout_invalid) = lossy_utf8(stdout);
+        let (stderr, stderr_invalid) = lossy_utf8(stderr);
+        let success = status.success();
+
+        // Truncate very long output, without splitting a character
+        let stdout_str = if stdout.len() > 15000 {
+            format!(
+                "{}...\n\n[Output truncated: showing first 15KB of {} bytes total]",
+                truncate_utf8(&stdout, 15000),
+                stdout.len()
+            )
+        } else {
+            stdout
+        };
+
+        let mut result = serde_json::json!({
+            "success": success,
+            "stdout": stdout_str,
+            "stderr": stderr,
+            "exit_code": status.code()
+        });
+        if stdout_invalid || stderr_invalid {
+            result["invalid_utf8"] = serde_json::Value::Bool(true);
         }
+        result
     }
 }
 
 /// Tool for reading files (with sensitive file protection)
-pub struct ReadFileTool;
+///
+/// Files larger than the size limit are refused and binary files are
+/// reported by size instead of being read as text. Invalid UTF-8 in a text
+/// file is replaced and the result flagged with `invalid_utf8`.
+pub struct ReadFileTool {
+    max_bytes: u64,
+}
+
+impl Default for ReadFileTool {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_READ_BYTES)
+    }
+}
+
+impl ReadFileTool {
+    /// Create the tool, refusing files larger than `max_bytes`
+    pub fn new(max_bytes: u64) -> Self {
+        Self { max_bytes }
+    }
+
+    /// Read up to `max_lines` lines of the file at `path` as a tool result
+    ///
+    /// Lines are read one at a time, so only the returned lines are kept in
+    /// memory; the rest of the file is only counted.
+    fn read_lines(&self, path: &str, max_lines: usize) -> std::io::Result<serde_json::Value> {
+        use std::io::{BufRead, Read};
+
+        let size = std::fs::metadata(path)?.len();
+        if size > self.max_bytes {
+            return Ok(serde_json::json!({
+                "success": false,
+                "size": size,
+                "error": format!(
+                    "File is too large to read ({} bytes, limit {} bytes). Use bash (e.g. head, tail or grep) to read parts of it.",
+                    size, self.max_bytes
+                )
+            }));
+        }
+
+        let mut reader = std::io::BufReader::new(std::fs::File::open(path)?);
+        let mut head = Vec::with_capacity(BINARY_SNIFF_BYTES);
+        (&mut reader)
+            .take(BINARY_SNIFF_BYTES as u64)
+            .read_to_end(&mut head)?;
+        if is_binary(&head) {
+            return Ok(binary_result(size));
+        }
+        let reader = std::io::Cursor::new(head).chain(reader);
+
+        let mut lines = Vec::new();
+        let mut total_lines = 0;
+        let mut invalid_utf8 = false;
+        for line in reader.split(b'\n') {
+            let mut line = line?;
+            if line.last() == Some(&b'\r') {
+                line.pop();
+            }
+            total_lines += 1;
+            if lines.len() < max_lines {
+                let (line, invalid) = lossy_utf8(&line);
+                invalid_utf8 |= invalid;
+                lines.push(line);
+            }
+        }
+
+        let mut result = serde_json::json!({
+            "success": true,
+            "content": lines.join("\n"),
+            "lines_read": lines.len(),
+            "total_lines": total_lines,
+            "truncated": total_lines > max_lines
+        });
+        if invalid_utf8 {
+            result["invalid_utf8"] = serde_json::Value::Bool(true);
+        }
+        Ok(result)
+    }
+}
+
+/// Whether the start of a file looks binary: it holds a NUL byte
+///
+/// Text that is not valid UTF-8 is still read, lossily, and flagged with
+/// `invalid_utf8`.
+fn is_binary(head: &[u8]) -> bool {
+    head.contains(&0)
+}
+
+/// Result for a file that is not text
+fn binary_result(size: u64) -> serde_json::Value {
+    serde_json::json!({
+        "success": true,
+        "binary": true,
+        "size": size,
+        "content": format!("Binary file, {} bytes (content not shown)", size)
+    })
+}
 
 impl ToolFunction for ReadFileTool {
     fn definition(&self) -> Tool {
@@ -873,6 +2827,17 @@ impl ToolFunction for ReadFileTool {
         )
     }
 
+    fn is_cacheable(&self) -> bool {
+        true
+    }
+
+    fn capabilities(&self) -> ToolCapabilities {
+        ToolCapabilities {
+            reads_files: true,
+            ..ToolCapabilities::default()
+        }
+    }
+
     fn execute(&self, args: serde_json::Value) -> Result<serde_json::Value> {
         let path = args
             .get("path")
@@ -895,6 +2860,7 @@ impl ToolFunction for ReadFileTool {
                         "success": false,
                         "needs_confirmation": true,
                         "confirmation_type": "sensitive_file",
+                        "confirm_arg": "confirm_sensitive",
                         "error": format!(
                             "⚠️ SENSITIVE FILE: '{}' appears to be a sensitive file (key, credential, or secret).\n\nPlease ask the user: \"This file may contain sensitive information. Do you want me to read it?\"",
                             path
@@ -904,20 +2870,8 @@ impl ToolFunction for ReadFileTool {
             }
         }
 
-        let content = std::fs::read_to_string(path);
-
-        match content {
-            Ok(content) => {
-                let total_lines = content.lines().count();
-                let lines: Vec<&str> = content.lines().take(max_lines).collect();
-                Ok(serde_json::json!({
-                    "success": true,
-                    "content": lines.join("\n"),
-                    "lines_read": lines.len(),
-                    "total_lines": total_lines,
-                    "truncated": total_lines > max_lines
-                }))
-            }
+        match self.read_lines(path, max_lines) {
+            Ok(result) => Ok(result),
             Err(e) => Ok(serde_json::json!({
                 "success": false,
                 "error": format!("Failed to read file: {}", e)
@@ -948,6 +2902,17 @@ impl ToolFunction for ListDirTool {
         )
     }
 
+    fn is_cacheable(&self) -> bool {
+        true
+    }
+
+    fn capabilities(&self) -> ToolCapabilities {
+        ToolCapabilities {
+            reads_files: true,
+            ..ToolCapabilities::default()
+        }
+    }
+
     fn execute(&self, args: serde_json::Value) -> Result<serde_json::Value> {
         let path = args.get("path").and_then(|p| p.as_str()).unwrap_or(".");
 
@@ -995,8 +2960,9 @@ impl ToolFunction for WriteFileTool {
     fn definition(&self) -> Tool {
         Tool::function(
             "write_file",
-            "Write content to a file. Creates the file if it doesn't exist, overwrites if it does.\n\n\
-             ⚠️ IMPORTANT: This will OVERWRITE existing files. Ask user confirmation before overwriting important files!",
+            "Write content to a file. Creates the file if it doesn't exist, overwrites if it does. \
+             Set append=true to add to the end of the file instead.\n\n\
+             ⚠️ IMPORTANT: Without append, this will OVERWRITE existing files. Ask user confirmation before overwriting important files!",
             serde_json::json!({
                 "type": "object",
                 "properties": {
@@ -1012,6 +2978,16 @@ impl ToolFunction for WriteFileTool {
                         "type": "boolean",
                         "description": "Set to true if user confirmed overwriting an existing file",
                         "default": false
+                    },
+                    "append": {
+                        "type": "boolean",
+                        "description": "Append to the end of the file instead of overwriting it",
+                        "default": false
+                    },
+                    "confirm_sensitive": {
+                        "type": "boolean",
+                        "description": "Set to true if user confirmed writing to a sensitive file",
+                        "default": false
                     }
                 },
                 "required": ["path", "content"],
@@ -1020,6 +2996,13 @@ impl ToolFunction for WriteFileTool {
         )
     }
 
+    fn capabilities(&self) -> ToolCapabilities {
+        ToolCapabilities {
+            writes_files: true,
+            ..ToolCapabilities::default()
+        }
+    }
+
     fn execute(&self, args: serde_json::Value) -> Result<serde_json::Value> {
         let path = args
             .get("path")
@@ -1036,12 +3019,43 @@ impl ToolFunction for WriteFileTool {
             .and_then(|c| c.as_bool())
             .unwrap_or(false);
 
+        let append = args
+            .get("append")
+            .and_then(|a| a.as_bool())
+            .unwrap_or(false);
+
+        let confirm_sensitive = args
+            .get("confirm_sensitive")
+            .and_then(|c| c.as_bool())
+            .unwrap_or(false);
+
+        // Keys, credentials and shell startup files need confirmation even
+        // for an append, which can plant a key or a command as well
+        if is_sensitive_path(path) && !confirm_sensitive {
+            return Ok(serde_json::json!({
+                "success": false,
+                "needs_confirmation": true,
+                "confirmation_type": "sensitive_file",
+                "confirm_arg": "confirm_sensitive",
+                "error": format!(
+                    "⚠️ SENSITIVE FILE: '{}' appears to be a sensitive file (key, credential, secret or shell startup file).\n\nPlease ask the user: \"Do you want me to write to this file?\"",
+                    path
+                )
+            }));
+        }
+
+        // Appending keeps existing content, so it needs no overwrite confirmation
+        if append {
+            return Ok(Self::append(path, content));
+        }
+
         // Check if file exists
         if std::path::Path::new(path).exists() && !confirm_overwrite {
             return Ok(serde_json::json!({
                 "success": false,
                 "needs_confirmation": true,
                 "confirmation_type": "overwrite",
+                "confirm_arg": "confirm_overwrite",
                 "error": format!(
                     "⚠️ FILE EXISTS: '{}' already exists. Overwriting will destroy its current contents.\n\nPlease ask the user: \"This file already exists. Do you want to overwrite it?\"",
                     path
@@ -1062,13 +3076,449 @@ impl ToolFunction for WriteFileTool {
     }
 }
 
+impl WriteFileTool {
+    /// Append content to a file, creating it if needed, and report its new size
+    fn append(path: &str, content: &str) -> serde_json::Value {
+        use std::io::Write;
+
+        let result = std::fs::OpenOptions::new()
+            .append(true)
+            .create(true)
+            .open(path)
+            .and_then(|mut file| {
+                file.write_all(content.as_bytes())?;
+                file.metadata()
+            });
+
+        match result {
+            Ok(metadata) => serde_json::json!({
+                "success": true,
+                "message": format!("Successfully appended to '{}'", path),
+                "size": metadata.len()
+            }),
+            Err(e) => serde_json::json!({
+                "success": false,
+                "error": format!("Failed to append to file: {}", e)
+            }),
+        }
+    }
+}
+
+/// Tool for deleting files and directories
+pub struct DeleteFileTool;
+
+impl ToolFunction for DeleteFileTool {
+    fn definition(&self) -> Tool {
+        Tool::function(
+            "delete_file",
+            "Delete a file or directory (directories are removed with all their contents). \
+             Use this instead of `rm` via bash.\n\n\
+             ⚠️ IMPORTANT: Deletion cannot be undone. Ask user confirmation first and set \
+             confirm_delete=true only after the user confirms. Sensitive files (keys, credentials, \
+             secrets) can never be deleted with this tool.",
+            serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "path": {
+                        "type": "string",
+                        "description": "The path to the file or directory to delete"
+                    },
+                    "confirm_delete": {
+                        "type": "boolean",
+                        "description": "Set to true if user confirmed the deletion",
+                        "default": false
+                    }
+                },
+                "required": ["path"],
+                "additionalProperties": false
+            }),
+        )
+    }
+
+    fn capabilities(&self) -> ToolCapabilities {
+        ToolCapabilities {
+            writes_files: true,
+            ..ToolCapabilities::default()
+        }
+    }
+
+    fn execute(&self, args: serde_json::Value) -> Result<serde_json::Value> {
+        let path = args
+            .get("path")
+            .and_then(|p| p.as_str())
+            .ok_or_else(|| anyhow!("Missing 'path' argument"))?;
+
+        let confirm_delete = args
+            .get("confirm_delete")
+            .and_then(|c| c.as_bool())
+            .unwrap_or(false);
+
+        // Sensitive files are never deleted, even with confirmation
+        if is_sensitive_path(path) {
+            return Ok(serde_json::json!({
+                "success": false,
+                "error": format!(
+                    "🚫 REFUSED: '{}' appears to be a sensitive file (key, credential, or secret) and cannot be deleted.",
+                    path
+                )
+            }));
+        }
+
+        let target = std::path::Path::new(path);
+        let metadata = match std::fs::symlink_metadata(target) {
+            Ok(metadata) => metadata,
+            Err(e) => {
+                return Ok(serde_json::json!({
+                    "success": false,
+                    "error": format!("Cannot delete '{}': {}", path, e)
+                }));
+            }
+        };
+
+        if !confirm_delete {
+            return Ok(serde_json::json!({
+                "success": false,
+                "needs_confirmation": true,
+                "confirmation_type": "delete",
+                "confirm_arg": "confirm_delete",
+                "error": format!(
+                    "⚠️ DELETE: '{}' will be permanently removed{}.\n\nPlease ask the user: \"Do you want me to delete this?\"",
+                    path,
+                    if metadata.is_dir() { " with all its contents" } else { "" }
+                )
+            }));
+        }
+
+        let result = if metadata.is_dir() {
+            std::fs::remove_dir_all(target)
+        } else {
+            std::fs::remove_file(target)
+        };
+
+        match result {
+            Ok(()) => Ok(serde_json::json!({
+                "success": true,
+                "message": format!("Successfully deleted '{}'", path)
+            })),
+            Err(e) => Ok(serde_json::json!({
+                "success": false,
+                "error": format!("Failed to delete: {}", e)
+            })),
+        }
+    }
+}
+
 /// Create a default tool registry with common tools
 pub fn create_default_tools() -> ToolRegistry {
-    let mut registry = ToolRegistry::new();
-    registry.register(Box::new(EchoTool));
-    registry.register(Box::new(BashTool));
-    registry.register(Box::new(ReadFileTool));
-    registry.register(Box::new(ListDirTool));
-    registry.register(Box::new(WriteFileTool));
+    create_tools(None, &[], true, DEFAULT_MAX_READ_BYTES)
+}
+
+/// Create a tool registry with a subset of the built-in tools
+///
+/// If `enabled` is set, only the listed tools are registered; tools listed in
+/// `disabled` are always skipped. Unless `bash_enabled`, the bash tool is
+/// registered but refuses to run commands. `read_file` refuses files larger
+/// than `max_read_bytes`.
+pub fn create_tools(
+    enabled: Option<&[String]>,
+    disabled: &[String],
+    bash_enabled: bool,
+    max_read_bytes: u64,
+) -> ToolRegistry {
+    if !bash_enabled {
+        info!("Shell execution disabled by policy");
+    }
+    let builtin: Vec<Box<dyn ToolFunction>> = vec![
+        Box::new(EchoTool),
+        Box::new(BashTool::new(bash_enabled)),
+        Box::new(ReadFileTool::new(max_read_bytes)),
+        Box::new(ListDirTool),
+        Box::new(WriteFileTool),
+        Box::new(DeleteFileTool),
+        Box::new(WebReadTool),
+    ];
+
+    let names: Vec<String> = builtin
+        .iter()
+        .map(|t| t.definition().function.name)
+        .collect();
+    for name in enabled.unwrap_or_default().iter().chain(disabled) {
+        if !names.contains(name) {
+            warn!("Unknown built-in tool in tools config: {}", name);
+        }
+    }
+
+    let registry = ToolRegistry::new();
+    let mut registered = Vec::new();
+    for (tool, name) in builtin.into_iter().zip(names) {
+        let is_enabled = enabled.is_none_or(|e| e.contains(&name)) && !disabled.contains(&name);
+        if is_enabled {
+            registry.register(tool);
+            registered.push(name);
+        } else {
+            info!("Built-in tool disabled: {}", name);
+        }
+    }
+
+    info!("Built-in tools enabled: {}", registered.join(", "));
     registry
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Write `content` to a fresh file in the temp directory
+    fn temp_file(name: &str, content: &[u8]) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("rustclaw-{}-{}", Uuid::new_v4(), name));
+        std::fs::write(&path, content).unwrap();
+        path
+    }
+
+    fn read(tool: &ReadFileTool, path: &std::path::Path, lines: u64) -> serde_json::Value {
+        tool.execute(serde_json::json!({ "path": path.to_str().unwrap(), "lines": lines }))
+            .unwrap()
+    }
+
+    #[test]
+    fn test_read_file_lines() {
+        let path = temp_file("notes.txt", b"one\ntwo\nthree\n");
+        let result = read(&ReadFileTool::default(), &path, 2);
+        assert_eq!(result["content"], "one\ntwo");
+        assert_eq!(result["total_lines"], 3);
+        assert_eq!(result["truncated"], true);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_write_file_sensitive_append() {
+        let path = std::env::temp_dir().join(format!("rustclaw-{}", Uuid::new_v4()));
+        let env_file = path.with_extension("env");
+        let append = |confirmed: bool| {
+            WriteFileTool
+                .execute(serde_json::json!({
+                    "path": env_file.to_str().unwrap(),
+                    "content": "TOKEN=planted\n",
+                    "append": true,
+                    "confirm_sensitive": confirmed
+                }))
+                .unwrap()
+        };
+
+        let result = append(false);
+        assert_eq!(result["success"], false);
+        assert_eq!(result["needs_confirmation"], true);
+        assert_eq!(result["confirm_arg"], "confirm_sensitive");
+        assert!(!env_file.exists());
+
+        assert_eq!(append(true)["success"], true);
+        assert_eq!(
+            std::fs::read_to_string(&env_file).unwrap(),
+            "TOKEN=planted\n"
+        );
+        std::fs::remove_file(&env_file).unwrap();
+    }
+
+    #[test]
+    fn test_read_file_binary() {
+        let path = temp_file("image.bin", &[0x89, b'P', b'N', b'G', 0, 0xff, 0xfe]);
+        let result = read(&ReadFileTool::default(), &path, 100);
+        assert_eq!(result["success"], true);
+        assert_eq!(result["binary"], true);
+        assert_eq!(
+            result["content"],
+            "Binary file, 7 bytes (content not shown)"
+        );
+        std::fs::remove_file(&path).unwrap();
+
+        // A NUL byte after the sniffed start still reads as text
+        let mut content = vec![b'a'; BINARY_SNIFF_BYTES];
+        content.extend_from_slice(b"\n\0\n");
+        let path = temp_file("late.bin", &content);
+        assert!(read(&ReadFileTool::default(), &path, 100)["binary"].is_null());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_read_file_invalid_utf8() {
+        let path = temp_file("latin1.txt", b"caf\xe9\r\nok\n");
+        let result = read(&ReadFileTool::default(), &path, 100);
+        assert_eq!(result["content"], "caf\u{FFFD}\nok");
+        assert_eq!(result["invalid_utf8"], true);
+        assert!(result["binary"].is_null());
+        std::fs::remove_file(&path).unwrap();
+
+        // Invalid bytes in lines that are not returned don't count
+        let path = temp_file("late.txt", b"fine\n\xff\xfe\n");
+        let result = read(&ReadFileTool::default(), &path, 1);
+        assert_eq!(result["content"], "fine");
+        assert!(result["invalid_utf8"].is_null());
+        std::fs::remove_file(&path).unwrap();
+
+        let path = temp_file("valid.txt", "grüß\n".as_bytes());
+        assert!(read(&ReadFileTool::default(), &path, 100)["invalid_utf8"].is_null());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_command_result_invalid_utf8() {
+        use std::os::unix::process::ExitStatusExt;
+
+        let status = std::process::ExitStatus::from_raw(0);
+        let result = BashTool::command_result(b"ok \xff\xfe\n", b"", status);
+        assert_eq!(result["stdout"], "ok \u{FFFD}\u{FFFD}\n");
+        assert_eq!(result["invalid_utf8"], true);
+
+        let result = BashTool::command_result(b"ok\n", b"warn\xc3", status);
+        assert_eq!(result["stderr"], "warn\u{FFFD}");
+        assert_eq!(result["invalid_utf8"], true);
+
+        let result = BashTool::command_result("grüß\n".as_bytes(), b"", status);
+        assert!(result["invalid_utf8"].is_null());
+
+        // Truncation doesn't split a multi-byte character
+        let long = format!("a{}", "ü".repeat(10000));
+        let result = BashTool::command_result(long.as_bytes(), b"", status);
+        assert!(result["stdout"]
+            .as_str()
+            .unwrap()
+            .starts_with(&format!("a{}...", "ü".repeat(7499))));
+    }
+
+    /// Whether process `pid` is gone
+    ///
+    /// A killed process takes a moment to exit, and may linger as a zombie
+    /// until it is reaped.
+    fn process_killed(pid: &str) -> bool {
+        (0..50).any(|_| {
+            let stat = std::fs::read_to_string(format!("/proc/{pid}/stat")).unwrap_or_default();
+            std::thread::sleep(Duration::from_millis(20));
+            stat.is_empty() || stat.contains(") Z ")
+        })
+    }
+
+    #[test]
+    fn test_bash_timeout() {
+        let started = std::time::Instant::now();
+        let result = BashTool::new(true)
+            .execute(serde_json::json!({
+                "command": "sleep 5 & echo $!; wait",
+                "timeout": 1
+            }))
+            .unwrap();
+        assert!(started.elapsed() < Duration::from_secs(3));
+        assert_eq!(result["success"], false);
+        assert_eq!(result["timed_out"], true);
+        assert!(result["exit_code"].is_null());
+
+        // Output from before the timeout is kept, and the background sleep
+        // was killed along with the shell
+        let pid = result["stdout"].as_str().unwrap().trim();
+        assert!(!pid.is_empty());
+        assert!(process_killed(pid));
+
+        let result = BashTool::new(true)
+            .execute(serde_json::json!({ "command": "echo done", "timeout": 1 }))
+            .unwrap();
+        assert_eq!(result["success"], true);
+        assert!(result["timed_out"].is_null());
+    }
+
+    #[test]
+    fn test_bash_background_process() {
+        // The background sleep keeps the output pipes open after the shell
+        // exits, until it is killed at the timeout
+        let started = std::time::Instant::now();
+        let result = BashTool::new(true)
+            .execute(serde_json::json!({
+                "command": "sleep 1000 & echo $!",
+                "timeout": 1
+            }))
+            .unwrap();
+        assert!(started.elapsed() < Duration::from_secs(4));
+        assert_eq!(result["success"], true);
+        assert_eq!(result["exit_code"], 0);
+
+        let pid = result["stdout"].as_str().unwrap().trim();
+        assert!(!pid.is_empty());
+        assert!(process_killed(pid));
+    }
+
+    #[test]
+    fn test_retry_temperature() {
+        assert_eq!(TelegramService::retry_temperature(""), Ok(None));
+        assert_eq!(TelegramService::retry_temperature(" 1.2 "), Ok(Some(1.2)));
+        assert_eq!(TelegramService::retry_temperature("0"), Ok(Some(0.0)));
+        assert!(TelegramService::retry_temperature("2.5").is_err());
+        assert!(TelegramService::retry_temperature("-1").is_err());
+        assert!(TelegramService::retry_temperature("hot").is_err());
+    }
+
+    #[test]
+    fn test_flag_truncation() {
+        let messages = Language::En.messages();
+        let mut response = AgenticResponse {
+            content: "Part of an answer".to_string(),
+            finish_reason: "length".to_string(),
+            usage: rustclaw_types::TokenUsage::default(),
+            confirmations: Vec::new(),
+            attachments: Vec::new(),
+        };
+        let mut parts = response.parts();
+        let saved = TelegramService::parts_text(&parts);
+        TelegramService::flag_truncation(&response, &mut parts, messages);
+
+        // The user sees the note, the saved history does not
+        assert_eq!(saved, "Part of an answer");
+        assert!(matches!(
+            &parts[0],
+            ResponsePart::Text(text) if text == &format!("Part of an answer\n\n{}", messages.truncated)
+        ));
+
+        response.finish_reason = "stop".to_string();
+        let mut parts = response.parts();
+        TelegramService::flag_truncation(&response, &mut parts, messages);
+        assert!(matches!(&parts[0], ResponsePart::Text(text) if text == "Part of an answer"));
+    }
+
+    #[test]
+    fn test_tools_reply() {
+        let messages = Language::En.messages();
+        let tools = ToolRegistry::new();
+        assert_eq!(
+            TelegramService::tools_reply(&tools, messages),
+            "🔧 No tools are available."
+        );
+
+        tools.register(Box::new(ReadFileTool::default()));
+        tools.register(Box::new(EchoTool));
+        let reply = TelegramService::tools_reply(&tools, messages);
+        assert!(reply.starts_with(
+            "🔧 Available tools (2):\n\n\
+             • **echo** - Echo back the input message\n\
+             • **read_file** - Read the contents of a file.\n\n"
+        ));
+
+        // Tools come and go with the registry
+        tools.unregister("echo");
+        assert!(!TelegramService::tools_reply(&tools, messages).contains("echo"));
+
+        let german = TelegramService::tools_reply(&tools, Language::De.messages());
+        assert!(german.starts_with("🔧 Verfügbare Werkzeuge (1):"));
+    }
+
+    #[test]
+    fn test_read_file_too_large() {
+        let path = temp_file("big.log", &[b'x'; 2048]);
+        let result = read(&ReadFileTool::new(1024), &path, 1);
+        assert_eq!(result["success"], false);
+        assert_eq!(result["size"], 2048);
+        assert!(result["error"]
+            .as_str()
+            .unwrap()
+            .contains("limit 1024 bytes"));
+
+        assert_eq!(read(&ReadFileTool::new(2048), &path, 1)["success"], true);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,94 @@
+//! Concurrent request limiting
+//!
+//! Every incoming message may start an agentic loop running tools and several
+//! LLM calls. The limiter caps how many run at once; messages arriving while
+//! all slots are taken are rejected with a "busy" reply instead of queuing up.
+
+use std::sync::Arc;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tracing::{debug, warn};
+
+/// Global limit on in-flight agentic loops shared with the message handlers
+#[derive(Debug)]
+pub(crate) struct RequestLimiter {
+    permits: Arc<Semaphore>,
+    /// Maximum concurrent requests (`0` = unlimited)
+    max: usize,
+}
+
+impl RequestLimiter {
+    /// Allow at most `max` concurrent requests (`0` disables the limit)
+    pub(crate) fn new(max: usize) -> Self {
+        let permits = if max == 0 {
+            Semaphore::MAX_PERMITS
+        } else {
+            max
+        };
+        Self {
+            permits: Arc::new(Semaphore::new(permits)),
+            max,
+        }
+    }
+
+    /// Number of requests currently being handled
+    pub(crate) fn in_flight(&self) -> usize {
+        let total = if self.max == 0 {
+            Semaphore::MAX_PERMITS
+        } else {
+            self.max
+        };
+        total - self.permits.available_permits()
+    }
+
+    /// Claim a slot for a request, or `None` if the limit is reached
+    ///
+    /// The slot is released when the returned permit is dropped.
+    pub(crate) fn try_acquire(&self) -> Option<OwnedSemaphorePermit> {
+        match Arc::clone(&self.permits).try_acquire_owned() {
+            Ok(permit) => {
+                debug!("Requests in flight: {}", self.in_flight());
+                Some(permit)
+            }
+            Err(_) => {
+                warn!(
+                    "Rejecting request: {} requests already in flight",
+                    self.in_flight()
+                );
+                None
+            }
+        }
+    }
+}
+
+impl Default for RequestLimiter {
+    fn default() -> Self {
+        Self::new(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rejects_when_full() {
+        let limiter = RequestLimiter::new(2);
+        let first = limiter.try_acquire();
+        let second = limiter.try_acquire();
+        assert!(first.is_some() && second.is_some());
+        assert_eq!(limiter.in_flight(), 2);
+        assert!(limiter.try_acquire().is_none());
+
+        drop(first);
+        assert_eq!(limiter.in_flight(), 1);
+        assert!(limiter.try_acquire().is_some());
+    }
+
+    #[test]
+    fn test_unlimited() {
+        let limiter = RequestLimiter::new(0);
+        let permits: Vec<_> = (0..100).filter_map(|_| limiter.try_acquire()).collect();
+        assert_eq!(permits.len(), 100);
+        assert_eq!(limiter.in_flight(), 100);
+    }
+}
@@ -0,0 +1,225 @@
+//! Long-term memory
+//!
+//! Facts the model chooses to keep about a chat ("name: Bob", "units:
+//! metric"), stored per chat and added to the system prompt of every later
+//! request. Unlike the conversation summary they are explicit key/value
+//! pairs that survive `/clear` until the user runs `/forget`.
+
+use anyhow::{anyhow, Result};
+use rustclaw_persistence::PersistenceService;
+use rustclaw_provider::ToolFunction;
+use rustclaw_types::Tool;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use tracing::info;
+
+/// Tool the model calls to store a fact about the chat
+pub(crate) struct RememberTool {
+    persistence: Arc<RwLock<PersistenceService>>,
+}
+
+/// Tool the model calls to look up stored facts about the chat
+pub(crate) struct RecallTool {
+    persistence: Arc<RwLock<PersistenceService>>,
+}
+
+/// The memory tools, storing facts in `persistence`
+pub(crate) fn memory_tools(
+    persistence: Arc<RwLock<PersistenceService>>,
+) -> Vec<Box<dyn ToolFunction>> {
+    vec![
+        Box::new(RememberTool {
+            persistence: persistence.clone(),
+        }),
+        Box::new(RecallTool { persistence }),
+    ]
+}
+
+/// Run a persistence operation from a tool's blocking thread
+pub(crate) fn block_on<T>(future: impl std::future::Future<Output = T>) -> T {
+    tokio::task::block_in_place(|| tokio::runtime::Handle::current().block_on(future))
+}
+
+/// Get a required, non-empty string argument
+fn string_arg<'a>(args: &'a serde_json::Value, name: &str) -> Result<&'a str> {
+    args.get(name)
+        .and_then(|v| v.as_str())
+        .map(str::trim)
+        .filter(|v| !v.is_empty())
+        .ok_or_else(|| anyhow!("Missing '{}' argument", name))
+}
+
+impl ToolFunction for RememberTool {
+    fn definition(&self) -> Tool {
+        Tool::function(
+            "remember",
+            "Remember a fact about the user or this chat for future conversations \
+             (e.g. their name or preferred units). Storing a key again replaces its value. \
+             Remembered facts are shown to you at the start of every conversation.",
+            serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "key": {
+                        "type": "string",
+                        "description": "Short name of the fact, e.g. 'name' or 'units'"
+                    },
+                    "value": {
+                        "type": "string",
+                        "description": "The fact to remember, e.g. 'Bob' or 'metric'"
+                    }
+                },
+                "required": ["key", "value"],
+                "additionalProperties": false
+            }),
+        )
+    }
+
+    fn is_chat_scoped(&self) -> bool {
+        true
+    }
+
+    fn execute(&self, _args: serde_json::Value) -> Result<serde_json::Value> {
+        Err(anyhow!("remember can only be used in a chat"))
+    }
+
+    fn execute_in_chat(&self, args: serde_json::Value, chat_id: i64) -> Result<serde_json::Value> {
+        let key = string_arg(&args, "key")?;
+        let value = string_arg(&args, "value")?;
+
+        block_on(async {
+            self.persistence
+                .read()
+                .await
+                .remember(chat_id, key, value)
+                .await
+        })?;
+        info!("Remembered '{}' for chat {}", key, chat_id);
+
+        Ok(serde_json::json!({
+            "success": true,
+            "message": format!("Remembered {}: {}", key, value)
+        }))
+    }
+}
+
+impl ToolFunction for RecallTool {
+    fn definition(&self) -> Tool {
+        Tool::function(
+            "recall",
+            "Look up facts remembered about the user or this chat. \
+             Returns one fact by key, or all facts when no key is given.",
+            serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "key": {
+                        "type": "string",
+                        "description": "Name of the fact to look up (omit for all facts)"
+                    }
+                },
+                "additionalProperties": false
+            }),
+        )
+    }
+
+    fn is_chat_scoped(&self) -> bool {
+        true
+    }
+
+    fn execute(&self, _args: serde_json::Value) -> Result<serde_json::Value> {
+        Err(anyhow!("recall can only be used in a chat"))
+    }
+
+    fn execute_in_chat(&self, args: serde_json::Value, chat_id: i64) -> Result<serde_json::Value> {
+        let key = args.get("key").and_then(|v| v.as_str()).map(str::trim);
+        let facts = block_on(async { self.persistence.read().await.get_memory(chat_id).await })?;
+
+        let facts: serde_json::Map<String, serde_json::Value> = facts
+            .into_iter()
+            .filter(|(k, _)| key.is_none_or(|key| key.is_empty() || k == key))
+            .map(|(k, v)| (k, serde_json::Value::String(v)))
+            .collect();
+        if facts.is_empty() {
+            return Ok(serde_json::json!({
+                "found": false,
+                "message": match key {
+                    Some(key) if !key.is_empty() => format!("Nothing remembered for '{}'", key),
+                    _ => "Nothing remembered yet".to_string(),
+                }
+            }));
+        }
+
+        Ok(serde_json::json!({ "found": true, "facts": facts }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_remember_and_recall() {
+        let path =
+            std::env::temp_dir().join(format!("rustclaw-memory-{}.db", uuid::Uuid::new_v4()));
+        let persistence = PersistenceService::new(path.to_str().unwrap())
+            .await
+            .unwrap();
+        let persistence = Arc::new(RwLock::new(persistence));
+        let tools: Vec<Arc<dyn ToolFunction>> = memory_tools(persistence.clone())
+            .into_iter()
+            .map(Arc::from)
+            .collect();
+        let run = |tool: usize, args: serde_json::Value, chat_id: i64| {
+            let tool = tools[tool].clone();
+            tokio::task::spawn_blocking(move || tool.execute_in_chat(args, chat_id))
+        };
+
+        run(0, serde_json::json!({ "key": "name", "value": "Bob" }), 1)
+            .await
+            .unwrap()
+            .unwrap();
+        run(
+            0,
+            serde_json::json!({ "key": "units", "value": "metric" }),
+            1,
+        )
+        .await
+        .unwrap()
+        .unwrap();
+        run(
+            0,
+            serde_json::json!({ "key": "name", "value": "Robert" }),
+            1,
+        )
+        .await
+        .unwrap()
+        .unwrap();
+        assert!(run(0, serde_json::json!({ "key": "name" }), 1)
+            .await
+            .unwrap()
+            .is_err());
+
+        let all = run(1, serde_json::json!({}), 1).await.unwrap().unwrap();
+        assert_eq!(
+            all["facts"],
+            serde_json::json!({ "name": "Robert", "units": "metric" })
+        );
+        let one = run(1, serde_json::json!({ "key": "units" }), 1)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(one["facts"], serde_json::json!({ "units": "metric" }));
+
+        // Facts belong to their chat
+        let other = run(1, serde_json::json!({}), 2).await.unwrap().unwrap();
+        assert_eq!(other["found"], false);
+
+        let store = persistence.read().await;
+        assert_eq!(store.forget(1, Some("name")).await.unwrap(), 1);
+        assert_eq!(store.get_memory(1).await.unwrap().len(), 1);
+        assert_eq!(store.forget(1, None).await.unwrap(), 1);
+        assert!(store.get_memory(1).await.unwrap().is_empty());
+
+        drop(store);
+        let _ = std::fs::remove_file(path);
+    }
+}
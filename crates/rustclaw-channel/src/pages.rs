@@ -0,0 +1,190 @@
+//! Splitting long replies into several Telegram messages
+//!
+//! Replies longer than a single message are split at paragraph, sentence or
+//! word boundaries. Each part can be marked with a page indicator such as
+//! `(1/3)`; the indicator's length is reserved up front so marked parts still
+//! fit the message limit.
+
+use serde::Deserialize;
+
+/// Marks the parts of a reply split into several messages
+///
+/// In the config: `"off"`, or a format where `{i}` is the part number and
+/// `{n}` the number of parts (default `"({i}/{n})\n\n"`).
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(from = "String")]
+pub enum PageIndicator {
+    /// Send parts without a marker
+    Off,
+    /// Prefix every part with this format
+    Format(String),
+}
+
+impl Default for PageIndicator {
+    fn default() -> Self {
+        PageIndicator::Format("({i}/{n})\n\n".to_string())
+    }
+}
+
+impl From<String> for PageIndicator {
+    fn from(format: String) -> Self {
+        if format.is_empty() || format.eq_ignore_ascii_case("off") {
+            PageIndicator::Off
+        } else {
+            PageIndicator::Format(format)
+        }
+    }
+}
+
+impl PageIndicator {
+    /// Marker for part `i` of `n`
+    fn render(&self, i: usize, n: usize) -> String {
+        match self {
+            PageIndicator::Off => String::new(),
+            PageIndicator::Format(format) => format
+                .replace("{i}", &i.to_string())
+                .replace("{n}", &n.to_string()),
+        }
+    }
+}
+
+/// Split `text` into messages of at most `max_len` bytes, each prefixed with
+/// its page marker when there is more than one
+pub(crate) fn paginate(text: &str, max_len: usize, pages: &PageIndicator) -> Vec<String> {
+    if text.len() <= max_len {
+        return vec![text.to_string()];
+    }
+
+    // The marker of the last part is the longest, and its length depends on
+    // the number of parts, so re-split until the reserved space suffices
+    let mut parts = 2;
+    let chunks = loop {
+        let reserved = pages.render(parts, parts).len();
+        let chunks = split_message(text, max_len.saturating_sub(reserved).max(1));
+        if chunks.len() <= 1 || pages.render(chunks.len(), chunks.len()).len() <= reserved {
+            break chunks;
+        }
+        parts = chunks.len();
+    };
+
+    if chunks.len() <= 1 {
+        return chunks;
+    }
+    let total = chunks.len();
+    chunks
+        .into_iter()
+        .enumerate()
+        .map(|(i, chunk)| format!("{}{}", pages.render(i + 1, total), chunk))
+        .collect()
+}
+
+/// Split a message into chunks of at most `max_len` bytes, preferring
+/// paragraph, then sentence, then word boundaries
+fn split_message(text: &str, max_len: usize) -> Vec<String> {
+    if text.len() <= max_len {
+        return vec![text.to_string()];
+    }
+
+    let mut chunks = Vec::new();
+    let mut current_chunk = String::new();
+
+    // Try to split on paragraph breaks first, then sentences, then words
+    for paragraph in text.split("\n\n") {
+        if current_chunk.len() + paragraph.len() + 2 > max_len {
+            if !current_chunk.is_empty() {
+                chunks.push(current_chunk.trim().to_string());
+                current_chunk = String::new();
+            }
+
+            // If paragraph itself is too long, split by sentences
+            if paragraph.len() > max_len {
+                for sentence in paragraph.split(". ") {
+                    if current_chunk.len() + sentence.len() + 2 > max_len {
+                        if !current_chunk.is_empty() {
+                            chunks.push(current_chunk.trim().to_string());
+                            current_chunk = String::new();
+                        }
+
+                        // If sentence is too long, split by words
+                        if sentence.len() > max_len {
+                            for word in sentence.split_whitespace() {
+                                if current_chunk.len() + word.len() + 1 > max_len {
+                                    if !current_chunk.is_empty() {
+                                        chunks.push(current_chunk.trim().to_string());
+                                    }
+                                    current_chunk = word.to_string();
+                                } else {
+                                    if !current_chunk.is_empty() {
+                                        current_chunk.push(' ');
+                                    }
+                                    current_chunk.push_str(word);
+                                }
+                            }
+                        } else {
+                            current_chunk = sentence.to_string();
+                        }
+                    } else {
+                        if !current_chunk.is_empty() {
+                            current_chunk.push_str(". ");
+                        }
+                        current_chunk.push_str(sentence);
+                    }
+                }
+            } else {
+                current_chunk = paragraph.to_string();
+            }
+        } else {
+            if !current_chunk.is_empty() {
+                current_chunk.push_str("\n\n");
+            }
+            current_chunk.push_str(paragraph);
+        }
+    }
+
+    if !current_chunk.trim().is_empty() {
+        chunks.push(current_chunk.trim().to_string());
+    }
+
+    chunks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn long_text() -> String {
+        (0..50)
+            .map(|i| format!("Paragraph {} {}", i, "word ".repeat(30)))
+            .collect::<Vec<_>>()
+            .join("\n\n")
+    }
+
+    #[test]
+    fn test_short_text_is_not_marked() {
+        let parts = paginate("hello", 100, &PageIndicator::default());
+        assert_eq!(parts, ["hello"]);
+    }
+
+    #[test]
+    fn test_marked_parts_fit_limit() {
+        let text = long_text();
+        let parts = paginate(&text, 500, &PageIndicator::default());
+        assert!(parts.len() > 10);
+        assert!(parts[0].starts_with(&format!("(1/{})\n\n", parts.len())));
+        assert!(parts.iter().all(|p| p.len() <= 500));
+
+        let custom = PageIndicator::from("Part {i} of {n}: ".to_string());
+        let parts = paginate(&text, 500, &custom);
+        assert!(parts[1].starts_with(&format!("Part 2 of {}: ", parts.len())));
+        assert!(parts.iter().all(|p| p.len() <= 500));
+    }
+
+    #[test]
+    fn test_indicator_off() {
+        assert_eq!(PageIndicator::from("off".to_string()), PageIndicator::Off);
+        let parts = paginate(&long_text(), 500, &PageIndicator::Off);
+        assert!(parts.len() > 1);
+        assert!(parts[0].starts_with("Paragraph 0"));
+        assert!(parts.iter().all(|p| p.len() <= 500));
+    }
+}
@@ -0,0 +1,203 @@
+//! Live tool output
+//!
+//! Relays output lines of long-running tools (e.g. a build started through
+//! `bash`) to the chat while the agentic loop is still running. The lines are
+//! shown in a single message that is edited periodically, so the user sees
+//! progress without flooding the chat.
+//!
+//! With `[telegram] show_tool_progress` the same message also names the tools
+//! each iteration of the agentic loop runs, and is deleted once the loop
+//! finishes so the final answer takes its place.
+
+use crate::i18n::Messages;
+use rustclaw_provider::{ToolOutputSink, ToolProgress, ToolProgressSink};
+use std::time::Duration;
+use teloxide::prelude::*;
+use teloxide::types::MessageId;
+use tokio::task::JoinHandle;
+use tracing::debug;
+use tracing::Instrument;
+
+/// Minimum time between edits of the progress message
+const EDIT_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Characters of the most recent output shown in the progress message
+const PREVIEW_CHARS: usize = 3000;
+
+/// Whether the tools being run are shown in the progress message, and the
+/// language it is written in
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct ToolStatus {
+    enabled: bool,
+    messages: &'static Messages,
+}
+
+impl ToolStatus {
+    /// Show the running tools when `enabled`, labeled in the bot's language
+    pub(crate) fn new(enabled: bool, messages: &'static Messages) -> Self {
+        Self { enabled, messages }
+    }
+
+    /// Status line for an iteration running `progress`
+    fn line(&self, progress: &ToolProgress) -> Option<String> {
+        self.enabled.then(|| {
+            format!(
+                "{} {}…",
+                self.messages.running_tools,
+                progress.tools.join(", ")
+            )
+        })
+    }
+
+    /// Text of the progress message: the running tools, then the latest
+    /// output
+    fn text(&self, running: Option<&str>, tail: &str) -> String {
+        let output = (!tail.is_empty()).then(|| format!("{}\n{}", self.messages.tool_output, tail));
+        match (running, output) {
+            (Some(running), Some(output)) => format!("{}\n\n{}", running, output),
+            (Some(running), None) => running.to_string(),
+            (None, Some(output)) => output,
+            (None, None) => String::new(),
+        }
+    }
+}
+
+/// Start relaying tool output and, if `status` is enabled, the running tools
+/// to `chat_id`
+///
+/// The relay finishes once every sender has been dropped, with a final edit
+/// of the progress message, or by deleting it when `status` is enabled.
+pub(crate) fn spawn_output_relay(
+    bot: Bot,
+    chat_id: ChatId,
+    status: ToolStatus,
+) -> (ToolOutputSink, ToolProgressSink, JoinHandle<()>) {
+    let (sink, mut lines) = tokio::sync::mpsc::unbounded_channel::<String>();
+    let (progress_sink, mut progress) = tokio::sync::mpsc::unbounded_channel::<ToolProgress>();
+
+    let relay = tokio::spawn(
+        async move {
+            let mut tail = String::new();
+            let mut running: Option<String> = None;
+            let mut message: Option<MessageId> = None;
+            let mut dirty = false;
+            let mut progress_open = true;
+            let mut ticker = tokio::time::interval(EDIT_INTERVAL);
+
+            loop {
+                tokio::select! {
+                    line = lines.recv() => match line {
+                        Some(line) => {
+                            push_tail(&mut tail, &line);
+                            dirty = true;
+                        }
+                        None => break,
+                    },
+                    event = progress.recv(), if progress_open => match event {
+                        Some(event) => {
+                            if let Some(line) = status.line(&event) {
+                                running = Some(line);
+                                dirty = true;
+                            }
+                        }
+                        None => progress_open = false,
+                    },
+                    _ = ticker.tick() => {
+                        if dirty {
+                            let text = status.text(running.as_deref(), &tail);
+                            show(&bot, chat_id, &mut message, text).await;
+                            dirty = false;
+                        }
+                    }
+                }
+            }
+
+            match message {
+                // The final answer replaces the status message
+                Some(id) if status.enabled => {
+                    if let Err(e) = bot.delete_message(chat_id, id).await {
+                        debug!("Failed to delete tool progress message: {}", e);
+                    }
+                }
+                _ if dirty => {
+                    let text = status.text(running.as_deref(), &tail);
+                    show(&bot, chat_id, &mut message, text).await;
+                }
+                _ => {}
+            }
+        }
+        .in_current_span(),
+    );
+
+    (sink, progress_sink, relay)
+}
+
+/// Send or update the progress message
+async fn show(bot: &Bot, chat_id: ChatId, message: &mut Option<MessageId>, text: String) {
+    let result = match message {
+        Some(id) => bot.edit_message_text(chat_id, *id, text).await.map(|_| ()),
+        None => bot.send_message(chat_id, text).await.map(|sent| {
+            *message = Some(sent.id);
+        }),
+    };
+
+    if let Err(e) = result {
+        debug!("Failed to update tool output message: {}", e);
+    }
+}
+
+/// Append a line, keeping only the last `PREVIEW_CHARS` characters
+fn push_tail(tail: &mut String, line: &str) {
+    tail.push_str(line);
+    tail.push('\n');
+
+    let excess = tail.chars().count().saturating_sub(PREVIEW_CHARS);
+    if excess > 0 {
+        let cut = tail
+            .char_indices()
+            .nth(excess)
+            .map_or(tail.len(), |(i, _)| i);
+        tail.drain(..cut);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_push_tail_keeps_latest_output() {
+        let mut tail = String::new();
+        push_tail(&mut tail, "first");
+        push_tail(&mut tail, "second");
+        assert_eq!(tail, "first\nsecond\n");
+
+        push_tail(&mut tail, &"é".repeat(PREVIEW_CHARS));
+        assert_eq!(tail.chars().count(), PREVIEW_CHARS);
+        assert!(tail.starts_with('é'));
+        assert!(tail.ends_with("é\n"));
+    }
+
+    #[test]
+    fn test_progress_text() {
+        let messages = crate::i18n::Language::En.messages();
+        let progress = ToolProgress {
+            iteration: 2,
+            tools: vec!["bash".to_string(), "read_file".to_string()],
+        };
+        assert_eq!(ToolStatus::new(false, messages).line(&progress), None);
+        let status = ToolStatus::new(true, messages);
+        let running = status.line(&progress).unwrap();
+        assert_eq!(running, "⚙️ Running tool: bash, read_file…");
+
+        assert_eq!(status.text(None, "a\n"), "⏳ Output:\na\n");
+        assert_eq!(status.text(Some(&running), ""), running);
+        assert_eq!(
+            status.text(Some(&running), "a\n"),
+            format!("{}\n\n⏳ Output:\na\n", running)
+        );
+
+        let german = ToolStatus::new(true, crate::i18n::Language::De.messages());
+        assert_eq!(german.text(None, "a\n"), "⏳ Ausgabe:\na\n");
+    }
+}
@@ -3,6 +3,8 @@
 //! This module provides utilities for formatting text content from various sources
 //! (MCP tools, LLM responses, etc.) for proper display on messaging platforms like Telegram.
 
+use rustclaw_provider::truncate_utf8;
+
 /// Format text for Telegram display
 ///
 /// This function handles:
@@ -58,7 +60,7 @@ pub fn format_for_telegram_truncated(text: &str, max_length: usize) -> String {
         formatted
     } else {
         // Try to truncate at a word boundary
-        let truncated = &formatted[..max_length.saturating_sub(50)];
+        let truncated = truncate_utf8(&formatted, max_length.saturating_sub(50));
         if let Some(last_period) = truncated.rfind('.') {
             format!(
                 "{}...\n\n[Message truncated - {} more characters]",
@@ -72,10 +74,7 @@ pub fn format_for_telegram_truncated(text: &str, max_length: usize) -> String {
                 formatted.len() - last_space
             )
         } else {
-            format!(
-                "{}...\n\n[Message truncated]",
-                &formatted[..max_length.saturating_sub(50)]
-            )
+            format!("{}...\n\n[Message truncated]", truncated)
         }
     }
 }
@@ -126,4 +125,18 @@ mod tests {
         assert!(result.len() <= 4050); // Allow some buffer for truncation message
         assert!(result.contains("[Message truncated"));
     }
+
+    #[test]
+    fn test_truncation_multibyte() {
+        // No period or space to cut at, and the limit falls inside a character
+        let input = "日本語".repeat(2000);
+        let result = format_for_telegram_truncated(&input, 4001);
+        assert!(result.len() <= 4050);
+        assert!(result.starts_with("日本語日本語"));
+        assert!(result.contains("...\n\n[Message truncated]"));
+
+        let input = "Grüße aus München. ".repeat(300);
+        let result = format_for_telegram_truncated(&input, 4000);
+        assert!(result.contains("[Message truncated - "));
+    }
 }
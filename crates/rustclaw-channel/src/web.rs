@@ -0,0 +1,386 @@
+//! Reading webpages
+//!
+//! The `web_read` tool fetches a page and converts its HTML to markdown, so
+//! the model gets readable text instead of raw markup. Only public addresses
+//! are fetched: every host, including each redirect target, is resolved and
+//! checked first, and the request is pinned to the checked address so a
+//! second DNS answer cannot point it at the local network.
+
+use crate::memory::block_on;
+use anyhow::{anyhow, Result};
+use reqwest::{header, redirect, StatusCode, Url};
+use rustclaw_provider::{lossy_utf8, ToolCapabilities, ToolFunction};
+use rustclaw_types::Tool;
+use std::net::{IpAddr, SocketAddr};
+use std::time::Duration;
+use tracing::{info, warn};
+
+/// Most bytes of a page downloaded
+const MAX_PAGE_BYTES: usize = 2 * 1024 * 1024;
+
+/// Characters of markdown returned when the model does not ask for fewer
+const DEFAULT_MAX_CHARS: usize = 20_000;
+
+/// Most characters of markdown returned
+const MAX_CHARS: usize = 50_000;
+
+/// Most redirects followed
+const MAX_REDIRECTS: usize = 5;
+
+/// Timeout of each request
+const FETCH_TIMEOUT: Duration = Duration::from_secs(20);
+
+/// Elements left out of the markdown
+const SKIPPED_TAGS: &[&str] = &[
+    "head", "script", "style", "noscript", "template", "svg", "iframe", "nav", "footer", "form",
+];
+
+/// Tool for reading a webpage as markdown
+pub struct WebReadTool;
+
+impl ToolFunction for WebReadTool {
+    fn definition(&self) -> Tool {
+        Tool::function(
+            "web_read",
+            "Fetch a webpage and return its title and main text as markdown. \
+             Use this to read articles and documentation instead of fetching raw HTML with curl.",
+            serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "url": {
+                        "type": "string",
+                        "description": "The http(s) URL of the page"
+                    },
+                    "max_chars": {
+                        "type": "integer",
+                        "description": "Maximum characters of text to return (default: 20000, max: 50000)",
+                        "default": DEFAULT_MAX_CHARS
+                    }
+                },
+                "required": ["url"],
+                "additionalProperties": false
+            }),
+        )
+    }
+
+    fn is_cacheable(&self) -> bool {
+        true
+    }
+
+    fn capabilities(&self) -> ToolCapabilities {
+        ToolCapabilities {
+            network: true,
+            ..ToolCapabilities::default()
+        }
+    }
+
+    fn execute(&self, args: serde_json::Value) -> Result<serde_json::Value> {
+        let url = args
+            .get("url")
+            .and_then(|u| u.as_str())
+            .ok_or_else(|| anyhow!("Missing 'url' argument"))?;
+        let max_chars = args
+            .get("max_chars")
+            .and_then(|m| m.as_u64())
+            .map_or(DEFAULT_MAX_CHARS, |m| m as usize)
+            .min(MAX_CHARS);
+
+        let (final_url, page) = match block_on(fetch(url)) {
+            Ok(page) => page,
+            Err(e) => {
+                warn!("Failed to read {}: {}", url, e);
+                return Ok(serde_json::json!({
+                    "success": false,
+                    "error": format!("Failed to read {}: {}", url, e)
+                }));
+            }
+        };
+        info!("Read {} ({} bytes)", final_url, page.body.len());
+
+        let (body, invalid_utf8) = lossy_utf8(&page.body);
+        let (title, text) = if page.is_html() {
+            (page_title(&body), html_to_markdown(&body)?)
+        } else {
+            (None, body)
+        };
+        let total_chars = text.chars().count();
+        let content: String = text.chars().take(max_chars).collect();
+
+        let mut result = serde_json::json!({
+            "success": true,
+            "url": final_url.as_str(),
+            "title": title,
+            "content": content,
+            "truncated": total_chars > max_chars || page.truncated,
+        });
+        if invalid_utf8 {
+            result["invalid_utf8"] = serde_json::Value::Bool(true);
+        }
+        Ok(result)
+    }
+}
+
+/// A downloaded page
+struct Page {
+    content_type: String,
+    body: Vec<u8>,
+    /// Whether the body was cut off at [`MAX_PAGE_BYTES`]
+    truncated: bool,
+}
+
+impl Page {
+    fn is_html(&self) -> bool {
+        self.content_type.is_empty() || self.content_type.contains("html")
+    }
+}
+
+/// Fetch `url`, following redirects to public addresses only
+async fn fetch(url: &str) -> Result<(Url, Page)> {
+    let mut url = Url::parse(url).map_err(|e| anyhow!("Invalid URL: {}", e))?;
+
+    for _ in 0..=MAX_REDIRECTS {
+        let address = resolve_public(&url).await?;
+        let host = url.host_str().unwrap_or_default().to_string();
+        let client = reqwest::Client::builder()
+            .redirect(redirect::Policy::none())
+            .timeout(FETCH_TIMEOUT)
+            .user_agent(concat!("rustclaw/", env!("CARGO_PKG_VERSION")))
+            // A proxy would resolve the host itself, bypassing the check
+            .no_proxy()
+            .resolve(&host, address)
+            .build()?;
+
+        let mut response = client.get(url.clone()).send().await?;
+        if response.status().is_redirection() {
+            let location = response
+                .headers()
+                .get(header::LOCATION)
+                .and_then(|l| l.to_str().ok())
+                .ok_or_else(|| anyhow!("Redirect without a location"))?;
+            url = url
+                .join(location)
+                .map_err(|e| anyhow!("Invalid redirect: {}", e))?;
+            continue;
+        }
+        let status = response.status();
+        if status != StatusCode::OK {
+            return Err(anyhow!("HTTP {}", status));
+        }
+
+        let content_type = response
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|c| c.to_str().ok())
+            .unwrap_or_default()
+            .to_lowercase();
+        if !is_text(&content_type) {
+            return Err(anyhow!("Unsupported content type '{}'", content_type));
+        }
+
+        let mut body = Vec::new();
+        let mut truncated = false;
+        while let Some(chunk) = response.chunk().await? {
+            body.extend_from_slice(&chunk);
+            if body.len() >= MAX_PAGE_BYTES {
+                body.truncate(MAX_PAGE_BYTES);
+                // Drop a character cut in half, which isn't invalid UTF-8
+                if let Err(e) = std::str::from_utf8(&body) {
+                    if e.error_len().is_none() {
+                        body.truncate(e.valid_up_to());
+                    }
+                }
+                truncated = true;
+                break;
+            }
+        }
+
+        let page = Page {
+            content_type,
+            body,
+            truncated,
+        };
+        return Ok((url, page));
+    }
+
+    Err(anyhow!("Too many redirects"))
+}
+
+/// Resolve the host of `url`, failing unless every address is public
+async fn resolve_public(url: &Url) -> Result<SocketAddr> {
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(anyhow!("Only http and https URLs can be read"));
+    }
+    let host = url
+        .host_str()
+        .ok_or_else(|| anyhow!("URL has no host"))?
+        .trim_start_matches('[')
+        .trim_end_matches(']');
+    let port = url.port_or_known_default().unwrap_or(80);
+
+    let addresses: Vec<SocketAddr> = tokio::net::lookup_host((host, port))
+        .await
+        .map_err(|e| anyhow!("Failed to resolve {}: {}", host, e))?
+        .collect();
+    if let Some(address) = addresses.iter().find(|a| !is_public(a.ip())) {
+        return Err(anyhow!(
+            "{} resolves to the non-public address {}",
+            host,
+            address.ip()
+        ));
+    }
+    addresses
+        .into_iter()
+        .next()
+        .ok_or_else(|| anyhow!("{} has no addresses", host))
+}
+
+/// Whether `ip` is a public internet address (not loopback, private,
+/// link-local, shared, multicast or reserved)
+///
+/// IPv6 addresses embedding an IPv4 one (IPv4-compatible, 6to4, NAT64) are
+/// refused outright, as they may be routed to any IPv4 address.
+fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, c, _] = ip.octets();
+            !(ip.is_private()
+                || ip.is_loopback()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                || ip.is_multicast()
+                || ip.is_documentation()
+                || a == 0
+                // IETF protocol assignments (192.0.0.0/24)
+                || (a == 192 && b == 0 && c == 0)
+                // Benchmarking (198.18.0.0/15)
+                || (a == 198 && (b & 0xfe) == 18)
+                // Shared address space (carrier-grade NAT)
+                || (a == 100 && (64..128).contains(&b))
+                // Reserved for future use
+                || a >= 240)
+        }
+        IpAddr::V6(ip) => {
+            if let Some(ip) = ip.to_ipv4_mapped() {
+                return is_public(IpAddr::V4(ip));
+            }
+            let segments = ip.segments();
+            let first = segments[0];
+            !(ip.is_loopback()
+                || ip.is_unspecified()
+                || ip.is_multicast()
+                // IPv4-compatible (::a.b.c.d)
+                || segments[..6] == [0; 6]
+                // 6to4 (2002::/16)
+                || first == 0x2002
+                // NAT64 (64:ff9b::/96)
+                || segments[..6] == [0x64, 0xff9b, 0, 0, 0, 0]
+                // Unique local (fc00::/7) and link-local (fe80::/10)
+                || (first & 0xfe00) == 0xfc00
+                || (first & 0xffc0) == 0xfe80)
+        }
+    }
+}
+
+/// Whether a response with `content_type` can be returned as text
+fn is_text(content_type: &str) -> bool {
+    content_type.is_empty()
+        || content_type.starts_with("text/")
+        || content_type.contains("html")
+        || content_type.contains("json")
+        || content_type.contains("xml")
+}
+
+/// The text of the page's `<title>` element, if any
+fn page_title(html: &str) -> Option<String> {
+    let lower = html.to_ascii_lowercase();
+    let open = lower.find("<title")?;
+    let start = open + lower[open..].find('>')? + 1;
+    let end = start + lower[start..].find("</title")?;
+    let title = htmd::convert(&html[start..end]).ok()?;
+    let title = title.split_whitespace().collect::<Vec<_>>().join(" ");
+    (!title.is_empty()).then_some(title)
+}
+
+/// Convert a page to markdown, leaving out scripts, styles and navigation
+fn html_to_markdown(html: &str) -> Result<String> {
+    let converter = htmd::HtmlToMarkdown::builder()
+        .skip_tags(SKIPPED_TAGS.to_vec())
+        .build();
+    converter
+        .convert(html)
+        .map_err(|e| anyhow!("Failed to convert page: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_html_to_markdown() {
+        let html = "<html><head><title>Rust &amp; Crabs</title><style>p{}</style></head>\
+                    <body><nav><a href=\"/\">Home</a></nav><script>track()</script>\
+                    <h1>Crabs</h1><p>They are <b>great</b>.</p></body></html>";
+
+        assert_eq!(page_title(html).as_deref(), Some("Rust & Crabs"));
+        assert_eq!(
+            html_to_markdown(html).unwrap(),
+            "# Crabs\n\nThey are **great**."
+        );
+        assert_eq!(page_title("<p>No title</p>"), None);
+    }
+
+    #[test]
+    fn test_is_public() {
+        for ip in [
+            "93.184.216.34",
+            "2606:2800:220:1::1",
+            "198.17.0.1",
+            "198.20.0.1",
+            "192.0.1.1",
+        ] {
+            assert!(is_public(ip.parse().unwrap()), "{}", ip);
+        }
+        for ip in [
+            "127.0.0.1",
+            "10.1.2.3",
+            "172.16.0.1",
+            "192.168.1.1",
+            "169.254.169.254",
+            "100.64.0.1",
+            "0.0.0.0",
+            "::1",
+            "fd00::1",
+            "fe80::1",
+            "::ffff:127.0.0.1",
+            "::7f00:1",
+            "::93.184.216.34",
+            "2002:7f00:1::1",
+            "64:ff9b::7f00:1",
+            "64:ff9b::93.184.216.34",
+            "198.18.0.1",
+            "198.19.255.254",
+            "192.0.0.8",
+        ] {
+            assert!(!is_public(ip.parse().unwrap()), "{}", ip);
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_refuses_local_urls() {
+        for url in [
+            "http://127.0.0.1:8080/",
+            "http://localhost/",
+            "http://[::1]/",
+            "file:///etc/passwd",
+        ] {
+            let result = tokio::task::spawn_blocking(move || {
+                WebReadTool.execute(serde_json::json!({ "url": url }))
+            })
+            .await
+            .unwrap()
+            .unwrap();
+            assert_eq!(result["success"], false, "{}", url);
+        }
+    }
+}
@@ -1,4 +1,10 @@
+use rustclaw_channel::{GroupMode, Language, PageIndicator};
+use rustclaw_logging::LogFormat;
+use rustclaw_provider::prompt::{PromptSection, DEFAULT_PROMPT_DROP_ORDER};
+use rustclaw_provider::ToolResultFormat;
+use rustclaw_types::{ModelPrice, ProvidersConfig};
 use serde::Deserialize;
+use std::collections::HashMap;
 use std::env;
 use std::fs;
 use std::path::PathBuf;
@@ -7,35 +13,107 @@ use std::path::PathBuf;
 const DEFAULT_CONFIG: &str = r#"
 [telegram]
 bot_token = ""  # Set via TELEGRAM_BOT_TOKEN env var
+group_mode = "all"  # "all" or "mention" (answer only @mentions / prefixed messages in groups)
+# group_prefix = "!ask"
+# admin_chat_id = 123456789  # Chat notified about startup problems (e.g. failed MCP servers)
+# admin_user_ids = [123456789]  # Users allowed to run admin commands like /reload-skills
+# page_indicator = "({i}/{n})\n\n"  # Prefix for parts of long replies ("off" to disable)
+language = "en"  # Language of the bot's own messages: "en" or "de"
+show_tool_progress = false  # Show the tool being run in a status message
 
 [providers]
-default = "openai"  # or "ollama"
+default = "openai"  # or "ollama", "gemini", "azure"
+allowed_models = []  # Extra models selectable per chat with /model (e.g. ["gpt-4o-mini"])
+allowed_providers = []  # Other providers selectable per chat with /provider (e.g. ["ollama"])
 
 [providers.openai]
 # api_key and base_url are optional - set via OPENAI_API_KEY and OPENAI_BASE_URL env vars
 model = "gpt-4o-mini"
+# headers = { "HTTP-Referer" = "https://example.com" }  # Extra headers for proxies like OpenRouter
 
 [providers.ollama]
 base_url = "http://localhost:11434"
 model = "llama3"
+# api = "auto"  # "native" (/api/chat), "openai" (/v1) or "auto" (openai if base_url ends in /v1)
+
+[providers.gemini]
+# api_key is optional here - set via GEMINI_API_KEY env var
+model = "gemini-2.0-flash"
+
+[providers.azure]
+# endpoint = "https://my-resource.openai.azure.com"
+# deployment = "gpt-4o-mini"  # Deployment name; /model selects other deployments
+# api_key is optional here - set via AZURE_OPENAI_API_KEY env var
+api_version = "2024-10-21"
 
 [agent]
 max_tool_iterations = 10  # Maximum tool calls per request
-context_window = 128000   # Token limit for context
+max_concurrent_tools = 4  # Tool calls executed in parallel per turn
+max_concurrent_requests = 8  # Messages handled at once; others get a "busy" reply (0 = unlimited)
+context_window = 128000   # Token limit for models without a known window
 recent_turns = 10         # Turns to keep before compression
+max_continuations = 0     # Auto-continue responses cut off by the output limit (0 = off)
+max_duration = 0          # Seconds a single message may take, tools included (0 = unlimited)
+# temperature = 0.7       # Sampling temperature (ignored for reasoning models such as o1/o3)
+max_output_tokens = 0     # Tokens generated per completion (0 = model default)
+show_reasoning = false    # Show the reasoning of models that return it above the answer
+recover_text_tool_calls = true  # Execute tool calls the model writes as JSON text
+summarize_at_tokens = 0   # Summarize older messages once the history passes this size (0 = only /summarize)
+summarize_tool_results_at = 0  # Summarize tool results over this many tokens before the model sees them (0 = off)
+# tool_summary_model = "gpt-4o-mini"  # Model writing those summaries (default: the chat's model)
+tool_cache_ttl = 0        # Seconds to cache results of read-only tools (0 = off)
+tool_result_format = "auto"  # "auto" (plain text for string results) or "json"
+system_prompt_budget = 0  # Tokens the system prompt may take; over it, sections are dropped (0 = unlimited)
+prompt_drop_order = ["skills", "facts", "summary"]  # Dropped first to last; unlisted sections are kept
+
+[agent.context_windows]   # Per-model token limits overriding the built-in ones
+# "gpt-4o-mini" = 128000
+
+[pricing]
+daily_budget = 0.0  # USD per UTC day across all chats; then requests are refused (0 = no cap)
+
+[pricing.models]    # USD per million input/output tokens; unlisted models are free
+# "gpt-4o-mini" = { input = 0.15, output = 0.60 }
+
+[memory]
+enabled = false  # remember/recall tools for per-chat facts; /memory lists, /forget clears (admins in groups)
+
+[server]
+# health_port = 8080  # Serve /healthz and /readyz (database, provider, MCP status as JSON)
 
 [database]
 path = "rustclaw.db"
 
 [logging]
 level = "info"  # trace, debug, info, warn, error
+format = "text"  # "text" or "json" (one object per line with request_id)
+tool_output_preview = 100  # Characters of tool output logged at info level (full output at trace)
+
+# Built-in tools (optional): bash, read_file, list_dir, write_file, delete_file, web_read, echo
+[tools]
+# enabled = ["read_file", "list_dir"]  # Only register these (default: all)
+disabled = []                         # Never register these
+# allowlist = ["read_file", "list_dir"]  # Only these may run, whatever the model asks (default: all)
+
+[tools.bash]
+enabled = true  # false = keep the tool but refuse every command
+
+[tools.read_file]
+max_bytes = 1048576  # Larger files are refused; binary files are reported by size
 
 # MCP servers (optional)
 [mcp]
 startup_timeout = 10  # seconds
+failure_threshold = 3  # consecutive failures before a tool is disabled (0 = never)
+failure_cooldown = 60  # seconds before a disabled tool is retried
+tool_naming = "prefixed"  # "prefixed" (server_tool), "bare" (tool) or { separator = "-" }
+require_all = false  # refuse to start unless every server starts
+require_any = false  # refuse to start if no server starts
+# config_file = "~/.config/Claude/claude_desktop_config.json"  # extra servers in "mcpServers" JSON format
 
 [mcp.servers]
 # Example: filesystem = "npx -y @modelcontextprotocol/server-filesystem /tmp"
+# Override tool descriptions: [mcp.servers.x.tool_descriptions] read_file = "..." (or { append = "..." })
 
 # Skills directories (optional)
 [skills]
@@ -43,31 +121,42 @@ startup_timeout = 10  # seconds
 # Personal skills: ~/.rustclaw/skills/
 # Project skills: ./.rustclaw/skills/
 directories = ["~/.rustclaw/skills", "./.rustclaw/skills"]
+track_usage = false       # List skills that often succeed first in the prompt
 "#;
 
 #[derive(Debug, Deserialize, Clone)]
 pub struct TelegramConfig {
     pub bot_token: String,
-}
 
-#[derive(Debug, Deserialize, Clone)]
-pub struct OpenAIConfig {
-    pub api_key: Option<String>,
-    pub model: String,
-    pub base_url: Option<String>,
-}
+    /// Which group chat messages the bot answers
+    #[serde(default)]
+    pub group_mode: GroupMode,
 
-#[derive(Debug, Deserialize, Clone)]
-pub struct OllamaConfig {
-    pub base_url: String,
-    pub model: String,
-}
+    /// Prefix that addresses the bot in mention mode (e.g. `!ask`)
+    #[serde(default)]
+    pub group_prefix: Option<String>,
 
-#[derive(Debug, Deserialize, Clone)]
-pub struct ProvidersConfig {
-    pub default: String,
-    pub openai: OpenAIConfig,
-    pub ollama: OllamaConfig,
+    /// Chat that receives operational notices (e.g. MCP servers that failed to start)
+    #[serde(default)]
+    pub admin_chat_id: Option<i64>,
+
+    /// Telegram users allowed to run admin commands (e.g. `/reload-skills`,
+    /// or `/forget` in groups)
+    #[serde(default)]
+    pub admin_user_ids: Vec<i64>,
+
+    /// Marker for the parts of long replies (`{i}`/`{n}` placeholders, or "off")
+    #[serde(default)]
+    pub page_indicator: PageIndicator,
+
+    /// Language of the bot's own messages; the model answers in the user's language
+    #[serde(default)]
+    pub language: Language,
+
+    /// Show the tool the agent is running in a status message that the
+    /// final answer replaces
+    #[serde(default)]
+    pub show_tool_progress: bool,
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -76,6 +165,14 @@ pub struct AgentConfig {
     #[serde(default = "default_max_tool_iterations")]
     pub max_tool_iterations: usize,
 
+    /// Maximum number of tool calls executed concurrently
+    #[serde(default = "default_max_concurrent_tools")]
+    pub max_concurrent_tools: usize,
+
+    /// Maximum number of messages handled concurrently (0 = unlimited)
+    #[serde(default = "default_max_concurrent_requests")]
+    pub max_concurrent_requests: usize,
+
     /// Context window size in tokens
     #[serde(default = "default_context_window")]
     pub context_window: usize,
@@ -83,24 +180,115 @@ pub struct AgentConfig {
     /// Number of recent turns to keep before compression
     #[serde(default = "default_recent_turns")]
     pub recent_turns: usize,
+
+    /// Times a length-truncated response is automatically continued (0 disables)
+    #[serde(default)]
+    pub max_continuations: usize,
+
+    /// Seconds one message may take across all model requests and tool
+    /// calls (0 disables)
+    #[serde(default)]
+    pub max_duration: u64,
+
+    /// Sampling temperature (the model's default if unset; never sent to
+    /// reasoning models)
+    #[serde(default)]
+    pub temperature: Option<f32>,
+
+    /// Tokens generated per completion (0 uses the model's default)
+    #[serde(default)]
+    pub max_output_tokens: u32,
+
+    /// Show the reasoning returned by reasoning models above their answer
+    #[serde(default)]
+    pub show_reasoning: bool,
+
+    /// Execute a final response that is only a tool call written as JSON
+    /// text instead of showing it to the user
+    #[serde(default = "default_recover_text_tool_calls")]
+    pub recover_text_tool_calls: bool,
+
+    /// Summarize a chat's older messages once its history grows past this
+    /// many tokens (0 leaves summarizing to `/summarize`)
+    #[serde(default)]
+    pub summarize_at_tokens: usize,
+
+    /// Summarize tool results over this many tokens before they are fed
+    /// back to the model (0 disables)
+    #[serde(default)]
+    pub summarize_tool_results_at: usize,
+
+    /// Model summarizing oversized tool results (the chat's model if unset)
+    #[serde(default)]
+    pub tool_summary_model: Option<String>,
+
+    /// Seconds to cache results of read-only tools for identical arguments (0 disables)
+    #[serde(default)]
+    pub tool_cache_ttl: u64,
+
+    /// How tool results are passed back to the model
+    #[serde(default)]
+    pub tool_result_format: ToolResultFormat,
+
+    /// Tokens the assembled system prompt may take (0 = unlimited)
+    #[serde(default)]
+    pub system_prompt_budget: usize,
+
+    /// Order system prompt sections are dropped in when over budget;
+    /// sections left out are never dropped
+    #[serde(default = "default_prompt_drop_order")]
+    pub prompt_drop_order: Vec<PromptSection>,
+
+    /// Per-model context window sizes overriding the built-in sizes and
+    /// `context_window`
+    #[serde(default)]
+    pub context_windows: HashMap<String, usize>,
 }
 
 fn default_max_tool_iterations() -> usize {
     10
 }
+fn default_max_concurrent_tools() -> usize {
+    4
+}
+fn default_max_concurrent_requests() -> usize {
+    8
+}
 fn default_context_window() -> usize {
     128_000
 }
 fn default_recent_turns() -> usize {
     10
 }
+fn default_recover_text_tool_calls() -> bool {
+    true
+}
+fn default_prompt_drop_order() -> Vec<PromptSection> {
+    DEFAULT_PROMPT_DROP_ORDER.to_vec()
+}
 
 impl Default for AgentConfig {
     fn default() -> Self {
         Self {
             max_tool_iterations: default_max_tool_iterations(),
+            max_concurrent_tools: default_max_concurrent_tools(),
+            max_concurrent_requests: default_max_concurrent_requests(),
             context_window: default_context_window(),
             recent_turns: default_recent_turns(),
+            max_continuations: 0,
+            max_duration: 0,
+            temperature: None,
+            max_output_tokens: 0,
+            show_reasoning: false,
+            recover_text_tool_calls: true,
+            summarize_at_tokens: 0,
+            summarize_tool_results_at: 0,
+            tool_summary_model: None,
+            tool_cache_ttl: 0,
+            tool_result_format: ToolResultFormat::default(),
+            system_prompt_budget: 0,
+            prompt_drop_order: default_prompt_drop_order(),
+            context_windows: HashMap::new(),
         }
     }
 }
@@ -113,6 +301,102 @@ pub struct DatabaseConfig {
 #[derive(Debug, Deserialize, Clone)]
 pub struct LoggingConfig {
     pub level: String,
+    /// Log line format; `json` includes the `request_id` of each request
+    #[serde(default)]
+    pub format: LogFormat,
+    /// Characters of each tool output logged at `info` level (`0` = none)
+    #[serde(default = "default_tool_output_preview")]
+    pub tool_output_preview: usize,
+}
+
+fn default_tool_output_preview() -> usize {
+    rustclaw_provider::DEFAULT_TOOL_OUTPUT_PREVIEW
+}
+
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct ToolsConfig {
+    /// Built-in tools to register (all when unset)
+    #[serde(default)]
+    pub enabled: Option<Vec<String>>,
+
+    /// Built-in tools to skip, applied after `enabled`
+    #[serde(default)]
+    pub disabled: Vec<String>,
+
+    /// Tools allowed to run, checked on every call (all when unset)
+    #[serde(default)]
+    pub allowlist: Option<Vec<String>>,
+
+    /// Policy for the bash tool
+    #[serde(default)]
+    pub bash: BashToolConfig,
+
+    /// Limits of the read_file tool
+    #[serde(default)]
+    pub read_file: ReadFileToolConfig,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct BashToolConfig {
+    /// Run shell commands; when false the tool stays registered but refuses
+    #[serde(default = "default_bash_enabled")]
+    pub enabled: bool,
+}
+
+impl Default for BashToolConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_bash_enabled(),
+        }
+    }
+}
+
+fn default_bash_enabled() -> bool {
+    true
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct ReadFileToolConfig {
+    /// Largest file in bytes the tool reads
+    #[serde(default = "default_read_max_bytes")]
+    pub max_bytes: u64,
+}
+
+impl Default for ReadFileToolConfig {
+    fn default() -> Self {
+        Self {
+            max_bytes: default_read_max_bytes(),
+        }
+    }
+}
+
+fn default_read_max_bytes() -> u64 {
+    rustclaw_channel::DEFAULT_MAX_READ_BYTES
+}
+
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct PricingConfig {
+    /// Maximum spend in USD per UTC day across all chats (`0` = no cap)
+    #[serde(default)]
+    pub daily_budget: f64,
+    /// Prices per model in USD per million tokens
+    #[serde(default)]
+    pub models: HashMap<String, ModelPrice>,
+}
+
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct MemoryConfig {
+    /// Offer the `remember` and `recall` tools and add remembered facts to
+    /// the system prompt (off by default)
+    #[serde(default)]
+    pub enabled: bool,
+}
+
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct ServerConfig {
+    /// Port of the `/healthz` and `/readyz` endpoints (disabled when unset)
+    #[serde(default)]
+    pub health_port: Option<u16>,
 }
 
 #[derive(Debug, Deserialize, Clone, Default)]
@@ -120,6 +404,10 @@ pub struct SkillsConfig {
     /// Directories to scan for skills
     #[serde(default)]
     pub directories: Vec<String>,
+
+    /// Record skill activations and rank frequently successful skills first
+    #[serde(default)]
+    pub track_usage: bool,
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -131,9 +419,17 @@ pub struct Config {
     pub database: DatabaseConfig,
     pub logging: LoggingConfig,
     #[serde(default)]
+    pub tools: ToolsConfig,
+    #[serde(default)]
     pub mcp: rustclaw_mcp::MCPConfig,
     #[serde(default)]
     pub skills: SkillsConfig,
+    #[serde(default)]
+    pub pricing: PricingConfig,
+    #[serde(default)]
+    pub memory: MemoryConfig,
+    #[serde(default)]
+    pub server: ServerConfig,
 }
 
 impl Config {
@@ -201,6 +497,18 @@ impl Config {
             builder = builder.set_override("providers__ollama__base_url", url)?;
         }
 
+        if let Ok(key) = env::var("GEMINI_API_KEY") {
+            builder = builder.set_override("providers__gemini__api_key", key)?;
+        }
+
+        if let Ok(key) = env::var("AZURE_OPENAI_API_KEY") {
+            builder = builder.set_override("providers__azure__api_key", key)?;
+        }
+
+        if let Ok(endpoint) = env::var("AZURE_OPENAI_ENDPOINT") {
+            builder = builder.set_override("providers__azure__endpoint", endpoint)?;
+        }
+
         // Agent config overrides
         if let Ok(iterations) = env::var("RUSTCLAW_MAX_TOOL_ITERATIONS") {
             if let Ok(v) = iterations.parse::<i64>() {
@@ -209,7 +517,9 @@ impl Config {
         }
 
         let config = builder.build()?;
-        let config: Self = config.try_deserialize()?;
+        let mut config: Self = config.try_deserialize()?;
+        config.mcp.load_config_file()?;
+        config.mcp.load_env_servers()?;
         Ok(config)
     }
 }
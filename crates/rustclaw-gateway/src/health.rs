@@ -0,0 +1,231 @@
+//! Health and readiness endpoints
+//!
+//! A minimal HTTP server for container orchestration, enabled with
+//! `[server] health_port`. `/healthz` answers while the process runs;
+//! `/readyz` checks the database, the provider and the MCP servers and
+//! returns their status as JSON, with `503` if any of them is not ready.
+
+use rustclaw_mcp::MCPToolRegistry;
+use rustclaw_persistence::PersistenceService;
+use serde_json::{json, Value};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tracing::{debug, info, warn};
+
+/// Time a client has to send its request line
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Dependencies checked by `/readyz`
+pub struct HealthChecks {
+    /// Database the conversations are stored in
+    pub persistence: PersistenceService,
+    /// Name of the configured provider, or why none could be configured
+    pub provider: Result<String, String>,
+    /// MCP servers that should be connected
+    pub mcp_servers: Vec<String>,
+    /// Connected MCP servers
+    pub mcp: Option<Arc<MCPToolRegistry>>,
+}
+
+impl HealthChecks {
+    /// Status of every dependency and whether all of them are ready
+    async fn readiness(&self) -> (bool, Value) {
+        let database = match self.persistence.ping().await {
+            Ok(()) => component(true, None),
+            Err(e) => component(false, Some(e.to_string())),
+        };
+        let provider = match &self.provider {
+            Ok(name) => component(true, Some(name.clone())),
+            Err(e) => component(false, Some(e.clone())),
+        };
+
+        let connected = match &self.mcp {
+            Some(registry) => registry.server_names().await,
+            None => Vec::new(),
+        };
+        let servers: serde_json::Map<String, Value> = self
+            .mcp_servers
+            .iter()
+            .map(|name| (name.clone(), Value::Bool(connected.contains(name))))
+            .collect();
+        let mcp_ready = servers.values().all(|up| up == &Value::Bool(true));
+        let mut mcp = component(mcp_ready, None);
+        mcp["servers"] = Value::Object(servers);
+
+        let ready = [&database, &provider, &mcp]
+            .iter()
+            .all(|c| c["status"] == "ok");
+        let body = json!({
+            "status": if ready { "ready" } else { "not_ready" },
+            "components": { "database": database, "provider": provider, "mcp": mcp },
+        });
+        (ready, body)
+    }
+}
+
+/// Status of one dependency
+fn component(ok: bool, detail: Option<String>) -> Value {
+    let mut status = json!({ "status": if ok { "ok" } else { "error" } });
+    if let Some(detail) = detail {
+        status["detail"] = Value::String(detail);
+    }
+    status
+}
+
+/// Health server listening on a bound port
+pub struct HealthServer {
+    listener: TcpListener,
+    checks: Arc<HealthChecks>,
+}
+
+impl HealthServer {
+    /// Listen on `port` on all interfaces
+    pub async fn bind(port: u16, checks: HealthChecks) -> anyhow::Result<Self> {
+        let listener = TcpListener::bind(("0.0.0.0", port))
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to bind health port {}: {}", port, e))?;
+        info!("Health endpoints listening on port {}", port);
+        Ok(Self {
+            listener,
+            checks: Arc::new(checks),
+        })
+    }
+
+    /// Answer requests until the task is dropped
+    pub async fn run(self) {
+        loop {
+            match self.listener.accept().await {
+                Ok((stream, _)) => {
+                    let checks = Arc::clone(&self.checks);
+                    tokio::spawn(async move {
+                        if let Err(e) = handle(stream, &checks).await {
+                            debug!("Health request failed: {}", e);
+                        }
+                    });
+                }
+                Err(e) => warn!("Failed to accept health connection: {}", e),
+            }
+        }
+    }
+}
+
+/// Answer one request and close the connection
+async fn handle(mut stream: TcpStream, checks: &HealthChecks) -> std::io::Result<()> {
+    let mut buffer = [0u8; 1024];
+    let read = tokio::time::timeout(READ_TIMEOUT, stream.read(&mut buffer))
+        .await
+        .map_err(|_| std::io::Error::from(std::io::ErrorKind::TimedOut))??;
+    let request = String::from_utf8_lossy(&buffer[..read]);
+
+    let request = parse_request(&request);
+    let (status, body) = match request.map(|(_, path)| path) {
+        Some("/healthz") => (200, json!({ "status": "ok" })),
+        Some("/readyz") => match checks.readiness().await {
+            (true, body) => (200, body),
+            (false, body) => (503, body),
+        },
+        Some(_) => (404, json!({ "error": "not found" })),
+        None => (400, json!({ "error": "bad request" })),
+    };
+    let head = matches!(request, Some(("HEAD", _)));
+
+    stream
+        .write_all(response(status, &body, head).as_bytes())
+        .await?;
+    stream.shutdown().await
+}
+
+/// Method and path of a `GET` or `HEAD` request, without the query string
+fn parse_request(request: &str) -> Option<(&str, &str)> {
+    let mut parts = request.lines().next()?.split_whitespace();
+    let method = parts.next()?;
+    let target = parts.next()?;
+    matches!(method, "GET" | "HEAD").then(|| (method, target.split('?').next().unwrap_or(target)))
+}
+
+/// HTTP/1.1 response with a JSON body
+///
+/// The response to a `HEAD` request (`head`) has the headers of the `GET`
+/// response, `Content-Length` included, but no body.
+fn response(status: u16, body: &Value, head: bool) -> String {
+    let reason = match status {
+        200 => "OK",
+        400 => "Bad Request",
+        404 => "Not Found",
+        _ => "Service Unavailable",
+    };
+    let body = body.to_string();
+    format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        reason,
+        body.len(),
+        if head { "" } else { &body }
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_request() {
+        assert_eq!(
+            parse_request("GET /healthz HTTP/1.1\r\nHost: x\r\n"),
+            Some(("GET", "/healthz"))
+        );
+        assert_eq!(
+            parse_request("HEAD /readyz?verbose=1 HTTP/1.1\r\n"),
+            Some(("HEAD", "/readyz"))
+        );
+        assert_eq!(parse_request("POST /healthz HTTP/1.1\r\n"), None);
+        assert_eq!(parse_request(""), None);
+    }
+
+    #[test]
+    fn test_head_response() {
+        let body = json!({ "status": "ok" });
+        let get = response(200, &body, false);
+        assert!(get.ends_with("\r\n\r\n{\"status\":\"ok\"}"));
+
+        // Same headers, Content-Length included, but no body
+        let head = response(200, &body, true);
+        assert!(head.contains("Content-Length: 15\r\n"));
+        assert!(head.ends_with("\r\n\r\n"));
+        assert_eq!(head.len() + 15, get.len());
+    }
+
+    #[tokio::test]
+    async fn test_readiness() {
+        let path = std::env::temp_dir().join(format!("rustclaw-health-{}.db", std::process::id()));
+        let persistence = PersistenceService::new(path.to_str().unwrap())
+            .await
+            .unwrap();
+        let checks = HealthChecks {
+            persistence,
+            provider: Ok("openai".to_string()),
+            mcp_servers: Vec::new(),
+            mcp: None,
+        };
+        let (ready, body) = checks.readiness().await;
+        assert!(ready);
+        assert_eq!(body["components"]["provider"]["detail"], "openai");
+
+        // A configured MCP server that is not connected is not ready
+        let checks = HealthChecks {
+            mcp_servers: vec!["filesystem".to_string()],
+            provider: Err("no API key".to_string()),
+            ..checks
+        };
+        let (ready, body) = checks.readiness().await;
+        assert!(!ready);
+        assert_eq!(body["status"], "not_ready");
+        assert_eq!(body["components"]["database"]["status"], "ok");
+        assert_eq!(body["components"]["mcp"]["servers"]["filesystem"], false);
+        assert_eq!(body["components"]["provider"]["status"], "error");
+
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -1,4 +1,5 @@
 mod config;
+mod health;
 mod service;
 
 use anyhow::Result;
@@ -11,6 +12,7 @@ async fn main() -> Result<()> {
     let config = Config::load()?;
 
     // Create and run gateway service
-    let gateway = GatewayService::new(config);
+    let dump_tools = std::env::args().skip(1).any(|arg| arg == "--dump-tools");
+    let gateway = GatewayService::new(config).with_dump_tools(dump_tools);
     gateway.run().await
 }
@@ -1,38 +1,60 @@
 use crate::config::Config;
+use crate::health::{HealthChecks, HealthServer};
 use anyhow::Result;
-use rustclaw_channel::{create_default_tools, TelegramService};
+use rustclaw_channel::{create_tools, TelegramService};
 use rustclaw_mcp::MCPToolRegistry;
 use rustclaw_persistence::PersistenceService;
 use rustclaw_provider::ProviderService;
 use rustclaw_skills::SkillsRegistry;
-use rustclaw_types::Provider;
+use rustclaw_types::{Provider, ProviderConfigError};
 
+use std::sync::Arc;
+use std::time::Duration;
 use tokio::signal;
 use tracing::{error, info, warn};
 
 /// Gateway service - main orchestrator
 pub struct GatewayService {
     config: Config,
+    /// Print the tool definitions and exit instead of starting the bot
+    dump_tools: bool,
 }
 
 impl GatewayService {
     /// Create a new gateway service
     pub fn new(config: Config) -> Self {
-        Self { config }
+        Self {
+            config,
+            dump_tools: false,
+        }
+    }
+
+    /// Print the tool definitions sent to the model (built-in and MCP) as
+    /// pretty JSON on stdout and exit once all tools are registered
+    pub fn with_dump_tools(mut self, dump_tools: bool) -> Self {
+        self.dump_tools = dump_tools;
+        self
     }
 
     /// Run the gateway service
     pub async fn run(self) -> Result<()> {
-        // Initialize logging
-        rustclaw_logging::init_logging(&self.config.logging.level)?;
+        // Initialize logging, keeping stdout for the tool dump
+        let logging = &self.config.logging;
+        if self.dump_tools {
+            rustclaw_logging::init_logging_to_stderr(&logging.level, logging.format)?;
+        } else {
+            rustclaw_logging::init_logging(&logging.level, logging.format)?;
+        }
         info!("Starting RustClaw Gateway Service");
 
         // Log agent config
         info!(
-            "Agent config: max_tool_iterations={}, context_window={}, recent_turns={}",
+            "Agent config: max_tool_iterations={}, context_window={}, recent_turns={}, max_continuations={}, max_concurrent_requests={}",
             self.config.agent.max_tool_iterations,
             self.config.agent.context_window,
-            self.config.agent.recent_turns
+            self.config.agent.recent_turns,
+            self.config.agent.max_continuations,
+            self.config.agent.max_concurrent_requests
         );
 
         // Initialize persistence
@@ -40,60 +62,88 @@ impl GatewayService {
         info!("Persistence service initialized");
 
         // Initialize provider based on config
-        let provider = match self.config.providers.default.as_str() {
-            "openai" => {
-                let model = &self.config.providers.openai.model;
-                let api_key = self
-                    .config
-                    .providers
-                    .openai
-                    .api_key
-                    .as_ref()
-                    .filter(|k| !k.is_empty());
-                let base_url = self
-                    .config
-                    .providers
-                    .openai
-                    .base_url
-                    .as_ref()
-                    .filter(|u| !u.is_empty());
-
-                // Use full constructor if we have API key and/or base URL
-                match (api_key, base_url) {
-                    (Some(key), Some(url)) => Provider::openai_full(model, key, url),
-                    (Some(key), None) => Provider::openai_with_api_key(model, key),
-                    (None, Some(url)) => Provider::openai_with_base_url(model, url),
-                    (None, None) => Provider::openai(model),
-                }
-            }
-            "ollama" => Provider::ollama(
-                &self.config.providers.ollama.model,
-                &self.config.providers.ollama.base_url,
-            ),
-            _ => {
-                warn!("Unknown provider, defaulting to OpenAI");
-                Provider::default()
+        let (provider, provider_status) = match Provider::from_config(&self.config.providers) {
+            Ok(provider) => (provider, Ok(self.config.providers.default.clone())),
+            Err(ProviderConfigError::UnknownProvider(name)) => {
+                warn!("Unknown provider '{}', defaulting to OpenAI", name);
+                let status = Err(format!("unknown provider '{}'", name));
+                (Provider::default(), status)
             }
+            Err(e) => anyhow::bail!(e),
         };
 
+        // Providers chats may switch to with /provider
+        let alternative_providers: Vec<Provider> = self
+            .config
+            .providers
+            .allowed_providers
+            .iter()
+            .filter_map(
+                |name| match Provider::from_config_named(&self.config.providers, name) {
+                    Ok(provider) => Some(provider),
+                    Err(e) => {
+                        warn!("Provider '{}' cannot be selected per chat: {}", name, e);
+                        None
+                    }
+                },
+            )
+            .collect();
+
         // Create tool registry with default tools (bash, file ops, etc.)
-        let tools = create_default_tools();
+        let mut tools = create_tools(
+            self.config.tools.enabled.as_deref(),
+            &self.config.tools.disabled,
+            self.config.tools.bash.enabled,
+            self.config.tools.read_file.max_bytes,
+        )
+        .with_cache_ttl(Duration::from_secs(self.config.agent.tool_cache_ttl))
+        .with_result_format(self.config.agent.tool_result_format);
+        if let Some(allowlist) = &self.config.tools.allowlist {
+            info!("Only allowlisted tools may run: {}", allowlist.join(", "));
+            tools = tools.with_allowlist(allowlist.iter().cloned());
+        }
         info!(
             "Tool registry initialized with {} built-in tools",
             tools.get_tools().len()
         );
 
         // Initialize MCP servers and wait for tools
+        let mut mcp_failure_notice = None;
+        let mut mcp_registry = None;
         let mcp_tools_list = if !self.config.mcp.servers.is_empty() {
             info!("Initializing MCP servers...");
-            let registry = MCPToolRegistry::start_all(&self.config.mcp).await;
+            let issues = self.config.mcp.validate();
+            for issue in &issues {
+                if issue.is_error() {
+                    error!("{}", issue);
+                } else {
+                    warn!("{}", issue);
+                }
+            }
+            let errors = issues.iter().filter(|issue| issue.is_error()).count();
+            if errors > 0 {
+                anyhow::bail!("MCP configuration has {} error(s), see the log", errors);
+            }
+
+            let (registry, report) = MCPToolRegistry::start_all(&self.config.mcp).await;
+            if let Some(reason) = report.unmet_requirement(&self.config.mcp) {
+                registry.shutdown_all().await;
+                anyhow::bail!(reason);
+            }
 
-            // Convert to tool functions
-            let tools = registry.to_tool_functions().await;
+            if report.has_failures() {
+                warn!("{}", report.summary());
+                mcp_failure_notice = Some(format!("⚠️ {}", report.summary()));
+            } else {
+                info!("{}", report.summary());
+            }
+
+            // Convert to tool functions, leaving built-in tools' names alone
+            let tools = registry.to_tool_functions(&tools.names()).await;
             info!("MCP initialized with {} tools", tools.len());
 
-            // Keep registry for reference if needed (currently we just need tools)
-            // mcp_registry = registry;
+            // Keep the registry for `/mcp`
+            mcp_registry = Some(Arc::new(registry));
             tools
         } else {
             Vec::new()
@@ -118,14 +168,24 @@ impl GatewayService {
         }
 
         // Discover skills (Phase 1: Load metadata only)
-        if let Err(e) = skills_registry.discover() {
+        if let Err(e) = skills_registry.discover_async().await {
             warn!("Failed to discover skills: {}", e);
         } else {
             info!("Discovered {} skills", skills_registry.len());
         }
 
-        // Generate skills list for system prompt
-        let skills_prompt = skills_registry.generate_system_prompt();
+        // Rank skills by their recorded activations
+        if self.config.skills.track_usage {
+            skills_registry = skills_registry.with_usage_tracking();
+            match persistence.get_skill_usage().await {
+                Ok(usage) => {
+                    for (name, usage) in usage {
+                        skills_registry.set_usage(&name, usage.uses as u64, usage.successes as u64);
+                    }
+                }
+                Err(e) => warn!("Failed to load skill usage: {}", e),
+            }
+        }
 
         // Create provider service with tools
         let base_prompt = "You are a helpful AI assistant. You have access to tools for executing \
@@ -140,13 +200,39 @@ impl GatewayService {
                  \
                  Always be helpful and provide clear explanations.";
 
-        let full_prompt = format!("{}{}", base_prompt, skills_prompt);
-
         // Initialize provider service with ALL tools
         let mut provider_service = ProviderService::new(provider)
             .with_tool_registry(tools) // Starts with default tools
             .with_max_tool_iterations(self.config.agent.max_tool_iterations)
-            .with_system_prompt(full_prompt);
+            .with_max_continuations(self.config.agent.max_continuations)
+            .with_max_concurrent_tools(self.config.agent.max_concurrent_tools)
+            .with_allowed_models(self.config.providers.allowed_models.clone())
+            .with_alternative_providers(alternative_providers)
+            .with_tool_output_preview(self.config.logging.tool_output_preview)
+            .with_context_window(self.config.agent.context_window)
+            .with_model_context_windows(self.config.agent.context_windows.clone())
+            .with_show_reasoning(self.config.agent.show_reasoning)
+            .with_text_tool_call_recovery(self.config.agent.recover_text_tool_calls)
+            .with_summarize_at_tokens(self.config.agent.summarize_at_tokens)
+            .with_summarize_tool_results_at(self.config.agent.summarize_tool_results_at)
+            .with_system_prompt(base_prompt)
+            .with_skill_summaries(skills_registry.summaries())
+            .with_system_prompt_budget(self.config.agent.system_prompt_budget)
+            .with_prompt_drop_order(self.config.agent.prompt_drop_order.clone());
+        if let Some(temperature) = self.config.agent.temperature {
+            provider_service = provider_service.with_temperature(temperature);
+        }
+        if self.config.agent.max_output_tokens > 0 {
+            provider_service =
+                provider_service.with_max_output_tokens(self.config.agent.max_output_tokens);
+        }
+        if let Some(model) = &self.config.agent.tool_summary_model {
+            provider_service = provider_service.with_tool_summary_model(model);
+        }
+        if self.config.agent.max_duration > 0 {
+            provider_service = provider_service
+                .with_max_duration(Duration::from_secs(self.config.agent.max_duration));
+        }
 
         // Register MCP tools
         for tool in mcp_tools_list {
@@ -155,11 +241,69 @@ impl GatewayService {
         info!("Provider service initialized");
 
         // Initialize Telegram channel
-        let telegram_service = TelegramService::new(
+        let mut telegram_service = TelegramService::new(
             &self.config.telegram.bot_token,
-            persistence,
+            persistence.clone(),
             provider_service,
+        )
+        .with_group_mode(
+            self.config.telegram.group_mode,
+            self.config.telegram.group_prefix.clone(),
+        )
+        .with_skills(skills_registry)
+        .with_admin_users(self.config.telegram.admin_user_ids.iter().copied())
+        .with_page_indicator(self.config.telegram.page_indicator.clone())
+        .with_language(self.config.telegram.language)
+        .with_tool_progress(self.config.telegram.show_tool_progress)
+        .with_max_concurrent_requests(self.config.agent.max_concurrent_requests)
+        .with_pricing(
+            self.config.pricing.models.clone(),
+            self.config.pricing.daily_budget,
         );
+        if let Some(registry) = &mcp_registry {
+            telegram_service = telegram_service.with_mcp(Arc::clone(registry));
+        }
+        if self.config.memory.enabled {
+            telegram_service = telegram_service.with_memory();
+        }
+        if let (Some(chat_id), Some(notice)) =
+            (self.config.telegram.admin_chat_id, mcp_failure_notice)
+        {
+            telegram_service = telegram_service.with_admin_notice(chat_id, notice);
+        }
+
+        if self.dump_tools {
+            let manifest = telegram_service.tool_manifest().await?;
+            println!("{}", serde_json::to_string_pretty(&manifest)?);
+            if let Some(registry) = mcp_registry {
+                registry.shutdown_all().await;
+            }
+            return Ok(());
+        }
+
+        // Bind the health endpoints before the bot starts answering
+        let health_server = match self.config.server.health_port {
+            Some(port) => {
+                // Lazy servers are not connected until first used
+                let mut mcp_servers: Vec<String> = self
+                    .config
+                    .mcp
+                    .servers
+                    .iter()
+                    .filter(|(_, server)| !server.is_lazy())
+                    .map(|(name, _)| name.clone())
+                    .collect();
+                mcp_servers.sort();
+                let checks = HealthChecks {
+                    persistence,
+                    provider: provider_status,
+                    mcp_servers,
+                    mcp: mcp_registry.clone(),
+                };
+                Some(HealthServer::bind(port, checks).await?)
+            }
+            None => None,
+        };
 
         // Setup signal handler for graceful shutdown
         let shutdown = async {
@@ -169,6 +313,13 @@ impl GatewayService {
             info!("Received shutdown signal");
         };
 
+        let health = async {
+            match health_server {
+                Some(server) => server.run().await,
+                None => std::future::pending().await,
+            }
+        };
+
         // Run the bot
         tokio::select! {
             result = telegram_service.run() => {
@@ -176,11 +327,17 @@ impl GatewayService {
                     error!("Telegram service error: {}", e);
                 }
             }
+            _ = health => {}
             _ = shutdown => {
                 info!("Shutting down gracefully...");
             }
         }
 
+        // Stop stdio MCP servers instead of leaving them to drop order
+        if let Some(registry) = mcp_registry {
+            registry.shutdown_all().await;
+        }
+
         info!("Gateway service stopped");
         Ok(())
     }
@@ -1,18 +1,59 @@
 use anyhow::Result;
+use serde::Deserialize;
+use tracing_subscriber::fmt::MakeWriter;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
 
+/// Output format of log lines
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// Human-readable lines (default)
+    #[default]
+    Text,
+    /// One JSON object per line, including the fields of the enclosing spans
+    /// (e.g. `request_id`)
+    Json,
+}
+
 /// Initialize the logging system
-pub fn init_logging(level: &str) -> Result<()> {
+pub fn init_logging(level: &str, format: LogFormat) -> Result<()> {
+    init_logging_with_writer(level, format, std::io::stdout)
+}
+
+/// Initialize the logging system, writing to stderr (e.g. when stdout
+/// carries a command's output)
+pub fn init_logging_to_stderr(level: &str, format: LogFormat) -> Result<()> {
+    init_logging_with_writer(level, format, std::io::stderr)
+}
+
+fn init_logging_with_writer<W>(level: &str, format: LogFormat, writer: W) -> Result<()>
+where
+    W: for<'a> MakeWriter<'a> + Send + Sync + 'static,
+{
     let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(level));
 
-    tracing_subscriber::registry()
-        .with(filter)
-        .with(
-            tracing_subscriber::fmt::layer()
-                .with_target(true)
-                .with_thread_ids(true),
-        )
-        .try_init()?;
+    let registry = tracing_subscriber::registry().with(filter);
+    match format {
+        LogFormat::Text => registry
+            .with(
+                tracing_subscriber::fmt::layer()
+                    .with_target(true)
+                    .with_thread_ids(true)
+                    .with_writer(writer),
+            )
+            .try_init()?,
+        LogFormat::Json => registry
+            .with(
+                tracing_subscriber::fmt::layer()
+                    .json()
+                    .with_current_span(true)
+                    .with_span_list(true)
+                    .with_target(true)
+                    .with_thread_ids(true)
+                    .with_writer(writer),
+            )
+            .try_init()?,
+    }
 
     Ok(())
 }
@@ -0,0 +1,211 @@
+//! Per-tool circuit breaker for MCP tool calls
+//!
+//! When a tool fails repeatedly (e.g. a misconfigured server), the breaker
+//! opens and further calls are short-circuited until a cooldown has passed.
+//! After the cooldown the breaker is half-open: a single trial call is let
+//! through while the others are still short-circuited, and its success
+//! closes the breaker again, its failure re-opens it.
+
+use std::sync::{Mutex, PoisonError};
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+/// Mutable breaker state guarded by a mutex
+#[derive(Debug, Default)]
+struct BreakerState {
+    /// Number of consecutive failed calls
+    consecutive_failures: u32,
+    /// When the breaker last opened (`None` while closed)
+    opened_at: Option<Instant>,
+    /// Whether the trial call of a half-open breaker is running
+    trial_running: bool,
+}
+
+/// Circuit breaker tracking consecutive failures of a single tool
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    /// Consecutive failures before the breaker opens (0 disables the breaker)
+    threshold: u32,
+    /// How long the breaker stays open before a trial call is allowed
+    cooldown: Duration,
+    state: Mutex<BreakerState>,
+}
+
+impl CircuitBreaker {
+    /// Create a new closed breaker
+    #[must_use]
+    pub fn new(threshold: u32, cooldown: Duration) -> Self {
+        Self {
+            threshold,
+            cooldown,
+            state: Mutex::new(BreakerState::default()),
+        }
+    }
+
+    /// Check whether calls should currently be short-circuited
+    #[must_use]
+    pub fn is_open(&self) -> bool {
+        let state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        state
+            .opened_at
+            .is_some_and(|opened| opened.elapsed() < self.cooldown || state.trial_running)
+    }
+
+    /// Ask to make a call, getting `None` if it should be short-circuited
+    ///
+    /// Once the cooldown has passed, only the first caller gets a permit,
+    /// for the trial call; the outcome of that call is recorded with
+    /// [`Self::record_success`] or [`Self::record_failure`] while the permit
+    /// is held.
+    #[must_use]
+    pub fn try_call(&self, tool: &str) -> Option<CallPermit<'_>> {
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        let trial = match state.opened_at {
+            None => false,
+            Some(opened) if opened.elapsed() < self.cooldown || state.trial_running => {
+                return None;
+            }
+            Some(_) => {
+                info!(
+                    "Circuit breaker for tool '{}' half-open, trying a call",
+                    tool
+                );
+                state.trial_running = true;
+                true
+            }
+        };
+        Some(CallPermit {
+            breaker: self,
+            trial,
+        })
+    }
+
+    /// Get the number of consecutive failures recorded so far
+    #[must_use]
+    pub fn consecutive_failures(&self) -> u32 {
+        self.state
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .consecutive_failures
+    }
+
+    /// Record a successful call, closing the breaker
+    pub fn record_success(&self, tool: &str) {
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        if state.opened_at.is_some() {
+            info!("Circuit breaker for tool '{}' closed after success", tool);
+        }
+        state.consecutive_failures = 0;
+        state.opened_at = None;
+        state.trial_running = false;
+    }
+
+    /// Record a failed call, opening the breaker once the threshold is reached
+    pub fn record_failure(&self, tool: &str) {
+        if self.threshold == 0 {
+            return;
+        }
+
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        state.consecutive_failures = state.consecutive_failures.saturating_add(1);
+
+        if state.consecutive_failures >= self.threshold {
+            warn!(
+                "Circuit breaker for tool '{}' opened after {} consecutive failures (cooldown {:?})",
+                tool, state.consecutive_failures, self.cooldown
+            );
+            state.opened_at = Some(Instant::now());
+        }
+        state.trial_running = false;
+    }
+}
+
+/// Permission to make one call, from [`CircuitBreaker::try_call`]
+///
+/// Dropping the permit of a trial call without recording its outcome (e.g.
+/// because the call was cancelled) lets the next caller make the trial.
+#[derive(Debug)]
+pub struct CallPermit<'a> {
+    breaker: &'a CircuitBreaker,
+    trial: bool,
+}
+
+impl Drop for CallPermit<'_> {
+    fn drop(&mut self) {
+        if self.trial {
+            self.breaker
+                .state
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .trial_running = false;
+        }
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_opens_after_threshold() {
+        let breaker = CircuitBreaker::new(2, Duration::from_mins(1));
+        breaker.record_failure("tool");
+        assert!(!breaker.is_open());
+        breaker.record_failure("tool");
+        assert!(breaker.is_open());
+    }
+
+    #[test]
+    fn test_success_resets() {
+        let breaker = CircuitBreaker::new(2, Duration::from_mins(1));
+        breaker.record_failure("tool");
+        breaker.record_success("tool");
+        breaker.record_failure("tool");
+        assert!(!breaker.is_open());
+        assert_eq!(breaker.consecutive_failures(), 1);
+    }
+
+    #[test]
+    fn test_cooldown_elapsed_allows_trial() {
+        let breaker = CircuitBreaker::new(1, Duration::ZERO);
+        breaker.record_failure("tool");
+        assert!(!breaker.is_open());
+
+        // Only one call is let through while the trial runs
+        let trial = breaker.try_call("tool").unwrap();
+        assert!(breaker.try_call("tool").is_none());
+        assert!(breaker.is_open());
+
+        // A failed trial re-opens the breaker, a successful one closes it
+        breaker.record_failure("tool");
+        drop(trial);
+        let trial = breaker.try_call("tool").unwrap();
+        assert!(breaker.try_call("tool").is_none());
+        breaker.record_success("tool");
+        drop(trial);
+        let _first = breaker.try_call("tool").unwrap();
+        let _second = breaker.try_call("tool").unwrap();
+        assert_eq!(breaker.consecutive_failures(), 0);
+    }
+
+    #[test]
+    fn test_cancelled_trial() {
+        let breaker = CircuitBreaker::new(1, Duration::ZERO);
+        breaker.record_failure("tool");
+
+        // A trial dropped without an outcome hands the trial to the next call
+        drop(breaker.try_call("tool").unwrap());
+        let _trial = breaker.try_call("tool").unwrap();
+        assert!(breaker.try_call("tool").is_none());
+    }
+
+    #[test]
+    fn test_zero_threshold_disables() {
+        let breaker = CircuitBreaker::new(0, Duration::from_mins(1));
+        for _ in 0..10 {
+            breaker.record_failure("tool");
+        }
+        assert!(!breaker.is_open());
+    }
+}
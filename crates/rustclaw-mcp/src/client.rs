@@ -1,24 +1,31 @@
 //! MCP client wrapper around rmcp SDK
 //!
 //! Provides [`MCPClient`] for connecting to MCP servers via stdio or Streamable HTTP
-//! transports, discovering available tools, and executing tool calls.
+//! transports (or a custom [`MCPTransport`]), discovering available tools, executing tool calls and
+//! subscribing to resource updates.
 
-use crate::config::{MCPServerConfig, TransportType};
+use crate::config::{MCPConfig, MCPServerConfig};
 use crate::error::{MCPError, Result};
-use crate::http_client::CompatibleHttpClient;
+use crate::http_client::INVALID_UTF8_META;
+use crate::transport::{ConfiguredTransport, Connector, MCPTransport};
 use rmcp::model::{
-    CallToolRequestParams, ClientCapabilities, ClientInfo, Implementation, ProtocolVersion,
+    CallToolRequestParams, CallToolResult, ClientCapabilities, ClientInfo, Implementation,
+    PaginatedRequestParams, ProtocolVersion, ResourceUpdatedNotificationParam, ServerCapabilities,
+    SubscribeRequestParams, UnsubscribeRequestParams,
 };
-use rmcp::service::{Peer, RoleClient, RunningService};
-use rmcp::transport::streamable_http_client::StreamableHttpClientTransport;
-use rmcp::ServiceExt;
+use rmcp::service::{NotificationContext, Peer, RoleClient, RunningService, ServiceError};
+use rmcp::ClientHandler;
 use serde_json::Value;
-use std::collections::HashMap;
-use std::sync::Arc;
-use std::time::Duration;
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, Instant};
 use tokio::sync::RwLock;
 use tracing::{debug, info, warn};
 
+/// Most tools accepted from one server, guarding against endless pagination
+const MAX_TOOLS: usize = 1000;
+
 /// MCP tool definition discovered from a server
 #[derive(Debug, Clone)]
 pub struct ToolDefinition {
@@ -30,10 +37,110 @@ pub struct ToolDefinition {
     pub input_schema: Value,
 }
 
+/// Summary of what a connected MCP server supports, for diagnostics
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServerDescription {
+    /// Configured server name
+    pub name: String,
+    /// Name and version the server reports (`None` while disconnected)
+    pub implementation: Option<String>,
+    /// Negotiated protocol version
+    pub protocol_version: String,
+    /// Transport used to reach the server (`stdio`, `http` or the kind of a
+    /// custom transport)
+    pub transport: &'static str,
+    /// Whether the connection is open (idle HTTP sessions are closed)
+    pub connected: bool,
+    /// Number of tools discovered at startup
+    pub tool_count: usize,
+    /// Negotiated capabilities, e.g. `tools`, `resources (subscribe)`,
+    /// `prompts` or `sampling` (empty while disconnected)
+    pub capabilities: Vec<String>,
+}
+
+impl std::fmt::Display for ServerDescription {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name)?;
+        if let Some(implementation) = &self.implementation {
+            write!(f, " ({implementation})")?;
+        }
+        let state = if self.connected {
+            "connected"
+        } else {
+            "disconnected"
+        };
+        let capabilities = if self.capabilities.is_empty() {
+            "unknown".to_string()
+        } else {
+            self.capabilities.join(", ")
+        };
+        write!(
+            f,
+            "\nTransport: {}, {}\nProtocol: {}\nTools: {}\nCapabilities: {}",
+            self.transport, state, self.protocol_version, self.tool_count, capabilities
+        )
+    }
+}
+
+/// A live connection to an MCP server
+struct Connection {
+    /// Peer handle for sending requests to the server
+    peer: Peer<RoleClient>,
+    /// Keep the running service alive — dropping it shuts down the connection
+    service: RunningService<RoleClient, NotificationHandler>,
+    /// Number of the session, counting up with every reconnect
+    session: u64,
+}
+
+/// Callback invoked with the URI of a subscribed resource that changed
+pub type ResourceUpdateCallback = Arc<dyn Fn(&str) + Send + Sync>;
+
+/// Handles notifications sent by the server
+pub(crate) struct NotificationHandler {
+    /// Server name, for logging
+    pub(crate) server: String,
+    /// Callback for `notifications/resources/updated`, shared with the client
+    pub(crate) on_resource_updated: Arc<Mutex<Option<ResourceUpdateCallback>>>,
+}
+
+impl NotificationHandler {
+    /// Pass a resource update to the registered callback
+    fn resource_updated(&self, uri: &str) {
+        debug!("MCP server '{}' updated resource '{}'", self.server, uri);
+        let callback = self
+            .on_resource_updated
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone();
+        if let Some(callback) = callback {
+            callback(uri);
+        } else {
+            debug!("No resource update callback registered, ignoring");
+        }
+    }
+}
+
+impl ClientHandler for NotificationHandler {
+    async fn on_resource_updated(
+        &self,
+        params: ResourceUpdatedNotificationParam,
+        _context: NotificationContext<RoleClient>,
+    ) {
+        self.resource_updated(&params.uri);
+    }
+
+    fn get_info(&self) -> ClientInfo {
+        client_info()
+    }
+}
+
 /// Handle to a running MCP server connection
 ///
 /// Wraps the rmcp `Peer` which allows sending requests to the server.
-/// The `Peer` is `Clone + Send + Sync` so it can be shared safely.
+/// HTTP connections with an idle timeout are closed by
+/// [`MCPClient::disconnect_if_idle`] and re-established on the next call.
+/// When an HTTP server expires the session, a tool call starts a new one and
+/// is retried.
 pub struct MCPClient {
     /// Server name
     pub name: String,
@@ -41,10 +148,28 @@ pub struct MCPClient {
     pub tools: Vec<ToolDefinition>,
     /// Negotiated protocol version
     pub protocol_version: String,
-    /// Peer handle for sending requests to the server
-    peer: Peer<RoleClient>,
-    /// Keep the running service alive — dropping it shuts down the connection
-    _service: Arc<RwLock<Option<Box<dyn std::any::Any + Send + Sync>>>>,
+    /// Live connection (`None` after an idle disconnect)
+    connection: RwLock<Option<Connection>>,
+    /// Transport the connection is opened over, kept for reconnecting
+    transport: Box<dyn MCPTransport>,
+    /// Server configuration (`None` for a custom transport)
+    config: Option<MCPServerConfig>,
+    /// Startup timeout, reused when reconnecting
+    timeout: Duration,
+    /// Disconnect after this long without tool calls
+    idle_timeout: Option<Duration>,
+    /// When the last tool call started or finished
+    last_used: Mutex<Instant>,
+    /// URIs of the resources currently subscribed to
+    subscriptions: Mutex<HashSet<String>>,
+    /// Callback for updates of subscribed resources
+    on_resource_updated: Arc<Mutex<Option<ResourceUpdateCallback>>>,
+    /// Set by [`MCPClient::close`]; a closed client never reconnects
+    closed: AtomicBool,
+    /// Sessions started so far, numbering the current connection
+    sessions: AtomicU64,
+    /// New sessions one tool call may start after the session expired
+    session_reconnects: u32,
 }
 
 /// Build the `ClientInfo` advertised during MCP initialization
@@ -73,166 +198,116 @@ impl MCPClient {
     /// # Errors
     /// Returns an error if the server fails to start or times out
     pub async fn start(name: String, config: &MCPServerConfig, timeout: Duration) -> Result<Self> {
-        info!("Starting MCP server '{}' with timeout {:?}", name, timeout);
-
-        let transport_type = config.detect_transport();
-
-        let result = tokio::time::timeout(timeout, async {
-            match transport_type {
-                TransportType::Stdio { program, args, env } => {
-                    Self::start_stdio(&name, &program, &args, &env).await
-                }
-                TransportType::HTTP(url, headers) => {
-                    // Case-insensitive lookup for Authorization header
-                    let auth_header = headers
-                        .iter()
-                        .find(|(k, _)| k.eq_ignore_ascii_case("authorization"))
-                        .map(|(_, v)| v.clone());
-
-                    if auth_header.is_none() {
-                        tracing::warn!(
-                            ?headers,
-                            "No Authorization header found for HTTP transport! Keys: {:?}",
-                            headers.keys()
-                        );
-                    }
-                    Self::start_http(&name, &url, auth_header).await
-                }
-            }
-        })
-        .await
-        .map_err(|_| MCPError::StartupTimeout {
-            server: name.clone(),
-            timeout,
-        })?;
-
-        result
+        let transport = Box::new(ConfiguredTransport::new(config.clone()));
+        Self::start_inner(name, transport, Some(config.clone()), timeout).await
     }
 
-    /// Start an MCP server via stdio (child process) transport
-    async fn start_stdio(
-        name: &str,
-        program: &str,
-        args: &[String],
-        env: &HashMap<String, String>,
+    /// Connect to an MCP server over a custom transport
+    ///
+    /// Without a server configuration, tool descriptions are used as the
+    /// server sends them, the connection is never closed while idle and
+    /// expired sessions are not renewed. Startup times out after the default
+    /// startup timeout.
+    ///
+    /// # Errors
+    /// Returns an error if the transport fails to connect or times out
+    pub async fn start_with_transport(
+        name: String,
+        transport: impl MCPTransport + 'static,
     ) -> Result<Self> {
-        debug!(
-            "Starting stdio transport for '{}': {} {:?} env={:?}",
-            name, program, args, env
-        );
-
-        // Build tokio Command for the child process
-        let mut cmd = tokio::process::Command::new(program);
-        cmd.args(args);
-
-        // Set custom environment variables
-        for (key, value) in env {
-            cmd.env(key, value);
-            // Also set uppercase version in case config loader lowercased it
-            // (e.g. Z_AI_API_KEY becoming z_ai_api_key)
-            let upper = key.to_uppercase();
-            if upper != *key {
-                cmd.env(upper, value);
-            }
-        }
-
-        // Pipe stdin/stdout for MCP communication
-        cmd.stdin(std::process::Stdio::piped());
-        cmd.stdout(std::process::Stdio::piped());
-        cmd.stderr(std::process::Stdio::piped());
-
-        // Create transport from child process (takes ownership of cmd)
-        let transport =
-            rmcp::transport::TokioChildProcess::new(cmd).map_err(|e| MCPError::StartupFailed {
-                server: name.into(),
-                reason: format!("Failed to spawn '{program}': {e}"),
-            })?;
+        let timeout = Duration::from_secs(MCPConfig::default().startup_timeout);
+        Self::start_inner(name, Box::new(transport), None, timeout).await
+    }
 
-        // Connect and initialize MCP protocol
-        let service: RunningService<RoleClient, _> = client_info()
-            .serve(transport)
-            .await
-            .map_err(|e| MCPError::Sdk(format!("Failed to initialize MCP for '{name}': {e}")))?;
+    async fn start_inner(
+        name: String,
+        transport: Box<dyn MCPTransport>,
+        config: Option<MCPServerConfig>,
+        timeout: Duration,
+    ) -> Result<Self> {
+        info!("Starting MCP server '{}' with timeout {:?}", name, timeout);
 
+        let on_resource_updated = Arc::new(Mutex::new(None));
+        let service = Self::connect(
+            &name,
+            transport.as_ref(),
+            timeout,
+            Arc::clone(&on_resource_updated),
+        )
+        .await?;
         let peer = service.peer().clone();
 
         // Discover tools from the server
-        let tools = Self::discover_tools(&peer, name).await?;
-
-        let protocol_version = "2024-11-05".to_string();
+        let tools = Self::discover_tools(&peer, &name).await?;
 
+        let protocol_version = transport.protocol_version();
         info!(
-            "MCP server '{}' connected via stdio ({} tools)",
+            "MCP server '{}' connected ({} tools, protocol {})",
             name,
-            tools.len()
+            tools.len(),
+            protocol_version
         );
 
         Ok(Self {
-            name: name.into(),
+            name,
             tools,
-            protocol_version,
-            peer,
-            _service: Arc::new(RwLock::new(Some(Box::new(service)))),
+            protocol_version: protocol_version.to_string(),
+            connection: RwLock::new(Some(Connection {
+                peer,
+                service,
+                session: 1,
+            })),
+            idle_timeout: config.as_ref().and_then(MCPServerConfig::idle_timeout),
+            session_reconnects: config
+                .as_ref()
+                .map_or(0, MCPServerConfig::session_reconnects),
+            transport,
+            config,
+            timeout,
+            last_used: Mutex::new(Instant::now()),
+            subscriptions: Mutex::new(HashSet::new()),
+            on_resource_updated,
+            closed: AtomicBool::new(false),
+            sessions: AtomicU64::new(1),
         })
     }
 
-    /// Start an MCP server via Streamable HTTP transport
-    async fn start_http(name: &str, url: &str, auth_header: Option<String>) -> Result<Self> {
-        debug!("Starting HTTP transport for '{}': {}", name, url);
-
-        // Build transport config
-        let mut config =
-            rmcp::transport::streamable_http_client::StreamableHttpClientTransportConfig::with_uri(
-                url,
-            );
-
-        // rmcp's reqwest impl uses `bearer_auth()` which adds "Bearer " prefix automatically,
-        // so we strip the "Bearer " prefix from our config to avoid "Bearer Bearer xxx".
-        if let Some(auth) = &auth_header {
-            let token = auth.strip_prefix("Bearer ").unwrap_or(auth);
-            config = config.auth_header(token.to_string());
-        }
-
-        let transport =
-            StreamableHttpClientTransport::with_client(CompatibleHttpClient::default(), config);
+    /// Open the transport and initialize the MCP protocol
+    async fn connect(
+        name: &str,
+        transport: &dyn MCPTransport,
+        timeout: Duration,
+        on_resource_updated: Arc<Mutex<Option<ResourceUpdateCallback>>>,
+    ) -> Result<RunningService<RoleClient, NotificationHandler>> {
+        let connector = Connector::new(NotificationHandler {
+            server: name.into(),
+            on_resource_updated,
+        });
 
-        // Connect and initialize MCP protocol
-        let service: RunningService<RoleClient, _> = client_info()
-            .serve(transport)
+        let session = tokio::time::timeout(timeout, transport.connect(connector))
             .await
-            .map_err(|e| MCPError::Sdk(format!("Failed to initialize MCP for '{name}': {e}")))?;
-
-        let peer = service.peer().clone();
-
-        // Discover tools from the server
-        let tools = Self::discover_tools(&peer, name).await?;
-
-        let protocol_version = "2025-03-26".to_string();
-
-        info!(
-            "MCP server '{}' connected via HTTP ({} tools)",
-            name,
-            tools.len()
-        );
-
-        Ok(Self {
-            name: name.into(),
-            tools,
-            protocol_version,
-            peer,
-            _service: Arc::new(RwLock::new(Some(Box::new(service)))),
-        })
+            .map_err(|_| MCPError::StartupTimeout {
+                server: name.into(),
+                timeout,
+            })??;
+        Ok(session.service)
     }
 
     /// Discover available tools from a connected MCP server
+    ///
+    /// Follows `next_cursor` until the last page, collecting at most
+    /// [`MAX_TOOLS`] tools.
     async fn discover_tools(peer: &Peer<RoleClient>, name: &str) -> Result<Vec<ToolDefinition>> {
-        let list_result = peer
-            .list_tools(None)
-            .await
-            .map_err(|e| MCPError::Sdk(format!("Failed to list tools from '{name}': {e}")))?;
+        let listed = collect_pages(name, |cursor| async move {
+            let page = peer
+                .list_tools(Some(PaginatedRequestParams { meta: None, cursor }))
+                .await
+                .map_err(|e| MCPError::from_service_error(name, "Failed to list tools", e))?;
+            Ok((page.tools, page.next_cursor))
+        })
+        .await?;
 
-        let tools: Vec<ToolDefinition> = list_result
-            .tools
+        let tools: Vec<ToolDefinition> = listed
             .into_iter()
             .map(|t| {
                 debug!("  Tool '{}': {:?}", t.name, t.description);
@@ -248,13 +323,213 @@ impl MCPClient {
         Ok(tools)
     }
 
+    /// Description of `tool` to show the model, after configured overrides
+    #[must_use]
+    pub fn tool_description(&self, tool: &ToolDefinition) -> Option<String> {
+        match &self.config {
+            Some(config) => config.tool_description(&tool.name, tool.description.as_deref()),
+            None => tool.description.clone(),
+        }
+    }
+
+    /// Get the idle timeout after which the connection is closed, if any
+    #[must_use]
+    pub fn idle_timeout(&self) -> Option<Duration> {
+        self.idle_timeout
+    }
+
+    /// Check whether the client currently holds a live connection
+    pub async fn is_connected(&self) -> bool {
+        self.connection.read().await.is_some()
+    }
+
+    /// Record that the client was just used
+    fn touch(&self) {
+        *self
+            .last_used
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner) = Instant::now();
+    }
+
+    /// Check whether the client has been unused for longer than its idle timeout
+    ///
+    /// Clients with active subscriptions are never idle, since closing the
+    /// connection would stop the server's update notifications.
+    fn is_idle(&self) -> bool {
+        if !self.lock_subscriptions().is_empty() {
+            return false;
+        }
+        self.idle_timeout.is_some_and(|timeout| {
+            self.last_used
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .elapsed()
+                >= timeout
+        })
+    }
+
+    /// Close the connection if it has been idle past the idle timeout
+    ///
+    /// Closing an HTTP connection deletes the server-side session. The client
+    /// reconnects lazily on the next tool call. Returns `true` if the
+    /// connection was closed.
+    pub async fn disconnect_if_idle(&self) -> bool {
+        if !self.is_idle() {
+            return false;
+        }
+
+        // Waits for in-flight calls, which hold the read lock
+        let mut connection = self.connection.write().await;
+        if !self.is_idle() {
+            return false;
+        }
+
+        let Some(conn) = connection.take() else {
+            return false;
+        };
+
+        info!(
+            "Disconnecting idle MCP server '{}' (idle timeout {:?})",
+            self.name, self.idle_timeout
+        );
+        self.close_connection(conn).await;
+        true
+    }
+
+    /// Unsubscribe from all resources and close the connection
+    ///
+    /// The client reconnects lazily on the next tool call; subscriptions are
+    /// not restored.
+    pub async fn disconnect(&self) {
+        let Some(conn) = self.connection.write().await.take() else {
+            return;
+        };
+
+        let uris: Vec<String> = self.lock_subscriptions().iter().cloned().collect();
+        for uri in uris {
+            if let Err(e) = conn
+                .peer
+                .unsubscribe(UnsubscribeRequestParams {
+                    meta: None,
+                    uri: uri.clone(),
+                })
+                .await
+            {
+                warn!(
+                    "Failed to unsubscribe from '{}' on MCP server '{}': {}",
+                    uri, self.name, e
+                );
+            }
+        }
+
+        info!("Disconnecting MCP server '{}'", self.name);
+        self.close_connection(conn).await;
+    }
+
+    /// Close the connection for good
+    ///
+    /// Unlike [`Self::disconnect`], later calls fail instead of reconnecting.
+    /// Returns once a stdio server's process has exited (it is killed if it
+    /// does not exit in time), so no child process outlives the client.
+    pub async fn close(&self) {
+        self.closed.store(true, Ordering::SeqCst);
+        self.disconnect().await;
+    }
+
+    /// Shut down a connection and forget its subscriptions
+    async fn close_connection(&self, mut conn: Connection) {
+        self.lock_subscriptions().clear();
+        if let Err(e) = conn.service.close().await {
+            warn!("Failed to close MCP server '{}' cleanly: {}", self.name, e);
+        }
+    }
+
+    /// Reconnect if the connection was closed while idle
+    async fn ensure_connected(&self) -> Result<()> {
+        if self.connection.read().await.is_some() {
+            return Ok(());
+        }
+        if self.closed.load(Ordering::SeqCst) {
+            return Err(MCPError::ServerDisconnected {
+                server: self.name.clone(),
+            });
+        }
+
+        let mut connection = self.connection.write().await;
+        if connection.is_none() {
+            info!("Reconnecting to MCP server '{}'", self.name);
+            let service = Self::connect(
+                &self.name,
+                self.transport.as_ref(),
+                self.timeout,
+                Arc::clone(&self.on_resource_updated),
+            )
+            .await?;
+            let peer = service.peer().clone();
+            let session = self.sessions.fetch_add(1, Ordering::SeqCst) + 1;
+            *connection = Some(Connection {
+                peer,
+                service,
+                session,
+            });
+        }
+        Ok(())
+    }
+
+    /// Drop the connection of an expired session, unless it was replaced already
+    async fn end_session(&self, session: u64) {
+        let mut connection = self.connection.write().await;
+        if connection
+            .as_ref()
+            .is_some_and(|conn| conn.session == session)
+        {
+            if let Some(conn) = connection.take() {
+                self.close_connection(conn).await;
+            }
+        }
+    }
+
     /// Call a tool on this MCP server
     ///
+    /// Text content is flattened into a single value (parsed as JSON where
+    /// possible); use [`Self::call_tool_raw`] to get every content block.
+    /// Output whose response held invalid UTF-8 is flagged with
+    /// `"invalid_utf8": true`.
+    ///
     /// # Errors
-    /// Returns an error if the tool call fails
+    /// Returns an error if the tool call fails or the tool reports an error
     pub async fn call_tool(&self, tool_name: &str, args: Value) -> Result<Value> {
+        let result = self.call_tool_raw(tool_name, args).await?;
+        let mut output = flatten_result(&result);
+        if has_invalid_utf8(&result) {
+            output = flag_invalid_utf8(output);
+        }
+
+        // If the tool call indicated an error, wrap it
+        if result.is_error.unwrap_or(false) {
+            return Err(MCPError::ToolExecution {
+                server: self.name.clone(),
+                tool: tool_name.into(),
+                reason: format!("Tool returned error: {output}"),
+            });
+        }
+
+        Ok(output)
+    }
+
+    /// Call a tool on this MCP server, returning the server's result as is
+    ///
+    /// Unlike [`Self::call_tool`], all content blocks (including non-text
+    /// content), the structured content, `is_error` and `_meta` are kept, and
+    /// a result flagged with `is_error` is returned rather than turned into an
+    /// error.
+    ///
+    /// # Errors
+    /// Returns an error if the request fails or the server rejects it
+    pub async fn call_tool_raw(&self, tool_name: &str, args: Value) -> Result<CallToolResult> {
         debug!("Calling tool '{}' on server '{}'", tool_name, self.name);
 
+        self.touch();
         let arguments = match args {
             Value::Object(map) => Some(map),
             Value::Null => None,
@@ -269,7 +544,45 @@ impl MCPClient {
             }
         };
 
-        let result = self
+        // The server rejected calls in an expired session without running
+        // them, so they are safe to send again in a new one
+        let mut reconnects = 0;
+        let result = loop {
+            self.ensure_connected().await?;
+            match self.send_tool_call(tool_name, arguments.clone()).await {
+                (Err(e), session)
+                    if e.is_session_expired() && reconnects < self.session_reconnects =>
+                {
+                    reconnects += 1;
+                    warn!(
+                        "Session with MCP server '{}' expired, starting a new one ({}/{})",
+                        self.name, reconnects, self.session_reconnects
+                    );
+                    self.end_session(session).await;
+                }
+                (result, _) => break result,
+            }
+        };
+        self.touch();
+        result
+    }
+
+    /// Send one `tools/call` request, returning the session it was sent in
+    async fn send_tool_call(
+        &self,
+        tool_name: &str,
+        arguments: Option<serde_json::Map<String, Value>>,
+    ) -> (Result<CallToolResult>, u64) {
+        // Hold the read lock for the call so an idle disconnect can't race it
+        let connection = self.connection.read().await;
+        let Some(conn) = connection.as_ref() else {
+            let error = MCPError::ServerDisconnected {
+                server: self.name.clone(),
+            };
+            return (Err(error), 0);
+        };
+
+        let result = conn
             .peer
             .call_tool(CallToolRequestParams {
                 name: String::from(tool_name).into(),
@@ -278,46 +591,663 @@ impl MCPClient {
                 task: None,
             })
             .await
-            .map_err(|e| MCPError::ToolExecution {
+            .map_err(|e| match e {
+                // Errors reported by the server stay tool failures
+                ServiceError::McpError(data) => MCPError::ToolExecution {
+                    server: self.name.clone(),
+                    tool: tool_name.into(),
+                    reason: format!("Mcp error: {data}"),
+                },
+                other => MCPError::from_service_error(
+                    &self.name,
+                    &format!("Failed to call tool '{tool_name}'"),
+                    other,
+                ),
+            });
+        (result, conn.session)
+    }
+
+    /// Register the callback invoked when a subscribed resource changes
+    ///
+    /// The callback receives the resource URI and replaces any previously
+    /// registered callback.
+    pub fn on_resource_updated(&self, callback: ResourceUpdateCallback) {
+        *self
+            .on_resource_updated
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner) = Some(callback);
+    }
+
+    /// Summarize the server's connection state and capabilities
+    pub async fn describe(&self) -> ServerDescription {
+        let connection = self.connection.read().await;
+        let info = connection.as_ref().and_then(|conn| conn.peer.peer_info());
+
+        ServerDescription {
+            name: self.name.clone(),
+            implementation: info
+                .map(|info| format!("{} {}", info.server_info.name, info.server_info.version)),
+            protocol_version: info.map_or_else(
+                || self.protocol_version.clone(),
+                |info| info.protocol_version.to_string(),
+            ),
+            transport: self.transport.kind(),
+            connected: connection.is_some(),
+            tool_count: self.tools.len(),
+            capabilities: info
+                .map(|info| capability_names(&info.capabilities, &client_info().capabilities))
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Check whether the server advertises the `resources.subscribe` capability
+    pub async fn supports_resource_subscriptions(&self) -> bool {
+        self.connection
+            .read()
+            .await
+            .as_ref()
+            .and_then(|conn| conn.peer.peer_info())
+            .is_some_and(|info| supports_subscribe(&info.capabilities))
+    }
+
+    /// URIs of the resources currently subscribed to
+    #[must_use]
+    pub fn subscriptions(&self) -> Vec<String> {
+        let mut uris: Vec<String> = self.lock_subscriptions().iter().cloned().collect();
+        uris.sort();
+        uris
+    }
+
+    /// Subscribe to updates of a resource
+    ///
+    /// The server then sends `notifications/resources/updated` whenever the
+    /// resource changes, which are passed to the callback registered with
+    /// [`Self::on_resource_updated`].
+    ///
+    /// # Errors
+    /// Returns an error if the server does not support subscriptions or
+    /// rejects the request
+    pub async fn subscribe_resource(&self, uri: &str) -> Result<()> {
+        self.ensure_connected().await?;
+
+        let connection = self.connection.read().await;
+        let peer = &connection
+            .as_ref()
+            .ok_or_else(|| MCPError::ServerDisconnected {
                 server: self.name.clone(),
-                tool: tool_name.into(),
-                reason: format!("{e}"),
+            })?
+            .peer;
+
+        if !peer
+            .peer_info()
+            .is_some_and(|info| supports_subscribe(&info.capabilities))
+        {
+            return Err(MCPError::Protocol(format!(
+                "Server '{}' does not support resource subscriptions",
+                self.name
+            )));
+        }
+
+        peer.subscribe(SubscribeRequestParams {
+            meta: None,
+            uri: uri.into(),
+        })
+        .await
+        .map_err(|e| {
+            MCPError::from_service_error(&self.name, &format!("Failed to subscribe to '{uri}'"), e)
+        })?;
+
+        debug!("Subscribed to '{}' on MCP server '{}'", uri, self.name);
+        self.lock_subscriptions().insert(uri.into());
+        Ok(())
+    }
+
+    /// Stop receiving updates of a resource
+    ///
+    /// # Errors
+    /// Returns an error if the server rejects the request
+    pub async fn unsubscribe_resource(&self, uri: &str) -> Result<()> {
+        if !self.lock_subscriptions().remove(uri) {
+            return Ok(());
+        }
+
+        let connection = self.connection.read().await;
+        let Some(conn) = connection.as_ref() else {
+            return Ok(());
+        };
+
+        conn.peer
+            .unsubscribe(UnsubscribeRequestParams {
+                meta: None,
+                uri: uri.into(),
+            })
+            .await
+            .map_err(|e| {
+                MCPError::from_service_error(
+                    &self.name,
+                    &format!("Failed to unsubscribe from '{uri}'"),
+                    e,
+                )
             })?;
 
-        // Convert CallToolResult content to JSON value
-        let content_values: Vec<Value> = result
-            .content
-            .iter()
-            .filter_map(|content| {
-                // Extract text content from the result
-                if let Some(text) = content.as_text() {
-                    // Try to parse as JSON first, fall back to string
-                    match serde_json::from_str(text.text.as_ref()) {
-                        Ok(v) => Some(v),
-                        Err(_) => Some(Value::String(text.text.clone())),
+        debug!("Unsubscribed from '{}' on MCP server '{}'", uri, self.name);
+        Ok(())
+    }
+
+    fn lock_subscriptions(&self) -> std::sync::MutexGuard<'_, HashSet<String>> {
+        self.subscriptions
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Check whether server capabilities include resource subscriptions
+fn supports_subscribe(capabilities: &ServerCapabilities) -> bool {
+    capabilities
+        .resources
+        .as_ref()
+        .and_then(|resources| resources.subscribe)
+        .unwrap_or(false)
+}
+
+/// Names of the capabilities negotiated with a server
+///
+/// Sampling is offered by the client rather than the server, so it is listed
+/// when the client advertises it.
+fn capability_names(server: &ServerCapabilities, client: &ClientCapabilities) -> Vec<String> {
+    let mut names = Vec::new();
+    if server.tools.is_some() {
+        names.push("tools".to_string());
+    }
+    if server.resources.is_some() {
+        names.push(if supports_subscribe(server) {
+            "resources (subscribe)".to_string()
+        } else {
+            "resources".to_string()
+        });
+    }
+    if server.prompts.is_some() {
+        names.push("prompts".to_string());
+    }
+    if server.logging.is_some() {
+        names.push("logging".to_string());
+    }
+    if server.completions.is_some() {
+        names.push("completions".to_string());
+    }
+    if client.sampling.is_some() {
+        names.push("sampling".to_string());
+    }
+    names
+}
+
+/// Convert the text and image content of a tool result into a single JSON
+/// value
+///
+/// Each text block is converted with [`text_value`] and each image kept as
+/// `{"type": "image", "data": ..., "mimeType": ...}`; a single block is
+/// returned directly and several as an array.
+fn flatten_result(result: &CallToolResult) -> Value {
+    let content_values: Vec<Value> = result
+        .content
+        .iter()
+        .filter_map(|content| {
+            if let Some(image) = content.as_image() {
+                return Some(serde_json::json!({
+                    "type": "image",
+                    "data": image.data,
+                    "mimeType": image.mime_type,
+                }));
+            }
+            Some(text_value(&content.as_text()?.text))
+        })
+        .collect();
+
+    // Return single value directly, or array if multiple
+    match content_values.len() {
+        0 => Value::Null,
+        1 => content_values.into_iter().next().unwrap_or(Value::Null),
+        _ => Value::Array(content_values),
+    }
+}
+
+/// Whether the response carrying `result` held invalid UTF-8, replaced on
+/// the way in
+fn has_invalid_utf8(result: &CallToolResult) -> bool {
+    result
+        .meta
+        .as_ref()
+        .and_then(|meta| meta.0.get(INVALID_UTF8_META))
+        .is_some_and(|flag| flag == &Value::Bool(true))
+}
+
+/// Flag tool output with `"invalid_utf8": true`, as the built-in tools do,
+/// so the model knows it may be binary or corrupted
+///
+/// Objects get the flag added and other output is wrapped in an object with
+/// it; the items of an array are flagged one by one.
+fn flag_invalid_utf8(output: Value) -> Value {
+    match output {
+        Value::Array(items) => Value::Array(items.into_iter().map(flag_invalid_utf8).collect()),
+        Value::Object(mut map) => {
+            map.insert("invalid_utf8".into(), Value::Bool(true));
+            Value::Object(map)
+        }
+        output => serde_json::json!({ "content": output, "invalid_utf8": true }),
+    }
+}
+
+/// Collect the items of every page of a paginated listing
+///
+/// `fetch` gets the cursor of the page to load (`None` for the first) and
+/// returns its items and the next cursor. Stops after [`MAX_TOOLS`] items or
+/// when a server hands out a cursor it already returned.
+async fn collect_pages<T, F, Fut>(name: &str, mut fetch: F) -> Result<Vec<T>>
+where
+    F: FnMut(Option<String>) -> Fut,
+    Fut: std::future::Future<Output = Result<(Vec<T>, Option<String>)>>,
+{
+    let mut items = Vec::new();
+    let mut seen = HashSet::new();
+    let mut cursor = None;
+    loop {
+        let (page, next) = fetch(cursor).await?;
+        items.extend(page);
+
+        if items.len() >= MAX_TOOLS {
+            warn!(
+                "Server '{}' lists more than {} tools, ignoring the rest",
+                name, MAX_TOOLS
+            );
+            items.truncate(MAX_TOOLS);
+            break;
+        }
+        match next {
+            Some(next) if seen.insert(next.clone()) => cursor = Some(next),
+            Some(next) => {
+                warn!(
+                    "Server '{}' repeated list cursor '{}', stopping",
+                    name, next
+                );
+                break;
+            }
+            None => break,
+        }
+    }
+    Ok(items)
+}
+
+/// Convert a text content block into a JSON value
+///
+/// Only text that looks like a JSON object or array is parsed; anything else,
+/// including `42` or `true`, stays a string so tools returning plain text
+/// are passed on exactly as written.
+fn text_value(text: &str) -> Value {
+    let trimmed = text.trim_start();
+    if trimmed.starts_with('{') || trimmed.starts_with('[') {
+        if let Ok(value) = serde_json::from_str(text) {
+            return value;
+        }
+    }
+    Value::String(text.to_string())
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used, clippy::expect_used)]
+mod tests {
+    use super::*;
+    use crate::transport::Session;
+    use futures::future::BoxFuture;
+    use rmcp::model::Content;
+    use serde_json::json;
+    use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader, DuplexStream};
+    use tokio::net::{TcpListener, TcpStream};
+
+    /// Transport to an in-process server with an `echo` tool
+    struct InMemory;
+
+    impl MCPTransport for InMemory {
+        fn kind(&self) -> &'static str {
+            "memory"
+        }
+
+        fn connect(&self, connector: Connector) -> BoxFuture<'_, Result<Session>> {
+            let (client, server) = tokio::io::duplex(64 * 1024);
+            tokio::spawn(echo_server(server));
+            Box::pin(connector.serve(client))
+        }
+    }
+
+    /// Answer requests line by line until the client hangs up
+    async fn echo_server(stream: DuplexStream) {
+        let (read, mut write) = tokio::io::split(stream);
+        let mut lines = BufReader::new(read).lines();
+        while let Ok(Some(line)) = lines.next_line().await {
+            let request: Value = serde_json::from_str(&line).unwrap();
+            // Notifications get no response
+            let Some(id) = request.get("id") else {
+                continue;
+            };
+            let line = format!("{}\n", respond(id, &request));
+            if write.write_all(line.as_bytes()).await.is_err() {
+                break;
+            }
+        }
+    }
+
+    /// The response of the `echo` server to a request
+    fn respond(id: &Value, request: &Value) -> Value {
+        let result = match request["method"].as_str() {
+            Some("initialize") => json!({
+                "protocolVersion": "2025-03-26",
+                "capabilities": { "tools": {} },
+                "serverInfo": { "name": "memory", "version": "1.0.0" },
+            }),
+            Some("tools/list") => json!({ "tools": [{
+                "name": "echo",
+                "description": "Echo the text",
+                "inputSchema": { "type": "object" },
+            }] }),
+            Some("tools/call") => json!({ "content": [{
+                "type": "text",
+                "text": request["params"]["arguments"]["text"],
+            }] }),
+            _ => json!({}),
+        };
+        json!({ "jsonrpc": "2.0", "id": id, "result": result })
+    }
+
+    /// Serve the `echo` server over HTTP at `/mcp`, returning its address
+    ///
+    /// Each initialize starts a new session; the first session expires on
+    /// its first tool call.
+    async fn expiring_server() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = format!("http://{}", listener.local_addr().unwrap());
+        let sessions = Arc::new(AtomicU64::new(0));
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(answer_http(stream, Arc::clone(&sessions)));
+            }
+        });
+        address
+    }
+
+    /// Answer one HTTP request, then close the connection
+    async fn answer_http(stream: TcpStream, sessions: Arc<AtomicU64>) {
+        let mut reader = BufReader::new(stream);
+        let mut request_line = String::new();
+        reader.read_line(&mut request_line).await.unwrap();
+        let mut session = None;
+        let mut length = 0;
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line).await.unwrap();
+            let Some((name, value)) = line.trim_end().split_once(':') else {
+                break;
+            };
+            match name.to_ascii_lowercase().as_str() {
+                "mcp-session-id" => session = Some(value.trim().to_string()),
+                "content-length" => length = value.trim().parse().unwrap(),
+                _ => {}
+            }
+        }
+        let mut body = vec![0; length];
+        reader.read_exact(&mut body).await.unwrap();
+
+        let (status, headers, body) = match request_line.split(' ').take(2).collect::<Vec<_>>()[..]
+        {
+            ["POST", "/mcp"] => {
+                let request: Value = serde_json::from_slice(&body).unwrap();
+                match request.get("id") {
+                    // Notifications are accepted without a response
+                    None => ("202 Accepted", String::new(), String::new()),
+                    Some(_)
+                        if request["method"] == "tools/call"
+                            && session.as_deref() == Some("s1") =>
+                    {
+                        ("404 Not Found", String::new(), String::new())
+                    }
+                    Some(id) => {
+                        let session = if request["method"] == "initialize" {
+                            let session = sessions.fetch_add(1, Ordering::SeqCst) + 1;
+                            format!("Mcp-Session-Id: s{session}\r\n")
+                        } else {
+                            String::new()
+                        };
+                        let headers = format!("Content-Type: application/json\r\n{session}");
+                        ("200 OK", headers, respond(id, &request).to_string())
                     }
-                } else {
-                    None
                 }
+            }
+            [_, "/mcp"] => ("405 Method Not Allowed", String::new(), String::new()),
+            _ => ("404 Not Found", String::new(), String::new()),
+        };
+        // `<invalid>` in echoed text stands for a byte that isn't UTF-8
+        let body = body
+            .split("<invalid>")
+            .map(str::as_bytes)
+            .collect::<Vec<_>>();
+        let body = body.join(&0xE9);
+        let head = format!(
+            "HTTP/1.1 {status}\r\n{headers}Content-Length: {}\r\nConnection: close\r\n\r\n",
+            body.len()
+        );
+        let _ = reader.get_mut().write_all(head.as_bytes()).await;
+        let _ = reader.get_mut().write_all(&body).await;
+    }
+
+    #[tokio::test]
+    async fn test_expired_session() {
+        let address = expiring_server().await;
+        let timeout = Duration::from_secs(5);
+
+        // The call in the expired session is sent again in a new one
+        let config = MCPServerConfig::Simple(format!("{address}/mcp"));
+        let client = MCPClient::start("remote".into(), &config, timeout)
+            .await
+            .unwrap();
+        let output = client.call_tool("echo", json!({ "text": "hi" })).await;
+        assert_eq!(output.unwrap(), json!("hi"));
+        client.close().await;
+
+        // A 404 before any session was started means a wrong URL
+        let config = MCPServerConfig::Simple(format!("{address}/missing"));
+        let error = MCPClient::start("remote".into(), &config, timeout)
+            .await
+            .err()
+            .unwrap();
+        assert!(!error.is_session_expired());
+    }
+
+    #[tokio::test]
+    async fn test_invalid_utf8_flagged() {
+        let address = expiring_server().await;
+        let config = MCPServerConfig::Simple(format!("{address}/mcp"));
+        let client = MCPClient::start("remote".into(), &config, Duration::from_secs(5))
+            .await
+            .unwrap();
+
+        let output = client
+            .call_tool("echo", json!({ "text": "caf<invalid>" }))
+            .await;
+        assert_eq!(
+            output.unwrap(),
+            json!({ "content": "caf\u{FFFD}", "invalid_utf8": true })
+        );
+        // Text that really holds U+FFFD is valid UTF-8
+        let output = client
+            .call_tool("echo", json!({ "text": "caf\u{FFFD}" }))
+            .await;
+        assert_eq!(output.unwrap(), json!("caf\u{FFFD}"));
+        client.close().await;
+
+        assert_eq!(
+            flag_invalid_utf8(json!(["ok", { "name": "x" }])),
+            json!([
+                { "content": "ok", "invalid_utf8": true },
+                { "name": "x", "invalid_utf8": true },
+            ])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_start_with_transport() {
+        let client = MCPClient::start_with_transport("memory".into(), InMemory)
+            .await
+            .unwrap();
+        assert_eq!(client.tools.len(), 1);
+        assert_eq!(
+            client.tool_description(&client.tools[0]).as_deref(),
+            Some("Echo the text")
+        );
+        let output = client.call_tool("echo", json!({ "text": "hi" })).await;
+        assert_eq!(output.unwrap(), json!("hi"));
+
+        let description = client.describe().await;
+        assert_eq!(description.transport, "memory");
+        assert_eq!(description.implementation.as_deref(), Some("memory 1.0.0"));
+
+        // Reconnecting opens a new connection over the same transport
+        client.disconnect().await;
+        assert!(!client.is_connected().await);
+        let output = client.call_tool("echo", json!({ "text": "again" })).await;
+        assert_eq!(output.unwrap(), json!("again"));
+        client.close().await;
+    }
+
+    #[tokio::test]
+    async fn test_collect_pages() {
+        let pages = |cursor: Option<String>| async move {
+            Ok(match cursor.as_deref() {
+                None => (vec![1, 2], Some("page2".to_string())),
+                Some("page2") => (vec![3], Some("page3".to_string())),
+                Some("page3") => (vec![4, 5], None),
+                Some(other) => panic!("unexpected cursor {other}"),
             })
-            .collect();
+        };
+        assert_eq!(
+            collect_pages("paged", pages).await.unwrap(),
+            [1, 2, 3, 4, 5]
+        );
+
+        // A server repeating its cursor does not loop forever
+        let looping = |_| async { Ok((vec![0], Some("again".to_string()))) };
+        assert_eq!(collect_pages("looping", looping).await.unwrap(), [0, 0]);
 
-        // Return single value directly, or array if multiple
-        let output = match content_values.len() {
-            0 => Value::Null,
-            1 => content_values.into_iter().next().unwrap_or(Value::Null),
-            _ => Value::Array(content_values),
+        // Nor does one that never stops listing new tools
+        let endless = |cursor: Option<String>| async move {
+            let n: usize = cursor.map_or(0, |c| c.parse().unwrap());
+            Ok((vec![n; 300], Some((n + 1).to_string())))
         };
+        assert_eq!(
+            collect_pages("endless", endless).await.unwrap().len(),
+            MAX_TOOLS
+        );
 
-        // If the tool call indicated an error, wrap it
-        if result.is_error.unwrap_or(false) {
-            return Err(MCPError::ToolExecution {
-                server: self.name.clone(),
-                tool: tool_name.into(),
-                reason: format!("Tool returned error: {output}"),
-            });
-        }
+        let failing =
+            |_| async { Err::<(Vec<u8>, _), _>(MCPError::Protocol("list failed".to_string())) };
+        assert!(collect_pages("failing", failing).await.is_err());
+    }
 
-        Ok(output)
+    #[test]
+    fn test_flatten_result() {
+        let single = CallToolResult::success(vec![Content::text("plain text")]);
+        assert_eq!(flatten_result(&single), Value::String("plain text".into()));
+
+        let mixed = CallToolResult::success(vec![
+            Content::text(r#"{"n": 1}"#),
+            Content::image("aGk=", "image/png"),
+            Content::text("done"),
+        ]);
+        assert_eq!(
+            flatten_result(&mixed),
+            serde_json::json!([
+                { "n": 1 },
+                { "type": "image", "data": "aGk=", "mimeType": "image/png" },
+                "done"
+            ])
+        );
+
+        assert_eq!(
+            flatten_result(&CallToolResult::success(vec![])),
+            Value::Null
+        );
+    }
+
+    #[test]
+    fn test_describe_capabilities() {
+        let server: ServerCapabilities = serde_json::from_value(serde_json::json!({
+            "tools": {},
+            "resources": { "subscribe": true },
+            "logging": {}
+        }))
+        .unwrap();
+        let names = capability_names(&server, &ClientCapabilities::default());
+        assert_eq!(names, ["tools", "resources (subscribe)", "logging"]);
+
+        let description = ServerDescription {
+            name: "github".to_string(),
+            implementation: Some("github-mcp 1.0.0".to_string()),
+            protocol_version: "2025-03-26".to_string(),
+            transport: "http",
+            connected: true,
+            tool_count: 3,
+            capabilities: names,
+        };
+        assert_eq!(
+            description.to_string(),
+            "github (github-mcp 1.0.0)\nTransport: http, connected\nProtocol: 2025-03-26\n\
+             Tools: 3\nCapabilities: tools, resources (subscribe), logging"
+        );
+    }
+
+    #[test]
+    fn test_text_value() {
+        // Scalars that happen to be valid JSON stay text
+        assert_eq!(text_value("42"), Value::String("42".into()));
+        assert_eq!(text_value("true"), Value::String("true".into()));
+        assert_eq!(text_value("null"), Value::String("null".into()));
+        assert_eq!(text_value("hello"), Value::String("hello".into()));
+        assert_eq!(
+            text_value(r#""quoted""#),
+            Value::String(r#""quoted""#.into())
+        );
+
+        assert_eq!(text_value(r#" {"n": 1}"#), serde_json::json!({ "n": 1 }));
+        assert_eq!(text_value("[1, 2]"), serde_json::json!([1, 2]));
+        // Text that only starts like JSON is kept as written
+        assert_eq!(
+            text_value("[draft] meeting notes"),
+            Value::String("[draft] meeting notes".into())
+        );
+    }
+
+    #[test]
+    fn test_resource_updates() {
+        let capabilities = ServerCapabilities::builder()
+            .enable_resources()
+            .enable_resources_subscribe()
+            .build();
+        assert!(supports_subscribe(&capabilities));
+        assert!(!supports_subscribe(
+            &ServerCapabilities::builder().enable_resources().build()
+        ));
+        assert!(!supports_subscribe(&ServerCapabilities::default()));
+
+        let handler = NotificationHandler {
+            server: "files".into(),
+            on_resource_updated: Arc::new(Mutex::new(None)),
+        };
+        // Updates without a registered callback are ignored
+        handler.resource_updated("file:///tmp/a.txt");
+
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&seen);
+        *handler.on_resource_updated.lock().unwrap() = Some(Arc::new(move |uri: &str| {
+            sink.lock().unwrap().push(uri.to_string());
+        }));
+        handler.resource_updated("file:///tmp/b.txt");
+        assert_eq!(*seen.lock().unwrap(), vec!["file:///tmp/b.txt".to_string()]);
     }
 }
@@ -1,7 +1,10 @@
 //! Configuration types for MCP client
 
+use crate::error::MCPError;
+use crate::http_client::{HttpTimeouts, RetryPolicy};
 use serde::Deserialize;
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
+use std::path::PathBuf;
 use std::time::Duration;
 
 /// MCP client configuration
@@ -11,6 +14,30 @@ pub struct MCPConfig {
     #[serde(default = "default_startup_timeout")]
     pub startup_timeout: u64,
 
+    /// Consecutive tool failures before the tool's circuit breaker opens (0 disables)
+    #[serde(default = "default_failure_threshold")]
+    pub failure_threshold: u32,
+
+    /// Seconds an open circuit breaker waits before allowing a trial call
+    #[serde(default = "default_failure_cooldown")]
+    pub failure_cooldown: u64,
+
+    /// How MCP tools are named when exposed to the model
+    #[serde(default)]
+    pub tool_naming: ToolNaming,
+
+    /// Abort startup unless every configured server started
+    #[serde(default)]
+    pub require_all: bool,
+
+    /// Abort startup if no configured server started
+    #[serde(default)]
+    pub require_any: bool,
+
+    /// JSON file with more servers in the Claude Desktop `mcpServers` format
+    #[serde(default)]
+    pub config_file: Option<PathBuf>,
+
     /// MCP server configurations
     #[serde(default)]
     pub servers: HashMap<String, MCPServerConfig>,
@@ -20,18 +47,288 @@ fn default_startup_timeout() -> u64 {
     10
 }
 
+fn default_failure_threshold() -> u32 {
+    3
+}
+
+fn default_failure_cooldown() -> u64 {
+    60
+}
+
 impl Default for MCPConfig {
     fn default() -> Self {
         Self {
             startup_timeout: default_startup_timeout(),
+            failure_threshold: default_failure_threshold(),
+            failure_cooldown: default_failure_cooldown(),
+            tool_naming: ToolNaming::default(),
+            require_all: false,
+            require_any: false,
+            config_file: None,
             servers: HashMap::new(),
         }
     }
 }
 
+/// Shape of a Claude Desktop-style server file
+#[derive(Debug, Deserialize)]
+struct ServersFile {
+    #[serde(rename = "mcpServers", default)]
+    mcp_servers: HashMap<String, MCPServerConfig>,
+}
+
+impl MCPConfig {
+    /// Add the servers defined in `config_file`, if one is set
+    ///
+    /// A leading `~/` in the path is expanded to the home directory.
+    /// Servers defined inline win over file servers with the same name.
+    ///
+    /// # Errors
+    /// Returns [`MCPError::Config`] if the file cannot be read or parsed
+    pub fn load_config_file(&mut self) -> Result<(), MCPError> {
+        let Some(path) = &self.config_file else {
+            return Ok(());
+        };
+        let path = expand_home(path);
+        let json = std::fs::read_to_string(&path).map_err(|e| {
+            MCPError::Config(format!(
+                "cannot read MCP config file {}: {e}",
+                path.display()
+            ))
+        })?;
+        let servers = parse_servers_file(&json).map_err(|e| {
+            MCPError::Config(format!("invalid MCP config file {}: {e}", path.display()))
+        })?;
+        self.merge_servers(servers);
+        Ok(())
+    }
+
+    /// Add the servers defined by `RUSTCLAW_MCP_<NAME>_*` environment
+    /// variables (see [`MCPServerConfig::from_env`])
+    ///
+    /// Like other environment variables, they take precedence over the
+    /// config: an environment server replaces an inline or config file server
+    /// with the same name.
+    ///
+    /// # Errors
+    /// Returns [`MCPError::Config`] if a server's variables are invalid
+    pub fn load_env_servers(&mut self) -> Result<(), MCPError> {
+        let servers = env_servers(std::env::vars())?;
+        for (name, server) in servers {
+            if self.servers.insert(name.clone(), server).is_some() {
+                tracing::warn!(
+                    "MCP server '{}' is defined in the config and the environment; using the environment one",
+                    name
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Add servers that are not already defined inline
+    fn merge_servers(&mut self, servers: HashMap<String, MCPServerConfig>) {
+        for (name, server) in servers {
+            if self.servers.contains_key(&name) {
+                tracing::warn!(
+                    "MCP server '{}' is defined inline and in the config file; using the inline one",
+                    name
+                );
+                continue;
+            }
+            self.servers.insert(name, server);
+        }
+    }
+
+    /// Check the configuration for mistakes that would otherwise only show up
+    /// as failures when a server is started
+    ///
+    /// Checks that stdio servers have a command, HTTP servers a valid
+    /// `http(s)` URL that does not send an `Authorization` header in plain
+    /// text to a remote host, and that timeouts are not zero. Issues are
+    /// ordered by server name.
+    #[must_use]
+    pub fn validate(&self) -> Vec<ConfigIssue> {
+        let mut issues = Vec::new();
+        if self.startup_timeout == 0 {
+            issues.push(ConfigIssue::error(
+                None,
+                "startup_timeout must be greater than 0",
+            ));
+        }
+
+        let mut names: Vec<&String> = self.servers.keys().collect();
+        names.sort();
+        for name in names {
+            issues.extend(
+                self.servers[name]
+                    .validate()
+                    .into_iter()
+                    .map(|(severity, message)| ConfigIssue {
+                        server: Some(name.clone()),
+                        severity,
+                        message,
+                    }),
+            );
+        }
+        issues
+    }
+}
+
+/// How serious a [`ConfigIssue`] is
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IssueSeverity {
+    /// Likely a mistake, but the server can still be started
+    Warning,
+    /// The server cannot work as configured
+    Error,
+}
+
+/// Problem found by [`MCPConfig::validate`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigIssue {
+    /// Server the issue is about (`None` for global settings)
+    pub server: Option<String>,
+    /// How serious the issue is
+    pub severity: IssueSeverity,
+    /// Description of the problem
+    pub message: String,
+}
+
+impl ConfigIssue {
+    fn error(server: Option<String>, message: impl Into<String>) -> Self {
+        Self {
+            server,
+            severity: IssueSeverity::Error,
+            message: message.into(),
+        }
+    }
+
+    /// Whether the issue should stop startup
+    #[must_use]
+    pub fn is_error(&self) -> bool {
+        self.severity == IssueSeverity::Error
+    }
+}
+
+impl std::fmt::Display for ConfigIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.server {
+            Some(server) => write!(f, "MCP server '{server}': {}", self.message),
+            None => write!(f, "MCP config: {}", self.message),
+        }
+    }
+}
+
+/// Parse the `mcpServers` map of a Claude Desktop-style config file
+fn parse_servers_file(json: &str) -> Result<HashMap<String, MCPServerConfig>, serde_json::Error> {
+    serde_json::from_str::<ServersFile>(json).map(|file| file.mcp_servers)
+}
+
+/// Prefix of the environment variables that define MCP servers
+const ENV_PREFIX: &str = "RUSTCLAW_MCP_";
+
+/// Suffixes of the environment variables of one server
+const ENV_SUFFIXES: [&str; 4] = ["_COMMAND", "_ARGS", "_ENV", "_URL"];
+
+/// New sessions one call may start after an HTTP session expired
+const DEFAULT_SESSION_RECONNECTS: u32 = 1;
+
+/// Collect the servers defined by `RUSTCLAW_MCP_<NAME>_*` variables in
+/// `vars`, keyed by lowercased name
+fn env_servers(
+    vars: impl IntoIterator<Item = (String, String)>,
+) -> Result<HashMap<String, MCPServerConfig>, MCPError> {
+    let vars: HashMap<String, String> = vars
+        .into_iter()
+        .filter(|(key, _)| key.starts_with(ENV_PREFIX))
+        .collect();
+    let names: BTreeSet<&str> = vars
+        .keys()
+        .filter_map(|key| {
+            let rest = key.strip_prefix(ENV_PREFIX)?;
+            ENV_SUFFIXES
+                .iter()
+                .find_map(|suffix| rest.strip_suffix(suffix))
+                .filter(|name| !name.is_empty())
+        })
+        .collect();
+
+    names
+        .into_iter()
+        .map(|name| {
+            let server = MCPServerConfig::from_vars(name, |suffix| {
+                vars.get(&format!("{ENV_PREFIX}{name}{suffix}")).cloned()
+            })?;
+            Ok((name.to_lowercase(), server))
+        })
+        .collect()
+}
+
+/// Parse `KEY=VALUE` pairs separated by commas
+fn parse_env_pairs(pairs: &str) -> Result<HashMap<String, SecretValue>, String> {
+    pairs
+        .split(',')
+        .map(str::trim)
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| match pair.split_once('=') {
+            Some((key, value)) if !key.trim().is_empty() => {
+                Ok((key.trim().to_string(), SecretValue::from(value)))
+            }
+            _ => Err(format!("expected KEY=VALUE, got '{pair}'")),
+        })
+        .collect()
+}
+
+/// Parse arguments given as a JSON array or separated by whitespace
+fn parse_env_args(args: &str) -> Result<Vec<String>, String> {
+    if args.trim_start().starts_with('[') {
+        serde_json::from_str(args).map_err(|e| format!("invalid JSON array: {e}"))
+    } else {
+        Ok(args.split_whitespace().map(String::from).collect())
+    }
+}
+
+/// Expand a leading `~/` to the user's home directory
+fn expand_home(path: &std::path::Path) -> PathBuf {
+    match (path.strip_prefix("~"), std::env::var_os("HOME")) {
+        (Ok(rest), Some(home)) => PathBuf::from(home).join(rest),
+        _ => path.to_path_buf(),
+    }
+}
+
+/// Naming strategy for MCP tools exposed to the model
+///
+/// In TOML: `tool_naming = "prefixed"`, `tool_naming = "bare"` or
+/// `tool_naming = { separator = "-" }`.
+#[derive(Debug, Deserialize, Clone, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ToolNaming {
+    /// `{server}_{tool}` (default)
+    #[default]
+    Prefixed,
+    /// The raw tool name; tools whose name is offered by more than one
+    /// server fall back to `{server}_{tool}`
+    Bare,
+    /// `{server}{separator}{tool}`
+    Separator(String),
+}
+
+impl ToolNaming {
+    /// Name under which `tool` of `server` is exposed, ignoring collisions
+    #[must_use]
+    pub fn name(&self, server: &str, tool: &str) -> String {
+        match self {
+            ToolNaming::Prefixed => format!("{server}_{tool}"),
+            ToolNaming::Bare => tool.to_string(),
+            ToolNaming::Separator(separator) => format!("{server}{separator}{tool}"),
+        }
+    }
+}
+
 /// Individual MCP server configuration
 #[derive(Debug, Deserialize, Clone)]
 #[serde(untagged)]
+#[allow(clippy::large_enum_variant)] // Read once at startup
 pub enum MCPServerConfig {
     /// Simple form: just a command string or URL
     Simple(String),
@@ -42,12 +339,75 @@ pub enum MCPServerConfig {
         #[serde(flatten)]
         transport: TransportConfig,
 
+        /// Transport to use instead of the one inferred from the fields
+        #[serde(default, rename = "transport")]
+        transport_override: Option<TransportKind>,
+
         /// Override global startup timeout
         #[serde(default)]
         startup_timeout: Option<u64>,
+
+        /// Replacement or extra text for the descriptions of specific tools
+        #[serde(default)]
+        tool_descriptions: HashMap<String, ToolDescription>,
+
+        /// Connect on the first call of one of the declared `tools` instead
+        /// of at startup
+        #[serde(default)]
+        lazy: bool,
+
+        /// Tools offered to the model before a lazy server is connected
+        #[serde(default)]
+        tools: Vec<DeclaredTool>,
     },
 }
 
+/// Tool of a lazily connected server, declared in the config
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+pub struct DeclaredTool {
+    /// Tool name on the server
+    pub name: String,
+    /// Description shown to the model
+    #[serde(default)]
+    pub description: Option<String>,
+    /// JSON Schema of the arguments (default: an object with any properties)
+    #[serde(default = "default_input_schema")]
+    pub input_schema: serde_json::Value,
+}
+
+fn default_input_schema() -> serde_json::Value {
+    serde_json::json!({ "type": "object" })
+}
+
+/// Operator-provided description of a tool
+///
+/// In TOML: `"text"` replaces the server's description, `{ append = "text" }`
+/// adds a sentence after it.
+#[derive(Debug, Deserialize, Clone, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum ToolDescription {
+    /// Description used instead of the server's
+    Replace(String),
+    /// Text appended to the server's description
+    Append {
+        /// Text to append
+        append: String,
+    },
+}
+
+/// Transport named explicitly with `transport = "stdio"` or `"http"`
+///
+/// Overrides the transport inferred from the config: with `"http"` the
+/// `command` is the server URL, with `"stdio"` the `url` is the command.
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum TransportKind {
+    /// Launch a subprocess
+    Stdio,
+    /// Connect over HTTP
+    Http,
+}
+
 /// Transport configuration
 #[derive(Debug, Deserialize, Clone)]
 #[serde(untagged)]
@@ -62,7 +422,37 @@ pub enum TransportConfig {
 
         /// Optional HTTP headers (e.g. `Authorization`)
         #[serde(default)]
-        headers: HashMap<String, String>,
+        headers: HashMap<String, SecretValue>,
+
+        /// Close the session after this many seconds without tool calls
+        /// (reconnects on the next call; disabled by default)
+        #[serde(default)]
+        idle_timeout: Option<u64>,
+
+        /// Retries for requests failing with connection errors or `5xx` (default 2)
+        #[serde(default)]
+        max_retries: Option<u32>,
+
+        /// Milliseconds before the first retry, doubled per retry (default 500)
+        #[serde(default)]
+        retry_backoff_ms: Option<u64>,
+
+        /// Seconds to wait for a connection (default 10)
+        #[serde(default)]
+        connect_timeout: Option<u64>,
+
+        /// Seconds to wait for response data before giving up (default 60)
+        #[serde(default)]
+        request_timeout: Option<u64>,
+
+        /// Start a new session when the server reports the current one
+        /// expired, then retry the call (default true)
+        #[serde(default)]
+        reconnect_on_expiry: Option<bool>,
+
+        /// New sessions started for one call before giving up (default 1)
+        #[serde(default)]
+        max_session_reconnects: Option<u32>,
     },
 
     /// stdio transport (launch subprocess)
@@ -79,8 +469,125 @@ pub enum TransportConfig {
 
         /// Optional environment variables to set for the child process
         #[serde(default)]
-        env: HashMap<String, String>,
+        env: HashMap<String, SecretValue>,
+    },
+}
+
+/// Value of an environment variable or header that may be kept out of the
+/// config file
+///
+/// In TOML: `"value"`, `{ file = "/run/secrets/api_key" }` (e.g. a Docker or
+/// Kubernetes secret mount) or `{ env = "API_KEY" }`.
+#[derive(Debug, Deserialize, Clone, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum SecretValue {
+    /// The value itself
+    Plain(String),
+    /// Contents of a file, without trailing newlines
+    File {
+        /// Path of the file holding the value
+        file: PathBuf,
     },
+    /// Value of an environment variable of the rustclaw process
+    Env {
+        /// Name of the environment variable
+        env: String,
+    },
+}
+
+impl SecretValue {
+    /// Resolve the value; `name` identifies the entry in error messages
+    ///
+    /// # Errors
+    /// Returns [`MCPError::Config`] if the file cannot be read or the
+    /// environment variable is not set
+    pub fn resolve(&self, name: &str) -> Result<String, MCPError> {
+        match self {
+            SecretValue::Plain(value) => Ok(value.clone()),
+            SecretValue::File { file } => std::fs::read_to_string(file)
+                .map(|value| value.trim_end_matches(['\n', '\r']).to_string())
+                .map_err(|e| {
+                    MCPError::Config(format!(
+                        "cannot read secret file '{}' for '{name}': {e}",
+                        file.display()
+                    ))
+                }),
+            SecretValue::Env { env } => std::env::var(env).map_err(|_| {
+                MCPError::Config(format!(
+                    "environment variable '{env}' for '{name}' is not set"
+                ))
+            }),
+        }
+    }
+}
+
+impl From<&str> for SecretValue {
+    fn from(value: &str) -> Self {
+        SecretValue::Plain(value.to_string())
+    }
+}
+
+/// Resolve every value of a map of secrets
+fn resolve_all(values: &HashMap<String, SecretValue>) -> Result<HashMap<String, String>, MCPError> {
+    values
+        .iter()
+        .map(|(name, value)| Ok((name.clone(), value.resolve(name)?)))
+        .collect()
+}
+
+/// Check the command of a stdio server
+fn validate_command(command: &str) -> Option<(IssueSeverity, String)> {
+    let command = command.trim();
+    if command.is_empty() {
+        Some((IssueSeverity::Error, "command is empty".to_string()))
+    } else if command.contains("://") {
+        Some((
+            IssueSeverity::Warning,
+            format!("command '{command}' looks like a URL; use `url` for HTTP servers"),
+        ))
+    } else {
+        None
+    }
+}
+
+/// Check the URL of an HTTP server that sends an `Authorization` header if
+/// `has_auth`
+fn validate_url(url: &str, has_auth: bool) -> Option<(IssueSeverity, String)> {
+    let parsed = match reqwest::Url::parse(url) {
+        Ok(parsed) => parsed,
+        Err(e) => return Some((IssueSeverity::Error, format!("invalid url '{url}': {e}"))),
+    };
+    match parsed.scheme() {
+        "http" if has_auth && !is_loopback(&parsed) => Some((
+            IssueSeverity::Error,
+            format!("Authorization header would be sent unencrypted to '{url}'; use https"),
+        )),
+        "http" | "https" => None,
+        scheme => Some((
+            IssueSeverity::Error,
+            format!("unsupported url scheme '{scheme}' (expected http or https)"),
+        )),
+    }
+}
+
+/// Whether a URL points at the local machine
+fn is_loopback(url: &reqwest::Url) -> bool {
+    matches!(url.host_str(), Some("localhost" | "127.0.0.1" | "[::1]"))
+}
+
+/// Whether a server string is an HTTP(S) URL rather than a command
+fn is_url(s: &str) -> bool {
+    s.starts_with("http://") || s.starts_with("https://")
+}
+
+/// stdio transport for a command line split on whitespace into program + args
+fn split_command(command: &str, env: HashMap<String, String>) -> TransportType {
+    let mut parts = command.split_whitespace().map(str::to_string);
+    TransportType::Stdio {
+        program: parts.next().unwrap_or_else(|| command.to_string()),
+        args: parts.collect(),
+        env,
+    }
 }
 
 /// Detected transport type with all parameters needed to start a connection
@@ -100,56 +607,141 @@ pub enum TransportType {
 }
 
 impl MCPServerConfig {
-    /// Detect transport type from configuration
-    #[must_use]
-    pub fn detect_transport(&self) -> TransportType {
-        match self {
+    /// Server defined by the `RUSTCLAW_MCP_<NAME>_*` environment variables,
+    /// where `<NAME>` is `name` in upper case
+    ///
+    /// `_URL` defines an HTTP server. `_COMMAND` defines a stdio server,
+    /// with optional `_ARGS` (whitespace-separated or a JSON array) and
+    /// `_ENV` (comma-separated `KEY=VALUE` pairs).
+    ///
+    /// # Errors
+    /// Returns [`MCPError::Config`] if neither or both of `_COMMAND` and
+    /// `_URL` are set, or `_ARGS` or `_ENV` cannot be parsed
+    pub fn from_env(name: &str) -> Result<Self, MCPError> {
+        let name = name.to_uppercase();
+        Self::from_vars(&name, |suffix| {
+            std::env::var(format!("{ENV_PREFIX}{name}{suffix}")).ok()
+        })
+    }
+
+    /// Server defined by the variables `var` returns for each suffix of
+    /// [`ENV_SUFFIXES`]
+    fn from_vars(name: &str, var: impl Fn(&str) -> Option<String>) -> Result<Self, MCPError> {
+        let invalid = |message: String| {
+            MCPError::Config(format!("MCP server {ENV_PREFIX}{name}_*: {message}"))
+        };
+        let transport = match (var("_COMMAND"), var("_URL")) {
+            (Some(command), None) => TransportConfig::Stdio {
+                command,
+                args: var("_ARGS")
+                    .map(|args| parse_env_args(&args))
+                    .transpose()
+                    .map_err(|e| invalid(format!("_ARGS: {e}")))?
+                    .unwrap_or_default(),
+                env: var("_ENV")
+                    .map(|env| parse_env_pairs(&env))
+                    .transpose()
+                    .map_err(|e| invalid(format!("_ENV: {e}")))?
+                    .unwrap_or_default(),
+            },
+            (None, Some(url)) => {
+                if var("_ARGS").is_some() || var("_ENV").is_some() {
+                    return Err(invalid("_ARGS and _ENV need _COMMAND, not _URL".into()));
+                }
+                TransportConfig::HTTP {
+                    url,
+                    headers: HashMap::new(),
+                    idle_timeout: None,
+                    max_retries: None,
+                    retry_backoff_ms: None,
+                    connect_timeout: None,
+                    request_timeout: None,
+                    reconnect_on_expiry: None,
+                    max_session_reconnects: None,
+                }
+            }
+            (Some(_), Some(_)) => return Err(invalid("set either _COMMAND or _URL".into())),
+            (None, None) => return Err(invalid("_COMMAND or _URL is required".into())),
+        };
+        Ok(MCPServerConfig::Advanced {
+            transport,
+            transport_override: None,
+            startup_timeout: None,
+            tool_descriptions: HashMap::new(),
+            lazy: false,
+            tools: Vec::new(),
+        })
+    }
+
+    /// Detect transport type from configuration, resolving secret values
+    ///
+    /// # Errors
+    /// Returns [`MCPError::Config`] if an environment variable or header
+    /// refers to a secret file or variable that cannot be read
+    pub fn detect_transport(&self) -> Result<TransportType, MCPError> {
+        Ok(match self {
             MCPServerConfig::Simple(s) => {
-                if s.starts_with("http://") || s.starts_with("https://") {
+                if is_url(s) {
                     TransportType::HTTP(s.clone(), HashMap::new())
                 } else {
-                    // Split simple command string into program + args
-                    let parts: Vec<&str> = s.split_whitespace().collect();
-                    let program = parts
-                        .first()
-                        .map_or_else(|| s.clone(), |p| (*p).to_string());
-                    let args: Vec<String> =
-                        parts.iter().skip(1).map(|a| (*a).to_string()).collect();
-                    TransportType::Stdio {
-                        program,
-                        args,
-                        env: HashMap::new(),
-                    }
+                    split_command(s, HashMap::new())
                 }
             }
-            MCPServerConfig::Advanced { transport, .. } => match transport {
-                TransportConfig::Stdio { command, args, env } => {
+            MCPServerConfig::Advanced {
+                transport,
+                transport_override,
+                ..
+            } => match (transport, transport_override) {
+                (TransportConfig::Stdio { command, .. }, Some(TransportKind::Http)) => {
+                    TransportType::HTTP(command.trim().to_string(), HashMap::new())
+                }
+                (TransportConfig::HTTP { url, .. }, Some(TransportKind::Stdio)) => {
+                    split_command(url, HashMap::new())
+                }
+                (TransportConfig::Stdio { command, args, env }, _) => {
                     if args.is_empty() {
                         // No explicit args — split command string like Simple variant
-                        let parts: Vec<&str> = command.split_whitespace().collect();
-                        let program = parts
-                            .first()
-                            .map_or_else(|| command.clone(), |p| (*p).to_string());
-                        let split_args: Vec<String> =
-                            parts.iter().skip(1).map(|a| (*a).to_string()).collect();
-                        TransportType::Stdio {
-                            program,
-                            args: split_args,
-                            env: env.clone(),
-                        }
+                        split_command(command, resolve_all(env)?)
                     } else {
                         // Explicit args — command is just the program name
                         TransportType::Stdio {
                             program: command.clone(),
                             args: args.clone(),
-                            env: env.clone(),
+                            env: resolve_all(env)?,
                         }
                     }
                 }
-                TransportConfig::HTTP { url, headers } => {
-                    TransportType::HTTP(url.clone(), headers.clone())
+                (TransportConfig::HTTP { url, headers, .. }, _) => {
+                    TransportType::HTTP(url.clone(), resolve_all(headers)?)
                 }
             },
+        })
+    }
+
+    /// Transport this configuration uses: the one named with `transport`,
+    /// or else the one inferred from the fields
+    #[must_use]
+    pub fn transport_kind(&self) -> TransportKind {
+        match self {
+            MCPServerConfig::Simple(s) if is_url(s) => TransportKind::Http,
+            MCPServerConfig::Simple(_) => TransportKind::Stdio,
+            MCPServerConfig::Advanced {
+                transport_override: Some(kind),
+                ..
+            } => *kind,
+            MCPServerConfig::Advanced { transport, .. } => match transport {
+                TransportConfig::HTTP { .. } => TransportKind::Http,
+                TransportConfig::Stdio { .. } => TransportKind::Stdio,
+            },
+        }
+    }
+
+    /// Name of the transport this configuration uses (`stdio` or `http`)
+    #[must_use]
+    pub fn transport_name(&self) -> &'static str {
+        match self.transport_kind() {
+            TransportKind::Http => "http",
+            TransportKind::Stdio => "stdio",
         }
     }
 
@@ -164,13 +756,212 @@ impl MCPServerConfig {
         }
     }
 
+    /// Description of `tool` after applying any configured override
+    ///
+    /// Returns `description` (the server's) when no override exists.
+    #[must_use]
+    pub fn tool_description(&self, tool: &str, description: Option<&str>) -> Option<String> {
+        let overridden = match self {
+            MCPServerConfig::Advanced {
+                tool_descriptions, ..
+            } => tool_descriptions.get(tool),
+            MCPServerConfig::Simple(_) => None,
+        };
+        match (overridden, description) {
+            (None, description) => description.map(str::to_string),
+            (Some(ToolDescription::Replace(text)), _) => Some(text.clone()),
+            (Some(ToolDescription::Append { append }), None) => Some(append.clone()),
+            (Some(ToolDescription::Append { append }), Some(description)) => {
+                Some(format!("{} {append}", description.trim_end()))
+            }
+        }
+    }
+
+    /// Whether the server connects on first use rather than at startup
+    ///
+    /// Only servers that declare their tools can be connected lazily.
+    #[must_use]
+    pub fn is_lazy(&self) -> bool {
+        matches!(self, MCPServerConfig::Advanced { lazy: true, tools, .. } if !tools.is_empty())
+    }
+
+    /// Tools declared in the config, offered before the server is connected
+    #[must_use]
+    pub fn declared_tools(&self) -> &[DeclaredTool] {
+        match self {
+            MCPServerConfig::Advanced { tools, .. } => tools,
+            MCPServerConfig::Simple(_) => &[],
+        }
+    }
+
+    /// Get the idle timeout for HTTP sessions, if configured
+    #[must_use]
+    pub fn idle_timeout(&self) -> Option<Duration> {
+        match self {
+            MCPServerConfig::Advanced {
+                transport:
+                    TransportConfig::HTTP {
+                        idle_timeout: Some(secs),
+                        ..
+                    },
+                ..
+            } if *secs > 0 => Some(Duration::from_secs(*secs)),
+            _ => None,
+        }
+    }
+
+    /// Get the HTTP retry policy, falling back to defaults for unset values
+    #[must_use]
+    pub fn retry_policy(&self) -> RetryPolicy {
+        let defaults = RetryPolicy::default();
+        match self {
+            MCPServerConfig::Advanced {
+                transport:
+                    TransportConfig::HTTP {
+                        max_retries,
+                        retry_backoff_ms,
+                        ..
+                    },
+                ..
+            } => RetryPolicy {
+                max_retries: max_retries.unwrap_or(defaults.max_retries),
+                initial_backoff: retry_backoff_ms
+                    .map_or(defaults.initial_backoff, Duration::from_millis),
+            },
+            _ => defaults,
+        }
+    }
+
+    /// Get the HTTP timeouts, falling back to defaults for unset values
+    #[must_use]
+    pub fn http_timeouts(&self) -> HttpTimeouts {
+        let defaults = HttpTimeouts::default();
+        match self {
+            MCPServerConfig::Advanced {
+                transport:
+                    TransportConfig::HTTP {
+                        connect_timeout,
+                        request_timeout,
+                        ..
+                    },
+                ..
+            } => HttpTimeouts {
+                connect: connect_timeout.map_or(defaults.connect, Duration::from_secs),
+                read: request_timeout.map_or(defaults.read, Duration::from_secs),
+            },
+            _ => defaults,
+        }
+    }
+
+    /// Get how many new sessions one call may start after the server
+    /// expired the current one (0 for stdio or when disabled)
+    #[must_use]
+    pub fn session_reconnects(&self) -> u32 {
+        if self.transport_kind() == TransportKind::Stdio {
+            return 0;
+        }
+        match self {
+            MCPServerConfig::Advanced {
+                transport:
+                    TransportConfig::HTTP {
+                        reconnect_on_expiry,
+                        max_session_reconnects,
+                        ..
+                    },
+                ..
+            } => match reconnect_on_expiry {
+                Some(false) => 0,
+                _ => max_session_reconnects.unwrap_or(DEFAULT_SESSION_RECONNECTS),
+            },
+            _ => DEFAULT_SESSION_RECONNECTS,
+        }
+    }
+
+    /// Problems with this server's configuration, see [`MCPConfig::validate`]
+    fn validate(&self) -> Vec<(IssueSeverity, String)> {
+        let mut issues = Vec::new();
+        let mut timeouts = Vec::new();
+        match self {
+            MCPServerConfig::Simple(s) if is_url(s) => issues.extend(validate_url(s, false)),
+            MCPServerConfig::Simple(command) => issues.extend(validate_command(command)),
+            MCPServerConfig::Advanced {
+                transport,
+                transport_override,
+                startup_timeout,
+                lazy,
+                tools,
+                ..
+            } => {
+                timeouts.push(("startup_timeout", *startup_timeout));
+                if *lazy && tools.is_empty() {
+                    issues.push((
+                        IssueSeverity::Warning,
+                        "lazy needs the server's tools declared in `tools`; connecting at startup"
+                            .into(),
+                    ));
+                }
+                match (transport, transport_override) {
+                    (TransportConfig::Stdio { command, args, env }, Some(TransportKind::Http)) => {
+                        issues.extend(validate_url(command, false));
+                        if !args.is_empty() || !env.is_empty() {
+                            issues.push((
+                                IssueSeverity::Error,
+                                "args and env cannot be used with transport = \"http\"".into(),
+                            ));
+                        }
+                    }
+                    (TransportConfig::HTTP { url, headers, .. }, Some(TransportKind::Stdio)) => {
+                        issues.extend(validate_command(url));
+                        if !headers.is_empty() {
+                            issues.push((
+                                IssueSeverity::Error,
+                                "headers cannot be used with transport = \"stdio\"".into(),
+                            ));
+                        }
+                    }
+                    (TransportConfig::Stdio { command, .. }, _) => {
+                        issues.extend(validate_command(command));
+                    }
+                    (
+                        TransportConfig::HTTP {
+                            url,
+                            headers,
+                            connect_timeout,
+                            request_timeout,
+                            ..
+                        },
+                        _,
+                    ) => {
+                        let has_auth = headers
+                            .keys()
+                            .any(|name| name.eq_ignore_ascii_case("authorization"));
+                        issues.extend(validate_url(url, has_auth));
+                        timeouts.push(("connect_timeout", *connect_timeout));
+                        timeouts.push(("request_timeout", *request_timeout));
+                    }
+                }
+            }
+        }
+        for (name, value) in timeouts {
+            if value == Some(0) {
+                issues.push((
+                    IssueSeverity::Error,
+                    format!("{name} must be greater than 0"),
+                ));
+            }
+        }
+        issues
+    }
+
     /// Extract Authorization header value if present
     #[must_use]
     pub fn get_auth_header(&self) -> Option<String> {
         match self {
             MCPServerConfig::Simple(_) => None,
             MCPServerConfig::Advanced { transport, .. } => match transport {
-                TransportConfig::HTTP { headers, .. } => headers.get("Authorization").cloned(),
+                TransportConfig::HTTP { headers, .. } => headers
+                    .get("Authorization")
+                    .and_then(|value| value.resolve("Authorization").ok()),
                 TransportConfig::Stdio { .. } => None,
             },
         }
@@ -178,7 +969,12 @@ impl MCPServerConfig {
 }
 
 #[cfg(test)]
-#[allow(clippy::unwrap_used, clippy::expect_used)]
+#[allow(
+    clippy::unwrap_used,
+    clippy::expect_used,
+    clippy::match_wildcard_for_single_variants,
+    clippy::redundant_closure_for_method_calls
+)]
 mod tests {
     use super::*;
 
@@ -186,7 +982,7 @@ mod tests {
     fn test_simple_config_stdio() {
         let config = MCPServerConfig::Simple("npx -y server".into());
         assert_eq!(
-            config.detect_transport(),
+            config.detect_transport().unwrap(),
             TransportType::Stdio {
                 program: "npx".into(),
                 args: vec!["-y".into(), "server".into()],
@@ -199,7 +995,7 @@ mod tests {
     fn test_simple_config_http() {
         let config = MCPServerConfig::Simple("http://localhost:3000".into());
         assert_eq!(
-            config.detect_transport(),
+            config.detect_transport().unwrap(),
             TransportType::HTTP("http://localhost:3000".into(), HashMap::new())
         );
     }
@@ -212,7 +1008,11 @@ mod tests {
                 args: Vec::new(),
                 env: HashMap::new(),
             },
+            transport_override: None,
             startup_timeout: Some(30),
+            tool_descriptions: HashMap::new(),
+            lazy: false,
+            tools: Vec::new(),
         };
         assert_eq!(config.get_timeout(10), Duration::from_secs(30));
     }
@@ -223,6 +1023,12 @@ mod tests {
         assert_eq!(config.get_timeout(10), Duration::from_secs(10));
     }
 
+    fn plain(env: &HashMap<String, String>) -> HashMap<String, SecretValue> {
+        env.iter()
+            .map(|(k, v)| (k.clone(), v.as_str().into()))
+            .collect()
+    }
+
     #[test]
     fn test_stdio_with_args_and_env() {
         let mut env = HashMap::new();
@@ -233,13 +1039,17 @@ mod tests {
             transport: TransportConfig::Stdio {
                 command: "npx".into(),
                 args: vec!["-y".into(), "@z_ai/mcp-server".into()],
-                env: env.clone(),
+                env: plain(&env),
             },
+            transport_override: None,
             startup_timeout: None,
+            tool_descriptions: HashMap::new(),
+            lazy: false,
+            tools: Vec::new(),
         };
 
         assert_eq!(
-            config.detect_transport(),
+            config.detect_transport().unwrap(),
             TransportType::Stdio {
                 program: "npx".into(),
                 args: vec!["-y".into(), "@z_ai/mcp-server".into()],
@@ -257,13 +1067,17 @@ mod tests {
             transport: TransportConfig::Stdio {
                 command: "npx -y server".into(),
                 args: Vec::new(),
-                env: env.clone(),
+                env: plain(&env),
             },
+            transport_override: None,
             startup_timeout: None,
+            tool_descriptions: HashMap::new(),
+            lazy: false,
+            tools: Vec::new(),
         };
 
         assert_eq!(
-            config.detect_transport(),
+            config.detect_transport().unwrap(),
             TransportType::Stdio {
                 program: "npx".into(),
                 args: vec!["-y".into(), "server".into()],
@@ -284,7 +1098,7 @@ mod tests {
         let config: MCPConfig = toml::from_str(toml_str).expect("Failed to parse TOML");
         let server = config.servers.get("zai").expect("Server not found");
 
-        match server.detect_transport() {
+        match server.detect_transport().unwrap() {
             TransportType::Stdio { program, args, env } => {
                 assert_eq!(program, "npx");
                 assert_eq!(args, vec!["-y", "@z_ai/mcp-server"]);
@@ -305,7 +1119,7 @@ mod tests {
         let config: MCPConfig = toml::from_str(toml_str).expect("Failed to parse TOML");
         let server = config.servers.get("web-search").expect("Server not found");
 
-        match server.detect_transport() {
+        match server.detect_transport().unwrap() {
             TransportType::HTTP(url, headers) => {
                 assert_eq!(url, "https://example.com");
                 // Check if Authorization header is present and case-preserved
@@ -316,5 +1130,363 @@ mod tests {
             }
             _ => panic!("Expected HTTP transport"),
         }
+        assert_eq!(server.idle_timeout(), None);
+    }
+
+    #[test]
+    fn test_http_idle_timeout() {
+        let toml_str = r#"
+            [servers.remote]
+            url = "https://example.com/mcp"
+            idle_timeout = 300
+            max_retries = 5
+            request_timeout = 120
+
+            [servers.local]
+            command = "server"
+        "#;
+
+        let config: MCPConfig = toml::from_str(toml_str).expect("Failed to parse TOML");
+        assert_eq!(
+            config.servers["remote"].idle_timeout(),
+            Some(Duration::from_mins(5))
+        );
+        assert_eq!(config.servers["local"].idle_timeout(), None);
+        assert_eq!(config.servers["remote"].retry_policy().max_retries, 5);
+        assert_eq!(
+            config.servers["remote"].retry_policy().initial_backoff,
+            RetryPolicy::default().initial_backoff
+        );
+        assert_eq!(
+            config.servers["remote"].http_timeouts(),
+            HttpTimeouts {
+                connect: HttpTimeouts::default().connect,
+                read: Duration::from_mins(2),
+            }
+        );
+        assert_eq!(config.servers["remote"].session_reconnects(), 1);
+        assert_eq!(config.servers["local"].session_reconnects(), 0);
+    }
+
+    #[test]
+    fn test_transport_override() {
+        let toml_str = r#"
+            [servers.inferred]
+            command = "https://example.com/mcp"
+
+            [servers.remote]
+            command = "https://example.com/mcp"
+            transport = "http"
+
+            [servers.local]
+            url = "mcp-server --stdio"
+            transport = "stdio"
+        "#;
+
+        let config: MCPConfig = toml::from_str(toml_str).expect("Failed to parse TOML");
+        let inferred = &config.servers["inferred"];
+        assert_eq!(inferred.transport_kind(), TransportKind::Stdio);
+
+        let remote = &config.servers["remote"];
+        assert_eq!(remote.transport_kind(), TransportKind::Http);
+        assert_eq!(remote.transport_name(), "http");
+        assert_eq!(
+            remote.detect_transport().unwrap(),
+            TransportType::HTTP("https://example.com/mcp".into(), HashMap::new())
+        );
+        assert_eq!(remote.session_reconnects(), 1);
+
+        let local = &config.servers["local"];
+        assert_eq!(local.transport_name(), "stdio");
+        assert_eq!(
+            local.detect_transport().unwrap(),
+            TransportType::Stdio {
+                program: "mcp-server".into(),
+                args: vec!["--stdio".into()],
+                env: HashMap::new(),
+            }
+        );
+        assert_eq!(local.session_reconnects(), 0);
+    }
+
+    #[test]
+    fn test_session_reconnects() {
+        let toml_str = r#"
+            [servers]
+            simple = "https://example.com/mcp"
+
+            [servers.retrying]
+            url = "https://example.com/mcp"
+            max_session_reconnects = 3
+
+            [servers.strict]
+            url = "https://example.com/mcp"
+            reconnect_on_expiry = false
+            max_session_reconnects = 3
+        "#;
+
+        let config: MCPConfig = toml::from_str(toml_str).expect("Failed to parse TOML");
+        assert_eq!(config.servers["retrying"].session_reconnects(), 3);
+        assert_eq!(config.servers["strict"].session_reconnects(), 0);
+        assert_eq!(config.servers["simple"].session_reconnects(), 1);
+    }
+
+    #[test]
+    fn test_secret_values() {
+        let dir = std::env::temp_dir().join(format!("rustclaw-secret-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let secret = dir.join("api_key");
+        std::fs::write(&secret, "s3cret\n").unwrap();
+
+        let toml_str = format!(
+            r#"
+            [servers.files]
+            command = "server"
+            env = {{ API_KEY = {{ file = "{}" }}, MODE = "TEST", HOME_DIR = {{ env = "PATH" }} }}
+
+            [servers.missing]
+            url = "https://example.com/mcp"
+            headers = {{ Authorization = {{ file = "{}" }} }}
+            "#,
+            secret.display(),
+            dir.join("missing").display()
+        );
+        let config: MCPConfig = toml::from_str(&toml_str).expect("Failed to parse TOML");
+
+        match config.servers["files"].detect_transport().unwrap() {
+            TransportType::Stdio { env, .. } => {
+                assert_eq!(env["API_KEY"], "s3cret");
+                assert_eq!(env["MODE"], "TEST");
+                assert_eq!(env["HOME_DIR"], std::env::var("PATH").unwrap());
+            }
+            TransportType::HTTP(..) => panic!("Expected Stdio transport"),
+        }
+
+        let err = config.servers["missing"].detect_transport().unwrap_err();
+        assert!(err.to_string().contains("cannot read secret file"));
+        assert!(err.to_string().contains("Authorization"));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_tool_descriptions() {
+        let config: MCPConfig = toml::from_str(
+            r#"
+            [servers.files]
+            command = "server"
+
+            [servers.files.tool_descriptions]
+            read_file = "Read a UTF-8 text file from the shared folder."
+            search = { append = "Prefer this over listing directories." }
+            "#,
+        )
+        .expect("Failed to parse TOML");
+        let files = &config.servers["files"];
+
+        assert_eq!(
+            files.tool_description("read_file", Some("Reads a file")),
+            Some("Read a UTF-8 text file from the shared folder.".to_string())
+        );
+        assert_eq!(
+            files.tool_description("search", Some("Search files. ")),
+            Some("Search files. Prefer this over listing directories.".to_string())
+        );
+        assert_eq!(
+            files.tool_description("search", None),
+            Some("Prefer this over listing directories.".to_string())
+        );
+        // Tools without an override keep the server's description
+        assert_eq!(
+            files.tool_description("write_file", Some("Writes a file")),
+            Some("Writes a file".to_string())
+        );
+        assert_eq!(files.tool_description("write_file", None), None);
+
+        let simple = MCPServerConfig::Simple("server".to_string());
+        assert_eq!(
+            simple.tool_description("read_file", Some("Reads a file")),
+            Some("Reads a file".to_string())
+        );
+    }
+
+    #[test]
+    fn test_servers_file() {
+        let json = r#"{
+            "mcpServers": {
+                "filesystem": {
+                    "command": "npx",
+                    "args": ["-y", "@modelcontextprotocol/server-filesystem", "/tmp"],
+                    "env": { "MODE": "TEST" }
+                },
+                "remote": { "url": "https://example.com/mcp" },
+                "inline": { "command": "from-file" }
+            },
+            "globalShortcut": "Ctrl+Space"
+        }"#;
+        let servers = parse_servers_file(json).expect("Failed to parse JSON");
+        assert_eq!(servers.len(), 3);
+
+        match servers["filesystem"].detect_transport().unwrap() {
+            TransportType::Stdio { program, args, env } => {
+                assert_eq!(program, "npx");
+                assert_eq!(args[2], "/tmp");
+                assert_eq!(env["MODE"], "TEST");
+            }
+            TransportType::HTTP(..) => panic!("Expected Stdio transport"),
+        }
+        assert!(matches!(
+            servers["remote"].detect_transport().unwrap(),
+            TransportType::HTTP(..)
+        ));
+
+        // Inline servers win over file servers with the same name
+        let mut config: MCPConfig = toml::from_str(
+            r#"
+            config_file = "mcp.json"
+            servers.inline = "from-toml"
+            "#,
+        )
+        .expect("Failed to parse TOML");
+        assert_eq!(config.config_file, Some(PathBuf::from("mcp.json")));
+        config.merge_servers(servers);
+        assert_eq!(config.servers.len(), 3);
+        match config.servers["inline"].detect_transport().unwrap() {
+            TransportType::Stdio { program, .. } => assert_eq!(program, "from-toml"),
+            TransportType::HTTP(..) => panic!("Expected Stdio transport"),
+        }
+
+        assert!(parse_servers_file("not json").is_err());
+        assert!(parse_servers_file("{}").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_env_servers() {
+        let vars = |pairs: &[(&str, &str)]| {
+            pairs
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect::<Vec<_>>()
+        };
+        let servers = env_servers(vars(&[
+            ("RUSTCLAW_MCP_FILE_SYSTEM_COMMAND", "npx"),
+            ("RUSTCLAW_MCP_FILE_SYSTEM_ARGS", "-y server-filesystem /tmp"),
+            ("RUSTCLAW_MCP_FILE_SYSTEM_ENV", "MODE=test, TOKEN=a=b"),
+            ("RUSTCLAW_MCP_SEARCH_URL", "https://example.com/mcp"),
+            ("RUSTCLAW_MCP_JSON_COMMAND", "tool"),
+            ("RUSTCLAW_MCP_JSON_ARGS", r#"["--dir", "My Files"]"#),
+            ("RUSTCLAW_MAX_TOOL_ITERATIONS", "5"),
+            ("PATH", "/usr/bin"),
+        ]))
+        .expect("Failed to parse env servers");
+        assert_eq!(servers.len(), 3);
+
+        match servers["file_system"].detect_transport().unwrap() {
+            TransportType::Stdio { program, args, env } => {
+                assert_eq!(program, "npx");
+                assert_eq!(args, ["-y", "server-filesystem", "/tmp"]);
+                assert_eq!(env["MODE"], "test");
+                assert_eq!(env["TOKEN"], "a=b");
+            }
+            TransportType::HTTP(..) => panic!("Expected Stdio transport"),
+        }
+        match servers["json"].detect_transport().unwrap() {
+            TransportType::Stdio { args, .. } => assert_eq!(args, ["--dir", "My Files"]),
+            TransportType::HTTP(..) => panic!("Expected Stdio transport"),
+        }
+        assert_eq!(
+            servers["search"].detect_transport().unwrap(),
+            TransportType::HTTP("https://example.com/mcp".to_string(), HashMap::new())
+        );
+
+        for invalid in [
+            vars(&[("RUSTCLAW_MCP_X_ARGS", "--flag")]),
+            vars(&[
+                ("RUSTCLAW_MCP_X_COMMAND", "a"),
+                ("RUSTCLAW_MCP_X_URL", "http://b"),
+            ]),
+            vars(&[
+                ("RUSTCLAW_MCP_X_URL", "http://b"),
+                ("RUSTCLAW_MCP_X_ENV", "A=1"),
+            ]),
+            vars(&[("RUSTCLAW_MCP_X_COMMAND", "a"), ("RUSTCLAW_MCP_X_ENV", "A")]),
+            vars(&[
+                ("RUSTCLAW_MCP_X_COMMAND", "a"),
+                ("RUSTCLAW_MCP_X_ARGS", "[1"),
+            ]),
+        ] {
+            assert!(env_servers(invalid).is_err());
+        }
+        assert!(env_servers(vars(&[("RUSTCLAW_MCP__URL", "x")]))
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_tool_naming() {
+        let parse = |toml_str: &str| {
+            toml::from_str::<MCPConfig>(toml_str)
+                .expect("Failed to parse TOML")
+                .tool_naming
+        };
+
+        assert_eq!(parse(""), ToolNaming::Prefixed);
+        assert_eq!(parse(r#"tool_naming = "bare""#), ToolNaming::Bare);
+        let custom = parse(r#"tool_naming = { separator = "-" }"#);
+        assert_eq!(custom, ToolNaming::Separator("-".to_string()));
+
+        assert_eq!(
+            ToolNaming::Prefixed.name("filesystem", "read_file"),
+            "filesystem_read_file"
+        );
+        assert_eq!(
+            ToolNaming::Bare.name("filesystem", "read_file"),
+            "read_file"
+        );
+        assert_eq!(custom.name("fs", "read_file"), "fs-read_file");
+    }
+
+    #[test]
+    fn test_validate() {
+        let config: MCPConfig = toml::from_str(
+            r#"
+            startup_timeout = 0
+
+            [servers]
+            good = "npx -y server"
+            local = { url = "http://localhost:3000/mcp", headers = { Authorization = "Bearer x" } }
+            empty = { command = "  " }
+            typo = { command = "https://example.com/mcp" }
+            bad_url = { url = "not a url" }
+            plain_auth = { url = "http://example.com/mcp", headers = { authorization = "Bearer x" } }
+            slow = { url = "https://example.com/mcp", connect_timeout = 0 }
+            idle = { command = "server", lazy = true }
+            "#,
+        )
+        .expect("Failed to parse TOML");
+
+        let issues = config.validate();
+        let summary: Vec<(Option<&str>, bool)> = issues
+            .iter()
+            .map(|issue| (issue.server.as_deref(), issue.is_error()))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (None, true),
+                (Some("bad_url"), true),
+                (Some("empty"), true),
+                (Some("idle"), false),
+                (Some("plain_auth"), true),
+                (Some("slow"), true),
+                (Some("typo"), false),
+            ]
+        );
+        assert_eq!(
+            issues[5].to_string(),
+            "MCP server 'slow': connect_timeout must be greater than 0"
+        );
+
+        assert!(MCPConfig::default().validate().is_empty());
     }
 }
@@ -1,5 +1,11 @@
 //! Error types for MCP client operations
 
+use crate::http_client::HttpClientError;
+use rmcp::model::{ErrorCode, ErrorData};
+use rmcp::service::{ClientInitializeError, ServiceError};
+use rmcp::transport::streamable_http_client::StreamableHttpError;
+use rmcp::transport::DynamicTransportError;
+use std::time::Duration;
 use thiserror::Error;
 
 /// MCP client errors
@@ -24,7 +30,36 @@ pub enum MCPError {
         /// Server name
         server: String,
         /// Timeout duration
-        timeout: std::time::Duration,
+        timeout: Duration,
+    },
+
+    /// Server rejected the credentials (HTTP 401/403)
+    #[error("Server '{server}' rejected authentication: {reason}")]
+    AuthFailed {
+        /// Server name
+        server: String,
+        /// Failure reason
+        reason: String,
+    },
+
+    /// Request to a running server timed out
+    #[error("Request to server '{server}' timed out after {timeout:?}")]
+    RequestTimeout {
+        /// Server name
+        server: String,
+        /// Timeout duration
+        timeout: Duration,
+    },
+
+    /// Server answered with a JSON-RPC error
+    #[error("Server '{server}' returned error {code}: {message}")]
+    Rpc {
+        /// Server name
+        server: String,
+        /// JSON-RPC error code
+        code: i32,
+        /// Error message
+        message: String,
     },
 
     /// Tool not found on server
@@ -54,6 +89,13 @@ pub enum MCPError {
         server: String,
     },
 
+    /// Server no longer knows the HTTP session (HTTP 404 with a session id)
+    #[error("Session with server '{server}' expired")]
+    SessionExpired {
+        /// Server name
+        server: String,
+    },
+
     /// Protocol-level error
     #[error("Protocol error: {0}")]
     Protocol(String),
@@ -84,5 +126,168 @@ pub enum MCPError {
     Io(#[from] std::io::Error),
 }
 
+impl MCPError {
+    /// Check whether retrying the same operation may succeed
+    ///
+    /// Connection problems, timeouts and internal server errors are
+    /// transient; auth, configuration and invalid-request errors are not.
+    #[must_use]
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Self::Transport(_)
+            | Self::StartupTimeout { .. }
+            | Self::RequestTimeout { .. }
+            | Self::ServerDisconnected { .. }
+            | Self::SessionExpired { .. }
+            | Self::Io(_) => true,
+            Self::Rpc { code, .. } => *code == ErrorCode::INTERNAL_ERROR.0,
+            _ => false,
+        }
+    }
+
+    /// Check whether the server expired the session, so a new one is needed
+    #[must_use]
+    pub fn is_session_expired(&self) -> bool {
+        matches!(self, Self::SessionExpired { .. })
+    }
+
+    /// Check whether the server rejected our credentials
+    #[must_use]
+    pub fn is_auth_error(&self) -> bool {
+        matches!(self, Self::AuthFailed { .. })
+    }
+
+    /// Classify an error from a request to a running server
+    pub(crate) fn from_service_error(server: &str, context: &str, error: ServiceError) -> Self {
+        match error {
+            ServiceError::McpError(data) => Self::from_error_data(server, context, &data),
+            ServiceError::TransportSend(e) => Self::from_transport_error(server, context, &e),
+            ServiceError::TransportClosed | ServiceError::Cancelled { .. } => {
+                Self::ServerDisconnected {
+                    server: server.into(),
+                }
+            }
+            ServiceError::Timeout { timeout } => Self::RequestTimeout {
+                server: server.into(),
+                timeout,
+            },
+            ServiceError::UnexpectedResponse => Self::InvalidResponse {
+                server: server.into(),
+                details: format!("{context}: unexpected response type"),
+            },
+            other => Self::Sdk(format!("{context} for '{server}': {other}")),
+        }
+    }
+
+    /// Classify an error from the MCP initialize handshake
+    pub(crate) fn from_init_error(server: &str, error: ClientInitializeError) -> Self {
+        let context = "Failed to initialize MCP";
+        match error {
+            ClientInitializeError::TransportError { error, .. } => {
+                Self::from_transport_error(server, context, &error)
+            }
+            ClientInitializeError::JsonRpcError(data) => {
+                Self::from_error_data(server, context, &data)
+            }
+            ClientInitializeError::ConnectionClosed(reason) => Self::Transport(format!(
+                "{context} for '{server}': connection closed: {reason}"
+            )),
+            e @ (ClientInitializeError::ExpectedInitResponse(_)
+            | ClientInitializeError::ExpectedInitResult(_)
+            | ClientInitializeError::ConflictInitResponseId(..)) => Self::InvalidResponse {
+                server: server.into(),
+                details: e.to_string(),
+            },
+            ClientInitializeError::Cancelled => {
+                Self::Sdk(format!("{context} for '{server}': cancelled"))
+            }
+        }
+    }
+
+    fn from_error_data(server: &str, context: &str, data: &ErrorData) -> Self {
+        Self::Rpc {
+            server: server.into(),
+            code: data.code.0,
+            message: format!("{context}: {}", data.message),
+        }
+    }
+
+    fn from_transport_error(server: &str, context: &str, error: &DynamicTransportError) -> Self {
+        if let Some(http) = error
+            .error
+            .downcast_ref::<StreamableHttpError<HttpClientError>>()
+        {
+            match http {
+                StreamableHttpError::AuthRequired(_) => {
+                    return Self::AuthFailed {
+                        server: server.into(),
+                        reason: "authorization required".into(),
+                    };
+                }
+                StreamableHttpError::Client(HttpClientError::SessionExpired(_)) => {
+                    return Self::SessionExpired {
+                        server: server.into(),
+                    };
+                }
+                StreamableHttpError::Client(HttpClientError::Request(e)) => {
+                    if let Some(
+                        status @ (reqwest::StatusCode::UNAUTHORIZED
+                        | reqwest::StatusCode::FORBIDDEN),
+                    ) = e.status()
+                    {
+                        return Self::AuthFailed {
+                            server: server.into(),
+                            reason: format!("HTTP {status}"),
+                        };
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        Self::Transport(format!("{context} for '{server}': {error}"))
+    }
+}
+
 /// Convenient Result type alias
 pub type Result<T> = std::result::Result<T, MCPError>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classification() {
+        let timeout = MCPError::from_service_error(
+            "github",
+            "Failed to list tools",
+            ServiceError::Timeout {
+                timeout: Duration::from_secs(5),
+            },
+        );
+        assert!(matches!(timeout, MCPError::RequestTimeout { .. }));
+        assert!(timeout.is_retryable());
+        assert!(!timeout.is_auth_error());
+
+        let invalid = MCPError::from_service_error(
+            "github",
+            "Failed to list tools",
+            ServiceError::McpError(ErrorData::invalid_params("bad cursor", None)),
+        );
+        assert!(!invalid.is_retryable());
+
+        let internal = MCPError::from_init_error(
+            "github",
+            ClientInitializeError::JsonRpcError(ErrorData::internal_error("boom", None)),
+        );
+        assert!(internal.is_retryable());
+
+        let auth = MCPError::AuthFailed {
+            server: "github".into(),
+            reason: "HTTP 401 Unauthorized".into(),
+        };
+        assert!(auth.is_auth_error());
+        assert!(!auth.is_retryable());
+        assert!(!MCPError::Config("missing url".into()).is_retryable());
+    }
+}
@@ -1,21 +1,31 @@
 //! Custom HTTP client wrapper for MCP Streamable HTTP transport.
 //!
 //! Wraps `reqwest::Client` to handle server compatibility issues where
-//! `200 OK` with empty body is returned instead of `202 Accepted`.
+//! `200 OK` with empty body is returned instead of `202 Accepted`, retries
+//! requests that fail with transient errors, bounds connect/read times so
+//! a hung server can't tie up a connection indefinitely, and keeps an SSE
+//! response open until the result of its request arrived.
 
+use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::Duration;
 
 use futures::stream::BoxStream;
 use rmcp::{
-    model::{ClientJsonRpcMessage, ServerJsonRpcMessage},
+    model::{ClientJsonRpcMessage, JsonRpcMessage, RequestId, ServerJsonRpcMessage},
     transport::streamable_http_client::{
         StreamableHttpClient, StreamableHttpError, StreamableHttpPostResponse,
     },
 };
 use sse_stream::{Error as SseError, Sse, SseStream};
-use tracing::debug;
+use tracing::{debug, warn};
 
 use futures::StreamExt;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use rustclaw_provider::{lossy_utf8, truncate_utf8};
+use serde_json::Value;
+
+use crate::error::MCPError;
 
 /// Header names used by MCP Streamable HTTP protocol
 const HEADER_SESSION_ID: &str = "mcp-session-id";
@@ -23,18 +33,397 @@ const HEADER_LAST_EVENT_ID: &str = "last-event-id";
 const EVENT_STREAM_MIME_TYPE: &str = "text/event-stream";
 const JSON_MIME_TYPE: &str = "application/json";
 
+/// Headers set by the transport itself, which configured headers may not replace
+const RESERVED_HEADERS: &[&str] = &[
+    "accept",
+    "content-type",
+    HEADER_SESSION_ID,
+    HEADER_LAST_EVENT_ID,
+];
+
+/// JSON-RPC method whose requests may have side effects and must not be
+/// retried after the server may have processed them
+const TOOL_CALL_METHOD: &str = "tools/call";
+
+/// `_meta` key set on results whose response held invalid UTF-8, read back
+/// by [`MCPClient::call_tool`](crate::client::MCPClient::call_tool)
+pub(crate) const INVALID_UTF8_META: &str = "rustclaw/invalidUtf8";
+
+/// Retry policy for `post_message`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Retries after the first attempt (0 disables retrying)
+    pub max_retries: u32,
+    /// Delay before the first retry; doubled for each further retry
+    pub initial_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 2,
+            initial_backoff: Duration::from_millis(500),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Get the delay before retry number `retry` (starting at 0)
+    #[must_use]
+    pub fn backoff(&self, retry: u32) -> Duration {
+        self.initial_backoff
+            .saturating_mul(2u32.saturating_pow(retry))
+    }
+}
+
+/// Connection and read timeouts for the HTTP transport
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct HttpTimeouts {
+    /// Maximum time to establish a connection
+    pub connect: Duration,
+    /// Maximum time to wait for data on a response
+    ///
+    /// Applied per read rather than to the whole request, so long-lived SSE
+    /// streams stay open as long as the server keeps sending events.
+    pub read: Duration,
+}
+
+impl Default for HttpTimeouts {
+    fn default() -> Self {
+        Self {
+            connect: Duration::from_secs(10),
+            read: Duration::from_mins(1),
+        }
+    }
+}
+
+/// Build a `reqwest::Client` with the given timeouts
+fn build_client(timeouts: HttpTimeouts) -> reqwest::Client {
+    reqwest::Client::builder()
+        .connect_timeout(timeouts.connect)
+        .read_timeout(timeouts.read)
+        .build()
+        .unwrap_or_else(|e| {
+            warn!(
+                "Failed to build HTTP client with timeouts, using defaults: {}",
+                e
+            );
+            reqwest::Client::new()
+        })
+}
+
+/// Errors from [`CompatibleHttpClient`] requests
+#[derive(Debug, thiserror::Error)]
+pub enum HttpClientError {
+    /// The request failed or the server answered with an error status
+    #[error(transparent)]
+    Request(#[from] reqwest::Error),
+    /// The server no longer knows the session the request was sent in
+    #[error("session expired: {0}")]
+    SessionExpired(reqwest::Error),
+}
+
 /// Custom HTTP client that wraps `reqwest::Client` with compatibility fixes.
 ///
 /// Some MCP servers (e.g., BigModel/ZhipuAI) return `200 OK` with an empty body
 /// for notification responses, instead of the `202 Accepted` that the rmcp SDK expects.
 /// This wrapper treats `200` with an empty/missing content-type body as "Accepted".
-#[derive(Clone, Debug, Default)]
+///
+/// `post_message` is retried with exponential backoff on connection errors.
+/// `5xx` responses and timeouts are only retried for messages that are safe
+/// to repeat (anything but `tools/call`). `4xx` responses are never retried.
+///
+/// Configured headers are sent with every request, alongside the bearer
+/// token passed in by the transport.
+#[derive(Clone, Debug)]
 pub struct CompatibleHttpClient {
     inner: reqwest::Client,
+    retry: RetryPolicy,
+    headers: HeaderMap,
+}
+
+impl Default for CompatibleHttpClient {
+    fn default() -> Self {
+        Self {
+            inner: build_client(HttpTimeouts::default()),
+            retry: RetryPolicy::default(),
+            headers: HeaderMap::new(),
+        }
+    }
+}
+
+impl CompatibleHttpClient {
+    /// Use custom connect and read timeouts
+    #[must_use]
+    pub fn with_timeout(mut self, timeouts: HttpTimeouts) -> Self {
+        self.inner = build_client(timeouts);
+        self
+    }
+
+    /// Use a custom retry policy
+    #[must_use]
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// Send these headers with every request
+    #[must_use]
+    pub fn with_headers(mut self, headers: HeaderMap) -> Self {
+        self.headers = headers;
+        self
+    }
+
+    /// Send a single POST attempt
+    async fn send_post(
+        &self,
+        uri: &str,
+        message: &ClientJsonRpcMessage,
+        session_id: Option<&str>,
+        auth_token: Option<&str>,
+    ) -> Result<reqwest::Response, reqwest::Error> {
+        let mut request = self.inner.post(uri).headers(self.headers.clone()).header(
+            reqwest::header::ACCEPT,
+            [EVENT_STREAM_MIME_TYPE, JSON_MIME_TYPE].join(", "),
+        );
+        if let Some(auth_header) = auth_token {
+            debug!(auth_token_preview = %format!("{}...", truncate_utf8(auth_header, 10)), "Setting bearer auth");
+            request = request.bearer_auth(auth_header);
+        }
+        if let Some(session_id) = session_id {
+            request = request.header(HEADER_SESSION_ID, session_id);
+        }
+        request.json(message).send().await
+    }
+
+    /// Send a POST, retrying transient failures according to the retry policy
+    ///
+    /// Returns the last response (which may be a server error) or transport error.
+    async fn send_with_retry(
+        &self,
+        uri: &str,
+        message: &ClientJsonRpcMessage,
+        session_id: Option<&str>,
+        auth_token: Option<&str>,
+    ) -> Result<reqwest::Response, reqwest::Error> {
+        let retry_safe = is_retry_safe(message);
+        let mut retry = 0;
+
+        loop {
+            let result = self.send_post(uri, message, session_id, auth_token).await;
+
+            let retryable = match &result {
+                Ok(response) => is_retryable_status(response.status(), retry_safe),
+                Err(e) => is_retryable_error(e, retry_safe),
+            };
+
+            if !retryable || retry >= self.retry.max_retries {
+                return result;
+            }
+
+            let delay = self.retry.backoff(retry);
+            match &result {
+                Ok(response) => warn!(
+                    status = %response.status(),
+                    uri = %uri,
+                    "MCP HTTP request failed, retrying in {:?} ({}/{})",
+                    delay,
+                    retry + 1,
+                    self.retry.max_retries
+                ),
+                Err(e) => warn!(
+                    error = %e,
+                    uri = %uri,
+                    "MCP HTTP request failed, retrying in {:?} ({}/{})",
+                    delay,
+                    retry + 1,
+                    self.retry.max_retries
+                ),
+            }
+            tokio::time::sleep(delay).await;
+            retry += 1;
+        }
+    }
+}
+
+/// Split configured headers into a bearer token and headers sent verbatim
+///
+/// An `Authorization` value of `Bearer <token>` or a bare token becomes the
+/// transport's bearer token; other schemes (e.g. `Basic ...`) are sent as
+/// configured. Header names are matched case-insensitively.
+///
+/// # Errors
+/// Returns a configuration error for invalid header names or values, or for
+/// headers the transport sets itself.
+pub(crate) fn split_headers(
+    headers: &HashMap<String, String>,
+) -> Result<(Option<String>, HeaderMap), MCPError> {
+    let mut bearer = None;
+    let mut map = HeaderMap::new();
+
+    for (name, value) in headers {
+        let header = HeaderName::from_bytes(name.as_bytes())
+            .map_err(|e| MCPError::Config(format!("invalid header name '{name}': {e}")))?;
+        if RESERVED_HEADERS.contains(&header.as_str()) {
+            return Err(MCPError::Config(format!(
+                "header '{name}' is set by the MCP transport and cannot be configured"
+            )));
+        }
+
+        if header == reqwest::header::AUTHORIZATION {
+            let token = value.strip_prefix("Bearer ").unwrap_or(value);
+            if !token.contains(' ') {
+                bearer = Some(token.to_string());
+                continue;
+            }
+        }
+
+        let mut value = HeaderValue::from_str(value)
+            .map_err(|e| MCPError::Config(format!("invalid value for header '{name}': {e}")))?;
+        value.set_sensitive(header == reqwest::header::AUTHORIZATION);
+        map.insert(header, value);
+    }
+
+    Ok((bearer, map))
+}
+
+/// Check whether a message can be sent again without risking duplicate side effects
+fn is_retry_safe(message: &ClientJsonRpcMessage) -> bool {
+    serde_json::to_value(message)
+        .ok()
+        .and_then(|v| v.get("method").and_then(|m| m.as_str()).map(str::to_string))
+        .is_none_or(|method| method != TOOL_CALL_METHOD)
+}
+
+/// Map authentication failures to errors callers can classify
+fn check_auth(response: &reqwest::Response) -> Result<(), StreamableHttpError<HttpClientError>> {
+    if response.status() == reqwest::StatusCode::UNAUTHORIZED {
+        if let Some(header) = response.headers().get(http::header::WWW_AUTHENTICATE) {
+            let header = header
+                .to_str()
+                .map_err(|_| {
+                    StreamableHttpError::UnexpectedServerResponse(std::borrow::Cow::from(
+                        "invalid www-authenticate header value",
+                    ))
+                })?
+                .to_string();
+            return Err(StreamableHttpError::AuthRequired(
+                rmcp::transport::streamable_http_client::AuthRequiredError {
+                    www_authenticate_header: header,
+                },
+            ));
+        }
+    }
+
+    // Keep the status on other auth failures so callers can classify them
+    if matches!(
+        response.status(),
+        reqwest::StatusCode::UNAUTHORIZED | reqwest::StatusCode::FORBIDDEN
+    ) {
+        if let Err(e) = response.error_for_status_ref() {
+            return Err(StreamableHttpError::Client(e.into()));
+        }
+    }
+    Ok(())
+}
+
+/// Check whether a server error status is worth retrying
+///
+/// Even a `502`/`503`/`504` may come from a proxy after the server got the
+/// request, so server errors are retried only when the message is safe to
+/// repeat.
+fn is_retryable_status(status: reqwest::StatusCode, retry_safe: bool) -> bool {
+    status.is_server_error() && retry_safe
+}
+
+/// JSON-RPC id of a request, `None` for notifications and responses
+fn request_id(message: &ClientJsonRpcMessage) -> Option<RequestId> {
+    match message {
+        JsonRpcMessage::Request(request) => Some(request.id.clone()),
+        _ => None,
+    }
+}
+
+/// Check whether an SSE event carries the result (or error) of request `id`
+fn is_result_for(event: &Result<Sse, SseError>, id: &RequestId) -> bool {
+    let Ok(sse) = event else {
+        return false;
+    };
+    if !matches!(sse.event.as_deref(), None | Some("" | "message")) {
+        return false;
+    }
+    match sse
+        .data
+        .as_deref()
+        .and_then(|data| serde_json::from_str::<ServerJsonRpcMessage>(data).ok())
+    {
+        Some(JsonRpcMessage::Response(response)) => response.id == *id,
+        Some(JsonRpcMessage::Error(error)) => error.id == *id,
+        _ => false,
+    }
+}
+
+/// End the SSE stream answering request `id` after the event with its result
+///
+/// A server may send any number of events on the stream before the result:
+/// progress notifications, requests of its own, responses to other requests
+/// or keep-alive events without data. All of them are passed on; the stream
+/// ends only once the result for `id` arrived (or the server closes it).
+/// Streams answering notifications are passed on unchanged.
+fn until_result(
+    stream: BoxStream<'static, Result<Sse, SseError>>,
+    id: Option<RequestId>,
+) -> BoxStream<'static, Result<Sse, SseError>> {
+    let Some(id) = id else {
+        return stream;
+    };
+    futures::stream::unfold((stream, Some(id)), |(mut stream, id)| async move {
+        let id = id?;
+        let event = stream.next().await?;
+        if is_result_for(&event, &id) {
+            debug!(%id, "Result received, closing SSE stream");
+            return Some((event, (stream, None)));
+        }
+        Some((event, (stream, Some(id))))
+    })
+    .boxed()
+}
+
+/// Check whether a response status means the session has expired
+///
+/// Only requests sent within a session can find it expired; a `404` to the
+/// initial request means the URL is wrong.
+fn is_session_expired(status: reqwest::StatusCode, has_session: bool) -> bool {
+    has_session && status == reqwest::StatusCode::NOT_FOUND
+}
+
+/// Note in the `_meta` of a JSON-RPC result that its response was not valid
+/// UTF-8
+///
+/// The bytes are already replaced by then, so this is the only way the
+/// result can tell them from text that really holds U+FFFD.
+fn mark_invalid_utf8(message: &mut Value) {
+    let Some(result) = message.get_mut("result").and_then(Value::as_object_mut) else {
+        return;
+    };
+    let meta = result
+        .entry("_meta")
+        .or_insert_with(|| Value::Object(serde_json::Map::new()));
+    if let Some(meta) = meta.as_object_mut() {
+        meta.insert(INVALID_UTF8_META.into(), Value::Bool(true));
+    }
+}
+
+/// Check whether a transport error is worth retrying
+///
+/// A failed connection never reached the server, so it is always retried;
+/// a timeout only when the message is safe to repeat.
+fn is_retryable_error(error: &reqwest::Error, retry_safe: bool) -> bool {
+    error.is_connect() || (error.is_timeout() && retry_safe)
 }
 
 impl StreamableHttpClient for CompatibleHttpClient {
-    type Error = reqwest::Error;
+    type Error = HttpClientError;
 
     async fn get_stream(
         &self,
@@ -46,6 +435,7 @@ impl StreamableHttpClient for CompatibleHttpClient {
         let mut request_builder = self
             .inner
             .get(uri.as_ref())
+            .headers(self.headers.clone())
             .header(
                 reqwest::header::ACCEPT,
                 [EVENT_STREAM_MIME_TYPE, JSON_MIME_TYPE].join(", "),
@@ -60,13 +450,21 @@ impl StreamableHttpClient for CompatibleHttpClient {
         let response = request_builder
             .send()
             .await
-            .map_err(StreamableHttpError::Client)?;
+            .map_err(|e| StreamableHttpError::Client(e.into()))?;
         if response.status() == reqwest::StatusCode::METHOD_NOT_ALLOWED {
             return Err(StreamableHttpError::ServerDoesNotSupportSse);
         }
+        // The stream is always opened within a session
+        if is_session_expired(response.status(), true) {
+            if let Err(e) = response.error_for_status_ref() {
+                return Err(StreamableHttpError::Client(
+                    HttpClientError::SessionExpired(e),
+                ));
+            }
+        }
         let response = response
             .error_for_status()
-            .map_err(StreamableHttpError::Client)?;
+            .map_err(|e| StreamableHttpError::Client(e.into()))?;
         match response.headers().get(reqwest::header::CONTENT_TYPE) {
             Some(ct) => {
                 if !ct.as_bytes().starts_with(EVENT_STREAM_MIME_TYPE.as_bytes())
@@ -81,6 +479,7 @@ impl StreamableHttpClient for CompatibleHttpClient {
                 return Err(StreamableHttpError::UnexpectedContentType(None));
             }
         }
+        #[allow(deprecated)]
         let event_stream = SseStream::from_byte_stream(response.bytes_stream()).boxed();
         Ok(event_stream)
     }
@@ -91,7 +490,10 @@ impl StreamableHttpClient for CompatibleHttpClient {
         session: Arc<str>,
         auth_token: Option<String>,
     ) -> Result<(), StreamableHttpError<Self::Error>> {
-        let mut request_builder = self.inner.delete(uri.as_ref());
+        let mut request_builder = self
+            .inner
+            .delete(uri.as_ref())
+            .headers(self.headers.clone());
         if let Some(auth_header) = auth_token {
             request_builder = request_builder.bearer_auth(auth_header);
         }
@@ -99,7 +501,7 @@ impl StreamableHttpClient for CompatibleHttpClient {
             .header(HEADER_SESSION_ID, session.as_ref())
             .send()
             .await
-            .map_err(StreamableHttpError::Client)?;
+            .map_err(|e| StreamableHttpError::Client(e.into()))?;
 
         if response.status() == reqwest::StatusCode::METHOD_NOT_ALLOWED {
             debug!("this server doesn't support deleting session");
@@ -107,7 +509,7 @@ impl StreamableHttpClient for CompatibleHttpClient {
         }
         let _response = response
             .error_for_status()
-            .map_err(StreamableHttpError::Client)?;
+            .map_err(|e| StreamableHttpError::Client(e.into()))?;
         Ok(())
     }
 
@@ -118,24 +520,28 @@ impl StreamableHttpClient for CompatibleHttpClient {
         session_id: Option<Arc<str>>,
         auth_token: Option<String>,
     ) -> Result<StreamableHttpPostResponse, StreamableHttpError<Self::Error>> {
-        let mut request = self.inner.post(uri.as_ref()).header(
-            reqwest::header::ACCEPT,
-            [EVENT_STREAM_MIME_TYPE, JSON_MIME_TYPE].join(", "),
-        );
-        if let Some(ref auth_header) = auth_token {
-            debug!(auth_token_preview = %format!("{}...", &auth_header[..auth_header.len().min(10)]), "Setting bearer auth");
-            request = request.bearer_auth(auth_header);
-        }
-        if let Some(session_id) = session_id {
-            request = request.header(HEADER_SESSION_ID, session_id.as_ref());
-        }
-        let response = request
-            .json(&message)
-            .send()
+        let response = self
+            .send_with_retry(
+                uri.as_ref(),
+                &message,
+                session_id.as_deref(),
+                auth_token.as_deref(),
+            )
             .await
-            .map_err(StreamableHttpError::Client)?;
+            .map_err(|e| StreamableHttpError::Client(e.into()))?;
 
         let status = response.status();
+
+        // Surface server errors with their status once retries are exhausted
+        if status.is_server_error() {
+            let body = response.text().await.unwrap_or_default();
+            return Err(StreamableHttpError::UnexpectedServerResponse(
+                std::borrow::Cow::from(format!(
+                    "HTTP {status}: {}",
+                    body.chars().take(200).collect::<String>()
+                )),
+            ));
+        }
         let content_type_header = response
             .headers()
             .get(reqwest::header::CONTENT_TYPE)
@@ -153,20 +559,14 @@ impl StreamableHttpClient for CompatibleHttpClient {
             "MCP HTTP response received"
         );
 
-        if status == reqwest::StatusCode::UNAUTHORIZED {
-            if let Some(header) = response.headers().get(http::header::WWW_AUTHENTICATE) {
-                let header = header
-                    .to_str()
-                    .map_err(|_| {
-                        StreamableHttpError::UnexpectedServerResponse(std::borrow::Cow::from(
-                            "invalid www-authenticate header value",
-                        ))
-                    })?
-                    .to_string();
-                return Err(StreamableHttpError::AuthRequired(
-                    rmcp::transport::streamable_http_client::AuthRequiredError {
-                        www_authenticate_header: header,
-                    },
+        check_auth(&response)?;
+
+        // The server answers 404 to requests for a session it no longer knows;
+        // keep the status so the client can start a new session
+        if is_session_expired(status, session_id.is_some()) {
+            if let Err(e) = response.error_for_status_ref() {
+                return Err(StreamableHttpError::Client(
+                    HttpClientError::SessionExpired(e),
                 ));
             }
         }
@@ -194,17 +594,34 @@ impl StreamableHttpClient for CompatibleHttpClient {
         match &content_type_header {
             Some(ct) if ct.starts_with(EVENT_STREAM_MIME_TYPE) => {
                 debug!("Routing to SSE path");
+                #[allow(deprecated)]
                 let event_stream = SseStream::from_byte_stream(response.bytes_stream()).boxed();
+                let event_stream = until_result(event_stream, request_id(&message));
                 Ok(StreamableHttpPostResponse::Sse(event_stream, session_id))
             }
             Some(ct) if ct.starts_with(JSON_MIME_TYPE) => {
                 debug!("Routing to JSON path");
                 // Use text() + from_str() instead of response.json() to avoid
                 // reqwest wrapping serde errors as Decode (hard to distinguish)
-                let body = response.text().await.map_err(StreamableHttpError::Client)?;
+                let body = response
+                    .bytes()
+                    .await
+                    .map_err(|e| StreamableHttpError::Client(e.into()))?;
+                // Invalid bytes are replaced rather than failing the call;
+                // text that really holds U+FFFD is left alone
+                let (body, invalid_utf8) = lossy_utf8(&body);
                 debug!(body_len = body.len(), body = %body, "JSON response body received");
-                let message: ServerJsonRpcMessage =
+                let mut message: Value =
                     serde_json::from_str(&body).map_err(StreamableHttpError::Deserialize)?;
+                if invalid_utf8 {
+                    warn!(
+                        uri = %uri,
+                        "MCP JSON response is not valid UTF-8, invalid bytes replaced"
+                    );
+                    mark_invalid_utf8(&mut message);
+                }
+                let message: ServerJsonRpcMessage =
+                    serde_json::from_value(message).map_err(StreamableHttpError::Deserialize)?;
                 Ok(StreamableHttpPostResponse::Json(message, session_id))
             }
             _ => {
@@ -221,3 +638,121 @@ impl StreamableHttpClient for CompatibleHttpClient {
         }
     }
 }
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff_doubles() {
+        let policy = RetryPolicy {
+            max_retries: 3,
+            initial_backoff: Duration::from_millis(100),
+        };
+        assert_eq!(policy.backoff(0), Duration::from_millis(100));
+        assert_eq!(policy.backoff(2), Duration::from_millis(400));
+    }
+
+    #[test]
+    fn test_split_headers() {
+        let headers = |pairs: &[(&str, &str)]| -> HashMap<String, String> {
+            pairs
+                .iter()
+                .map(|(k, v)| ((*k).to_string(), (*v).to_string()))
+                .collect()
+        };
+
+        let (bearer, map) = split_headers(&headers(&[
+            ("authorization", "Bearer secret"),
+            ("X-Api-Version", "2024-01"),
+        ]))
+        .unwrap();
+        assert_eq!(bearer.as_deref(), Some("secret"));
+        assert_eq!(map.len(), 1);
+        assert_eq!(map["x-api-version"], "2024-01");
+
+        // Other auth schemes are passed through unchanged
+        let (bearer, map) =
+            split_headers(&headers(&[("Authorization", "Basic dXNlcjpwYXNz")])).unwrap();
+        assert!(bearer.is_none());
+        assert_eq!(map["authorization"], "Basic dXNlcjpwYXNz");
+
+        assert!(split_headers(&headers(&[("Mcp-Session-Id", "x")])).is_err());
+        assert!(split_headers(&headers(&[("bad header", "x")])).is_err());
+    }
+
+    #[test]
+    fn test_retryable_status() {
+        use reqwest::StatusCode;
+
+        assert!(is_retryable_status(StatusCode::SERVICE_UNAVAILABLE, true));
+        assert!(is_retryable_status(StatusCode::INTERNAL_SERVER_ERROR, true));
+        assert!(!is_retryable_status(StatusCode::SERVICE_UNAVAILABLE, false));
+        assert!(!is_retryable_status(StatusCode::BAD_GATEWAY, false));
+        assert!(!is_retryable_status(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            false
+        ));
+        assert!(!is_retryable_status(StatusCode::TOO_MANY_REQUESTS, true));
+        assert!(!is_retryable_status(StatusCode::BAD_REQUEST, true));
+    }
+
+    #[tokio::test]
+    async fn test_sse_until_result() {
+        let event = |data: &str| -> Result<Sse, SseError> { Ok(Sse::default().data(data)) };
+        let events = || {
+            vec![
+                event(
+                    r#"{"jsonrpc":"2.0","method":"notifications/progress","params":{"progressToken":7,"progress":1,"total":2}}"#,
+                ),
+                Ok(Sse::default()),
+                event(r#"{"jsonrpc":"2.0","id":3,"result":{"content":[]}}"#),
+                event(r#"{"jsonrpc":"2.0","id":7,"result":{"content":[],"isError":false}}"#),
+                event(
+                    r#"{"jsonrpc":"2.0","method":"notifications/message","params":{"level":"info","data":"late"}}"#,
+                ),
+            ]
+        };
+
+        // Progress, keep-alive and another request's result pass; the stream
+        // ends after the result for request 7
+        let stream = until_result(
+            futures::stream::iter(events()).boxed(),
+            Some(RequestId::Number(7)),
+        );
+        let received: Vec<_> = stream.collect().await;
+        assert_eq!(received.len(), 4);
+        assert_eq!(
+            received[3]
+                .as_ref()
+                .ok()
+                .and_then(|sse| sse.data.as_deref()),
+            Some(r#"{"jsonrpc":"2.0","id":7,"result":{"content":[],"isError":false}}"#)
+        );
+
+        // An error result ends the stream as well
+        let stream = until_result(
+            futures::stream::iter(vec![
+                event(r#"{"jsonrpc":"2.0","id":"a","error":{"code":-32602,"message":"bad"}}"#),
+                event(r#"{"jsonrpc":"2.0","id":"b","result":{}}"#),
+            ])
+            .boxed(),
+            Some(RequestId::String("a".into())),
+        );
+        assert_eq!(stream.count().await, 1);
+
+        // Streams answering a notification are not cut
+        let stream = until_result(futures::stream::iter(events()).boxed(), None);
+        assert_eq!(stream.count().await, 5);
+    }
+
+    #[test]
+    fn test_session_expired() {
+        use reqwest::StatusCode;
+
+        assert!(is_session_expired(StatusCode::NOT_FOUND, true));
+        assert!(!is_session_expired(StatusCode::NOT_FOUND, false));
+        assert!(!is_session_expired(StatusCode::BAD_REQUEST, true));
+    }
+}
@@ -4,30 +4,46 @@
 //!
 //! ## Features
 //!
-//! - Connect to MCP servers via stdio or Streamable HTTP transports
+//! - Connect to MCP servers via stdio or Streamable HTTP transports, or a
+//!   custom [`MCPTransport`]
 //! - Auto-negotiate protocol versions via `rmcp` SDK
 //! - Discover and execute remote tools with full JSON Schema support
 //! - Async startup with configurable timeouts
-//! - Graceful error handling and degradation
-//! - Bearer token authentication for remote servers
+//! - Graceful error handling and degradation with a per-server startup report
+//! - Per-tool circuit breakers for repeatedly failing tools
+//! - Bearer token authentication and custom request headers for remote servers
+//! - Resource subscriptions with update callbacks
+//! - Server descriptions (protocol, transport, capabilities) for diagnostics
 //! - Zero unsafe code
 
 #![deny(unsafe_code, dead_code, unused_imports, unused_variables, missing_docs)]
 
+pub mod circuit_breaker;
 pub mod client;
 pub mod config;
 pub mod error;
 pub mod http_client;
 pub mod registry;
 pub mod tool_bridge;
+pub mod transport;
 
-pub use client::MCPClient;
-pub use config::{MCPConfig, MCPServerConfig, TransportConfig};
+pub use circuit_breaker::{CallPermit, CircuitBreaker};
+pub use client::{MCPClient, ResourceUpdateCallback, ServerDescription};
+pub use config::{
+    ConfigIssue, IssueSeverity, MCPConfig, MCPServerConfig, SecretValue, ToolDescription,
+    ToolNaming, TransportConfig,
+};
 pub use error::MCPError;
-pub use registry::MCPToolRegistry;
+pub use http_client::{HttpTimeouts, RetryPolicy};
+pub use registry::{MCPToolRegistry, StartupReport};
+/// Full result of [`MCPClient::call_tool_raw`]
+pub use rmcp::model::CallToolResult;
 pub use tool_bridge::MCPToolWrapper;
+pub use transport::{Connector, MCPTransport, Session};
 
 /// Prelude for convenient imports
 pub mod prelude {
-    pub use crate::{MCPClient, MCPConfig, MCPError, MCPServerConfig, MCPToolRegistry};
+    pub use crate::{
+        MCPClient, MCPConfig, MCPError, MCPServerConfig, MCPToolRegistry, StartupReport,
+    };
 }
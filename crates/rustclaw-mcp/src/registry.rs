@@ -1,46 +1,235 @@
 //! MCP tool registry for managing multiple MCP clients
 
-use crate::client::MCPClient;
-use crate::config::MCPConfig;
+use crate::circuit_breaker::CircuitBreaker;
+use crate::client::{MCPClient, ServerDescription, ToolDefinition};
+use crate::config::{MCPConfig, MCPServerConfig, ToolNaming};
 use crate::error::MCPError;
 use crate::tool_bridge::MCPToolWrapper;
 use serde_json::Value;
-use std::collections::HashMap;
-use std::sync::Arc;
-use tokio::sync::RwLock;
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Weak};
+use std::time::Duration;
+use tokio::sync::{Mutex, RwLock};
 use tokio::task::JoinSet;
-use tracing::{error, info};
+use tracing::{error, info, warn};
+
+/// Outcome of starting the configured MCP servers
+#[derive(Debug, Default)]
+pub struct StartupReport {
+    /// Servers that started successfully (sorted by name)
+    pub succeeded: Vec<String>,
+    /// Servers that failed to start, with the reason (sorted by name)
+    pub failed: Vec<(String, MCPError)>,
+}
+
+impl StartupReport {
+    /// Total number of servers that were attempted
+    #[must_use]
+    pub fn total(&self) -> usize {
+        self.succeeded.len() + self.failed.len()
+    }
+
+    /// Check whether any server failed to start
+    #[must_use]
+    pub fn has_failures(&self) -> bool {
+        !self.failed.is_empty()
+    }
+
+    /// One-line human-readable summary, listing failed servers
+    #[must_use]
+    pub fn summary(&self) -> String {
+        let mut summary = format!(
+            "MCP: {}/{} servers started",
+            self.succeeded.len(),
+            self.total()
+        );
+
+        if self.has_failures() {
+            let failures: Vec<String> = self
+                .failed
+                .iter()
+                .map(|(name, e)| format!("{name} ({e})"))
+                .collect();
+            summary.push_str("; failed: ");
+            summary.push_str(&failures.join(", "));
+        }
+
+        summary
+    }
+
+    /// Explain why startup must be aborted under the `require_all` and
+    /// `require_any` settings of `config`, or `None` if it may continue
+    #[must_use]
+    pub fn unmet_requirement(&self, config: &MCPConfig) -> Option<String> {
+        if config.require_all && self.has_failures() {
+            let failed: Vec<&str> = self.failed.iter().map(|(name, _)| name.as_str()).collect();
+            return Some(format!(
+                "MCP servers required (require_all) but failed to start: {}",
+                failed.join(", ")
+            ));
+        }
+        if config.require_any && self.total() > 0 && self.succeeded.is_empty() {
+            return Some(format!(
+                "No MCP server started but at least one is required (require_any); {}",
+                self.summary()
+            ));
+        }
+        None
+    }
+}
+
+/// Server connected on the first call of one of its declared tools
+pub struct LazyServer {
+    /// Server name
+    name: String,
+    /// Configuration to connect with
+    config: MCPServerConfig,
+    /// Time connecting (including tool discovery) may take
+    timeout: Duration,
+    /// Held while connecting, so concurrent first calls connect only once
+    connecting: Mutex<()>,
+    /// Set on shutdown, after which the server is not connected any more
+    closed: AtomicBool,
+}
+
+impl LazyServer {
+    fn new(name: String, config: MCPServerConfig) -> Self {
+        Self {
+            name,
+            timeout: config.get_timeout(10),
+            config,
+            connecting: Mutex::new(()),
+            closed: AtomicBool::new(false),
+        }
+    }
+
+    /// Connect the server and add it to `clients`, unless it is connected
+    ///
+    /// # Errors
+    /// Returns [`MCPError::StartupTimeout`] if connecting takes longer than
+    /// the server's startup timeout, or the error the start failed with
+    pub async fn ensure_connected(
+        &self,
+        clients: &RwLock<HashMap<String, MCPClient>>,
+    ) -> Result<(), MCPError> {
+        if clients.read().await.contains_key(&self.name) {
+            return Ok(());
+        }
+        let _connecting = self.connecting.lock().await;
+        if clients.read().await.contains_key(&self.name) {
+            return Ok(());
+        }
+        let shut_down = || MCPError::StartupFailed {
+            server: self.name.clone(),
+            reason: "the MCP registry was shut down".into(),
+        };
+        if self.closed.load(Ordering::Acquire) {
+            return Err(shut_down());
+        }
+
+        info!("Connecting MCP server '{}' on first use", self.name);
+        let start = MCPClient::start(self.name.clone(), &self.config, self.timeout);
+        let client = tokio::time::timeout(self.timeout, start)
+            .await
+            .map_err(|_| MCPError::StartupTimeout {
+                server: self.name.clone(),
+                timeout: self.timeout,
+            })??;
+        for tool in self.config.declared_tools() {
+            if !client.tools.iter().any(|offered| offered.name == tool.name) {
+                warn!(
+                    "MCP server '{}' does not offer the declared tool '{}'",
+                    self.name, tool.name
+                );
+            }
+        }
+
+        if self.closed.load(Ordering::Acquire) {
+            client.close().await;
+            return Err(shut_down());
+        }
+        clients.write().await.insert(self.name.clone(), client);
+        Ok(())
+    }
+}
 
 /// Registry of MCP clients and their tools
 pub struct MCPToolRegistry {
     /// Connected MCP clients (`server_name` → client)
     clients: Arc<RwLock<HashMap<String, MCPClient>>>,
+    /// Servers connected on first use (`server_name` → server)
+    lazy: HashMap<String, Arc<LazyServer>>,
+    /// Consecutive failures before a tool's circuit breaker opens
+    failure_threshold: u32,
+    /// Cooldown before an open circuit breaker allows a trial call
+    failure_cooldown: Duration,
+    /// How exposed tool names are derived from server and tool names
+    tool_naming: ToolNaming,
 }
 
 impl MCPToolRegistry {
     /// Create an empty registry
     #[must_use]
     pub fn new() -> Self {
+        let defaults = MCPConfig::default();
         Self {
             clients: Arc::new(RwLock::new(HashMap::new())),
+            lazy: HashMap::new(),
+            failure_threshold: defaults.failure_threshold,
+            failure_cooldown: Duration::from_secs(defaults.failure_cooldown),
+            tool_naming: defaults.tool_naming,
         }
     }
 
     /// Start all MCP servers configured in parallel
-    pub async fn start_all(config: &MCPConfig) -> Self {
-        let registry = Self::new();
+    ///
+    /// Servers that fail are skipped; the returned report lists which
+    /// servers started and why the others failed. Lazy servers (see
+    /// [`MCPServerConfig::is_lazy`]) are not started and not in the report;
+    /// they connect on the first call of one of their tools.
+    pub async fn start_all(config: &MCPConfig) -> (Self, StartupReport) {
+        let lazy: HashMap<String, Arc<LazyServer>> = config
+            .servers
+            .iter()
+            .filter(|(_, server)| server.is_lazy())
+            .map(|(name, server)| {
+                info!(
+                    "MCP server '{}' connects on first use ({} tools declared)",
+                    name,
+                    server.declared_tools().len()
+                );
+                let server = LazyServer::new(name.clone(), server.clone());
+                (name.clone(), Arc::new(server))
+            })
+            .collect();
+        let registry = Self {
+            lazy,
+            failure_threshold: config.failure_threshold,
+            failure_cooldown: Duration::from_secs(config.failure_cooldown),
+            tool_naming: config.tool_naming.clone(),
+            ..Self::new()
+        };
 
-        if config.servers.is_empty() {
-            info!("No MCP servers configured");
-            return registry;
+        let eager: Vec<_> = config
+            .servers
+            .iter()
+            .filter(|(_, server)| !server.is_lazy())
+            .collect();
+        if eager.is_empty() {
+            if registry.lazy.is_empty() {
+                info!("No MCP servers configured");
+            }
+            registry.spawn_idle_monitor().await;
+            return (registry, StartupReport::default());
         }
 
-        info!("Starting {} MCP server(s)", config.servers.len());
+        info!("Starting {} MCP server(s)", eager.len());
 
         let mut tasks = JoinSet::new();
 
         // Spawn all clients concurrently
-        for (name, server_config) in &config.servers {
+        for (name, server_config) in eager {
             let name = name.clone();
             let config = server_config.clone();
             let timeout_secs = config.get_timeout(10).as_secs();
@@ -61,27 +250,76 @@ impl MCPToolRegistry {
                             client.tools.len(),
                             client.protocol_version
                         );
-                        clients.write().await.insert(name, client);
+                        clients.write().await.insert(name.clone(), client);
+                        (name, Ok(()))
                     }
                     Err(e) => {
                         error!("❌ MCP server '{}' failed: {}", name, e);
                         // Graceful degradation: continue without this server
+                        (name, Err(e))
                     }
                 }
             });
         }
 
         // Wait for all tasks to complete
-        while tasks.join_next().await.is_some() {}
+        let mut report = StartupReport::default();
+        while let Some(joined) = tasks.join_next().await {
+            match joined {
+                Ok((name, Ok(()))) => report.succeeded.push(name),
+                Ok((name, Err(e))) => report.failed.push((name, e)),
+                Err(e) => error!("MCP startup task panicked: {}", e),
+            }
+        }
+        report.succeeded.sort();
+        report.failed.sort_by(|a, b| a.0.cmp(&b.0));
 
-        let count = registry.clients.read().await.len();
         info!(
             "MCP registry ready: {}/{} servers started",
-            count,
-            config.servers.len()
+            report.succeeded.len(),
+            report.total()
         );
 
-        registry
+        registry.spawn_idle_monitor().await;
+
+        (registry, report)
+    }
+
+    /// Periodically disconnect clients that exceeded their idle timeout
+    ///
+    /// The task stops once every handle to the client map has been dropped.
+    async fn spawn_idle_monitor(&self) {
+        let lazy = self
+            .lazy
+            .values()
+            .filter_map(|server| server.config.idle_timeout());
+        let Some(shortest) = self
+            .clients
+            .read()
+            .await
+            .values()
+            .filter_map(MCPClient::idle_timeout)
+            .chain(lazy)
+            .min()
+        else {
+            return;
+        };
+
+        let interval = (shortest / 2).max(Duration::from_secs(1));
+        let clients: Weak<RwLock<HashMap<String, MCPClient>>> = Arc::downgrade(&self.clients);
+
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                let Some(clients) = clients.upgrade() else {
+                    break;
+                };
+                for client in clients.read().await.values() {
+                    client.disconnect_if_idle().await;
+                }
+            }
+        });
     }
 
     /// Execute a tool on a specific server
@@ -107,25 +345,112 @@ impl MCPToolRegistry {
     }
 
     /// Get all tools from all connected servers as `ToolFunction` wrappers
-    pub async fn to_tool_functions(&self) -> Vec<Box<dyn rustclaw_provider::ToolFunction>> {
+    ///
+    /// Lazy servers that are not connected yet contribute their declared
+    /// tools. Tools are named according to the configured [`ToolNaming`],
+    /// but never take one of the `reserved` names (e.g. of built-in tools):
+    /// such a tool is prefixed with its server name, or left out if even
+    /// that name is taken.
+    pub async fn to_tool_functions(
+        &self,
+        reserved: &HashSet<String>,
+    ) -> Vec<Box<dyn rustclaw_provider::ToolFunction>> {
         let clients = self.clients.read().await;
-        let mut tools = Vec::new();
-
-        for (server_name, client) in clients.iter() {
-            for mcp_tool in &client.tools {
-                let wrapper = MCPToolWrapper {
-                    server_name: server_name.clone(),
-                    tool_name: mcp_tool.name.clone(),
-                    full_name: format!("{}_{}", server_name, mcp_tool.name),
-                    definition: mcp_tool.clone(),
-                    registry: Arc::clone(&self.clients),
+
+        let mut entries: Vec<(&str, ToolDefinition)> = Vec::new();
+        for (server, client) in clients.iter() {
+            entries.extend(client.tools.iter().map(|tool| {
+                let definition = ToolDefinition {
+                    description: client.tool_description(tool),
+                    ..tool.clone()
                 };
+                (server.as_str(), definition)
+            }));
+        }
+        for (server, lazy) in &self.lazy {
+            if clients.contains_key(server) {
+                continue;
+            }
+            entries.extend(lazy.config.declared_tools().iter().map(|tool| {
+                let definition = ToolDefinition {
+                    name: tool.name.clone(),
+                    description: lazy
+                        .config
+                        .tool_description(&tool.name, tool.description.as_deref()),
+                    input_schema: tool.input_schema.clone(),
+                };
+                (server.as_str(), definition)
+            }));
+        }
+        // Stable, so each server's tools keep their order
+        entries.sort_by(|a, b| a.0.cmp(b.0));
 
-                tools.push(Box::new(wrapper) as Box<dyn rustclaw_provider::ToolFunction>);
+        let pairs: Vec<(&str, &str)> = entries
+            .iter()
+            .map(|(server, tool)| (*server, tool.name.as_str()))
+            .collect();
+        let names = assign_names(&self.tool_naming, &pairs, reserved);
+
+        let mut functions = Vec::new();
+
+        for ((server_name, definition), full_name) in entries.into_iter().zip(names) {
+            if reserved.contains(&full_name) {
+                warn!(
+                    "MCP tool '{}' of server '{}' would replace the tool '{}', leaving it out",
+                    definition.name, server_name, full_name
+                );
+                continue;
             }
+            let wrapper = MCPToolWrapper {
+                server_name: server_name.to_string(),
+                tool_name: definition.name.clone(),
+                full_name,
+                definition,
+                registry: Arc::clone(&self.clients),
+                lazy: self.lazy.get(server_name).cloned(),
+                breaker: CircuitBreaker::new(self.failure_threshold, self.failure_cooldown),
+            };
+
+            functions.push(Box::new(wrapper) as Box<dyn rustclaw_provider::ToolFunction>);
         }
 
-        tools
+        functions
+    }
+
+    /// Close every client and remove it from the registry
+    ///
+    /// Waits until all stdio server processes have exited. Tools created by
+    /// [`Self::to_tool_functions`] fail afterwards.
+    pub async fn shutdown_all(&self) {
+        for server in self.lazy.values() {
+            server.closed.store(true, Ordering::Release);
+        }
+        let clients: Vec<MCPClient> = self
+            .clients
+            .write()
+            .await
+            .drain()
+            .map(|(_, client)| client)
+            .collect();
+        if clients.is_empty() {
+            return;
+        }
+
+        info!("Shutting down {} MCP server(s)", clients.len());
+        futures::future::join_all(clients.iter().map(MCPClient::close)).await;
+    }
+
+    /// Names of the connected servers, sorted
+    pub async fn server_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.clients.read().await.keys().cloned().collect();
+        names.sort();
+        names
+    }
+
+    /// Summarize a connected server's capabilities, if it is known
+    pub async fn describe(&self, server_name: &str) -> Option<ServerDescription> {
+        let clients = self.clients.read().await;
+        Some(clients.get(server_name)?.describe().await)
     }
 
     /// Check if registry is empty
@@ -149,8 +474,183 @@ impl MCPToolRegistry {
     }
 }
 
+/// Exposed names for `(server, tool)` pairs, in the same order
+///
+/// With [`ToolNaming::Bare`], tools whose name is offered by more than one
+/// server keep the `{server}_{tool}` form so every exposed name is unique.
+/// A tool whose name would be one of the `reserved` names gets that form
+/// too, whatever the naming.
+fn assign_names(
+    naming: &ToolNaming,
+    tools: &[(&str, &str)],
+    reserved: &HashSet<String>,
+) -> Vec<String> {
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    for (_, tool) in tools {
+        *counts.entry(tool).or_default() += 1;
+    }
+
+    tools
+        .iter()
+        .map(|(server, tool)| {
+            let name = naming.name(server, tool);
+            if *naming == ToolNaming::Bare && counts.get(tool).copied().unwrap_or(0) > 1 {
+                warn!(
+                    "MCP tool '{}' is offered by several servers, exposing it as '{}_{}'",
+                    tool, server, tool
+                );
+                ToolNaming::Prefixed.name(server, tool)
+            } else if reserved.contains(&name) {
+                warn!(
+                    "MCP tool '{}' has the name of a built-in tool, exposing it as '{}_{}'",
+                    name, server, tool
+                );
+                ToolNaming::Prefixed.name(server, tool)
+            } else {
+                name
+            }
+        })
+        .collect()
+}
+
 impl Default for MCPToolRegistry {
     fn default() -> Self {
         Self::new()
     }
 }
+
+#[cfg(test)]
+#[allow(clippy::expect_used)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_assign_names() {
+        let tools = [
+            ("filesystem", "read_file"),
+            ("github", "search"),
+            ("web", "search"),
+        ];
+
+        let none = HashSet::new();
+
+        assert_eq!(
+            assign_names(&ToolNaming::Prefixed, &tools, &none),
+            ["filesystem_read_file", "github_search", "web_search"]
+        );
+        assert_eq!(
+            assign_names(&ToolNaming::Bare, &tools, &none),
+            ["read_file", "github_search", "web_search"]
+        );
+        assert_eq!(
+            assign_names(&ToolNaming::Separator("-".to_string()), &tools, &none),
+            ["filesystem-read_file", "github-search", "web-search"]
+        );
+
+        // Built-in tools keep their names
+        let builtin = HashSet::from(["read_file".to_string(), "web-search".to_string()]);
+        assert_eq!(
+            assign_names(&ToolNaming::Bare, &tools, &builtin),
+            ["filesystem_read_file", "github_search", "web_search"]
+        );
+        assert_eq!(
+            assign_names(&ToolNaming::Separator("-".to_string()), &tools, &builtin),
+            ["filesystem-read_file", "github-search", "web_search"]
+        );
+    }
+
+    #[test]
+    fn test_startup_report_summary() {
+        let report = StartupReport {
+            succeeded: vec!["github".to_string()],
+            failed: vec![(
+                "search".to_string(),
+                MCPError::Config("missing url".to_string()),
+            )],
+        };
+
+        assert!(report.has_failures());
+        assert_eq!(
+            report.summary(),
+            "MCP: 1/2 servers started; failed: search (Configuration error: missing url)"
+        );
+        assert_eq!(
+            StartupReport::default().summary(),
+            "MCP: 0/0 servers started"
+        );
+    }
+
+    #[test]
+    fn test_startup_requirements() {
+        let failure = || MCPError::Config("missing url".to_string());
+        let partial = StartupReport {
+            succeeded: vec!["github".to_string()],
+            failed: vec![("search".to_string(), failure())],
+        };
+        let none = StartupReport {
+            succeeded: Vec::new(),
+            failed: vec![("search".to_string(), failure())],
+        };
+
+        // Lenient by default
+        let lenient = MCPConfig::default();
+        assert!(partial.unmet_requirement(&lenient).is_none());
+        assert!(none.unmet_requirement(&lenient).is_none());
+
+        let require_any = MCPConfig {
+            require_any: true,
+            ..MCPConfig::default()
+        };
+        assert!(partial.unmet_requirement(&require_any).is_none());
+        assert!(none.unmet_requirement(&require_any).is_some());
+        assert!(StartupReport::default()
+            .unmet_requirement(&require_any)
+            .is_none());
+
+        let require_all = MCPConfig {
+            require_all: true,
+            ..MCPConfig::default()
+        };
+        assert_eq!(
+            partial.unmet_requirement(&require_all).as_deref(),
+            Some("MCP servers required (require_all) but failed to start: search")
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_lazy_server() {
+        let config: MCPConfig = toml::from_str(
+            r#"
+            [servers.idle]
+            command = "rustclaw-nonexistent-mcp-server"
+            lazy = true
+            startup_timeout = 2
+
+            [[servers.idle.tools]]
+            name = "lookup"
+            description = "Look something up"
+            "#,
+        )
+        .expect("Failed to parse TOML");
+
+        // Not started, but its declared tools are offered
+        let (registry, report) = MCPToolRegistry::start_all(&config).await;
+        assert_eq!(report.total(), 0);
+        let functions = registry.to_tool_functions(&HashSet::new()).await;
+        assert_eq!(functions.len(), 1);
+        let definition = functions[0].definition();
+        assert_eq!(definition.function.name, "idle_lookup");
+        assert_eq!(definition.function.description, "Look something up");
+
+        // The first call connects, and the failure reaches the caller
+        let error = functions[0]
+            .execute(serde_json::json!({}))
+            .expect_err("server should fail to start");
+        assert!(error
+            .to_string()
+            .starts_with("MCP server 'idle' not available"));
+        assert!(registry.server_names().await.is_empty());
+
+        registry.shutdown_all().await;
+    }
+}
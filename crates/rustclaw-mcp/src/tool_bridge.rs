@@ -1,7 +1,10 @@
 //! Bridge between MCP tools and rustclaw's `ToolFunction` trait
 
+use crate::circuit_breaker::CircuitBreaker;
 use crate::client::{MCPClient, ToolDefinition};
+use crate::registry::LazyServer;
 use anyhow::Result;
+use futures::future::BoxFuture;
 use rustclaw_types::Tool;
 use serde_json::Value;
 use std::sync::Arc;
@@ -13,45 +16,210 @@ pub struct MCPToolWrapper {
     pub server_name: String,
     /// Original MCP tool name
     pub tool_name: String,
-    /// Full namespaced tool name (`server_tool`)
+    /// Name exposed to the model (see [`crate::config::ToolNaming`])
     pub full_name: String,
     /// Tool definition from MCP server
     pub definition: ToolDefinition,
     /// Reference to registry for tool execution
     pub registry: Arc<RwLock<std::collections::HashMap<String, MCPClient>>>,
+    /// Server to connect on the first call, if it connects lazily
+    pub lazy: Option<Arc<LazyServer>>,
+    /// Circuit breaker guarding against repeatedly failing calls
+    pub breaker: CircuitBreaker,
+}
+
+/// Check whether a JSON schema satisfies strict function-calling rules
+///
+/// `OpenAI` strict mode requires every object schema to set `additionalProperties: false`
+/// and list all of its properties in `required`. Nested schemas (properties,
+/// array items, combinators and definitions) are checked recursively.
+#[must_use]
+pub fn is_strict_compatible(schema: &Value) -> bool {
+    let Some(obj) = schema.as_object() else {
+        // Boolean schemas (`true`/`false`) aren't supported in strict mode
+        return false;
+    };
+
+    let is_object_schema =
+        obj.get("type").and_then(Value::as_str) == Some("object") || obj.contains_key("properties");
+
+    if is_object_schema {
+        if obj.get("additionalProperties") != Some(&Value::Bool(false)) {
+            return false;
+        }
+
+        let required: Vec<&str> = obj
+            .get("required")
+            .and_then(Value::as_array)
+            .map(|r| r.iter().filter_map(Value::as_str).collect())
+            .unwrap_or_default();
+
+        if let Some(properties) = obj.get("properties").and_then(Value::as_object) {
+            if !properties.keys().all(|k| required.contains(&k.as_str())) {
+                return false;
+            }
+            if !properties.values().all(is_strict_compatible) {
+                return false;
+            }
+        }
+    }
+
+    let nested_ok = |key: &str| match obj.get(key) {
+        Some(Value::Array(schemas)) => schemas.iter().all(is_strict_compatible),
+        Some(Value::Object(defs)) if key == "$defs" || key == "definitions" => {
+            defs.values().all(is_strict_compatible)
+        }
+        Some(schema @ Value::Object(_)) => is_strict_compatible(schema),
+        _ => true,
+    };
+
+    ["items", "anyOf", "oneOf", "allOf", "$defs", "definitions"]
+        .into_iter()
+        .all(nested_ok)
 }
 
 impl rustclaw_provider::ToolFunction for MCPToolWrapper {
     fn definition(&self) -> Tool {
-        Tool::function(
+        let mut tool = Tool::function(
             &self.full_name,
             self.definition
                 .description
                 .as_deref()
                 .unwrap_or("No description"),
             self.definition.input_schema.clone(),
-        )
+        );
+
+        // Looser schemas are rejected in strict mode, so send them non-strict
+        if !is_strict_compatible(&self.definition.input_schema) {
+            tool.function.strict = Some(false);
+        }
+
+        tool
+    }
+
+    fn is_available(&self) -> bool {
+        !self.breaker.is_open()
+    }
+
+    /// What a server's tool does is unknown, so it is assumed to do anything
+    fn capabilities(&self) -> rustclaw_provider::ToolCapabilities {
+        rustclaw_provider::ToolCapabilities {
+            reads_files: true,
+            writes_files: true,
+            executes_commands: true,
+            network: true,
+        }
+    }
+
+    /// Results with several content blocks come back as a JSON array, one
+    /// element per block
+    fn returns_items(&self) -> bool {
+        true
     }
 
     fn execute(&self, args: Value) -> Result<Value> {
-        // Convert async call_tool to sync (ToolFunction trait is sync)
-        let registry = Arc::clone(&self.registry);
-        let server = self.server_name.clone();
-        let tool = self.tool_name.clone();
+        // For callers without a runtime of their own; the registry runs MCP
+        // tools with `execute_async`
+        tokio::task::block_in_place(|| tokio::runtime::Handle::current().block_on(self.call(args)))
+    }
+
+    fn is_async(&self) -> bool {
+        true
+    }
 
-        tokio::task::block_in_place(|| {
-            tokio::runtime::Handle::current().block_on(async {
-                let clients = registry.read().await;
+    fn execute_async(&self, args: Value) -> BoxFuture<'_, Result<Value>> {
+        Box::pin(self.call(args))
+    }
+}
 
-                let client = clients
-                    .get(&server)
-                    .ok_or_else(|| anyhow::anyhow!("MCP server '{server}' not available"))?;
+impl MCPToolWrapper {
+    /// Call the tool on its server, connecting a lazy server first
+    async fn call(&self, args: Value) -> Result<Value> {
+        let Some(_permit) = self.breaker.try_call(&self.full_name) else {
+            return Err(anyhow::anyhow!(
+                "Tool '{}' is temporarily unavailable after repeated failures",
+                self.full_name
+            ));
+        };
 
-                client
-                    .call_tool(&tool, args)
+        let server = &self.server_name;
+        let result = async {
+            if let Some(lazy) = &self.lazy {
+                lazy.ensure_connected(&self.registry)
                     .await
-                    .map_err(|e| anyhow::anyhow!("MCP tool call failed: {e}"))
-            })
-        })
+                    .map_err(|e| anyhow::anyhow!("MCP server '{server}' not available: {e}"))?;
+            }
+            let clients = self.registry.read().await;
+
+            let client = clients
+                .get(server)
+                .ok_or_else(|| anyhow::anyhow!("MCP server '{server}' not available"))?;
+
+            client
+                .call_tool(&self.tool_name, args)
+                .await
+                .map_err(|e| anyhow::anyhow!("MCP tool call failed: {e}"))
+        }
+        .await;
+
+        match &result {
+            Ok(_) => self.breaker.record_success(&self.full_name),
+            Err(_) => self.breaker.record_failure(&self.full_name),
+        }
+
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_strict_compatible_schema() {
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "path": { "type": "string" },
+                "options": {
+                    "type": "object",
+                    "properties": { "recursive": { "type": "boolean" } },
+                    "required": ["recursive"],
+                    "additionalProperties": false
+                }
+            },
+            "required": ["path", "options"],
+            "additionalProperties": false
+        });
+        assert!(is_strict_compatible(&schema));
+    }
+
+    #[test]
+    fn test_loose_schemas_not_strict_compatible() {
+        // Missing additionalProperties
+        assert!(!is_strict_compatible(&json!({
+            "type": "object",
+            "properties": { "path": { "type": "string" } },
+            "required": ["path"]
+        })));
+
+        // Optional property
+        assert!(!is_strict_compatible(&json!({
+            "type": "object",
+            "properties": { "path": { "type": "string" }, "limit": { "type": "integer" } },
+            "required": ["path"],
+            "additionalProperties": false
+        })));
+
+        // Loose object nested in array items
+        assert!(!is_strict_compatible(&json!({
+            "type": "object",
+            "properties": {
+                "files": { "type": "array", "items": { "type": "object" } }
+            },
+            "required": ["files"],
+            "additionalProperties": false
+        })));
     }
 }
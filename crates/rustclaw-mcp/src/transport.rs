@@ -0,0 +1,214 @@
+//! Transports to MCP servers
+//!
+//! [`MCPTransport`] opens the connection an [`MCPClient`](crate::MCPClient)
+//! talks over. [`MCPClient::start`](crate::MCPClient::start) uses the stdio
+//! or Streamable HTTP transport described by the server's configuration;
+//! implement the trait to reach a server another way (an in-process server
+//! in tests, a Unix socket, ...) and pass it to
+//! [`MCPClient::start_with_transport`](crate::MCPClient::start_with_transport).
+
+use crate::client::NotificationHandler;
+use crate::config::{MCPServerConfig, TransportKind, TransportType};
+use crate::error::{MCPError, Result};
+use crate::http_client::{split_headers, CompatibleHttpClient};
+use futures::future::BoxFuture;
+use rmcp::service::{RoleClient, RunningService};
+use rmcp::transport::streamable_http_client::{
+    StreamableHttpClientTransport, StreamableHttpClientTransportConfig,
+};
+use rmcp::transport::IntoTransport;
+use rmcp::ServiceExt;
+use std::collections::HashMap;
+use tracing::{debug, warn};
+
+/// A way of connecting to an MCP server
+pub trait MCPTransport: Send + Sync {
+    /// Name of the transport shown in diagnostics, e.g. `stdio` or `http`
+    fn kind(&self) -> &'static str;
+
+    /// Protocol version reported until the server tells its own
+    fn protocol_version(&self) -> &'static str {
+        "2025-03-26"
+    }
+
+    /// Open a connection and initialize it with `connector`
+    ///
+    /// Called at startup and again on every reconnect, so each call opens a
+    /// fresh connection.
+    ///
+    /// # Errors
+    /// Returns an error if the connection cannot be opened or initialized
+    fn connect(&self, connector: Connector) -> BoxFuture<'_, Result<Session>>;
+}
+
+/// Runs the client side of the MCP protocol over a transport
+///
+/// Handed to [`MCPTransport::connect`], which opens the transport and
+/// passes it to [`Connector::serve`].
+pub struct Connector {
+    handler: NotificationHandler,
+}
+
+impl Connector {
+    pub(crate) fn new(handler: NotificationHandler) -> Self {
+        Self { handler }
+    }
+
+    /// Name of the server being connected to
+    #[must_use]
+    pub fn server(&self) -> &str {
+        &self.handler.server
+    }
+
+    /// Initialize the MCP protocol over `transport`
+    ///
+    /// Takes anything rmcp accepts as a client transport, such as a child
+    /// process, a Streamable HTTP client or an async reader and writer pair.
+    ///
+    /// # Errors
+    /// Returns an error if the server does not complete initialization
+    pub async fn serve<T, E, A>(self, transport: T) -> Result<Session>
+    where
+        T: IntoTransport<RoleClient, E, A>,
+        E: std::error::Error + Send + Sync + 'static,
+    {
+        let server = self.handler.server.clone();
+        let service = self
+            .handler
+            .serve(transport)
+            .await
+            .map_err(|e| MCPError::from_init_error(&server, e))?;
+        Ok(Session { service })
+    }
+}
+
+/// An initialized connection to an MCP server, made by [`Connector::serve`]
+pub struct Session {
+    pub(crate) service: RunningService<RoleClient, NotificationHandler>,
+}
+
+/// The stdio or Streamable HTTP transport of a server's configuration
+///
+/// Secrets are resolved on every connect, so a reconnect picks up rotated
+/// credentials.
+pub(crate) struct ConfiguredTransport {
+    config: MCPServerConfig,
+}
+
+impl ConfiguredTransport {
+    pub(crate) fn new(config: MCPServerConfig) -> Self {
+        Self { config }
+    }
+
+    /// Start an MCP server via stdio (child process) transport
+    async fn start_stdio(
+        connector: Connector,
+        program: &str,
+        args: &[String],
+        env: &HashMap<String, String>,
+    ) -> Result<Session> {
+        let name = connector.server().to_string();
+        // Values may be secrets, so only the names are logged
+        debug!(
+            "Starting stdio transport for '{}': {} {:?} env={:?}",
+            name,
+            program,
+            args,
+            env.keys().collect::<Vec<_>>()
+        );
+
+        // Build tokio Command for the child process
+        let mut cmd = tokio::process::Command::new(program);
+        cmd.args(args);
+
+        // Set custom environment variables
+        for (key, value) in env {
+            cmd.env(key, value);
+            // Also set uppercase version in case config loader lowercased it
+            // (e.g. Z_AI_API_KEY becoming z_ai_api_key)
+            let upper = key.to_uppercase();
+            if upper != *key {
+                cmd.env(upper, value);
+            }
+        }
+
+        // Pipe stdin/stdout for MCP communication
+        cmd.stdin(std::process::Stdio::piped());
+        cmd.stdout(std::process::Stdio::piped());
+        cmd.stderr(std::process::Stdio::piped());
+
+        // Create transport from child process (takes ownership of cmd)
+        let transport =
+            rmcp::transport::TokioChildProcess::new(cmd).map_err(|e| MCPError::StartupFailed {
+                server: name.clone(),
+                reason: format!("Failed to spawn '{program}': {e}"),
+            })?;
+
+        let session = connector.serve(transport).await?;
+        debug!("MCP server '{}' connected via stdio", name);
+        Ok(session)
+    }
+
+    /// Start an MCP server via Streamable HTTP transport
+    async fn start_http(
+        connector: Connector,
+        url: &str,
+        headers: &HashMap<String, String>,
+        client: CompatibleHttpClient,
+    ) -> Result<Session> {
+        let name = connector.server().to_string();
+        debug!("Starting HTTP transport for '{}': {}", name, url);
+
+        let (bearer_token, headers) = split_headers(headers)?;
+        if bearer_token.is_none() && !headers.contains_key(reqwest::header::AUTHORIZATION) {
+            warn!(
+                "No Authorization header found for HTTP transport! Keys: {:?}",
+                headers.keys()
+            );
+        }
+
+        // Build transport config
+        let mut config = StreamableHttpClientTransportConfig::with_uri(url);
+
+        // The token is sent with `bearer_auth()`, which adds the "Bearer " prefix
+        if let Some(token) = bearer_token {
+            config = config.auth_header(token);
+        }
+
+        let transport =
+            StreamableHttpClientTransport::with_client(client.with_headers(headers), config);
+
+        let session = connector.serve(transport).await?;
+        debug!("MCP server '{}' connected via HTTP", name);
+        Ok(session)
+    }
+}
+
+impl MCPTransport for ConfiguredTransport {
+    fn kind(&self) -> &'static str {
+        self.config.transport_name()
+    }
+
+    fn protocol_version(&self) -> &'static str {
+        match self.config.transport_kind() {
+            TransportKind::Stdio => "2024-11-05",
+            TransportKind::Http => "2025-03-26",
+        }
+    }
+
+    fn connect(&self, connector: Connector) -> BoxFuture<'_, Result<Session>> {
+        Box::pin(async move {
+            match self.config.detect_transport()? {
+                TransportType::Stdio { program, args, env } => {
+                    Self::start_stdio(connector, &program, &args, &env).await
+                }
+                TransportType::HTTP(url, headers) => {
+                    let client = CompatibleHttpClient::default()
+                        .with_timeout(self.config.http_timeouts())
+                        .with_retry(self.config.retry_policy());
+                    Self::start_http(connector, &url, &headers, client).await
+                }
+            }
+        })
+    }
+}
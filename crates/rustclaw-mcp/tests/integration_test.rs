@@ -11,8 +11,16 @@ async fn test_stdio_transport_with_real_server() {
     let client = MCPClient::start("test".into(), &config, std::time::Duration::from_secs(30))
         .await
         .expect("Failed to start MCP server");
+    let tool_count = client.tools.len();
 
-    assert!(!client.tools.is_empty());
+    // Close before asserting so a failure doesn't leave the server running
+    client.close().await;
+    assert!(!client.is_connected().await);
+    assert!(client
+        .call_tool("echo", serde_json::json!({}))
+        .await
+        .is_err());
+    assert!(tool_count > 0);
 }
 
 #[tokio::test]
@@ -26,10 +34,14 @@ async fn test_graceful_degradation() {
     let config = MCPConfig {
         startup_timeout: 1,
         servers,
+        ..MCPConfig::default()
     };
 
-    let registry = MCPToolRegistry::start_all(&config).await;
+    let (registry, report) = MCPToolRegistry::start_all(&config).await;
+    registry.shutdown_all().await;
     assert_eq!(registry.server_count().await, 0);
+    assert_eq!(report.failed.len(), 1);
+    assert!(report.succeeded.is_empty());
 }
 
 #[tokio::test]
@@ -40,6 +52,7 @@ async fn test_startup_timeout() {
 
     // For now, just test that start_all works with empty config
     let config = MCPConfig::default();
-    let registry = MCPToolRegistry::start_all(&config).await;
+    let (registry, _report) = MCPToolRegistry::start_all(&config).await;
+    registry.shutdown_all().await;
     assert_eq!(registry.server_count().await, 0);
 }
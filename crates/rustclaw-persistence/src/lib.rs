@@ -1,9 +1,59 @@
 use anyhow::{anyhow, Result};
-use rustclaw_types::{Message, MessageContent, User};
-use sqlx::SqlitePool;
-use tracing::info;
+use rustclaw_types::{ChatMessage, Message, MessageContent, Role, TokenUsage, User};
+use sqlx::sqlite::{SqliteConnectOptions, SqliteJournalMode, SqliteRow};
+use sqlx::{Row, SqlitePool};
+use std::collections::HashMap;
+use std::future::Future;
+use std::str::FromStr;
+use std::time::Duration;
+use tracing::{info, warn};
+
+/// User ID under which assistant and tool turns are stored
+const ASSISTANT_USER_ID: i64 = 0;
+
+/// Attempts of a write that fails because the database is locked
+const WRITE_ATTEMPTS: u32 = 5;
+
+/// Delay before the first retry of a locked write, doubled for each further one
+const WRITE_RETRY_DELAY: Duration = Duration::from_millis(50);
+
+/// Aggregated token usage and cost of completed requests
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct UsageTotals {
+    pub requests: i64,
+    pub prompt_tokens: i64,
+    pub completion_tokens: i64,
+    /// Cost in USD
+    pub cost: f64,
+}
+
+/// How often a skill was activated and how many of those runs succeeded
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SkillUsage {
+    pub uses: i64,
+    pub successes: i64,
+}
+
+/// Summary of the older messages of a chat
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChatSummary {
+    pub summary: String,
+    /// Timestamp of the newest message the summary covers
+    pub covers_until: chrono::DateTime<chrono::Utc>,
+}
+
+/// A tool round message saved by [`PersistenceService::save_chat_message`]
+#[derive(Debug, Clone)]
+pub struct SavedChatMessage {
+    pub message: ChatMessage,
+    /// When the message was saved
+    pub timestamp: chrono::DateTime<chrono::Utc>,
+}
 
 /// Persistence service for storing data in SQLite
+///
+/// Clones share the same connection pool.
+#[derive(Clone)]
 pub struct PersistenceService {
     pool: SqlitePool,
 }
@@ -12,7 +62,10 @@ impl PersistenceService {
     /// Create a new persistence service
     pub async fn new(database_path: &str) -> Result<Self> {
         let database_url = format!("sqlite:{}?mode=rwc", database_path);
-        let pool = SqlitePool::connect(&database_url).await?;
+        // WAL lets readers continue while a message is written
+        let options =
+            SqliteConnectOptions::from_str(&database_url)?.journal_mode(SqliteJournalMode::Wal);
+        let pool = SqlitePool::connect_with(options).await?;
 
         let service = Self { pool };
         service.run_migrations().await?;
@@ -24,6 +77,12 @@ impl PersistenceService {
         Ok(service)
     }
 
+    /// Check that the database answers a query
+    pub async fn ping(&self) -> Result<()> {
+        sqlx::query("SELECT 1").execute(&self.pool).await?;
+        Ok(())
+    }
+
     /// Run database migrations
     async fn run_migrations(&self) -> Result<()> {
         sqlx::query(
@@ -43,34 +102,112 @@ impl PersistenceService {
                 user_id TEXT NOT NULL,
                 content TEXT NOT NULL,
                 timestamp TEXT NOT NULL,
+                role TEXT NOT NULL DEFAULT 'user',
+                tool_calls TEXT,
+                tool_call_id TEXT,
+                tool_name TEXT,
                 FOREIGN KEY (user_id) REFERENCES users(id)
             );
 
             CREATE INDEX IF NOT EXISTS idx_messages_chat_id ON messages(chat_id);
             CREATE INDEX IF NOT EXISTS idx_messages_timestamp ON messages(timestamp);
+
+            CREATE TABLE IF NOT EXISTS chat_settings (
+                chat_id INTEGER PRIMARY KEY,
+                model TEXT
+            );
+
+            CREATE TABLE IF NOT EXISTS usage (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                chat_id INTEGER NOT NULL,
+                model TEXT NOT NULL,
+                prompt_tokens INTEGER NOT NULL,
+                completion_tokens INTEGER NOT NULL,
+                cost REAL NOT NULL,
+                timestamp TEXT NOT NULL
+            );
+
+            CREATE INDEX IF NOT EXISTS idx_usage_chat_id ON usage(chat_id);
+            CREATE INDEX IF NOT EXISTS idx_usage_timestamp ON usage(timestamp);
+
+            CREATE TABLE IF NOT EXISTS skill_usage (
+                name TEXT PRIMARY KEY,
+                uses INTEGER NOT NULL DEFAULT 0,
+                successes INTEGER NOT NULL DEFAULT 0,
+                last_used TEXT NOT NULL
+            );
+
+            CREATE TABLE IF NOT EXISTS chat_summaries (
+                chat_id INTEGER PRIMARY KEY,
+                summary TEXT NOT NULL,
+                covers_until TEXT NOT NULL
+            );
+
+            CREATE TABLE IF NOT EXISTS memory (
+                chat_id INTEGER NOT NULL,
+                key TEXT NOT NULL,
+                value TEXT NOT NULL,
+                updated_at TEXT NOT NULL,
+                PRIMARY KEY (chat_id, key)
+            );
             "#,
         )
         .execute(&self.pool)
         .await?;
 
+        // Upgrade databases created before role/tool columns existed
+        self.ensure_column("messages", "role", "TEXT NOT NULL DEFAULT 'user'")
+            .await?;
+        self.ensure_column("messages", "tool_calls", "TEXT").await?;
+        self.ensure_column("messages", "tool_call_id", "TEXT")
+            .await?;
+        self.ensure_column("messages", "tool_name", "TEXT").await?;
+        self.ensure_column("chat_settings", "provider", "TEXT")
+            .await?;
+
         info!("Database migrations completed");
         Ok(())
     }
 
+    /// Add a column to a table if it does not exist yet
+    async fn ensure_column(&self, table: &str, column: &str, definition: &str) -> Result<()> {
+        let exists: i64 =
+            sqlx::query("SELECT COUNT(*) AS count FROM pragma_table_info(?) WHERE name = ?")
+                .bind(table)
+                .bind(column)
+                .fetch_one(&self.pool)
+                .await?
+                .get("count");
+
+        if exists == 0 {
+            info!("Adding column {}.{}", table, column);
+            sqlx::query(&format!(
+                "ALTER TABLE {} ADD COLUMN {} {}",
+                table, column, definition
+            ))
+            .execute(&self.pool)
+            .await?;
+        }
+
+        Ok(())
+    }
+
     /// Save a user to the database
     pub async fn save_user(&self, user: &User) -> Result<()> {
-        sqlx::query(
-            r#"
-            INSERT OR REPLACE INTO users (id, telegram_user_id, username, first_name, last_name)
-            VALUES (?, ?, ?, ?, ?)
-            "#,
-        )
-        .bind(user.id.to_string())
-        .bind(user.telegram_user_id)
-        .bind(&user.username)
-        .bind(&user.first_name)
-        .bind(&user.last_name)
-        .execute(&self.pool)
+        retry_locked("save user", || {
+            sqlx::query(
+                r#"
+                INSERT OR REPLACE INTO users (id, telegram_user_id, username, first_name, last_name)
+                VALUES (?, ?, ?, ?, ?)
+                "#,
+            )
+            .bind(user.id.to_string())
+            .bind(user.telegram_user_id)
+            .bind(&user.username)
+            .bind(&user.first_name)
+            .bind(&user.last_name)
+            .execute(&self.pool)
+        })
         .await?;
 
         Ok(())
@@ -85,25 +222,138 @@ impl PersistenceService {
         let content_json = serde_json::to_string(&message.content)
             .map_err(|e| anyhow!("Failed to serialize message content: {}", e))?;
 
+        retry_locked("save message", || {
+            sqlx::query(
+                r#"
+                INSERT INTO messages (id, chat_id, user_id, content, timestamp)
+                VALUES (?, ?, ?, ?, ?)
+                "#,
+            )
+            .bind(message.id.to_string())
+            .bind(message.chat_id)
+            .bind(message.sender.id.to_string())
+            .bind(content_json.as_str())
+            .bind(message.timestamp.to_rfc3339())
+            .execute(&self.pool)
+        })
+        .await?;
+
+        Ok(())
+    }
+
+    /// Save an assistant tool-call turn or tool result from the agentic loop
+    pub async fn save_chat_message(&self, chat_id: i64, message: &ChatMessage) -> Result<()> {
+        self.save_user(&User::new(ASSISTANT_USER_ID)).await?;
+
+        // Store content in the same JSON format as regular messages
+        let content_json = serde_json::to_string(&MessageContent::Text(
+            message.content.clone().unwrap_or_default(),
+        ))
+        .map_err(|e| anyhow!("Failed to serialize message content: {}", e))?;
+
+        let tool_calls_json = message
+            .tool_calls
+            .as_ref()
+            .map(serde_json::to_string)
+            .transpose()
+            .map_err(|e| anyhow!("Failed to serialize tool calls: {}", e))?;
+
         sqlx::query(
             r#"
-            INSERT INTO messages (id, chat_id, user_id, content, timestamp)
-            VALUES (?, ?, ?, ?, ?)
+            INSERT INTO messages (id, chat_id, user_id, content, timestamp, role, tool_calls, tool_call_id, tool_name)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)
             "#,
         )
-        .bind(message.id.to_string())
-        .bind(message.chat_id)
-        .bind(message.sender.id.to_string())
+        .bind(uuid::Uuid::new_v4().to_string())
+        .bind(chat_id)
+        .bind(ASSISTANT_USER_ID.to_string())
         .bind(content_json)
-        .bind(message.timestamp.to_rfc3339())
+        .bind(chrono::Utc::now().to_rfc3339())
+        .bind(message.role.as_str())
+        .bind(tool_calls_json)
+        .bind(&message.tool_call_id)
+        .bind(&message.name)
         .execute(&self.pool)
         .await?;
 
         Ok(())
     }
 
-    /// Get recent messages for a chat
+    /// Get the tool rounds saved for a chat after `since`, oldest first
+    ///
+    /// These are the messages [`Self::get_recent_messages`] leaves out, so
+    /// they can be replayed between the history messages they followed.
+    pub async fn get_tool_rounds(
+        &self,
+        chat_id: i64,
+        since: chrono::DateTime<chrono::Utc>,
+    ) -> Result<Vec<SavedChatMessage>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT content, timestamp, role, tool_calls, tool_call_id, tool_name
+            FROM messages
+            WHERE chat_id = ?
+              AND (role = 'tool' OR tool_calls IS NOT NULL)
+              AND timestamp > ?
+            ORDER BY timestamp ASC
+            "#,
+        )
+        .bind(chat_id)
+        .bind(since.to_rfc3339())
+        .fetch_all(&self.pool)
+        .await?;
+
+        let rounds = rows
+            .iter()
+            .map(|row| {
+                let role_str: String = row.get("role");
+                let role = role_str.parse().unwrap_or_else(|e| {
+                    warn!("{}, treating as tool", e);
+                    Role::Tool
+                });
+
+                let content_str: String = row.get("content");
+                let content = match serde_json::from_str(&content_str) {
+                    Ok(MessageContent::Text(text)) => text,
+                    _ => content_str,
+                };
+
+                let tool_calls = row
+                    .get::<Option<String>, _>("tool_calls")
+                    .and_then(|json| serde_json::from_str(&json).ok());
+
+                let timestamp_str: String = row.get("timestamp");
+                let timestamp = chrono::DateTime::parse_from_rfc3339(&timestamp_str)
+                    .map(|dt| dt.with_timezone(&chrono::Utc))
+                    .unwrap_or_else(|_| chrono::Utc::now());
+
+                SavedChatMessage {
+                    message: ChatMessage {
+                        role,
+                        content: Some(content).filter(|c| !c.is_empty()),
+                        name: row.get("tool_name"),
+                        tool_calls,
+                        tool_call_id: row.get("tool_call_id"),
+                        parts: Vec::new(),
+                    },
+                    timestamp,
+                }
+            })
+            .collect();
+
+        Ok(rounds)
+    }
+
+    /// Get up to `limit` of a chat's most recent messages, oldest first
+    ///
+    /// The agentic loop's tool rounds and messages covered by the chat's
+    /// summary are left out. The order is the conversation's, so the result
+    /// can be replayed to the model as is.
     pub async fn get_recent_messages(&self, chat_id: i64, limit: i32) -> Result<Vec<Message>> {
+        let covers_until = self
+            .get_chat_summary(chat_id)
+            .await?
+            .map(|s| s.covers_until);
         let rows = sqlx::query(
             r#"
             SELECT 
@@ -118,46 +368,826 @@ impl PersistenceService {
                 u.last_name
             FROM messages m
             JOIN users u ON m.user_id = u.id
-            WHERE m.chat_id = ?
+            WHERE m.chat_id = ?1
+              AND m.role != 'tool'
+              AND m.tool_calls IS NULL
+              AND (?2 IS NULL OR julianday(m.timestamp) >= julianday(?2))
             ORDER BY m.timestamp DESC
-            LIMIT ?
+            LIMIT ?3
             "#,
         )
         .bind(chat_id)
+        .bind(covers_until.map(|t| t.to_rfc3339()))
         .bind(limit)
         .fetch_all(&self.pool)
         .await?;
 
+        // SQLite compares times to the millisecond, so messages sent within
+        // the summary's last millisecond are sorted out here
         let messages = rows
             .iter()
-            .map(|row| {
-                use sqlx::Row;
-                let timestamp_str: String = row.get("timestamp");
-                let timestamp = chrono::DateTime::parse_from_rfc3339(&timestamp_str)
-                    .map(|dt| dt.with_timezone(&chrono::Utc))
-                    .unwrap_or_else(|_| chrono::Utc::now());
+            .rev()
+            .map(message_from_row)
+            .filter(|m| covers_until.is_none_or(|until| m.timestamp > until))
+            .collect();
 
-                // Try to parse content as JSON, fall back to Text
-                let content_str: String = row.get("content");
-                let content: MessageContent =
-                    serde_json::from_str(&content_str).unwrap_or(MessageContent::Text(content_str));
-
-                Message {
-                    id: row.get("message_id"),
-                    chat_id: row.get("chat_id"),
-                    sender: User {
-                        id: row.get::<String, _>("user_id").parse().unwrap_or(0),
-                        telegram_user_id: row.get("telegram_user_id"),
-                        username: row.get("username"),
-                        first_name: row.get("first_name"),
-                        last_name: row.get("last_name"),
+        Ok(messages)
+    }
+
+    /// Delete a chat's history, returning how many messages were removed
+    ///
+    /// The chat's summary goes too, so none of the old conversation is sent
+    /// to the model again. Remembered facts and chat settings are kept.
+    pub async fn delete_messages(&self, chat_id: i64) -> Result<u64> {
+        // Together, so a summary never outlives the messages it covers
+        let mut tx = self.pool.begin().await?;
+        let result = sqlx::query("DELETE FROM messages WHERE chat_id = ?")
+            .bind(chat_id)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query("DELETE FROM chat_summaries WHERE chat_id = ?")
+            .bind(chat_id)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// Get the most recent message `user_id` sent in a chat, skipping text
+    /// messages that are exactly `skip_text` (e.g. a reply recorded for the
+    /// user by pressing a button)
+    pub async fn get_last_user_message(
+        &self,
+        chat_id: i64,
+        user_id: i64,
+        skip_text: &str,
+    ) -> Result<Option<Message>> {
+        let skipped = serde_json::to_string(&MessageContent::Text(skip_text.to_string()))?;
+        let row = sqlx::query(
+            r#"
+            SELECT 
+                m.id as message_id,
+                m.chat_id,
+                m.content,
+                m.timestamp,
+                u.id as user_id,
+                u.telegram_user_id,
+                u.username,
+                u.first_name,
+                u.last_name
+            FROM messages m
+            JOIN users u ON m.user_id = u.id
+            WHERE m.chat_id = ?
+              AND m.user_id = ?
+              AND m.role = 'user'
+              AND m.content != ?
+            ORDER BY m.timestamp DESC
+            LIMIT 1
+            "#,
+        )
+        .bind(chat_id)
+        .bind(user_id.to_string())
+        .bind(skipped)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(row.as_ref().map(message_from_row))
+    }
+
+    /// Get the model selected for a chat, if any
+    pub async fn get_chat_model(&self, chat_id: i64) -> Result<Option<String>> {
+        let model = sqlx::query("SELECT model FROM chat_settings WHERE chat_id = ?")
+            .bind(chat_id)
+            .fetch_optional(&self.pool)
+            .await?
+            .and_then(|row| row.get::<Option<String>, _>("model"));
+
+        Ok(model)
+    }
+
+    /// Set (or with `None`, clear) the model selected for a chat
+    pub async fn set_chat_model(&self, chat_id: i64, model: Option<&str>) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO chat_settings (chat_id, model) VALUES (?, ?)
+            ON CONFLICT(chat_id) DO UPDATE SET model = excluded.model
+            "#,
+        )
+        .bind(chat_id)
+        .bind(model)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Get the provider selected for a chat, if any
+    pub async fn get_chat_provider(&self, chat_id: i64) -> Result<Option<String>> {
+        let provider = sqlx::query("SELECT provider FROM chat_settings WHERE chat_id = ?")
+            .bind(chat_id)
+            .fetch_optional(&self.pool)
+            .await?
+            .and_then(|row| row.get::<Option<String>, _>("provider"));
+
+        Ok(provider)
+    }
+
+    /// Set (or with `None`, clear) the provider selected for a chat
+    ///
+    /// Clears the chat's model too, since it was selected for the previous
+    /// provider.
+    pub async fn set_chat_provider(&self, chat_id: i64, provider: Option<&str>) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO chat_settings (chat_id, provider) VALUES (?, ?)
+            ON CONFLICT(chat_id) DO UPDATE SET provider = excluded.provider, model = NULL
+            "#,
+        )
+        .bind(chat_id)
+        .bind(provider)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Get the summary of a chat's older messages, if any
+    pub async fn get_chat_summary(&self, chat_id: i64) -> Result<Option<ChatSummary>> {
+        let row = sqlx::query("SELECT summary, covers_until FROM chat_summaries WHERE chat_id = ?")
+            .bind(chat_id)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        row.map(|row| {
+            let covers_until: String = row.get("covers_until");
+            Ok(ChatSummary {
+                summary: row.get("summary"),
+                covers_until: chrono::DateTime::parse_from_rfc3339(&covers_until)
+                    .map_err(|e| anyhow!("Invalid summary timestamp: {}", e))?
+                    .with_timezone(&chrono::Utc),
+            })
+        })
+        .transpose()
+    }
+
+    /// Save the summary of a chat's older messages, replacing any earlier one
+    ///
+    /// Messages up to `covers_until` are no longer returned as history.
+    pub async fn save_chat_summary(&self, chat_id: i64, summary: &ChatSummary) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO chat_summaries (chat_id, summary, covers_until) VALUES (?, ?, ?)
+            ON CONFLICT(chat_id) DO UPDATE SET
+                summary = excluded.summary,
+                covers_until = excluded.covers_until
+            "#,
+        )
+        .bind(chat_id)
+        .bind(&summary.summary)
+        .bind(summary.covers_until.to_rfc3339())
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Remember a fact about a chat, replacing any earlier value of `key`
+    pub async fn remember(&self, chat_id: i64, key: &str, value: &str) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO memory (chat_id, key, value, updated_at) VALUES (?, ?, ?, ?)
+            ON CONFLICT(chat_id, key) DO UPDATE SET
+                value = excluded.value,
+                updated_at = excluded.updated_at
+            "#,
+        )
+        .bind(chat_id)
+        .bind(key)
+        .bind(value)
+        .bind(chrono::Utc::now().to_rfc3339())
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Get the facts remembered about a chat as `(key, value)` pairs, by key
+    pub async fn get_memory(&self, chat_id: i64) -> Result<Vec<(String, String)>> {
+        let rows = sqlx::query("SELECT key, value FROM memory WHERE chat_id = ? ORDER BY key")
+            .bind(chat_id)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(rows
+            .iter()
+            .map(|row| (row.get("key"), row.get("value")))
+            .collect())
+    }
+
+    /// Forget one fact about a chat, or all of them with `None`, returning
+    /// how many were removed
+    pub async fn forget(&self, chat_id: i64, key: Option<&str>) -> Result<u64> {
+        let result =
+            sqlx::query("DELETE FROM memory WHERE chat_id = ?1 AND (?2 IS NULL OR key = ?2)")
+                .bind(chat_id)
+                .bind(key)
+                .execute(&self.pool)
+                .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// Record the token usage and cost of a completed request
+    pub async fn record_usage(
+        &self,
+        chat_id: i64,
+        model: &str,
+        usage: &TokenUsage,
+        cost: f64,
+    ) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO usage (chat_id, model, prompt_tokens, completion_tokens, cost, timestamp)
+            VALUES (?, ?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(chat_id)
+        .bind(model)
+        .bind(usage.prompt_tokens as i64)
+        .bind(usage.completion_tokens as i64)
+        .bind(cost)
+        .bind(chrono::Utc::now().to_rfc3339())
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Sum the usage of one chat (or all chats with `None`), optionally only
+    /// counting requests made at or after `since`
+    pub async fn get_usage_totals(
+        &self,
+        chat_id: Option<i64>,
+        since: Option<chrono::DateTime<chrono::Utc>>,
+    ) -> Result<UsageTotals> {
+        let since = since.map(|t| t.to_rfc3339());
+        let row = sqlx::query(
+            r#"
+            SELECT
+                COUNT(*) AS requests,
+                COALESCE(SUM(prompt_tokens), 0) AS prompt_tokens,
+                COALESCE(SUM(completion_tokens), 0) AS completion_tokens,
+                COALESCE(SUM(cost), 0.0) AS cost
+            FROM usage
+            WHERE (?1 IS NULL OR chat_id = ?1) AND (?2 IS NULL OR timestamp >= ?2)
+            "#,
+        )
+        .bind(chat_id)
+        .bind(since)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(UsageTotals {
+            requests: row.get("requests"),
+            prompt_tokens: row.get("prompt_tokens"),
+            completion_tokens: row.get("completion_tokens"),
+            cost: row.get("cost"),
+        })
+    }
+
+    /// Record an activation of a skill and whether it succeeded
+    pub async fn record_skill_use(&self, name: &str, success: bool) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO skill_usage (name, uses, successes, last_used) VALUES (?1, 1, ?2, ?3)
+            ON CONFLICT(name) DO UPDATE SET
+                uses = uses + 1,
+                successes = successes + excluded.successes,
+                last_used = excluded.last_used
+            "#,
+        )
+        .bind(name)
+        .bind(i64::from(success))
+        .bind(chrono::Utc::now().to_rfc3339())
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Get the recorded usage of every skill that was activated, by name
+    pub async fn get_skill_usage(&self) -> Result<HashMap<String, SkillUsage>> {
+        let rows = sqlx::query("SELECT name, uses, successes FROM skill_usage")
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(rows
+            .iter()
+            .map(|row| {
+                (
+                    row.get("name"),
+                    SkillUsage {
+                        uses: row.get("uses"),
+                        successes: row.get("successes"),
                     },
-                    content,
-                    timestamp,
+                )
+            })
+            .collect())
+    }
+}
+
+/// Run a write, retrying with exponential backoff while SQLite reports the
+/// database as busy or locked (concurrent writers)
+async fn retry_locked<T, F, Fut>(operation: &str, mut write: F) -> Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, sqlx::Error>>,
+{
+    let mut delay = WRITE_RETRY_DELAY;
+    let mut attempt = 1;
+    loop {
+        match write().await {
+            Err(e) if is_locked(&e) => {
+                if attempt == WRITE_ATTEMPTS {
+                    return Err(anyhow!(
+                        "Failed to {}: the database stayed locked after {} attempts ({}). \
+                         Is another process writing to it?",
+                        operation,
+                        WRITE_ATTEMPTS,
+                        e
+                    ));
                 }
+                warn!(
+                    "Database is locked, retrying {} in {:?} (attempt {}/{})",
+                    operation, delay, attempt, WRITE_ATTEMPTS
+                );
+                tokio::time::sleep(delay).await;
+                delay *= 2;
+                attempt += 1;
+            }
+            result => return Ok(result?),
+        }
+    }
+}
+
+/// Whether a query failed with `SQLITE_BUSY` or `SQLITE_LOCKED`
+fn is_locked(error: &sqlx::Error) -> bool {
+    const SQLITE_BUSY: i32 = 5;
+    const SQLITE_LOCKED: i32 = 6;
+
+    error
+        .as_database_error()
+        .and_then(|e| e.code())
+        .and_then(|code| code.parse::<i32>().ok())
+        // Extended result codes keep the primary code in the low byte
+        .is_some_and(|code| matches!(code & 0xff, SQLITE_BUSY | SQLITE_LOCKED))
+}
+
+/// Build a message from a row with the columns selected by
+/// [`PersistenceService::get_recent_messages`]
+fn message_from_row(row: &SqliteRow) -> Message {
+    let timestamp_str: String = row.get("timestamp");
+    let timestamp = chrono::DateTime::parse_from_rfc3339(&timestamp_str)
+        .map(|dt| dt.with_timezone(&chrono::Utc))
+        .unwrap_or_else(|_| chrono::Utc::now());
+
+    // Try to parse content as JSON, fall back to Text
+    let content_str: String = row.get("content");
+    let content: MessageContent =
+        serde_json::from_str(&content_str).unwrap_or(MessageContent::Text(content_str));
+
+    Message {
+        id: row.get("message_id"),
+        chat_id: row.get("chat_id"),
+        sender: User {
+            id: row.get::<String, _>("user_id").parse().unwrap_or(0),
+            telegram_user_id: row.get("telegram_user_id"),
+            username: row.get("username"),
+            first_name: row.get("first_name"),
+            last_name: row.get("last_name"),
+        },
+        content,
+        timestamp,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_concurrent_save_message() {
+        let path = std::env::temp_dir().join(format!("rustclaw-{}.db", uuid::Uuid::new_v4()));
+        let persistence = Arc::new(
+            PersistenceService::new(path.to_str().unwrap())
+                .await
+                .unwrap(),
+        );
+
+        let tasks: Vec<_> = (0..32)
+            .map(|task| {
+                let persistence = persistence.clone();
+                tokio::spawn(async move {
+                    for i in 0..20 {
+                        let message = Message::new(
+                            1,
+                            User::new(task),
+                            MessageContent::Text(format!("message {} from {}", i, task)),
+                        );
+                        persistence.save_message(&message).await.unwrap();
+                    }
+                })
             })
             .collect();
+        for task in tasks {
+            task.await.unwrap();
+        }
 
-        Ok(messages)
+        let saved = persistence.get_recent_messages(1, 1000).await.unwrap();
+        assert_eq!(saved.len(), 32 * 20);
+        persistence.ping().await.unwrap();
+
+        drop(persistence);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[tokio::test]
+    async fn test_chat_provider() {
+        let path = std::env::temp_dir().join(format!("rustclaw-{}.db", uuid::Uuid::new_v4()));
+        let persistence = PersistenceService::new(path.to_str().unwrap())
+            .await
+            .unwrap();
+
+        assert_eq!(persistence.get_chat_provider(1).await.unwrap(), None);
+        persistence.set_chat_model(1, Some("gpt-4o")).await.unwrap();
+        persistence
+            .set_chat_provider(1, Some("ollama"))
+            .await
+            .unwrap();
+        assert_eq!(
+            persistence.get_chat_provider(1).await.unwrap().as_deref(),
+            Some("ollama")
+        );
+        // The model chosen for the previous provider is cleared
+        assert_eq!(persistence.get_chat_model(1).await.unwrap(), None);
+
+        persistence.set_chat_provider(1, None).await.unwrap();
+        assert_eq!(persistence.get_chat_provider(1).await.unwrap(), None);
+
+        drop(persistence);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[tokio::test]
+    async fn test_chat_summary() {
+        let path = std::env::temp_dir().join(format!("rustclaw-{}.db", uuid::Uuid::new_v4()));
+        let persistence = PersistenceService::new(path.to_str().unwrap())
+            .await
+            .unwrap();
+        assert!(persistence.get_chat_summary(1).await.unwrap().is_none());
+
+        // Timestamps with different sub-second precision around the cut
+        let cut = chrono::DateTime::parse_from_rfc3339("2026-01-01T12:00:05.5+00:00")
+            .unwrap()
+            .with_timezone(&chrono::Utc);
+        let offsets = [
+            -chrono::Duration::milliseconds(500),
+            -chrono::Duration::microseconds(1),
+            chrono::Duration::zero(),
+            chrono::Duration::nanoseconds(1),
+            chrono::Duration::microseconds(123_456),
+            chrono::Duration::milliseconds(500),
+        ];
+        for (i, offset) in offsets.into_iter().enumerate() {
+            let mut message = Message::new(
+                1,
+                User::new(7),
+                MessageContent::Text(format!("message {}", i)),
+            );
+            message.timestamp = cut + offset;
+            persistence.save_message(&message).await.unwrap();
+        }
+
+        let summary = ChatSummary {
+            summary: "The first messages".to_string(),
+            covers_until: cut,
+        };
+        persistence.save_chat_summary(1, &summary).await.unwrap();
+        let saved = persistence.get_chat_summary(1).await.unwrap().unwrap();
+        assert_eq!(saved.summary, summary.summary);
+        assert_eq!(saved.covers_until, cut);
+
+        // Messages up to and including the cut are covered by the summary
+        let texts: Vec<String> = persistence
+            .get_recent_messages(1, 10)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|m| match m.content {
+                MessageContent::Text(text) => text,
+                other => panic!("unexpected content {:?}", other),
+            })
+            .collect();
+        assert_eq!(texts, ["message 3", "message 4", "message 5"]);
+
+        // A newer summary replaces the old one
+        let newer = ChatSummary {
+            summary: "All messages".to_string(),
+            covers_until: cut + chrono::Duration::seconds(1),
+        };
+        persistence.save_chat_summary(1, &newer).await.unwrap();
+        let saved = persistence.get_chat_summary(1).await.unwrap().unwrap();
+        assert_eq!(saved.summary, "All messages");
+        assert!(persistence
+            .get_recent_messages(1, 10)
+            .await
+            .unwrap()
+            .is_empty());
+        assert!(persistence.get_chat_summary(2).await.unwrap().is_none());
+
+        drop(persistence);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[tokio::test]
+    async fn test_last_user_message() {
+        let path = std::env::temp_dir().join(format!("rustclaw-{}.db", uuid::Uuid::new_v4()));
+        let persistence = PersistenceService::new(path.to_str().unwrap())
+            .await
+            .unwrap();
+        assert!(persistence
+            .get_last_user_message(1, 7, "Yes")
+            .await
+            .unwrap()
+            .is_none());
+
+        let start = chrono::Utc::now() - chrono::Duration::minutes(10);
+        let sent = [
+            (7, "first from 7"),
+            (7, "second from 7"),
+            (8, "from 8"),
+            (ASSISTANT_USER_ID, "answer"),
+            (7, "Yes"),
+        ];
+        for (i, (user_id, text)) in sent.into_iter().enumerate() {
+            let mut message =
+                Message::new(1, User::new(user_id), MessageContent::Text(text.into()));
+            message.timestamp = start + chrono::Duration::seconds(i as i64);
+            persistence.save_message(&message).await.unwrap();
+        }
+
+        let text = |message: Option<Message>| match message.map(|m| m.content) {
+            Some(MessageContent::Text(text)) => text,
+            other => panic!("unexpected content {:?}", other),
+        };
+        // Each user gets their own message, without the skipped reply
+        let last = persistence
+            .get_last_user_message(1, 7, "Yes")
+            .await
+            .unwrap();
+        assert_eq!(text(last), "second from 7");
+        let last = persistence
+            .get_last_user_message(1, 8, "Yes")
+            .await
+            .unwrap();
+        assert_eq!(text(last), "from 8");
+        let last = persistence.get_last_user_message(1, 7, "").await.unwrap();
+        assert_eq!(text(last), "Yes");
+        assert!(persistence
+            .get_last_user_message(2, 7, "Yes")
+            .await
+            .unwrap()
+            .is_none());
+
+        drop(persistence);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[tokio::test]
+    async fn test_recent_messages_oldest_first() {
+        let path = std::env::temp_dir().join(format!("rustclaw-{}.db", uuid::Uuid::new_v4()));
+        let persistence = PersistenceService::new(path.to_str().unwrap())
+            .await
+            .unwrap();
+
+        let start = chrono::Utc::now() - chrono::Duration::minutes(10);
+        for i in 0..5 {
+            let mut message = Message::new(
+                1,
+                User::new(7),
+                MessageContent::Text(format!("message {}", i)),
+            );
+            message.timestamp = start + chrono::Duration::seconds(i);
+            persistence.save_message(&message).await.unwrap();
+        }
+
+        // The newest three, in the order they were sent
+        let recent = persistence.get_recent_messages(1, 3).await.unwrap();
+        let texts: Vec<_> = recent
+            .iter()
+            .map(|m| match &m.content {
+                MessageContent::Text(text) => text.as_str(),
+                other => panic!("unexpected content {:?}", other),
+            })
+            .collect();
+        assert_eq!(texts, ["message 2", "message 3", "message 4"]);
+        assert!(recent.windows(2).all(|w| w[0].timestamp < w[1].timestamp));
+
+        drop(persistence);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[tokio::test]
+    async fn test_tool_round_round_trip() {
+        let path = std::env::temp_dir().join(format!("rustclaw-{}.db", uuid::Uuid::new_v4()));
+        let persistence = PersistenceService::new(path.to_str().unwrap())
+            .await
+            .unwrap();
+
+        let question = Message::new(
+            1,
+            User::new(7),
+            MessageContent::Text("What time is it?".into()),
+        );
+        persistence.save_message(&question).await.unwrap();
+        let call = rustclaw_types::ToolCall {
+            id: "call_1".to_string(),
+            call_type: "function".to_string(),
+            function: rustclaw_types::FunctionCall {
+                name: "time".to_string(),
+                arguments: "{}".to_string(),
+            },
+        };
+        persistence
+            .save_chat_message(1, &ChatMessage::assistant_with_tools(None, vec![call]))
+            .await
+            .unwrap();
+        let mut result = ChatMessage::tool_result("call_1", "12:00");
+        result.name = Some("time".to_string());
+        persistence.save_chat_message(1, &result).await.unwrap();
+
+        let rounds = persistence
+            .get_tool_rounds(1, question.timestamp)
+            .await
+            .unwrap();
+        assert_eq!(rounds.len(), 2);
+        let (assistant, tool) = (&rounds[0].message, &rounds[1].message);
+        assert_eq!(assistant.role, Role::Assistant);
+        assert_eq!(assistant.content, None);
+        let calls = assistant.tool_calls.as_ref().unwrap();
+        assert_eq!(calls[0].id, "call_1");
+        assert_eq!(calls[0].function.name, "time");
+        assert_eq!(tool.role, Role::Tool);
+        assert_eq!(tool.content.as_deref(), Some("12:00"));
+        assert_eq!(tool.tool_call_id.as_deref(), Some("call_1"));
+        assert_eq!(tool.name.as_deref(), Some("time"));
+        assert!(rounds[0].timestamp > question.timestamp);
+
+        // The round is kept out of the plain history, and rounds saved
+        // before `since` are left out
+        assert_eq!(
+            persistence.get_recent_messages(1, 10).await.unwrap().len(),
+            1
+        );
+        assert!(persistence
+            .get_tool_rounds(1, rounds[1].timestamp)
+            .await
+            .unwrap()
+            .is_empty());
+
+        drop(persistence);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[tokio::test]
+    async fn test_delete_messages() {
+        let path = std::env::temp_dir().join(format!("rustclaw-{}.db", uuid::Uuid::new_v4()));
+        let persistence = PersistenceService::new(path.to_str().unwrap())
+            .await
+            .unwrap();
+
+        for (chat_id, text) in [(1, "one"), (1, "two"), (1, "three"), (2, "other chat")] {
+            let message = Message::new(chat_id, User::new(7), MessageContent::Text(text.into()));
+            persistence.save_message(&message).await.unwrap();
+        }
+        let summary = ChatSummary {
+            summary: "Talked about numbers".to_string(),
+            covers_until: chrono::Utc::now(),
+        };
+        persistence.save_chat_summary(1, &summary).await.unwrap();
+
+        assert_eq!(persistence.delete_messages(1).await.unwrap(), 3);
+        assert!(persistence
+            .get_recent_messages(1, 10)
+            .await
+            .unwrap()
+            .is_empty());
+        assert!(persistence.get_chat_summary(1).await.unwrap().is_none());
+        assert_eq!(
+            persistence.get_recent_messages(2, 10).await.unwrap().len(),
+            1
+        );
+        assert_eq!(persistence.delete_messages(1).await.unwrap(), 0);
+
+        drop(persistence);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[tokio::test]
+    async fn test_usage_totals() {
+        let path = std::env::temp_dir().join(format!("rustclaw-{}.db", uuid::Uuid::new_v4()));
+        let persistence = PersistenceService::new(path.to_str().unwrap())
+            .await
+            .unwrap();
+
+        let empty = persistence.get_usage_totals(None, None).await.unwrap();
+        assert_eq!(empty.requests, 0);
+        assert_eq!(empty.cost, 0.0);
+
+        let before = chrono::Utc::now();
+        persistence
+            .record_usage(1, "gpt-4o", &TokenUsage::new(100, 20), 0.5)
+            .await
+            .unwrap();
+        persistence
+            .record_usage(1, "gpt-4o", &TokenUsage::new(50, 10), 0.25)
+            .await
+            .unwrap();
+        persistence
+            .record_usage(2, "gpt-4o-mini", &TokenUsage::new(7, 3), 0.01)
+            .await
+            .unwrap();
+
+        let chat = persistence.get_usage_totals(Some(1), None).await.unwrap();
+        assert_eq!(chat.requests, 2);
+        assert_eq!(chat.prompt_tokens, 150);
+        assert_eq!(chat.completion_tokens, 30);
+        assert!((chat.cost - 0.75).abs() < 1e-9);
+
+        let all = persistence
+            .get_usage_totals(None, Some(before))
+            .await
+            .unwrap();
+        assert_eq!(all.requests, 3);
+        assert_eq!(all.prompt_tokens, 157);
+        assert!((all.cost - 0.76).abs() < 1e-9);
+
+        // Requests before `since` are left out
+        let later = chrono::Utc::now() + chrono::Duration::seconds(1);
+        assert_eq!(
+            persistence
+                .get_usage_totals(None, Some(later))
+                .await
+                .unwrap()
+                .requests,
+            0
+        );
+
+        drop(persistence);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[tokio::test]
+    async fn test_skill_usage() {
+        let path = std::env::temp_dir().join(format!("rustclaw-{}.db", uuid::Uuid::new_v4()));
+        let persistence = PersistenceService::new(path.to_str().unwrap())
+            .await
+            .unwrap();
+        assert!(persistence.get_skill_usage().await.unwrap().is_empty());
+
+        persistence.record_skill_use("weather", true).await.unwrap();
+        persistence
+            .record_skill_use("weather", false)
+            .await
+            .unwrap();
+        persistence.record_skill_use("weather", true).await.unwrap();
+        persistence.record_skill_use("notes", false).await.unwrap();
+        drop(persistence);
+
+        // The counts survive a restart
+        let persistence = PersistenceService::new(path.to_str().unwrap())
+            .await
+            .unwrap();
+        let usage = persistence.get_skill_usage().await.unwrap();
+        assert_eq!(usage.len(), 2);
+        assert_eq!(
+            usage["weather"],
+            SkillUsage {
+                uses: 3,
+                successes: 2
+            }
+        );
+        assert_eq!(
+            usage["notes"],
+            SkillUsage {
+                uses: 1,
+                successes: 0
+            }
+        );
+
+        drop(persistence);
+        let _ = std::fs::remove_file(&path);
     }
 }
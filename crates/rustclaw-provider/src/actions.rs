@@ -0,0 +1,261 @@
+//! Channel Actions
+//!
+//! A tool result can carry parts for the channel to deliver to the user
+//! rather than for the model to read, either as the whole result or as
+//! elements of an array:
+//!
+//! - MCP images: `{"type": "image", "data": <base64>, "mimeType": ...}`
+//! - Channel actions, objects naming the action under `_channel_action`:
+//!   - `send_photo`: `data` (base64) and optionally `mime_type`
+//!     (`image/*`, default `image/png`); at most 10 MB
+//!   - `send_document`: `data` (base64) and `file_name`; at most 50 MB
+//!   - `send_location`: `latitude` and `longitude` in degrees
+//!
+//! Each part is taken out of the result and replaced by a short note, so the
+//! model learns what the user got without encoded data filling its context.
+//! An invalid action is replaced by an error telling the model what is
+//! wrong with it, and nothing is sent.
+
+use rustclaw_types::{ResponseDocument, ResponseImage, ResponsePart};
+use serde_json::Value;
+use tracing::warn;
+
+/// Key naming the action of a channel action object
+pub const CHANNEL_ACTION_KEY: &str = "_channel_action";
+
+/// Actions a tool result can ask the channel for
+pub const CHANNEL_ACTIONS: &[&str] = &["send_photo", "send_document", "send_location"];
+
+/// Largest photo Telegram accepts from bots
+const MAX_PHOTO_BYTES: usize = 10 * 1024 * 1024;
+
+/// Largest file Telegram accepts from bots
+const MAX_DOCUMENT_BYTES: usize = 50 * 1024 * 1024;
+
+/// Take the parts meant for the user out of a tool result
+pub(crate) fn extract_attachments(result: Value) -> (Value, Vec<ResponsePart>) {
+    let mut attachments = Vec::new();
+    let mut take = |value: Value| {
+        let part = if value.get(CHANNEL_ACTION_KEY).is_some() {
+            match parse_action(&value) {
+                Ok(part) => part,
+                Err(e) => {
+                    warn!("Rejected channel action from tool: {}", e);
+                    return serde_json::json!({ "error": format!("Invalid channel action: {}", e) });
+                }
+            }
+        } else {
+            match decode_image(&value) {
+                Some(image) => ResponsePart::Image(image),
+                None => return value,
+            }
+        };
+        let note = note(&part);
+        attachments.push(part);
+        Value::String(note)
+    };
+    let result = match result {
+        Value::Array(values) => values.into_iter().map(&mut take).collect(),
+        value => take(value),
+    };
+    (result, attachments)
+}
+
+/// Note telling the model what the user was sent
+fn note(part: &ResponsePart) -> String {
+    match part {
+        ResponsePart::Text(_) => "[Message sent to the user]".to_string(),
+        ResponsePart::Image(image) => format!("[Image ({}) sent to the user]", image.mime_type),
+        ResponsePart::Document(document) => {
+            format!("[File {} sent to the user]", document.file_name)
+        }
+        ResponsePart::Location {
+            latitude,
+            longitude,
+        } => format!("[Location {}, {} sent to the user]", latitude, longitude),
+    }
+}
+
+/// Validate a channel action object
+fn parse_action(value: &Value) -> Result<ResponsePart, String> {
+    let action = value[CHANNEL_ACTION_KEY]
+        .as_str()
+        .ok_or_else(|| format!("`{}` must be a string", CHANNEL_ACTION_KEY))?;
+    match action {
+        "send_photo" => {
+            let mime_type = match value.get("mime_type") {
+                None => "image/png",
+                Some(mime) => mime
+                    .as_str()
+                    .filter(|mime| mime.starts_with("image/"))
+                    .ok_or("`mime_type` must be an image type like `image/png`")?,
+            };
+            Ok(ResponsePart::Image(ResponseImage {
+                data: decode_data(value, MAX_PHOTO_BYTES)?,
+                mime_type: mime_type.to_string(),
+            }))
+        }
+        "send_document" => {
+            let file_name = value
+                .get("file_name")
+                .and_then(Value::as_str)
+                .map(str::trim)
+                .filter(|name| !name.is_empty() && !name.contains(['/', '\\']))
+                .ok_or("`file_name` must be a file name without a path")?;
+            Ok(ResponsePart::Document(ResponseDocument {
+                data: decode_data(value, MAX_DOCUMENT_BYTES)?,
+                file_name: file_name.to_string(),
+            }))
+        }
+        "send_location" => Ok(ResponsePart::Location {
+            latitude: coordinate(value, "latitude", 90.0)?,
+            longitude: coordinate(value, "longitude", 180.0)?,
+        }),
+        other => Err(format!(
+            "unknown action `{}` (supported: {})",
+            other,
+            CHANNEL_ACTIONS.join(", ")
+        )),
+    }
+}
+
+/// Decode the base64 `data` of an action, at most `max_bytes` long
+fn decode_data(value: &Value, max_bytes: usize) -> Result<Vec<u8>, String> {
+    use base64::Engine;
+
+    let data = value
+        .get("data")
+        .and_then(Value::as_str)
+        .ok_or("`data` must be a base64 string")?;
+    let data = base64::engine::general_purpose::STANDARD
+        .decode(data)
+        .map_err(|e| format!("`data` is not valid base64: {}", e))?;
+    if data.is_empty() || data.len() > max_bytes {
+        return Err(format!(
+            "`data` must hold 1 to {} bytes, got {}",
+            max_bytes,
+            data.len()
+        ));
+    }
+    Ok(data)
+}
+
+/// A coordinate in degrees between `-limit` and `limit`
+fn coordinate(value: &Value, key: &str, limit: f64) -> Result<f64, String> {
+    value
+        .get(key)
+        .and_then(Value::as_f64)
+        .filter(|degrees| degrees.abs() <= limit)
+        .ok_or_else(|| format!("`{}` must be a number from -{} to {}", key, limit, limit))
+}
+
+/// Decode an MCP-style image object
+fn decode_image(value: &Value) -> Option<ResponseImage> {
+    use base64::Engine;
+
+    if value.get("type")?.as_str()? != "image" {
+        return None;
+    }
+    let data = value.get("data")?.as_str()?;
+    let mime_type = value.get("mimeType")?.as_str()?;
+    match base64::engine::general_purpose::STANDARD.decode(data) {
+        Ok(data) => Some(ResponseImage {
+            data,
+            mime_type: mime_type.to_string(),
+        }),
+        Err(e) => {
+            warn!("Ignoring tool image with invalid base64 data: {}", e);
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_extract_images() {
+        let image = json!({ "type": "image", "data": "aGk=", "mimeType": "image/png" });
+        let (result, attachments) = extract_attachments(json!([{ "n": 1 }, image.clone()]));
+        assert_eq!(
+            result,
+            json!([{ "n": 1 }, "[Image (image/png) sent to the user]"])
+        );
+        assert_eq!(
+            attachments,
+            vec![ResponsePart::Image(ResponseImage {
+                data: b"hi".to_vec(),
+                mime_type: "image/png".to_string(),
+            })]
+        );
+
+        // Invalid data is left for the model to see
+        let broken = json!({ "type": "image", "data": "!!", "mimeType": "image/png" });
+        let (result, attachments) = extract_attachments(broken.clone());
+        assert_eq!(result, broken);
+        assert!(attachments.is_empty());
+    }
+
+    #[test]
+    fn test_extract_channel_actions() {
+        let (result, attachments) = extract_attachments(json!([
+            { "_channel_action": "send_photo", "data": "aGk=" },
+            { "_channel_action": "send_document", "data": "aGk=", "file_name": "report.csv" },
+            { "_channel_action": "send_location", "latitude": 52.52, "longitude": 13.405 },
+        ]));
+        assert_eq!(
+            result,
+            json!([
+                "[Image (image/png) sent to the user]",
+                "[File report.csv sent to the user]",
+                "[Location 52.52, 13.405 sent to the user]",
+            ])
+        );
+        assert_eq!(
+            attachments,
+            vec![
+                ResponsePart::Image(ResponseImage {
+                    data: b"hi".to_vec(),
+                    mime_type: "image/png".to_string(),
+                }),
+                ResponsePart::Document(ResponseDocument {
+                    data: b"hi".to_vec(),
+                    file_name: "report.csv".to_string(),
+                }),
+                ResponsePart::Location {
+                    latitude: 52.52,
+                    longitude: 13.405
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_invalid_channel_actions() {
+        let invalid = [
+            json!({ "_channel_action": "send_sticker", "data": "aGk=" }),
+            json!({ "_channel_action": 1 }),
+            json!({ "_channel_action": "send_photo", "data": "!!" }),
+            json!({ "_channel_action": "send_photo", "data": "" }),
+            json!({ "_channel_action": "send_photo", "data": "aGk=", "mime_type": "text/plain" }),
+            json!({ "_channel_action": "send_document", "data": "aGk=" }),
+            json!({ "_channel_action": "send_document", "data": "aGk=", "file_name": "../x" }),
+            json!({ "_channel_action": "send_location", "latitude": 91, "longitude": 0 }),
+            json!({ "_channel_action": "send_location", "latitude": "1", "longitude": 0 }),
+        ];
+        for action in invalid {
+            let (result, attachments) = extract_attachments(action.clone());
+            assert!(attachments.is_empty(), "{} was accepted", action);
+            let error = result["error"].as_str().unwrap();
+            assert!(error.starts_with("Invalid channel action: "), "{}", error);
+        }
+
+        let (result, _) = extract_attachments(json!({ "_channel_action": "send_sticker" }));
+        assert!(result["error"]
+            .as_str()
+            .unwrap()
+            .contains("supported: send_photo, send_document, send_location"));
+    }
+}
@@ -0,0 +1,175 @@
+//! Tool Argument Canonicalization
+//!
+//! Models sometimes write arguments that differ only in form from what the
+//! tool's JSON schema asks for: `"5"` for an integer, `" true "` for a
+//! boolean, keys in a different order. Arguments are brought into one
+//! canonical form before a tool runs, so such calls validate and hit the
+//! result cache like their well-formed equivalents.
+//!
+//! Only conversions that cannot change what the model meant are made: a
+//! string is coerced (and trimmed) only where the schema rules out strings
+//! and the whole trimmed value parses as the declared type. Strings the
+//! schema allows are left exactly as written.
+
+use serde_json::{Map, Number, Value};
+
+/// Canonicalize `args` against the tool's parameter `schema`
+///
+/// Object keys are sorted at every level; strings are coerced to the
+/// integer, number or boolean the schema declares where that is unambiguous.
+pub fn canonicalize(args: Value, schema: &Value) -> Value {
+    match args {
+        Value::Object(map) => {
+            let properties = schema.get("properties");
+            let mut entries: Vec<(String, Value)> = map.into_iter().collect();
+            entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+            Value::Object(
+                entries
+                    .into_iter()
+                    .map(|(key, value)| {
+                        let value = match properties.and_then(|p| p.get(&key)) {
+                            Some(schema) => canonicalize(value, schema),
+                            None => canonicalize(value, &Value::Null),
+                        };
+                        (key, value)
+                    })
+                    .collect::<Map<_, _>>(),
+            )
+        }
+        Value::Array(items) => {
+            let schema = schema.get("items").unwrap_or(&Value::Null);
+            Value::Array(
+                items
+                    .into_iter()
+                    .map(|item| canonicalize(item, schema))
+                    .collect(),
+            )
+        }
+        Value::String(text) => coerce(&text, schema).unwrap_or(Value::String(text)),
+        other => other,
+    }
+}
+
+/// The scalar `text` stands for, if the schema rules out strings and
+/// declares exactly one type that the trimmed text parses as
+fn coerce(text: &str, schema: &Value) -> Option<Value> {
+    let types = declared_types(schema);
+    if types.is_empty() || types.contains(&"string") {
+        return None;
+    }
+
+    let text = text.trim();
+    let mut candidates = types.iter().filter_map(|kind| match *kind {
+        "integer" => parse_integer(text),
+        "number" => parse_integer(text).or_else(|| {
+            text.parse::<f64>()
+                .ok()
+                .and_then(Number::from_f64)
+                .map(Value::Number)
+        }),
+        "boolean" => match text {
+            "true" => Some(Value::Bool(true)),
+            "false" => Some(Value::Bool(false)),
+            _ => None,
+        },
+        _ => None,
+    });
+
+    let value = candidates.next()?;
+    // Ambiguous if another declared type accepts the text too
+    candidates.next().is_none().then_some(value)
+}
+
+/// Parse a whole integer, positive or negative
+fn parse_integer(text: &str) -> Option<Value> {
+    text.parse::<i64>()
+        .map(Number::from)
+        .or_else(|_| text.parse::<u64>().map(Number::from))
+        .ok()
+        .map(Value::Number)
+}
+
+/// Types a schema declares with `type` (a name or a list of names)
+fn declared_types(schema: &Value) -> Vec<&str> {
+    match schema.get("type") {
+        Some(Value::String(kind)) => vec![kind.as_str()],
+        Some(Value::Array(kinds)) => kinds.iter().filter_map(Value::as_str).collect(),
+        _ => Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "path": { "type": "string" },
+                "limit": { "type": "integer" },
+                "ratio": { "type": "number" },
+                "recursive": { "type": "boolean" },
+                "offset": { "type": ["integer", "null"] },
+                "id": { "type": ["integer", "string"] },
+                "ids": { "type": "array", "items": { "type": "integer" } },
+                "filter": {
+                    "type": "object",
+                    "properties": { "depth": { "type": "integer" } }
+                }
+            }
+        })
+    }
+
+    #[test]
+    fn test_coerces_per_schema() {
+        let args = json!({
+            "recursive": " true ",
+            "limit": "10",
+            "ratio": "0.5",
+            "offset": "-3",
+            "ids": ["1", 2, " 3"],
+            "filter": { "depth": "2" },
+            "path": "  ./src  "
+        });
+        let canonical = canonicalize(args, &schema());
+        assert_eq!(
+            canonical,
+            json!({
+                "filter": { "depth": 2 },
+                "ids": [1, 2, 3],
+                "limit": 10,
+                "offset": -3,
+                "path": "  ./src  ",
+                "ratio": 0.5,
+                "recursive": true
+            })
+        );
+
+        // Keys are sorted at every level
+        assert_eq!(
+            canonical.to_string(),
+            r#"{"filter":{"depth":2},"ids":[1,2,3],"limit":10,"offset":-3,"path":"  ./src  ","ratio":0.5,"recursive":true}"#
+        );
+    }
+
+    #[test]
+    fn test_leaves_ambiguous_values() {
+        let args = json!({
+            "limit": "ten",
+            "ratio": "NaN",
+            "recursive": "yes",
+            "id": "42",
+            "unknown": "7",
+            "path": 3
+        });
+        assert_eq!(canonicalize(args.clone(), &schema()), args);
+
+        // Without a schema only the key order changes
+        assert_eq!(
+            canonicalize(json!({"b": "1", "a": " x "}), &Value::Null).to_string(),
+            r#"{"a":" x ","b":"1"}"#
+        );
+    }
+}
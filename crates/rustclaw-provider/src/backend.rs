@@ -0,0 +1,259 @@
+//! Chat Completion Backends
+//!
+//! [`ChatBackend`] is the seam between the agentic loop and the API that
+//! answers its completion requests. [`ProviderService`](crate::ProviderService)
+//! talks to the configured provider unless a backend is set with
+//! [`with_backend`](crate::ProviderService::with_backend), which lets tests
+//! drive the loop with [`MockBackend`] and its scripted responses.
+
+use anyhow::Result;
+use async_openai::config::Config;
+use async_openai::types::chat::{
+    CreateChatCompletionRequest, CreateChatCompletionResponse, CreateChatCompletionStreamResponse,
+};
+use async_openai::Client;
+use futures::future::BoxFuture;
+use futures::stream::{self, BoxStream, StreamExt};
+use serde_json::Value;
+
+#[cfg(any(test, feature = "testing"))]
+pub use mock::MockBackend;
+
+/// Chunks of a streamed chat completion, in the order they arrive
+pub type ChunkStream = BoxStream<'static, Result<CreateChatCompletionStreamResponse>>;
+
+/// Source of chat completions for [`ProviderService`](crate::ProviderService)
+pub trait ChatBackend: Send + Sync {
+    /// Answer one chat completion request
+    fn create(
+        &self,
+        request: CreateChatCompletionRequest,
+    ) -> BoxFuture<'_, Result<CreateChatCompletionResponse>>;
+
+    /// Answer one chat completion request as a stream of chunks
+    ///
+    /// Defaults to waiting for [`create`](Self::create) and sending the
+    /// whole response as a single chunk, for backends that can't stream.
+    fn create_stream(
+        &self,
+        request: CreateChatCompletionRequest,
+    ) -> BoxFuture<'_, Result<ChunkStream>> {
+        Box::pin(async move {
+            let chunk = into_chunk(self.create(request).await?)?;
+            Ok(stream::iter([Ok(chunk)]).boxed())
+        })
+    }
+}
+
+/// Backend sending requests to the provider's OpenAI-compatible API
+pub(crate) struct ApiBackend {
+    pub(crate) client: Client<Box<dyn Config>>,
+}
+
+impl ChatBackend for ApiBackend {
+    fn create(
+        &self,
+        request: CreateChatCompletionRequest,
+    ) -> BoxFuture<'_, Result<CreateChatCompletionResponse>> {
+        Box::pin(async move { Ok(self.client.chat().create(request).await?) })
+    }
+
+    fn create_stream(
+        &self,
+        request: CreateChatCompletionRequest,
+    ) -> BoxFuture<'_, Result<ChunkStream>> {
+        Box::pin(async move {
+            let chunks = self.client.chat().create_stream(request).await?;
+            Ok(chunks
+                .map(|chunk| chunk.map_err(anyhow::Error::from))
+                .boxed())
+        })
+    }
+}
+
+/// Turn a whole response into the single chunk streaming it would have sent
+fn into_chunk(
+    response: CreateChatCompletionResponse,
+) -> Result<CreateChatCompletionStreamResponse> {
+    let mut chunk = serde_json::to_value(response)?;
+    chunk["object"] = Value::from("chat.completion.chunk");
+    if let Some(choices) = chunk["choices"].as_array_mut() {
+        for choice in choices.iter_mut().filter_map(Value::as_object_mut) {
+            let mut delta = choice.remove("message").unwrap_or_default();
+            // Tool call chunks say which call they belong to
+            if let Some(calls) = delta["tool_calls"].as_array_mut() {
+                for (index, call) in calls.iter_mut().enumerate() {
+                    call["index"] = Value::from(index);
+                }
+            }
+            choice.insert("delta".to_string(), delta);
+        }
+    }
+    Ok(serde_json::from_value(chunk)?)
+}
+
+#[cfg(any(test, feature = "testing"))]
+mod mock {
+    use super::ChatBackend;
+    use anyhow::{anyhow, Result};
+    use async_openai::types::chat::{CreateChatCompletionRequest, CreateChatCompletionResponse};
+    use futures::future::BoxFuture;
+    use serde_json::{json, Value};
+    use std::collections::VecDeque;
+    use std::sync::{Mutex, PoisonError};
+
+    /// Backend that replays scripted responses in order and records the
+    /// requests it receives
+    ///
+    /// Requests beyond the script fail, unless a response was set with
+    /// [`repeat`](Self::repeat), which answers every request once the script
+    /// runs out with a fresh call id each time.
+    #[derive(Debug, Default)]
+    pub struct MockBackend {
+        /// Messages to answer with and their finish reasons
+        script: Mutex<VecDeque<(Value, &'static str)>>,
+        repeat: Option<(String, Value)>,
+        requests: Mutex<Vec<CreateChatCompletionRequest>>,
+    }
+
+    impl MockBackend {
+        /// Create a backend with an empty script
+        pub fn new() -> Self {
+            Self::default()
+        }
+
+        /// Queue a final text response
+        pub fn text(self, content: impl Into<String>) -> Self {
+            self.push(text_message(content.into()))
+        }
+
+        /// Queue a text response cut off by the output token limit
+        pub fn truncated_text(self, content: impl Into<String>) -> Self {
+            self.push_finished(text_message(content.into()), "length")
+        }
+
+        /// Queue a response requesting one tool call
+        pub fn tool_call(self, id: &str, name: &str, arguments: Value) -> Self {
+            self.tool_calls(&[(id, name, arguments)])
+        }
+
+        /// Queue a response requesting one tool call with `arguments` sent
+        /// as they are, e.g. to script malformed JSON
+        pub fn raw_tool_call(self, id: &str, name: &str, arguments: &str) -> Self {
+            self.push(json!({
+                "role": "assistant",
+                "content": null,
+                "tool_calls": [{
+                    "id": id,
+                    "type": "function",
+                    "function": { "name": name, "arguments": arguments },
+                }],
+            }))
+        }
+
+        /// Queue a response requesting several tool calls at once
+        pub fn tool_calls(self, calls: &[(&str, &str, Value)]) -> Self {
+            self.push(tool_call_message(calls))
+        }
+
+        /// Answer every request after the script with a call to `name`,
+        /// e.g. to exhaust the iteration limit
+        pub fn repeat(mut self, name: &str, arguments: Value) -> Self {
+            self.repeat = Some((name.to_string(), arguments));
+            self
+        }
+
+        /// Requests received so far, oldest first
+        pub fn requests(&self) -> Vec<CreateChatCompletionRequest> {
+            self.requests
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .clone()
+        }
+
+        fn push(self, message: Value) -> Self {
+            let finish_reason = if message.get("tool_calls").is_some() {
+                "tool_calls"
+            } else {
+                "stop"
+            };
+            self.push_finished(message, finish_reason)
+        }
+
+        fn push_finished(self, message: Value, finish_reason: &'static str) -> Self {
+            self.script
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .push_back((message, finish_reason));
+            self
+        }
+
+        fn next_response(&self, request_number: usize) -> Result<CreateChatCompletionResponse> {
+            let next = self
+                .script
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .pop_front();
+            let repeated = || {
+                self.repeat.as_ref().map(|(name, arguments)| {
+                    let id = format!("repeat_{request_number}");
+                    let message = tool_call_message(&[(&id, name, arguments.clone())]);
+                    (message, "tool_calls")
+                })
+            };
+            let (message, finish_reason) = next
+                .or_else(repeated)
+                .ok_or_else(|| anyhow!("MockBackend script exhausted"))?;
+            let response = json!({
+                "id": "mock",
+                "object": "chat.completion",
+                "created": 0,
+                "model": "mock",
+                "choices": [{
+                    "index": 0,
+                    "message": message,
+                    "finish_reason": finish_reason,
+                }],
+                "usage": {
+                    "prompt_tokens": 10,
+                    "completion_tokens": 5,
+                    "total_tokens": 15,
+                },
+            });
+            Ok(serde_json::from_value(response)?)
+        }
+    }
+
+    impl ChatBackend for MockBackend {
+        fn create(
+            &self,
+            request: CreateChatCompletionRequest,
+        ) -> BoxFuture<'_, Result<CreateChatCompletionResponse>> {
+            let request_number = {
+                let mut requests = self.requests.lock().unwrap_or_else(PoisonError::into_inner);
+                requests.push(request);
+                requests.len()
+            };
+            let response = self.next_response(request_number);
+            Box::pin(async move { response })
+        }
+    }
+
+    fn text_message(content: String) -> Value {
+        json!({ "role": "assistant", "content": content })
+    }
+
+    fn tool_call_message(calls: &[(&str, &str, Value)]) -> Value {
+        let tool_calls: Vec<Value> = calls
+            .iter()
+            .map(|(id, name, arguments)| {
+                json!({
+                    "id": id,
+                    "type": "function",
+                    "function": { "name": name, "arguments": arguments.to_string() },
+                })
+            })
+            .collect();
+        json!({ "role": "assistant", "content": null, "tool_calls": tool_calls })
+    }
+}
@@ -0,0 +1,111 @@
+//! Tool Result Cache
+//!
+//! Caches results of side-effect-free tools keyed by `(tool_name, args)` so
+//! repeated identical calls within a TTL window skip re-execution. Arguments
+//! are canonicalized (object keys sorted) so `{"a":1,"b":2}` and
+//! `{"b":2,"a":1}` share an entry.
+
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::{Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+/// In-memory TTL cache for tool results
+#[derive(Debug)]
+pub struct ToolCache {
+    ttl: Duration,
+    entries: Mutex<HashMap<(String, String), (Instant, Value)>>,
+}
+
+impl ToolCache {
+    /// Create a new cache whose entries expire after `ttl`
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Get a cached result if present and not expired
+    pub fn get(&self, tool: &str, args: &Value) -> Option<Value> {
+        let key = (tool.to_string(), canonicalize(args));
+        let mut entries = self.entries.lock().unwrap_or_else(PoisonError::into_inner);
+
+        match entries.get(&key) {
+            Some((stored_at, value)) if stored_at.elapsed() < self.ttl => Some(value.clone()),
+            Some(_) => {
+                entries.remove(&key);
+                None
+            }
+            None => None,
+        }
+    }
+
+    /// Store a result
+    pub fn insert(&self, tool: &str, args: &Value, result: Value) {
+        let key = (tool.to_string(), canonicalize(args));
+        let mut entries = self.entries.lock().unwrap_or_else(PoisonError::into_inner);
+
+        // Drop expired entries so the map doesn't grow without bound
+        entries.retain(|_, (stored_at, _)| stored_at.elapsed() < self.ttl);
+        entries.insert(key, (Instant::now(), result));
+    }
+
+    /// Remove all entries (e.g. after a tool with side effects ran)
+    pub fn clear(&self) {
+        self.entries
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clear();
+    }
+}
+
+/// Serialize a JSON value with object keys sorted at every level
+fn canonicalize(value: &Value) -> String {
+    fn sorted(value: &Value) -> Value {
+        match value {
+            Value::Object(map) => {
+                let mut keys: Vec<_> = map.keys().collect();
+                keys.sort();
+                Value::Object(
+                    keys.into_iter()
+                        .map(|k| (k.clone(), sorted(&map[k])))
+                        .collect(),
+                )
+            }
+            Value::Array(items) => Value::Array(items.iter().map(sorted).collect()),
+            other => other.clone(),
+        }
+    }
+
+    sorted(value).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_hit_ignores_key_order() {
+        let cache = ToolCache::new(Duration::from_mins(1));
+        cache.insert("read_file", &json!({"path": "a", "limit": 1}), json!("x"));
+
+        assert_eq!(
+            cache.get("read_file", &json!({"limit": 1, "path": "a"})),
+            Some(json!("x"))
+        );
+        assert_eq!(cache.get("read_file", &json!({"path": "b"})), None);
+        assert_eq!(
+            cache.get("list_dir", &json!({"path": "a", "limit": 1})),
+            None
+        );
+    }
+
+    #[test]
+    fn test_expired_entry_misses() {
+        let cache = ToolCache::new(Duration::ZERO);
+        cache.insert("read_file", &json!({"path": "a"}), json!("x"));
+        assert_eq!(cache.get("read_file", &json!({"path": "a"})), None);
+    }
+}
@@ -9,7 +9,7 @@
 //! - Hybrid approach combining both
 
 use chrono::{DateTime, Utc};
-use rustclaw_types::{ChatMessage, Role, ToolCall};
+use rustclaw_types::{ChatMessage, Message, MessageContent, Role, ToolCall, ToolResult, User};
 use serde::{Deserialize, Serialize};
 use std::collections::VecDeque;
 use tracing::{debug, info};
@@ -20,7 +20,57 @@ use uuid::Uuid;
 // ============================================================================
 
 /// Default context window size (in tokens, approximate)
-const DEFAULT_CONTEXT_WINDOW: usize = 128_000;
+pub const DEFAULT_CONTEXT_WINDOW: usize = 128_000;
+
+/// Context window sizes (in tokens) of well-known models, keyed by name prefix
+///
+/// The longest matching prefix wins, so `gpt-4o-mini-2024-07-18` matches
+/// `gpt-4o-mini` and `llama3.1:8b` matches `llama3.1` rather than `llama3`.
+const KNOWN_CONTEXT_WINDOWS: &[(&str, usize)] = &[
+    // OpenAI
+    ("gpt-3.5-turbo", 16_385),
+    ("gpt-4", 8_192),
+    ("gpt-4-turbo", 128_000),
+    ("gpt-4.1", 1_047_576),
+    ("gpt-4.5", 128_000),
+    ("gpt-4o", 128_000),
+    ("gpt-4o-mini", 128_000),
+    ("gpt-5", 400_000),
+    ("o1", 200_000),
+    ("o3", 200_000),
+    ("o4-mini", 200_000),
+    // Anthropic
+    ("claude", 200_000),
+    // Google
+    ("gemini-1.5-flash", 1_048_576),
+    ("gemini-1.5-pro", 2_097_152),
+    ("gemini-2.0-flash", 1_048_576),
+    ("gemini-2.5", 1_048_576),
+    ("gemma2", 8_192),
+    ("gemma3", 128_000),
+    // Meta
+    ("llama2", 4_096),
+    ("llama3", 8_192),
+    ("llama3.1", 128_000),
+    ("llama3.2", 128_000),
+    ("llama3.3", 128_000),
+    // Mistral
+    ("mistral", 32_768),
+    ("mixtral", 32_768),
+];
+
+/// Look up the context window of a well-known model
+///
+/// Matching ignores case and any provider prefix such as `openai/` in
+/// OpenRouter model names. Returns `None` for unknown models.
+pub fn known_context_window(model: &str) -> Option<usize> {
+    let name = model.rsplit('/').next().unwrap_or(model).to_lowercase();
+    KNOWN_CONTEXT_WINDOWS
+        .iter()
+        .filter(|(prefix, _)| name.starts_with(prefix))
+        .max_by_key(|(prefix, _)| prefix.len())
+        .map(|&(_, tokens)| tokens)
+}
 
 /// Percentage of context to trigger compression (70-80% recommended)
 const COMPRESSION_THRESHOLD: f32 = 0.75;
@@ -28,6 +78,11 @@ const COMPRESSION_THRESHOLD: f32 = 0.75;
 /// Number of recent turns to always keep in full detail
 const RECENT_TURNS_TO_KEEP: usize = 10;
 
+/// Estimate the token count of a text (rough approximation: 1 token ≈ 4 chars)
+pub fn estimate_tokens(text: &str) -> usize {
+    text.len() / 4
+}
+
 // ============================================================================
 // Message Types
 // ============================================================================
@@ -40,10 +95,15 @@ pub struct ConversationTurn {
     pub content: Option<String>,
     pub tool_calls: Option<Vec<ToolCall>>,
     pub tool_call_id: Option<String>,
+    /// Name of the tool that produced a tool result
+    pub name: Option<String>,
     pub timestamp: DateTime<Utc>,
     pub token_count: usize,
     pub is_summarized: bool,
     pub is_masked: bool,
+    /// Separate content parts of a tool result (see [`ToolResult::items`])
+    #[serde(default)]
+    pub parts: Vec<String>,
 }
 
 impl ConversationTurn {
@@ -55,10 +115,12 @@ impl ConversationTurn {
             content: Some(content),
             tool_calls: None,
             tool_call_id: None,
+            name: None,
             timestamp: Utc::now(),
             token_count: 0,
             is_summarized: false,
             is_masked: false,
+            parts: Vec::new(),
         }
     }
 
@@ -70,10 +132,12 @@ impl ConversationTurn {
             content: Some(content),
             tool_calls: None,
             tool_call_id: None,
+            name: None,
             timestamp: Utc::now(),
             token_count: 0,
             is_summarized: false,
             is_masked: false,
+            parts: Vec::new(),
         }
     }
 
@@ -84,10 +148,12 @@ impl ConversationTurn {
             content,
             tool_calls: Some(tool_calls),
             tool_call_id: None,
+            name: None,
             timestamp: Utc::now(),
             token_count: 0,
             is_summarized: false,
             is_masked: false,
+            parts: Vec::new(),
         }
     }
 
@@ -99,23 +165,56 @@ impl ConversationTurn {
             content: Some(content),
             tool_calls: None,
             tool_call_id: Some(tool_call_id.into()),
+            name: None,
             timestamp: Utc::now(),
             token_count: 0,
             is_summarized: false,
             is_masked: false,
+            parts: Vec::new(),
+        }
+    }
+
+    /// Rebuild a turn from the message stored for it at `timestamp`
+    pub fn from_chat_message(message: ChatMessage, timestamp: DateTime<Utc>) -> Self {
+        Self {
+            id: Uuid::new_v4().to_string(),
+            role: message.role,
+            content: message.content,
+            tool_calls: message.tool_calls,
+            tool_call_id: message.tool_call_id,
+            name: message.name,
+            timestamp,
+            token_count: 0,
+            is_summarized: false,
+            is_masked: false,
+            parts: message.parts,
         }
     }
 
-    /// Estimate token count (rough approximation: 1 token ≈ 4 chars)
+    /// Create the turn feeding a tool result back to the model
+    pub fn from_result(result: ToolResult) -> Self {
+        let mut turn = Self::tool_result(result.tool_call_id, result.output);
+        turn.name = result.tool_name;
+        turn.parts = result.items;
+        turn
+    }
+
+    /// Attach the name of the tool that produced this result
+    pub fn with_name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    /// Estimate token count (see [`estimate_tokens`])
     pub fn estimate_tokens(&mut self) -> usize {
         let mut count = 0;
         if let Some(ref content) = self.content {
-            count += content.len() / 4;
+            count += estimate_tokens(content);
         }
         if let Some(ref calls) = self.tool_calls {
             for call in calls {
-                count += call.function.name.len() / 4;
-                count += call.function.arguments.len() / 4;
+                count += estimate_tokens(&call.function.name);
+                count += estimate_tokens(&call.function.arguments);
             }
         }
         self.token_count = count.max(1);
@@ -127,23 +226,57 @@ impl ConversationTurn {
         ChatMessage {
             role: self.role.clone(),
             content: self.content.clone(),
-            name: None,
+            name: self.name.clone(),
             tool_calls: self.tool_calls.clone(),
             tool_call_id: self.tool_call_id.clone(),
+            parts: self.parts.clone(),
         }
     }
 
+    /// The message to store for this turn in chat `chat_id`
+    ///
+    /// Assistant turns are sent by the AI user (id 0), user turns by
+    /// `user_id`. System and tool turns, and assistant turns holding only
+    /// tool calls, are not stored and give `None`.
+    pub fn to_message(&self, chat_id: i64, user_id: i64) -> Option<Message> {
+        let sender = match self.role {
+            Role::User => User::new(user_id),
+            Role::Assistant => User::new(0),
+            Role::System | Role::Tool => return None,
+        };
+        let mut message =
+            Message::new(chat_id, sender, MessageContent::Text(self.content.clone()?));
+        message.id = self.id.clone();
+        message.timestamp = self.timestamp;
+        Some(message)
+    }
+
     /// Create a masked version (placeholder for old content)
     pub fn masked(&self) -> Self {
         let mut masked = self.clone();
         masked.is_masked = true;
         masked.content = Some("[Previous context omitted for brevity]".to_string());
         masked.tool_calls = None;
+        masked.parts = Vec::new();
         masked.token_count = 10; // Minimal tokens
         masked
     }
 }
 
+/// A stored message as a user or assistant turn, by [`Message::role`]
+impl From<&Message> for ConversationTurn {
+    fn from(msg: &Message) -> Self {
+        let text = crate::message_text(msg);
+        let mut turn = match msg.role() {
+            Role::Assistant => Self::assistant(text),
+            _ => Self::user(text),
+        };
+        turn.id = msg.id.clone();
+        turn.timestamp = msg.timestamp;
+        turn
+    }
+}
+
 // ============================================================================
 // Context Manager
 // ============================================================================
@@ -159,6 +292,17 @@ pub struct ConversationSummary {
     pub token_count: usize,
 }
 
+impl ConversationSummary {
+    /// The summary followed by its key facts, if any
+    pub fn to_text(&self) -> String {
+        if self.key_facts.is_empty() {
+            self.summary.clone()
+        } else {
+            format!("{}\nKey facts: {}", self.summary, self.key_facts.join(", "))
+        }
+    }
+}
+
 /// Context management strategy
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ContextStrategy {
@@ -314,7 +458,9 @@ impl ContextManager {
     }
 
     /// Apply a summary (replacing old turns)
-    pub fn apply_summary(&mut self, summary: ConversationSummary) {
+    ///
+    /// Returns the estimated number of tokens saved.
+    pub fn apply_summary(&mut self, summary: ConversationSummary) -> usize {
         let token_count = summary.token_count;
 
         // Remove summarized turns
@@ -337,10 +483,12 @@ impl ContextManager {
             content: Some(format!("[Conversation Summary]\n{}", summary.summary)),
             tool_calls: None,
             tool_call_id: None,
+            name: None,
             timestamp: summary.timestamp,
             token_count: summary.token_count,
             is_summarized: true,
             is_masked: false,
+            parts: Vec::new(),
         };
 
         self.total_tokens = self
@@ -350,10 +498,31 @@ impl ContextManager {
         self.turns.push_front(summary_turn);
         self.summaries.push(summary);
 
-        info!(
-            "Applied summary, saved {} tokens",
-            removed_tokens.saturating_sub(token_count)
-        );
+        let saved = removed_tokens.saturating_sub(token_count);
+        info!("Applied summary, saved {} tokens", saved);
+        saved
+    }
+
+    /// Drop the oldest turns until the estimated total fits within `max_tokens`
+    ///
+    /// Returns the number of turns removed.
+    pub fn truncate_oldest(&mut self, max_tokens: usize) -> usize {
+        let mut removed = 0;
+        while self.total_tokens > max_tokens {
+            let Some(turn) = self.turns.pop_front() else {
+                break;
+            };
+            self.total_tokens = self.total_tokens.saturating_sub(turn.token_count);
+            removed += 1;
+        }
+
+        if removed > 0 {
+            info!(
+                "Dropped {} oldest turns to fit {} tokens",
+                removed, max_tokens
+            );
+        }
+        removed
     }
 
     /// Get all messages for API call
@@ -368,9 +537,8 @@ impl ContextManager {
         // Add summaries as context
         for summary in &self.summaries {
             messages.push(ChatMessage::system(format!(
-                "[Previous Conversation Summary]\n{}\nKey facts: {}",
-                summary.summary,
-                summary.key_facts.join(", ")
+                "[Previous Conversation Summary]\n{}",
+                summary.to_text()
             )));
         }
 
@@ -483,10 +651,63 @@ Respond only with valid JSON."#,
     )
 }
 
+/// Parse a response to [`generate_summarization_prompt`] into its summary
+/// and key facts
+///
+/// Models often wrap the JSON in a code block or add a sentence around it;
+/// a response without usable JSON is taken as the summary itself.
+pub fn parse_summary_response(response: &str) -> (String, Vec<String>) {
+    let text = response.trim();
+    let json = match (text.find('{'), text.rfind('}')) {
+        (Some(start), Some(end)) if start < end => {
+            serde_json::from_str::<serde_json::Value>(&text[start..=end]).ok()
+        }
+        _ => None,
+    };
+
+    let Some(summary) = json.as_ref().and_then(|json| json.get("summary")?.as_str()) else {
+        return (text.to_string(), Vec::new());
+    };
+    let key_facts = json
+        .as_ref()
+        .and_then(|json| json.get("key_facts")?.as_array())
+        .map(|facts| {
+            facts
+                .iter()
+                .filter_map(|fact| fact.as_str().map(str::to_string))
+                .collect()
+        })
+        .unwrap_or_default();
+    (summary.trim().to_string(), key_facts)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_parse_summary_response() {
+        let (summary, facts) = parse_summary_response(
+            "```json\n{\"summary\": \"Planned a trip.\", \"key_facts\": [\"Lisbon\", \"May 3\"]}\n```",
+        );
+        assert_eq!(summary, "Planned a trip.");
+        assert_eq!(facts, ["Lisbon", "May 3"]);
+
+        let (summary, facts) = parse_summary_response("  The user asked about Rust.  ");
+        assert_eq!(summary, "The user asked about Rust.");
+        assert!(facts.is_empty());
+
+        let summary = ConversationSummary {
+            id: "s".to_string(),
+            turns_covered: Vec::new(),
+            summary: "Planned a trip.".to_string(),
+            key_facts: vec!["Lisbon".to_string()],
+            timestamp: Utc::now(),
+            token_count: 0,
+        };
+        assert_eq!(summary.to_text(), "Planned a trip.\nKey facts: Lisbon");
+    }
+
     #[test]
     fn test_context_manager() {
         let mut manager = ContextManager::new()
@@ -522,6 +743,20 @@ mod tests {
         assert!(stats.masked_turns > 0);
     }
 
+    #[test]
+    fn test_truncate_oldest() {
+        let mut manager = ContextManager::new().with_strategy(ContextStrategy::None);
+        for i in 0..5 {
+            manager.add_turn(ConversationTurn::user(format!("{}: {}", i, "x".repeat(40))));
+        }
+
+        assert_eq!(manager.truncate_oldest(25), 3);
+        let messages = manager.get_messages();
+        assert_eq!(messages.len(), 2);
+        assert!(messages[0].content.as_deref().unwrap().starts_with("3:"));
+        assert_eq!(manager.truncate_oldest(1000), 0);
+    }
+
     #[test]
     fn test_token_estimation() {
         let mut turn = ConversationTurn::user("Hello world, this is a test message.");
@@ -529,4 +764,44 @@ mod tests {
         println!("Estimated tokens: {}", tokens);
         assert!(tokens > 0);
     }
+
+    #[test]
+    fn test_tool_result_name() {
+        let turn = ConversationTurn::tool_result("call_1", "ok").with_name("read_file");
+        let message = turn.to_chat_message();
+        assert_eq!(message.name.as_deref(), Some("read_file"));
+        assert_eq!(message.tool_call_id.as_deref(), Some("call_1"));
+
+        let message = ConversationTurn::tool_result("call_2", "ok").to_chat_message();
+        assert_eq!(message.name, None);
+    }
+
+    #[test]
+    fn test_message_conversion() {
+        let question = Message::new(7, User::new(42), MessageContent::Text("Hi?".to_string()));
+        let answer = Message::new(7, User::new(0), MessageContent::Text("Hello!".to_string()));
+
+        let turn = ConversationTurn::from(&question);
+        assert_eq!(turn.role, Role::User);
+        assert_eq!(turn.content.as_deref(), Some("Hi?"));
+        assert_eq!(turn.id, question.id);
+        let turn = ConversationTurn::from(&answer);
+        assert_eq!(turn.role, Role::Assistant);
+
+        // And back, keeping id, time and sender
+        let message = turn.to_message(7, 42).unwrap();
+        assert_eq!(message.id, answer.id);
+        assert_eq!(message.timestamp, answer.timestamp);
+        assert_eq!(message.role(), Role::Assistant);
+        let message = ConversationTurn::from(&question).to_message(7, 42).unwrap();
+        assert_eq!(message.sender.id, 42);
+        assert!(matches!(message.content, MessageContent::Text(ref text) if text == "Hi?"));
+
+        assert!(ConversationTurn::tool_result("call_1", "ok")
+            .to_message(7, 42)
+            .is_none());
+        assert!(ConversationTurn::assistant_with_tools(None, Vec::new())
+            .to_message(7, 42)
+            .is_none());
+    }
 }
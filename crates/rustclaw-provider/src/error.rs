@@ -0,0 +1,271 @@
+//! Provider Error Classification
+//!
+//! Sorts errors from the model provider's API into the few categories a user
+//! can act on, so the channel can answer with a clear message instead of the
+//! raw API error. Providers report the same problem in different shapes
+//! (OpenAI's `invalid_api_key` code, Azure's `"401"` code, OpenRouter's
+//! numeric code that does not even parse as an OpenAI error), so both the
+//! parsed error and the raw body of unparsable errors are inspected.
+
+use async_openai::error::{ApiError, OpenAIError};
+use rustclaw_types::TokenUsage;
+use std::fmt;
+
+/// What went wrong with a request to the model provider
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProviderErrorKind {
+    /// The provider rejected the API key or credentials
+    Authentication,
+    /// The account has no quota or credit left
+    QuotaExceeded,
+    /// Too many requests; retrying later may work
+    RateLimited,
+    /// The provider could not be reached or timed out
+    Unavailable,
+    /// Anything else
+    Other,
+}
+
+/// Error codes and types providers use for rejected credentials
+const AUTH_CODES: &[&str] = &[
+    "invalid_api_key",
+    "invalid_authentication",
+    "authentication_error",
+    "unauthorized",
+    "401",
+];
+
+/// Phrases in error messages about rejected credentials
+const AUTH_PHRASES: &[&str] = &[
+    "incorrect api key",
+    "invalid api key",
+    "api key not valid",
+    "invalid subscription key",
+    "no auth credentials",
+    "unauthenticated",
+];
+
+impl ProviderErrorKind {
+    /// Classify an error returned by a completion request
+    pub fn of(error: &anyhow::Error) -> Self {
+        error
+            .chain()
+            .find_map(|e| e.downcast_ref::<OpenAIError>())
+            .map_or(ProviderErrorKind::Other, Self::of_openai)
+    }
+
+    fn of_openai(error: &OpenAIError) -> Self {
+        match error {
+            OpenAIError::ApiError(api) => Self::of_api_error(api),
+            OpenAIError::JSONDeserialize(_, body) => Self::of_body(body),
+            OpenAIError::Reqwest(e) => match e.status().map(|s| s.as_u16()) {
+                Some(401) => ProviderErrorKind::Authentication,
+                Some(429) => ProviderErrorKind::RateLimited,
+                _ if e.is_connect() || e.is_timeout() => ProviderErrorKind::Unavailable,
+                _ => ProviderErrorKind::Other,
+            },
+            _ => ProviderErrorKind::Other,
+        }
+    }
+
+    fn of_api_error(error: &ApiError) -> Self {
+        let fields = [error.code.as_deref(), error.r#type.as_deref()];
+        let has = |values: &[&str]| {
+            fields
+                .iter()
+                .flatten()
+                .any(|field| values.iter().any(|v| field.eq_ignore_ascii_case(v)))
+        };
+
+        if has(AUTH_CODES) || Self::mentions_auth(&error.message) {
+            ProviderErrorKind::Authentication
+        } else if has(&["insufficient_quota"]) {
+            ProviderErrorKind::QuotaExceeded
+        } else if has(&["rate_limit_exceeded", "429"]) {
+            ProviderErrorKind::RateLimited
+        } else {
+            ProviderErrorKind::Other
+        }
+    }
+
+    /// Classify the body of an error that did not parse as an OpenAI error
+    fn of_body(body: &str) -> Self {
+        let error = serde_json::from_str::<serde_json::Value>(body)
+            .ok()
+            .and_then(|value| value.get("error").cloned());
+        let code = error.as_ref().and_then(|e| match e.get("code") {
+            Some(serde_json::Value::Number(n)) => n.as_u64(),
+            Some(serde_json::Value::String(s)) => s.parse().ok(),
+            _ => None,
+        });
+
+        match code {
+            Some(401) => ProviderErrorKind::Authentication,
+            Some(429) => ProviderErrorKind::RateLimited,
+            _ if Self::mentions_auth(body) => ProviderErrorKind::Authentication,
+            _ => ProviderErrorKind::Other,
+        }
+    }
+
+    fn mentions_auth(message: &str) -> bool {
+        let message = message.to_lowercase();
+        AUTH_PHRASES.iter().any(|phrase| message.contains(phrase))
+    }
+}
+
+/// An error that ended a run after it had already used tokens
+///
+/// Shows as the error it wraps, which stays its source so
+/// [`ProviderErrorKind::of`] still classifies it.
+#[derive(Debug)]
+pub struct UsageError {
+    /// Tokens used before the run failed
+    pub usage: TokenUsage,
+    error: anyhow::Error,
+}
+
+impl UsageError {
+    /// Attach `usage` to `error`, adding it to the usage already attached
+    pub fn wrap(error: anyhow::Error, usage: TokenUsage) -> anyhow::Error {
+        match error.downcast::<UsageError>() {
+            Ok(mut spent) => {
+                spent.usage += usage;
+                spent.into()
+            }
+            Err(error) => UsageError { usage, error }.into(),
+        }
+    }
+
+    /// Tokens used before `error`, none if it does not say
+    pub fn usage_of(error: &anyhow::Error) -> TokenUsage {
+        error
+            .downcast_ref::<UsageError>()
+            .map(|e| e.usage)
+            .unwrap_or_default()
+    }
+}
+
+impl fmt::Display for UsageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.error.fmt(f)
+    }
+}
+
+impl std::error::Error for UsageError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(self.error.as_ref())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::anyhow;
+
+    fn api_error(message: &str, r#type: Option<&str>, code: Option<&str>) -> anyhow::Error {
+        anyhow!(OpenAIError::ApiError(ApiError {
+            message: message.to_string(),
+            r#type: r#type.map(str::to_string),
+            param: None,
+            code: code.map(str::to_string),
+        }))
+    }
+
+    fn unparsed(body: &str) -> anyhow::Error {
+        let error = serde_json::from_str::<ApiError>("{}").unwrap_err();
+        anyhow!(OpenAIError::JSONDeserialize(error, body.to_string()))
+    }
+
+    #[test]
+    fn test_authentication() {
+        // OpenAI
+        let openai = api_error(
+            "Incorrect API key provided: sk-abc. You can find your API key at ...",
+            Some("invalid_request_error"),
+            Some("invalid_api_key"),
+        );
+        assert_eq!(
+            ProviderErrorKind::of(&openai),
+            ProviderErrorKind::Authentication
+        );
+
+        // Azure
+        let azure = api_error(
+            "Access denied due to invalid subscription key or wrong API endpoint.",
+            None,
+            Some("401"),
+        );
+        assert_eq!(
+            ProviderErrorKind::of(&azure),
+            ProviderErrorKind::Authentication
+        );
+
+        // OpenRouter's numeric code does not parse as an OpenAI error
+        let openrouter =
+            unparsed(r#"{"error":{"message":"No auth credentials found","code":401}}"#);
+        assert_eq!(
+            ProviderErrorKind::of(&openrouter),
+            ProviderErrorKind::Authentication
+        );
+
+        // Context added on the way up does not hide the cause
+        let wrapped = api_error("Incorrect API key provided", None, Some("invalid_api_key"))
+            .context("Completion failed");
+        assert_eq!(
+            ProviderErrorKind::of(&wrapped),
+            ProviderErrorKind::Authentication
+        );
+    }
+
+    #[test]
+    fn test_other_kinds() {
+        let quota = api_error(
+            "You exceeded your current quota",
+            Some("insufficient_quota"),
+            Some("insufficient_quota"),
+        );
+        assert_eq!(
+            ProviderErrorKind::of(&quota),
+            ProviderErrorKind::QuotaExceeded
+        );
+
+        let limited = api_error(
+            "Rate limit reached",
+            Some("requests"),
+            Some("rate_limit_exceeded"),
+        );
+        assert_eq!(
+            ProviderErrorKind::of(&limited),
+            ProviderErrorKind::RateLimited
+        );
+
+        let context = api_error(
+            "This model's maximum context length is 8192 tokens",
+            Some("invalid_request_error"),
+            Some("context_length_exceeded"),
+        );
+        assert_eq!(ProviderErrorKind::of(&context), ProviderErrorKind::Other);
+        assert_eq!(
+            ProviderErrorKind::of(&anyhow!("Message too long")),
+            ProviderErrorKind::Other
+        );
+    }
+
+    #[test]
+    fn test_usage_error() {
+        let body = r#"{"error":{"code":429}}"#;
+        let error = UsageError::wrap(unparsed(body), TokenUsage::new(10, 5));
+        let error = UsageError::wrap(error, TokenUsage::new(3, 2));
+
+        assert_eq!(UsageError::usage_of(&error), TokenUsage::new(13, 7));
+        assert_eq!(
+            ProviderErrorKind::of(&error),
+            ProviderErrorKind::RateLimited
+        );
+        assert_eq!(error.to_string(), unparsed(body).to_string());
+        assert_eq!(
+            UsageError::usage_of(&anyhow!("no usage")),
+            TokenUsage::default()
+        );
+    }
+}
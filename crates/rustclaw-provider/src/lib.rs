@@ -1,24 +1,47 @@
 //! RustClaw Provider Service
 //!
 //! This module provides a unified interface for interacting with LLM providers
-//! (OpenAI, Ollama, etc.) with full support for tool calling.
+//! (OpenAI, Ollama, Gemini, etc.) with full support for tool calling.
 
+pub mod actions;
+pub mod args;
+pub mod backend;
+pub mod cache;
 pub mod context;
+pub mod error;
+pub mod ollama;
+pub mod prompt;
+pub mod stream;
 
 use anyhow::{anyhow, Result};
-use async_openai::config::OpenAIConfig;
+use async_openai::config::{AzureConfig, Config, OpenAIConfig};
 use async_openai::types::chat::{
-    ChatChoice, ChatCompletionMessageToolCalls, ChatCompletionRequestMessage,
-    ChatCompletionRequestSystemMessageArgs, ChatCompletionRequestToolMessageArgs,
-    ChatCompletionRequestUserMessageArgs, ChatCompletionTool, ChatCompletionTools,
-    CreateChatCompletionRequestArgs, FunctionObject,
+    ChatChoice, ChatCompletionMessageToolCall, ChatCompletionMessageToolCalls,
+    ChatCompletionRequestAssistantMessageArgs, ChatCompletionRequestMessage,
+    ChatCompletionRequestMessageContentPartText, ChatCompletionRequestSystemMessageArgs,
+    ChatCompletionRequestToolMessageArgs, ChatCompletionRequestToolMessageContent,
+    ChatCompletionRequestToolMessageContentPart, ChatCompletionRequestUserMessageArgs,
+    ChatCompletionStreamOptions, ChatCompletionTool, ChatCompletionTools,
+    CreateChatCompletionRequest, CreateChatCompletionRequestArgs, FunctionObject,
 };
 use async_openai::Client;
+use backend::ChatBackend;
+use cache::ToolCache;
+use context::{ContextManager, ContextStrategy, ConversationTurn, DEFAULT_CONTEXT_WINDOW};
+use error::UsageError;
+use futures::future::BoxFuture;
+use prompt::{PromptSection, DEFAULT_PROMPT_DROP_ORDER};
 use rustclaw_types::{
-    CompletionResponse, Message, MessageContent, Provider, Tool, ToolCall, ToolResult,
+    ChatMessage, CompletionResponse, Message, MessageContent, Provider, ResponsePart, Role,
+    TokenUsage, Tool, ToolCall, ToolResult,
 };
-use std::collections::HashMap;
-use tracing::{debug, info, warn};
+use serde::Deserialize;
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use std::time::Duration;
+use tokio::sync::Semaphore;
+use tracing::{debug, info, trace, warn, Instrument};
 
 // ============================================================================
 // Tool Registry
@@ -29,13 +52,180 @@ pub trait ToolFunction: Send + Sync {
     /// Get the tool definition
     fn definition(&self) -> Tool;
 
+    /// Whether the tool should currently be offered to the model
+    fn is_available(&self) -> bool {
+        true
+    }
+
+    /// Whether results can be cached (the tool is side-effect-free and
+    /// deterministic for identical arguments)
+    fn is_cacheable(&self) -> bool {
+        false
+    }
+
+    /// What the tool may do
+    ///
+    /// Defaults to no capabilities (a pure function of its arguments). Calls
+    /// of [destructive](ToolCapabilities::is_destructive) tools clear the
+    /// tool cache.
+    fn capabilities(&self) -> ToolCapabilities {
+        ToolCapabilities::default()
+    }
+
+    /// Whether the tool returns a JSON array of discrete items (search
+    /// hits, listings, ...) that should reach the model as separate chunks
+    fn returns_items(&self) -> bool {
+        false
+    }
+
+    /// Whether the tool keeps per-chat state and is run with
+    /// [`ToolFunction::execute_in_chat`]
+    fn is_chat_scoped(&self) -> bool {
+        false
+    }
+
+    /// Argument holding the call's timeout in seconds, if the tool takes one
+    ///
+    /// In a run with a deadline it is capped to the time left, so the tool
+    /// stops by then instead of running on after the run gave up on it.
+    fn timeout_argument(&self) -> Option<&str> {
+        None
+    }
+
     /// Execute the tool with the given arguments
     fn execute(&self, args: serde_json::Value) -> Result<serde_json::Value>;
+
+    /// Execute a call made in chat `chat_id`
+    ///
+    /// Only used for chat-scoped tools; defaults to [`ToolFunction::execute`].
+    fn execute_in_chat(&self, args: serde_json::Value, chat_id: i64) -> Result<serde_json::Value> {
+        let _ = chat_id;
+        self.execute(args)
+    }
+
+    /// Execute the tool, sending output lines to `output` as they are produced
+    ///
+    /// Tools without incremental output just run [`ToolFunction::execute`].
+    fn execute_streaming(
+        &self,
+        args: serde_json::Value,
+        output: &ToolOutputSink,
+    ) -> Result<serde_json::Value> {
+        let _ = output;
+        self.execute(args)
+    }
+
+    /// Whether the tool does its work in [`ToolFunction::execute_async`]
+    ///
+    /// Calls of async tools run on the async runtime instead of a blocking
+    /// thread, so they don't hold a thread while they wait. They are not
+    /// chat-scoped and don't stream output.
+    fn is_async(&self) -> bool {
+        false
+    }
+
+    /// Execute the tool without blocking the async runtime
+    ///
+    /// Used for the calls of [async](ToolFunction::is_async) tools; defaults
+    /// to [`ToolFunction::execute`].
+    fn execute_async(&self, args: serde_json::Value) -> BoxFuture<'_, Result<serde_json::Value>> {
+        Box::pin(async move { self.execute(args) })
+    }
+}
+
+/// Side effects a tool may have
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ToolCapabilities {
+    /// Reads local files
+    pub reads_files: bool,
+    /// Creates, modifies or deletes local files
+    pub writes_files: bool,
+    /// Runs arbitrary commands
+    pub executes_commands: bool,
+    /// Accesses the network
+    pub network: bool,
+}
+
+impl ToolCapabilities {
+    /// Whether the tool can change or destroy state, which invalidates
+    /// cached results of other tools
+    pub fn is_destructive(&self) -> bool {
+        self.writes_files || self.executes_commands
+    }
+}
+
+/// How tool results are written into the conversation for the model
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ToolResultFormat {
+    /// Pass bare string results as plain text and everything else as JSON
+    /// (default), so text tools are not double-encoded as `"\"text\""`
+    #[default]
+    Auto,
+    /// Always JSON-encode results, including bare strings
+    Json,
+}
+
+impl ToolResultFormat {
+    /// Render a tool's return value as tool message content
+    pub fn render(&self, value: &serde_json::Value) -> String {
+        match (self, value) {
+            (ToolResultFormat::Auto, serde_json::Value::String(text)) => text.clone(),
+            _ => serde_json::to_string(value).unwrap_or_else(|_| "{}".to_string()),
+        }
+    }
+}
+
+/// Sink receiving output lines of a running tool as they are produced
+pub type ToolOutputSink = tokio::sync::mpsc::UnboundedSender<String>;
+
+/// Sink receiving the tools each iteration of the agentic loop is about to run
+pub type ToolProgressSink = tokio::sync::mpsc::UnboundedSender<ToolProgress>;
+
+/// Tools an iteration of the agentic loop is about to run
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ToolProgress {
+    /// Iteration of the loop, starting at 1
+    pub iteration: usize,
+    /// Names of the tools called, in call order
+    pub tools: Vec<String>,
+}
+
+impl ToolProgress {
+    /// Progress of an iteration running `calls`
+    fn new(iteration: usize, calls: &[ToolCall]) -> Self {
+        Self {
+            iteration,
+            tools: calls.iter().map(|c| c.function.name.clone()).collect(),
+        }
+    }
+
+    /// Log the tools about to run and report them to `sink`, if any
+    fn report(self, sink: Option<&ToolProgressSink>) {
+        info!(
+            "Running tools (iteration {}): {}",
+            self.iteration,
+            self.tools.join(", ")
+        );
+        if let Some(sink) = sink {
+            // A closed receiver only means nobody is watching anymore
+            let _ = sink.send(self);
+        }
+    }
 }
 
 /// Registry of available tools
+///
+/// Clones share the same tool set, so a clone handed to another component
+/// (e.g. one that reconnects MCP servers) can add and remove tools while the
+/// provider keeps using the registry. Calls already running are unaffected.
+#[derive(Clone)]
 pub struct ToolRegistry {
-    tools: HashMap<String, Box<dyn ToolFunction>>,
+    tools: Arc<RwLock<HashMap<String, Arc<dyn ToolFunction>>>>,
+    cache: Option<Arc<ToolCache>>,
+    result_format: ToolResultFormat,
+    /// Names of the tools allowed to run (all when unset)
+    allowlist: Option<Arc<HashSet<String>>>,
 }
 
 impl Default for ToolRegistry {
@@ -47,51 +237,366 @@ impl Default for ToolRegistry {
 impl ToolRegistry {
     pub fn new() -> Self {
         Self {
-            tools: HashMap::new(),
+            tools: Arc::new(RwLock::new(HashMap::new())),
+            cache: None,
+            result_format: ToolResultFormat::default(),
+            allowlist: None,
         }
     }
 
-    /// Register a tool
-    pub fn register(&mut self, tool: Box<dyn ToolFunction>) {
+    /// Cache results of cacheable tools for `ttl` (zero disables caching)
+    pub fn with_cache_ttl(mut self, ttl: Duration) -> Self {
+        self.cache = (!ttl.is_zero()).then(|| Arc::new(ToolCache::new(ttl)));
+        self
+    }
+
+    /// Set how tool results are passed back to the model
+    pub fn with_result_format(mut self, format: ToolResultFormat) -> Self {
+        self.result_format = format;
+        self
+    }
+
+    /// Only run the tools named in `names`, refusing calls to any other
+    ///
+    /// Independent of which tools are registered and offered to the model:
+    /// a call to a tool outside the allowlist fails with "not permitted"
+    /// whatever the model asked for.
+    pub fn with_allowlist(mut self, names: impl IntoIterator<Item = String>) -> Self {
+        self.allowlist = Some(Arc::new(names.into_iter().collect()));
+        self
+    }
+
+    /// Register a tool, replacing any tool with the same name
+    ///
+    /// Returns `true` if an existing tool was replaced.
+    pub fn register(&self, tool: Box<dyn ToolFunction>) -> bool {
         let name = tool.definition().function.name.clone();
         info!("Registering tool: {}", name);
-        self.tools.insert(name, tool);
+        let replaced = self
+            .write_tools()
+            .insert(name.clone(), Arc::from(tool))
+            .is_some();
+        if replaced {
+            warn!(
+                "Tool '{}' was already registered and has been replaced",
+                name
+            );
+            self.clear_cache();
+        }
+        replaced
+    }
+
+    /// Remove a tool by name
+    ///
+    /// Returns `true` if the tool was registered.
+    pub fn unregister(&self, name: &str) -> bool {
+        let removed = self.write_tools().remove(name).is_some();
+        if removed {
+            info!("Unregistered tool: {}", name);
+            self.clear_cache();
+        }
+        removed
+    }
+
+    fn read_tools(&self) -> RwLockReadGuard<'_, HashMap<String, Arc<dyn ToolFunction>>> {
+        self.tools.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn write_tools(&self) -> RwLockWriteGuard<'_, HashMap<String, Arc<dyn ToolFunction>>> {
+        self.tools.write().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Get a registered tool by name
+    fn tool(&self, name: &str) -> Option<Arc<dyn ToolFunction>> {
+        self.read_tools().get(name).cloned()
+    }
+
+    /// Drop cached results, which may have come from a tool that changed
+    fn clear_cache(&self) {
+        if let Some(cache) = &self.cache {
+            cache.clear();
+        }
     }
 
-    /// Get all tool definitions for the API
+    /// Get all currently available tool definitions for the API
     pub fn get_tools(&self) -> Vec<Tool> {
-        self.tools.values().map(|t| t.definition()).collect()
+        self.read_tools()
+            .values()
+            .filter(|t| t.is_available())
+            .map(|t| t.definition())
+            .collect()
+    }
+
+    /// Get the definition of a registered tool by name
+    ///
+    /// Returns the definition even if the tool is currently unavailable.
+    pub fn get_definition(&self, name: &str) -> Option<Tool> {
+        self.tool(name).map(|t| t.definition())
+    }
+
+    /// Names of all registered tools, available or not
+    pub fn names(&self) -> HashSet<String> {
+        self.read_tools().keys().cloned().collect()
+    }
+
+    /// Check whether a tool with this name is registered
+    pub fn contains(&self, name: &str) -> bool {
+        self.read_tools().contains_key(name)
     }
 
     /// Check if we have any tools
     pub fn is_empty(&self) -> bool {
-        self.tools.is_empty()
+        self.read_tools().is_empty()
+    }
+
+    /// Check that `name` may run, logging the call if it may not
+    fn permit(&self, name: &str, args: &str) -> Result<()> {
+        match &self.allowlist {
+            Some(allowlist) if !allowlist.contains(name) => {
+                warn!("Blocked tool call not in the allowlist: {} {}", name, args);
+                Err(anyhow!("Tool '{}' is not permitted to run", name))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// The failed result of a call that may not run, if it may not
+    fn refuse(&self, call: &ToolCall) -> Option<ToolResult> {
+        let name = &call.function.name;
+        let e = self.permit(name, &call.function.arguments).err()?;
+        Some(ToolResult::error(call.id.clone(), e.to_string()).with_tool_name(name))
     }
 
     /// Execute a tool by name
     pub fn execute(&self, name: &str, args: serde_json::Value) -> Result<serde_json::Value> {
-        self.tools
-            .get(name)
+        self.permit(name, &args.to_string())?;
+        self.tool(name)
             .ok_or_else(|| anyhow!("Unknown tool: {}", name))?
             .execute(args)
     }
 
     /// Execute a tool call
     pub fn execute_call(&self, call: &ToolCall) -> ToolResult {
-        match serde_json::from_str(&call.function.arguments) {
-            Ok(args) => match self.execute(&call.function.name, args) {
-                Ok(result) => ToolResult::from_json(call.id.clone(), &result),
-                Err(e) => ToolResult::new(
-                    call.id.clone(),
-                    serde_json::json!({"error": e.to_string()}).to_string(),
-                ),
-            },
-            Err(e) => ToolResult::new(
-                call.id.clone(),
-                serde_json::json!({"error": format!("Failed to parse arguments: {}", e)})
-                    .to_string(),
-            ),
+        if let Some(refused) = self.refuse(call) {
+            return refused;
+        }
+        Self::run_call(
+            self.tool(&call.function.name),
+            self.cache.as_deref(),
+            self.result_format,
+            call,
+            None,
+            None,
+            None,
+        )
+    }
+
+    /// Execute a tool call on the blocking thread pool so it can run
+    /// concurrently with other calls without stalling the async runtime
+    ///
+    /// [Async](ToolFunction::is_async) tools run on the runtime itself.
+    pub async fn execute_call_blocking(&self, call: &ToolCall) -> ToolResult {
+        self.execute_call_streaming(call, None, None, None).await
+    }
+
+    /// Like [`ToolRegistry::execute_call_blocking`], streaming the tool's
+    /// output lines to `output` while it runs
+    ///
+    /// `chat_id` is the chat the call was made in, needed by chat-scoped
+    /// tools. `time_left` caps the call's
+    /// [timeout](ToolFunction::timeout_argument).
+    pub async fn execute_call_streaming(
+        &self,
+        call: &ToolCall,
+        output: Option<ToolOutputSink>,
+        chat_id: Option<i64>,
+        time_left: Option<Duration>,
+    ) -> ToolResult {
+        if let Some(refused) = self.refuse(call) {
+            return refused;
+        }
+        let tool = self.tool(&call.function.name);
+        let cache = self.cache.clone();
+        let format = self.result_format;
+        let owned_call = call.clone();
+        // Log lines of the tool (including MCP calls) belong to the request
+        let span = tracing::info_span!("tool", name = %call.function.name, call_id = %call.id);
+
+        if let Some(tool) = tool.as_ref().filter(|t| t.is_async()) {
+            return Self::run_async_call(tool.as_ref(), cache.as_deref(), format, call)
+                .instrument(span)
+                .await;
+        }
+
+        match tokio::task::spawn_blocking(move || {
+            span.in_scope(|| {
+                Self::run_call(
+                    tool,
+                    cache.as_deref(),
+                    format,
+                    &owned_call,
+                    output.as_ref(),
+                    chat_id,
+                    time_left,
+                )
+            })
+        })
+        .await
+        {
+            Ok(result) => result,
+            Err(e) => ToolResult::error(call.id.clone(), format!("Tool execution failed: {}", e))
+                .with_tool_name(&call.function.name),
+        }
+    }
+
+    /// Parse arguments, canonicalize them against the tool's schema and run
+    /// a (possibly unknown) tool for a call
+    #[allow(clippy::too_many_arguments)]
+    fn run_call(
+        tool: Option<Arc<dyn ToolFunction>>,
+        cache: Option<&ToolCache>,
+        format: ToolResultFormat,
+        call: &ToolCall,
+        output: Option<&ToolOutputSink>,
+        chat_id: Option<i64>,
+        time_left: Option<Duration>,
+    ) -> ToolResult {
+        let result = Self::call_args(tool.as_deref(), call).and_then(|(t, mut args)| {
+            let name = &call.function.name;
+            if let Some(time_left) = time_left {
+                cap_timeout(t, &mut args, time_left);
+            }
+            let result = Self::execute_cached(t, cache, name, args, output, chat_id)?;
+            Ok((result, t.returns_items()))
+        });
+        Self::call_result(format, call, result)
+    }
+
+    /// Like [`ToolRegistry::run_call`], for an [async](ToolFunction::is_async) tool
+    async fn run_async_call(
+        tool: &dyn ToolFunction,
+        cache: Option<&ToolCache>,
+        format: ToolResultFormat,
+        call: &ToolCall,
+    ) -> ToolResult {
+        let result = match Self::call_args(Some(tool), call) {
+            Ok((tool, args)) => Self::execute_cached_async(tool, cache, &call.function.name, args)
+                .await
+                .map(|result| (result, tool.returns_items())),
+            Err(e) => Err(e),
+        };
+        Self::call_result(format, call, result)
+    }
+
+    /// Parse a call's arguments and canonicalize them against the schema of
+    /// its tool, which must exist
+    fn call_args<'a>(
+        tool: Option<&'a dyn ToolFunction>,
+        call: &ToolCall,
+    ) -> Result<(&'a dyn ToolFunction, serde_json::Value)> {
+        let args = serde_json::from_str(&call.function.arguments)
+            .map_err(|e| anyhow!("Failed to parse arguments: {}", e))?;
+        let tool = tool.ok_or_else(|| anyhow!("Unknown tool: {}", call.function.name))?;
+        let args = args::canonicalize(args, &tool.definition().function.parameters);
+        Ok((tool, args))
+    }
+
+    /// The result of a call from its tool's output and whether the tool
+    /// returns items
+    fn call_result(
+        format: ToolResultFormat,
+        call: &ToolCall,
+        result: Result<(serde_json::Value, bool)>,
+    ) -> ToolResult {
+        let result = match result {
+            Ok((result, returns_items)) => {
+                let (result, attachments) = actions::extract_attachments(result);
+                let items = match &result {
+                    serde_json::Value::Array(items) if returns_items => {
+                        items.iter().map(|item| format.render(item)).collect()
+                    }
+                    _ => Vec::new(),
+                };
+                ToolResult::new(call.id.clone(), format.render(&result))
+                    .with_items(items)
+                    .with_attachments(attachments)
+            }
+            Err(e) => ToolResult::error(call.id.clone(), e.to_string()),
+        };
+        result.with_tool_name(&call.function.name)
+    }
+
+    /// Execute a tool, serving cacheable tools from the cache when possible
+    fn execute_cached(
+        tool: &dyn ToolFunction,
+        cache: Option<&ToolCache>,
+        name: &str,
+        args: serde_json::Value,
+        output: Option<&ToolOutputSink>,
+        chat_id: Option<i64>,
+    ) -> Result<serde_json::Value> {
+        let run = |args| {
+            if tool.is_chat_scoped() {
+                let chat_id =
+                    chat_id.ok_or_else(|| anyhow!("Tool {} can only be used in a chat", name))?;
+                return tool.execute_in_chat(args, chat_id);
+            }
+            match output {
+                Some(output) => tool.execute_streaming(args, output),
+                None => tool.execute(args),
+            }
+        };
+
+        let Some(cache) = cache else {
+            return run(args);
+        };
+
+        if !tool.is_cacheable() {
+            let result = run(args);
+            // A tool that may change files invalidates anything cached so far
+            if tool.capabilities().is_destructive() {
+                cache.clear();
+            }
+            return result;
+        }
+
+        if let Some(cached) = cache.get(name, &args) {
+            debug!("Tool cache hit: {}", name);
+            return Ok(cached);
+        }
+
+        let result = tool.execute(args.clone())?;
+        cache.insert(name, &args, result.clone());
+        Ok(result)
+    }
+
+    /// Like [`ToolRegistry::execute_cached`], for an [async](ToolFunction::is_async) tool
+    async fn execute_cached_async(
+        tool: &dyn ToolFunction,
+        cache: Option<&ToolCache>,
+        name: &str,
+        args: serde_json::Value,
+    ) -> Result<serde_json::Value> {
+        let Some(cache) = cache else {
+            return tool.execute_async(args).await;
+        };
+
+        if !tool.is_cacheable() {
+            let result = tool.execute_async(args).await;
+            if tool.capabilities().is_destructive() {
+                cache.clear();
+            }
+            return result;
+        }
+
+        if let Some(cached) = cache.get(name, &args) {
+            debug!("Tool cache hit: {}", name);
+            return Ok(cached);
         }
+
+        let result = tool.execute_async(args.clone()).await?;
+        cache.insert(name, &args, result.clone());
+        Ok(result)
     }
 }
 
@@ -99,22 +604,305 @@ impl ToolRegistry {
 // Provider Service
 // ============================================================================
 
+/// Google's OpenAI-compatible Gemini endpoint
+const GEMINI_BASE_URL: &str = "https://generativelanguage.googleapis.com/v1beta/openai";
+
+/// Prompt sent to ask the model to resume a response cut off by its output limit
+const CONTINUE_PROMPT: &str =
+    "Continue exactly where you left off. Do not repeat anything you already wrote.";
+
+/// Re-prompts with the parse error and schema a run gives a model whose
+/// tool call arguments are not valid JSON; the run stops at the next one
+const MAX_ARGUMENT_CORRECTIONS: usize = 1;
+
+/// Error reported for tool calls abandoned at the run's deadline
+const TOOL_TIMEOUT_ERROR: &str = "Tool call abandoned: the request's time limit was reached";
+
+/// Prompt asking for a summary of an oversized tool result, followed by
+/// the tool's name and output
+const TOOL_SUMMARY_PROMPT: &str = "Summarize the tool output below for the assistant that \
+     called the tool. Keep every detail it may need to continue: names, numbers, paths, \
+     errors and the overall outcome. Leave out repetition and noise. Reply with the summary \
+     only.";
+
+/// Characters of a tool output logged at `info` level by default
+pub const DEFAULT_TOOL_OUTPUT_PREVIEW: usize = 100;
+
+/// Model name prefixes of reasoning models (OpenAI o-series)
+const REASONING_MODEL_PREFIXES: &[&str] = &["o1", "o3", "o4"];
+
+/// Tags some reasoning models (e.g. DeepSeek-R1, QwQ) wrap their reasoning in
+const THINK_OPEN: &str = "<think>";
+const THINK_CLOSE: &str = "</think>";
+
+/// Sink receiving each intermediate turn of the agentic loop as it happens
+///
+/// Assistant tool-call turns and their tool results are sent in order, so a
+/// receiver can persist the full transcript while the loop is still running.
+pub type TurnRecorder = tokio::sync::mpsc::UnboundedSender<ConversationTurn>;
+
+/// Per-request settings threaded through the agentic loop
+#[derive(Clone, Copy, Default)]
+struct LoopOptions<'a> {
+    /// Receives every intermediate tool round
+    recorder: Option<&'a TurnRecorder>,
+    /// Overrides the configured model
+    model: Option<&'a str>,
+    /// Personalizes the system prompt
+    metadata: Option<&'a ConversationMetadata>,
+    /// Receives tool output lines as they are produced
+    output: Option<&'a ToolOutputSink>,
+    /// Receives the tools each iteration is about to run
+    progress: Option<&'a ToolProgressSink>,
+}
+
+/// Final result of an agentic completion
+#[derive(Debug, Clone)]
+pub struct AgenticResponse {
+    /// Final response text
+    pub content: String,
+    /// Finish reason reported by the last completion (e.g. `stop`, `length`)
+    pub finish_reason: String,
+    /// Tokens used by all completions of the loop
+    pub usage: TokenUsage,
+    /// Tool calls the loop paused on until the user confirms them
+    pub confirmations: Vec<PendingConfirmation>,
+    /// Images, files and other parts produced by tools during the loop, in
+    /// the order they were made
+    pub attachments: Vec<ResponsePart>,
+}
+
+impl AgenticResponse {
+    /// The response as parts to deliver: the text, if any, then the
+    /// attachments
+    pub fn parts(&self) -> Vec<ResponsePart> {
+        let text =
+            (!self.content.trim().is_empty()).then(|| ResponsePart::Text(self.content.clone()));
+        text.into_iter()
+            .chain(self.attachments.iter().cloned())
+            .collect()
+    }
+
+    /// Attach parts produced before this response was built
+    fn with_attachments(mut self, mut attachments: Vec<ResponsePart>) -> Self {
+        attachments.append(&mut self.attachments);
+        self.attachments = attachments;
+        self
+    }
+
+    /// Whether the response was cut off by the model's output token limit
+    pub fn is_truncated(&self) -> bool {
+        self.finish_reason == "length"
+    }
+
+    /// Whether the loop paused for the user to confirm tool calls
+    pub fn needs_confirmation(&self) -> bool {
+        !self.confirmations.is_empty()
+    }
+}
+
+/// A tool call refused until the user confirms it
+///
+/// Tools ask for confirmation by returning
+/// `{"needs_confirmation": true, "confirmation_type": .., "confirm_arg": ..}`,
+/// where `confirm_arg` names the boolean argument that confirms the call.
+/// Resume with [`ProviderService::resume_confirmed`].
+#[derive(Debug, Clone)]
+pub struct PendingConfirmation {
+    /// The call to run once confirmed, with its confirmation argument set
+    pub call: ToolCall,
+    /// What kind of confirmation the tool asked for (e.g. `destructive`)
+    pub confirmation_type: String,
+    /// The tool's explanation of why it needs confirmation
+    pub reason: String,
+}
+
+impl PendingConfirmation {
+    /// Detect a confirmation request in the result of `call`
+    pub fn from_result(call: &ToolCall, result: &ToolResult) -> Option<Self> {
+        let output: serde_json::Value = serde_json::from_str(&result.output).ok()?;
+        if output.get("needs_confirmation").and_then(|v| v.as_bool()) != Some(true) {
+            return None;
+        }
+        // Without a confirmation argument the call cannot be re-run
+        // confirmed, so the model has to handle the result itself
+        let confirm_arg = output.get("confirm_arg")?.as_str()?;
+
+        let mut args: serde_json::Value = serde_json::from_str(&call.function.arguments).ok()?;
+        args.as_object_mut()?
+            .insert(confirm_arg.to_string(), serde_json::Value::Bool(true));
+
+        let mut call = call.clone();
+        call.id = format!("{}_confirmed", call.id);
+        call.function.arguments = args.to_string();
+
+        Some(Self {
+            call,
+            confirmation_type: output
+                .get("confirmation_type")
+                .and_then(|v| v.as_str())
+                .unwrap_or("confirmation")
+                .to_string(),
+            reason: output
+                .get("error")
+                .and_then(|v| v.as_str())
+                .unwrap_or("This action needs your confirmation.")
+                .to_string(),
+        })
+    }
+}
+
+/// Who the model is talking to, added to the system prompt when known
+#[derive(Debug, Clone, Default)]
+pub struct ConversationMetadata {
+    /// The user's display name (e.g. Telegram first name)
+    pub user_name: Option<String>,
+    /// The user's handle, without a leading `@`
+    pub username: Option<String>,
+    /// The user's language as an IETF tag (e.g. `en`, `de`)
+    pub locale: Option<String>,
+    /// Summary of the earlier part of the conversation
+    pub summary: Option<String>,
+    /// Facts remembered about the chat, as `(key, value)` pairs
+    pub facts: Vec<(String, String)>,
+    /// Chat the conversation happens in, passed to chat-scoped tools
+    pub chat_id: Option<i64>,
+    /// Tool rounds saved during the conversation, replayed between the
+    /// history messages they happened between
+    pub tool_rounds: Vec<ConversationTurn>,
+}
+
+impl ConversationMetadata {
+    /// Render the metadata as a system prompt section, if any field is set
+    pub fn to_prompt(&self) -> Option<String> {
+        let mut lines = Vec::new();
+
+        match (&self.user_name, &self.username) {
+            (Some(name), Some(handle)) => {
+                lines.push(format!("You are talking to {} (@{}).", name, handle))
+            }
+            (Some(name), None) => lines.push(format!("You are talking to {}.", name)),
+            (None, Some(handle)) => lines.push(format!("You are talking to @{}.", handle)),
+            (None, None) => {}
+        }
+        if let Some(locale) = &self.locale {
+            lines.push(format!(
+                "The user's language is '{}'; reply in it unless they write in another language.",
+                locale
+            ));
+        }
+        if let Some(summary) = &self.summary {
+            lines.push(format!("Summary of the earlier conversation:\n{}", summary));
+        }
+        if !self.facts.is_empty() {
+            let facts: Vec<String> = self
+                .facts
+                .iter()
+                .map(|(key, value)| format!("- {}: {}", key, value))
+                .collect();
+            lines.push(format!(
+                "Facts you remember about this chat:\n{}",
+                facts.join("\n")
+            ));
+        }
+
+        (!lines.is_empty()).then(|| lines.join(" "))
+    }
+}
+
 /// Provider service for interacting with LLM providers
+///
+/// Clones share the tool registry and backend.
+#[derive(Clone)]
 pub struct ProviderService {
     provider: Provider,
     tools: ToolRegistry,
     system_prompt: String,
+    /// Summaries of the available skills, best ranked first, shared with
+    /// clones so they all list the same skills
+    skills: Arc<RwLock<Vec<String>>>,
+    /// Token limit for the assembled system prompt (`None` = unlimited)
+    system_prompt_budget: Option<usize>,
+    /// Order sections are dropped in when the system prompt is over budget
+    prompt_drop_order: Vec<PromptSection>,
     max_tool_iterations: usize,
+    max_continuations: usize,
+    max_concurrent_tools: usize,
+    /// Wall-clock limit for one agentic run (`None` = unlimited)
+    max_duration: Option<Duration>,
+    /// Sampling temperature (`None` = the model's default)
+    temperature: Option<f32>,
+    /// Limit on tokens generated per completion (`None` = the model's default)
+    max_output_tokens: Option<u32>,
+    /// Show reasoning returned by the model above its answer
+    show_reasoning: bool,
+    /// Execute tool calls the model wrote as JSON text instead of returning them
+    recover_text_tool_calls: bool,
+    /// Models that may be selected per request besides the configured one
+    allowed_models: Vec<String>,
+    /// Other providers that may be selected per chat, by config name
+    alternative_providers: HashMap<String, Provider>,
+    /// Prompt token limit for models without their own entry
+    context_window: usize,
+    /// Per-model prompt token limits
+    model_context_windows: HashMap<String, usize>,
+    /// Summarize a chat's history once it grows past this many tokens
+    summarize_at_tokens: Option<usize>,
+    /// Characters of each tool output shown in the `info` log
+    tool_output_preview: usize,
+    /// Summarize tool results longer than this many tokens before the model
+    /// sees them
+    summarize_tool_results_at: Option<usize>,
+    /// Model summarizing oversized tool results (`None` = the request's model)
+    tool_summary_model: Option<String>,
+    /// Answers completion requests instead of the provider's API when set
+    backend: Option<Arc<dyn ChatBackend>>,
+    /// Client of Ollama's native API, when the provider uses it
+    ollama: Option<ollama::OllamaBackend>,
+}
+
+/// Summary of the older messages of a conversation, from
+/// [`ProviderService::summarize_history`]
+#[derive(Debug, Clone)]
+pub struct HistorySummary {
+    /// The summary with its key facts
+    pub text: String,
+    /// Ids of the messages the summary covers
+    pub messages_covered: Vec<String>,
+    /// Estimated tokens saved by sending the summary instead of those messages
+    pub tokens_saved: usize,
+    /// Tokens used by the summarization request
+    pub usage: TokenUsage,
 }
 
 impl ProviderService {
     /// Create a new provider service
     pub fn new(provider: Provider) -> Self {
         Self {
+            ollama: ollama::OllamaBackend::for_provider(&provider),
             provider,
             tools: ToolRegistry::new(),
             system_prompt: "You are a helpful assistant.".to_string(),
+            skills: Arc::new(RwLock::new(Vec::new())),
+            system_prompt_budget: None,
+            prompt_drop_order: DEFAULT_PROMPT_DROP_ORDER.to_vec(),
             max_tool_iterations: 10,
+            max_continuations: 0,
+            max_concurrent_tools: 4,
+            max_duration: None,
+            temperature: None,
+            max_output_tokens: None,
+            show_reasoning: false,
+            recover_text_tool_calls: true,
+            allowed_models: Vec::new(),
+            alternative_providers: HashMap::new(),
+            context_window: DEFAULT_CONTEXT_WINDOW,
+            model_context_windows: HashMap::new(),
+            summarize_at_tokens: None,
+            tool_output_preview: DEFAULT_TOOL_OUTPUT_PREVIEW,
+            summarize_tool_results_at: None,
+            tool_summary_model: None,
+            backend: None,
         }
     }
 
@@ -124,6 +912,33 @@ impl ProviderService {
         self
     }
 
+    /// List the available skills in the system prompt, one summary line
+    /// each, best ranked first
+    pub fn with_skill_summaries(mut self, skills: Vec<String>) -> Self {
+        self.skills = Arc::new(RwLock::new(skills));
+        self
+    }
+
+    /// Replace the skills listed in the system prompt, e.g. after they were
+    /// discovered again or ranked anew
+    pub fn set_skill_summaries(&self, skills: Vec<String>) {
+        *self.skills.write().unwrap_or_else(PoisonError::into_inner) = skills;
+    }
+
+    /// Limit the assembled system prompt to about `tokens` tokens (`0` =
+    /// unlimited), dropping sections in the drop order when it is over
+    pub fn with_system_prompt_budget(mut self, tokens: usize) -> Self {
+        self.system_prompt_budget = (tokens > 0).then_some(tokens);
+        self
+    }
+
+    /// Set the order sections are dropped in when the system prompt is over
+    /// budget; sections left out are never dropped
+    pub fn with_prompt_drop_order(mut self, order: Vec<PromptSection>) -> Self {
+        self.prompt_drop_order = order;
+        self
+    }
+
     /// Set tool registry directly
     pub fn with_tool_registry(mut self, registry: ToolRegistry) -> Self {
         self.tools = registry;
@@ -136,357 +951,3343 @@ impl ProviderService {
         self
     }
 
-    /// Get a reference to the tool registry
-    pub fn tools(&self) -> &ToolRegistry {
-        &self.tools
+    /// Set how many times a length-truncated response is automatically continued
+    ///
+    /// `0` disables auto-continue; truncated responses are returned as-is.
+    pub fn with_max_continuations(mut self, max: usize) -> Self {
+        self.max_continuations = max;
+        self
     }
 
-    /// Get a mutable reference to the tool registry
-    pub fn tools_mut(&mut self) -> &mut ToolRegistry {
-        &mut self.tools
+    /// Set the maximum number of tool calls executed concurrently
+    pub fn with_max_concurrent_tools(mut self, max: usize) -> Self {
+        self.max_concurrent_tools = max.max(1);
+        self
     }
 
-    /// Complete a conversation (simple text-only interface)
-    pub async fn complete(&self, messages: &[Message], prompt: &str) -> Result<String> {
-        let response = self.complete_with_tools(messages, prompt, None).await?;
-        Ok(response.content.unwrap_or_default())
+    /// Limit the wall-clock time of one agentic run
+    ///
+    /// Model requests and tool calls still running at the deadline are
+    /// abandoned and the run returns what it has so far with a "timed out"
+    /// note.
+    pub fn with_max_duration(mut self, max: Duration) -> Self {
+        self.max_duration = Some(max);
+        self
     }
 
-    /// Complete a conversation with tool calling support
-    pub async fn complete_with_tools(
-        &self,
-        messages: &[Message],
-        prompt: &str,
-        tool_results: Option<Vec<ToolResult>>,
-    ) -> Result<CompletionResponse> {
-        let client = self.create_client()?;
-
-        // Build chat messages
-        let chat_messages = self.build_messages(messages, prompt, tool_results)?;
+    /// Set the sampling temperature
+    ///
+    /// Not sent to reasoning models, which only support their default.
+    pub fn with_temperature(mut self, temperature: f32) -> Self {
+        self.temperature = Some(temperature);
+        self
+    }
 
-        // Build request
-        let request = if !self.tools.is_empty() {
-            let tools = self.build_tools_for_api()?;
-            debug!("Sending {} tools to API", tools.len());
-            CreateChatCompletionRequestArgs::default()
-                .model(self.model_name())
-                .messages(chat_messages)
-                .tools(tools)
-                .build()?
-        } else {
-            CreateChatCompletionRequestArgs::default()
-                .model(self.model_name())
-                .messages(chat_messages)
-                .build()?
-        };
+    /// Limit the tokens generated per completion
+    ///
+    /// Sent as `max_completion_tokens` to reasoning models (the limit then
+    /// includes reasoning tokens) and as `max_tokens` to all others.
+    pub fn with_max_output_tokens(mut self, max: u32) -> Self {
+        self.max_output_tokens = Some(max);
+        self
+    }
 
-        debug!("Sending completion request to {}", self.provider_name());
+    /// Summarize a chat's history automatically once the messages not yet
+    /// summarized exceed `tokens` (`0` disables automatic summaries)
+    pub fn with_summarize_at_tokens(mut self, tokens: usize) -> Self {
+        self.summarize_at_tokens = (tokens > 0).then_some(tokens);
+        self
+    }
 
-        let response = client.chat().create(request).await?;
+    /// Set how many characters of each tool output are logged at `info`
+    /// level (`0` logs no output, and failures without it; the full output
+    /// is logged at `trace` level)
+    pub fn with_tool_output_preview(mut self, chars: usize) -> Self {
+        self.tool_output_preview = chars;
+        self
+    }
 
-        let choice = response
-            .choices
-            .first()
-            .ok_or_else(|| anyhow!("No choices returned from API"))?;
+    /// Summarize tool results over `tokens` tokens with a model before
+    /// feeding them back (`0` disables); the full output is still logged and
+    /// recorded
+    pub fn with_summarize_tool_results_at(mut self, tokens: usize) -> Self {
+        self.summarize_tool_results_at = (tokens > 0).then_some(tokens);
+        self
+    }
 
-        self.parse_response(choice)
+    /// Set the model summarizing oversized tool results, e.g. a cheaper one
+    /// (defaults to the model of the request)
+    ///
+    /// It need not be one of the allowed models, and does not become
+    /// selectable per chat.
+    pub fn with_tool_summary_model(mut self, model: impl Into<String>) -> Self {
+        self.tool_summary_model = Some(model.into());
+        self
     }
 
-    /// Execute tool calls and return results
-    pub async fn execute_tool_calls(&self, tool_calls: &[ToolCall]) -> Vec<ToolResult> {
-        tool_calls
-            .iter()
-            .map(|call| self.tools.execute_call(call))
-            .collect()
+    /// Send completion requests to `backend` instead of the provider's API
+    pub fn with_backend(mut self, backend: Arc<dyn ChatBackend>) -> Self {
+        self.backend = Some(backend);
+        self
     }
 
-    /// Complete with automatic tool execution using configured max iterations
-    pub async fn complete_agentic_default(
-        &self,
-        messages: &[Message],
-        prompt: &str,
-    ) -> Result<String> {
-        self.complete_agentic(messages, prompt, self.max_tool_iterations)
-            .await
+    /// Check whether `messages` have grown past the automatic summary threshold
+    pub fn needs_summary(&self, messages: &[Message]) -> bool {
+        self.summarize_at_tokens.is_some_and(|threshold| {
+            let tokens: usize = messages
+                .iter()
+                .map(|msg| context::estimate_tokens(&message_text(msg)))
+                .sum();
+            tokens >= threshold
+        })
     }
 
-    /// Complete with automatic tool execution (agentic loop)
-    pub async fn complete_agentic(
-        &self,
-        messages: &[Message],
-        prompt: &str,
-        max_iterations: usize,
-    ) -> Result<String> {
-        let current_messages = messages.to_vec();
-        let current_prompt = prompt.to_string();
-        let mut tool_results = None;
-        let mut last_tool_output: Option<String> = None;
+    /// Show the reasoning a model returns above its answer (hidden by default)
+    pub fn with_show_reasoning(mut self, show: bool) -> Self {
+        self.show_reasoning = show;
+        self
+    }
 
-        for iteration in 0..max_iterations {
-            debug!("Agentic iteration {} of {}", iteration + 1, max_iterations);
+    /// Set whether a final response that is only a tool call written as JSON
+    /// text, naming a registered tool, is executed like a real tool call
+    /// (enabled by default)
+    ///
+    /// Without this, models that ignore native tool calling show the user
+    /// the raw call instead of its result.
+    pub fn with_text_tool_call_recovery(mut self, enabled: bool) -> Self {
+        self.recover_text_tool_calls = enabled;
+        self
+    }
 
-            let response = self
-                .complete_with_tools(&current_messages, &current_prompt, tool_results.take())
-                .await?;
+    /// Set the models that may be selected per request (the configured
+    /// model is always allowed)
+    pub fn with_allowed_models(mut self, models: Vec<String>) -> Self {
+        self.allowed_models = models;
+        self
+    }
 
-            if !response.has_tool_calls() {
-                // If LLM returns empty content but we have tool output, use that
-                let content_is_empty = response
-                    .content
-                    .as_ref()
-                    .is_none_or(|c| c.trim().is_empty());
-                if content_is_empty {
-                    if let Some(output) = last_tool_output.take() {
-                        debug!("LLM returned empty content, using tool output directly");
-                        return Ok(output);
-                    }
-                }
-                return Ok(response.content.unwrap_or_default());
-            }
+    /// Set other providers that may be selected per chat (each with its
+    /// configured model only)
+    pub fn with_alternative_providers(mut self, providers: Vec<Provider>) -> Self {
+        self.alternative_providers = providers
+            .into_iter()
+            .filter(|p| p.config_name() != self.provider.config_name())
+            .map(|p| (p.config_name().to_string(), p))
+            .collect();
+        self
+    }
 
-            // Execute tool calls
-            let results = self.execute_tool_calls(&response.tool_calls).await;
+    /// Get the config name of the configured provider, e.g. `openai`
+    pub fn default_provider(&self) -> &'static str {
+        self.provider.config_name()
+    }
 
-            // Log tool executions and save last output
-            for (call, result) in response.tool_calls.iter().zip(results.iter()) {
-                let truncated_output = if result.output.chars().count() > 100 {
-                    result.output.chars().take(100).collect::<String>() + "..."
-                } else {
-                    result.output.clone()
-                };
-                info!(
-                    "Tool executed: {} -> {}",
-                    call.function.name, truncated_output
-                );
-                // Save the last tool output in case LLM returns empty
-                last_tool_output = Some(result.output.clone());
-            }
+    /// Get all selectable providers, starting with the configured one
+    pub fn providers(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self
+            .alternative_providers
+            .keys()
+            .map(String::as_str)
+            .collect();
+        names.sort_unstable();
+        names.insert(0, self.default_provider());
+        names
+    }
+
+    /// Check whether a provider may be selected
+    pub fn is_provider_allowed(&self, name: &str) -> bool {
+        name == self.default_provider() || self.alternative_providers.contains_key(name)
+    }
 
-            // Prepare for next iteration
-            tool_results = Some(results);
+    /// Get the service answering requests for a chat that selected `name`
+    ///
+    /// Other providers share this service's tools, settings and backend, but
+    /// only offer their own configured model. Without a selection, or for a
+    /// provider that is not allowed, this service is used.
+    pub fn for_provider(&self, name: Option<&str>) -> Cow<'_, ProviderService> {
+        match name.and_then(|name| self.alternative_providers.get(name)) {
+            Some(provider) => Cow::Owned(ProviderService {
+                provider: provider.clone(),
+                ollama: ollama::OllamaBackend::for_provider(provider),
+                allowed_models: Vec::new(),
+                alternative_providers: HashMap::new(),
+                ..self.clone()
+            }),
+            None => {
+                if let Some(name) = name.filter(|name| *name != self.default_provider()) {
+                    warn!(
+                        "Provider '{}' is not allowed, using {}",
+                        name,
+                        self.default_provider()
+                    );
+                }
+                Cow::Borrowed(self)
+            }
         }
+    }
 
-        warn!("Max tool iterations reached without final response");
-        Ok("[Max tool iterations reached]".to_string())
+    /// Set the prompt token limit used for unknown models without their own
+    /// limit
+    pub fn with_context_window(mut self, tokens: usize) -> Self {
+        self.context_window = tokens;
+        self
     }
 
-    // ========================================================================
-    // Private helpers
-    // ========================================================================
+    /// Set prompt token limits for specific models
+    pub fn with_model_context_windows(mut self, windows: HashMap<String, usize>) -> Self {
+        self.model_context_windows = windows;
+        self
+    }
 
-    fn create_client(&self) -> Result<Client<OpenAIConfig>> {
-        let (api_key, base_url) = match &self.provider {
-            Provider::OpenAI {
-                api_key, base_url, ..
-            } => (api_key.clone(), base_url.clone()),
-            Provider::Ollama { base_url, .. } => (None, Some(base_url.clone())),
-        };
+    /// Get the prompt token limit for a model
+    ///
+    /// Limits set with [`Self::with_model_context_windows`] take precedence,
+    /// then the known window of the model, then the configured default.
+    pub fn context_window(&self, model: &str) -> usize {
+        self.model_context_windows
+            .get(model)
+            .copied()
+            .or_else(|| context::known_context_window(model))
+            .unwrap_or(self.context_window)
+    }
 
-        // Build config with API key and optional base URL
-        let mut config = OpenAIConfig::new();
+    /// Get the configured model name
+    pub fn default_model(&self) -> &str {
+        self.model_name()
+    }
 
-        if let Some(key) = api_key {
-            let preview_len = 20.min(key.len());
-            debug!("Using API key: {}...", &key[..preview_len]);
-            config = config.with_api_key(key);
+    /// Get all selectable models, starting with the configured one
+    pub fn allowed_models(&self) -> Vec<&str> {
+        let mut models = vec![self.model_name()];
+        for model in &self.allowed_models {
+            if !models.contains(&model.as_str()) {
+                models.push(model);
+            }
         }
+        models
+    }
 
-        if let Some(url) = base_url {
-            debug!("Using API base URL: {}", url);
-            config = config.with_api_base(url);
-        }
+    /// Check whether a model may be selected
+    pub fn is_model_allowed(&self, model: &str) -> bool {
+        model == self.model_name() || self.allowed_models.iter().any(|m| m == model)
+    }
 
-        let client = Client::with_config(config);
-        Ok(client)
+    /// Get a reference to the tool registry
+    pub fn tools(&self) -> &ToolRegistry {
+        &self.tools
     }
 
-    fn model_name(&self) -> &str {
-        match &self.provider {
-            Provider::OpenAI { model, .. } => model,
-            Provider::Ollama { model, .. } => model,
-        }
+    /// Get a mutable reference to the tool registry
+    pub fn tools_mut(&mut self) -> &mut ToolRegistry {
+        &mut self.tools
     }
 
-    fn provider_name(&self) -> &str {
-        match &self.provider {
-            Provider::OpenAI { .. } => "OpenAI",
-            Provider::Ollama { .. } => "Ollama",
-        }
+    /// Add a tool to the live tool set, replacing any tool with the same name
+    ///
+    /// Takes effect from the next completion, including for requests already
+    /// in their agentic loop. Returns `true` if an existing tool was replaced.
+    pub fn add_tool(&self, tool: Box<dyn ToolFunction>) -> bool {
+        self.tools.register(tool)
     }
 
-    fn build_messages(
+    /// Remove a tool from the live tool set
+    ///
+    /// Returns `true` if the tool was registered.
+    pub fn remove_tool(&self, name: &str) -> bool {
+        self.tools.unregister(name)
+    }
+
+    /// Complete a conversation (simple text-only interface)
+    pub async fn complete(&self, messages: &[Message], prompt: &str) -> Result<String> {
+        let response = self
+            .complete_with_tools(messages, prompt, None, None)
+            .await?;
+        Ok(response.content.unwrap_or_default())
+    }
+
+    /// Complete a conversation with tool calling support
+    ///
+    /// `model` overrides the configured model for this request only.
+    pub async fn complete_with_tools(
         &self,
         messages: &[Message],
         prompt: &str,
         tool_results: Option<Vec<ToolResult>>,
-    ) -> Result<Vec<ChatCompletionRequestMessage>> {
-        let mut chat_messages = vec![ChatCompletionRequestSystemMessageArgs::default()
-            .content(self.system_prompt.clone())
-            .build()?
-            .into()];
+        model: Option<&str>,
+    ) -> Result<CompletionResponse> {
+        // Build chat messages
+        let messages = self.fit_history(messages, prompt, model)?;
+        let chat_messages = self.build_messages(messages, prompt, tool_results, None)?;
+
+        self.send_request(chat_messages, true, model).await
+    }
 
-        // Add conversation history
+    /// Summarize all but the most recent messages of a conversation
+    ///
+    /// `messages` are oldest first; `previous` is an earlier summary that is
+    /// folded into the new one. Returns `None` when there are too few
+    /// messages to summarize.
+    pub async fn summarize_history(
+        &self,
+        messages: &[Message],
+        previous: Option<&str>,
+        model: Option<&str>,
+    ) -> Result<Option<HistorySummary>> {
+        let mut history = ContextManager::new().with_strategy(ContextStrategy::Summarization);
+        if let Some(previous) = previous {
+            let mut turn = ConversationTurn::user(previous);
+            turn.role = Role::System;
+            history.add_turn(turn);
+        }
         for msg in messages {
-            let content = match &msg.content {
-                MessageContent::Text(text) => text.clone(),
-                MessageContent::Image(img) => {
-                    // Include image context in the conversation
-                    let caption = img.caption.as_deref().unwrap_or("[Image]");
-                    format!(
-                        "[Image: {}x{}, caption: {}]",
-                        img.width, img.height, caption
-                    )
-                }
-                MessageContent::Document(doc) => {
-                    // Include document context in the conversation
-                    let name = doc.file_name.as_deref().unwrap_or("Unknown");
-                    format!("[Document: {}, {} bytes]", name, doc.file_size.unwrap_or(0))
-                }
-            };
-            chat_messages.push(
-                ChatCompletionRequestUserMessageArgs::default()
-                    .content(content)
-                    .build()?
-                    .into(),
-            );
+            history.add_turn(ConversationTurn::from(msg));
         }
 
-        // Add current prompt if provided
-        if !prompt.is_empty() {
-            chat_messages.push(
-                ChatCompletionRequestUserMessageArgs::default()
-                    .content(prompt)
-                    .build()?
-                    .into(),
-            );
+        // Oldest first, like the conversation itself
+        let mut turns = history.get_turns_to_summarize();
+        turns.reverse();
+        let messages_covered: Vec<String> = messages
+            .iter()
+            .filter(|msg| turns.iter().any(|turn| turn.id == msg.id))
+            .map(|msg| msg.id.clone())
+            .collect();
+        if messages_covered.is_empty() {
+            return Ok(None);
         }
 
-        // Add tool results if provided
-        if let Some(results) = tool_results {
-            for result in results {
-                chat_messages.push(
-                    ChatCompletionRequestToolMessageArgs::default()
-                        .content(result.output.clone())
-                        .tool_call_id(result.tool_call_id.clone())
-                        .build()?
-                        .into(),
-                );
-            }
+        let prompt = context::generate_summarization_prompt(&turns);
+        let turns_covered = turns.iter().map(|turn| turn.id.clone()).collect();
+        let request = vec![ChatCompletionRequestUserMessageArgs::default()
+            .content(prompt)
+            .build()?
+            .into()];
+        let response = self.send_request(request, false, model).await?;
+
+        let (summary, key_facts) =
+            context::parse_summary_response(response.content.as_deref().unwrap_or_default());
+        if summary.is_empty() {
+            return Err(anyhow!("The model returned an empty summary"));
         }
+        let mut summary = context::ConversationSummary {
+            id: uuid::Uuid::new_v4().to_string(),
+            turns_covered,
+            summary,
+            key_facts,
+            timestamp: chrono::Utc::now(),
+            token_count: 0,
+        };
+        let text = summary.to_text();
+        summary.token_count = context::estimate_tokens(&text);
+        let tokens_saved = history.apply_summary(summary);
 
-        Ok(chat_messages)
+        Ok(Some(HistorySummary {
+            text,
+            messages_covered,
+            tokens_saved,
+            usage: response.usage,
+        }))
     }
 
-    fn build_tools_for_api(&self) -> Result<Vec<ChatCompletionTools>> {
-        self.tools
-            .get_tools()
-            .into_iter()
-            .map(|tool| {
-                Ok(ChatCompletionTools::Function(ChatCompletionTool {
-                    function: FunctionObject {
-                        name: tool.function.name,
-                        description: Some(tool.function.description),
-                        parameters: Some(tool.function.parameters),
-                        strict: tool.function.strict,
-                    },
-                }))
-            })
-            .collect()
-    }
+    /// Stream a completion of `prompt` after the conversation so far
+    ///
+    /// Yields the response text piece by piece as the model writes it, so it
+    /// can be shown before the model is done. The registered tools are
+    /// offered; a tool call the model makes is sent whole once all of its
+    /// fragments arrived, and is not executed. The tokens used come last, if
+    /// the provider reports them. Providers that can't stream send the whole
+    /// response at once.
+    ///
+    /// `model` overrides the configured model for this request only.
+    pub async fn complete_stream(
+        &self,
+        messages: &[Message],
+        prompt: &str,
+        model: Option<&str>,
+    ) -> Result<impl futures::Stream<Item = Result<stream::StreamDelta>> + Send + 'static> {
+        let messages = self.fit_history(messages, prompt, model)?;
+        let chat_messages = self.build_messages(messages, prompt, None, None)?;
+        let model = self.resolve_model(model);
+        let mut request = self.build_request(chat_messages, true, model)?;
+        request.stream_options = Some(ChatCompletionStreamOptions {
+            include_usage: Some(true),
+            include_obfuscation: None,
+        });
 
-    fn parse_response(&self, choice: &ChatChoice) -> Result<CompletionResponse> {
-        let message = &choice.message;
+        debug!(
+            "Streaming completion from {} (model {})",
+            self.provider_name(),
+            model
+        );
+        let chunks = self.chat_backend(model)?.create_stream(request).await?;
+        Ok(stream::deltas(chunks))
+    }
 
-        let content = message.content.clone();
+    /// Build a chat completion request, optionally offering the registered tools
+    fn build_request(
+        &self,
+        chat_messages: Vec<ChatCompletionRequestMessage>,
+        with_tools: bool,
+        model: &str,
+    ) -> Result<CreateChatCompletionRequest> {
+        let mut args = CreateChatCompletionRequestArgs::default();
+        args.model(model).messages(chat_messages);
+        if with_tools && !self.tools.is_empty() {
+            let tools = self.build_tools_for_api()?;
+            debug!("Sending {} tools to API", tools.len());
+            args.tools(tools);
+        }
+        self.apply_sampling(&mut args, model);
+        Ok(args.build()?)
+    }
 
-        let tool_calls: Vec<ToolCall> = message
-            .tool_calls
-            .as_ref()
-            .map(|calls| {
-                calls
-                    .iter()
-                    .filter_map(|tc| match tc {
-                        ChatCompletionMessageToolCalls::Function(func_call) => Some(ToolCall {
-                            id: func_call.id.clone(),
-                            call_type: "function".to_string(),
-                            function: rustclaw_types::FunctionCall {
-                                name: func_call.function.name.clone(),
-                                arguments: func_call.function.arguments.clone(),
-                            },
-                        }),
-                        ChatCompletionMessageToolCalls::Custom(_) => None,
-                    })
-                    .collect()
-            })
-            .unwrap_or_default();
+    /// Send a chat completion request, optionally offering the registered tools
+    async fn send_request(
+        &self,
+        chat_messages: Vec<ChatCompletionRequestMessage>,
+        with_tools: bool,
+        model: Option<&str>,
+    ) -> Result<CompletionResponse> {
+        self.send_request_to(chat_messages, with_tools, self.resolve_model(model))
+            .await
+    }
 
-        let finish_reason = choice
-            .finish_reason
-            .as_ref()
-            .map(|r| format!("{:?}", r).to_lowercase())
-            .unwrap_or_else(|| "unknown".to_string());
+    /// Send a chat completion request to `model`, which is not checked
+    /// against the allowed models
+    async fn send_request_to(
+        &self,
+        chat_messages: Vec<ChatCompletionRequestMessage>,
+        with_tools: bool,
+        model: &str,
+    ) -> Result<CompletionResponse> {
+        let request = self.build_request(chat_messages, with_tools, model)?;
 
         debug!(
-            "Response parsed: content={}, tool_calls={}, finish_reason={}",
-            content.as_deref().unwrap_or("none"),
-            tool_calls.len(),
-            finish_reason
+            "Sending completion request to {} (model {})",
+            self.provider_name(),
+            model
         );
 
-        Ok(CompletionResponse {
-            content,
-            tool_calls,
-            finish_reason,
-        })
-    }
-}
+        let response = self.chat_backend(model)?.create(request).await?;
 
-// ============================================================================
-// Built-in Example Tools
-// ============================================================================
+        let choice = response
+            .choices
+            .first()
+            .ok_or_else(|| anyhow!("No choices returned from API"))?;
 
-/// A simple echo tool for testing
-pub struct EchoTool;
+        let mut parsed = self.parse_response(choice)?;
+        if let Some(usage) = &response.usage {
+            parsed.usage = TokenUsage::new(
+                u64::from(usage.prompt_tokens),
+                u64::from(usage.completion_tokens),
+            );
+            if let Some(reasoning_tokens) = usage
+                .completion_tokens_details
+                .as_ref()
+                .and_then(|details| details.reasoning_tokens)
+            {
+                debug!("Model used {} reasoning tokens", reasoning_tokens);
+            }
+        }
+        Ok(parsed)
+    }
 
-impl ToolFunction for EchoTool {
-    fn definition(&self) -> Tool {
-        Tool::function(
-            "echo",
-            "Echo back the input message",
-            serde_json::json!({
-                "type": "object",
-                "properties": {
-                    "message": {
-                        "type": "string",
-                        "description": "The message to echo back"
-                    }
-                },
-                "required": ["message"],
-                "additionalProperties": false
-            }),
-        )
+    /// Turn a response that is only a tool call written as JSON text into a
+    /// real call, if it names a registered tool
+    fn text_tool_call(&self, content: Option<&str>, iteration: usize) -> Option<ToolCall> {
+        let (name, arguments) = parse_text_tool_call(content?)?;
+        self.tools.tool(&name)?;
+        Some(ToolCall {
+            id: format!("text_call_{}", iteration),
+            call_type: "function".to_string(),
+            function: rustclaw_types::FunctionCall { name, arguments },
+        })
     }
 
-    fn execute(&self, args: serde_json::Value) -> Result<serde_json::Value> {
-        let message = args
-            .get("message")
-            .and_then(|m| m.as_str())
-            .ok_or_else(|| anyhow!("Missing 'message' argument"))?;
-        Ok(serde_json::json!({ "echoed": message }))
+    /// Set the sampling parameters a model supports
+    ///
+    /// Reasoning models reject `temperature` and `max_tokens`.
+    fn apply_sampling(&self, args: &mut CreateChatCompletionRequestArgs, model: &str) {
+        if is_reasoning_model(model) {
+            if let Some(max) = self.max_output_tokens {
+                args.max_completion_tokens(max);
+            }
+            return;
+        }
+
+        if let Some(temperature) = self.temperature {
+            args.temperature(temperature);
+        }
+        if let Some(max) = self.max_output_tokens {
+            // Still the only limit understood by most OpenAI-compatible servers
+            #[allow(deprecated)]
+            args.max_tokens(max);
+        }
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    /// Execute tool calls concurrently (bounded by `max_concurrent_tools`)
+    /// and return results in call order
+    pub async fn execute_tool_calls(&self, tool_calls: &[ToolCall]) -> Vec<ToolResult> {
+        self.run_tool_calls(tool_calls, None, None, &mut HashMap::new(), None)
+            .await
+    }
 
-    #[test]
-    fn test_tool_registry() {
-        let mut registry = ToolRegistry::new();
-        registry.register(Box::new(EchoTool));
+    /// Execute tool calls concurrently, streaming their output to `output`
+    ///
+    /// `executed` holds the results of calls already run in this agentic run,
+    /// keyed by call id. A call whose id is in there is not executed again;
+    /// its earlier result is returned instead, so a retried iteration cannot
+    /// repeat side effects such as appending to a file. Timeouts of calls
+    /// are capped to the time left until `deadline`.
+    async fn run_tool_calls(
+        &self,
+        tool_calls: &[ToolCall],
+        output: Option<&ToolOutputSink>,
+        chat_id: Option<i64>,
+        executed: &mut HashMap<String, ToolResult>,
+        deadline: Option<tokio::time::Instant>,
+    ) -> Vec<ToolResult> {
+        let semaphore = Semaphore::new(self.max_concurrent_tools);
 
-        assert!(!registry.is_empty());
+        // Calls without an id cannot be correlated and always run
+        let mut pending_ids = HashSet::new();
+        let pending: Vec<&ToolCall> = tool_calls
+            .iter()
+            .filter(|call| {
+                if call.id.is_empty() {
+                    return true;
+                }
+                if executed.contains_key(&call.id) || !pending_ids.insert(call.id.as_str()) {
+                    warn!(
+                        "Skipping tool call {} ({}): already executed",
+                        call.id, call.function.name
+                    );
+                    return false;
+                }
+                true
+            })
+            .collect();
+
+        let executions = pending.iter().map(|call| async {
+            // The semaphore is never closed, so acquiring cannot fail
+            let _permit = semaphore.acquire().await.ok();
+            let time_left = deadline
+                .map(|deadline| deadline.saturating_duration_since(tokio::time::Instant::now()));
+            self.tools
+                .execute_call_streaming(call, output.cloned(), chat_id, time_left)
+                .await
+        });
+        let fresh = futures::future::join_all(executions).await;
+
+        correlate_results(tool_calls, fresh, executed)
+    }
+
+    /// Complete with automatic tool execution using configured max iterations
+    pub async fn complete_agentic_default(
+        &self,
+        messages: &[Message],
+        prompt: &str,
+    ) -> Result<AgenticResponse> {
+        self.complete_agentic(messages, prompt, self.max_tool_iterations)
+            .await
+    }
+
+    /// Complete with automatic tool execution using configured max iterations,
+    /// reporting every intermediate tool round to `recorder`
+    ///
+    /// `model` overrides the configured model for this request only;
+    /// `metadata` personalizes the system prompt; `output` receives tool
+    /// output lines (e.g. from long-running commands) as they are produced;
+    /// `progress` is told which tools each iteration runs before they start.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn complete_agentic_recorded(
+        &self,
+        messages: &[Message],
+        prompt: &str,
+        recorder: &TurnRecorder,
+        model: Option<&str>,
+        metadata: Option<&ConversationMetadata>,
+        output: Option<&ToolOutputSink>,
+        progress: Option<&ToolProgressSink>,
+    ) -> Result<AgenticResponse> {
+        let options = LoopOptions {
+            recorder: Some(recorder),
+            model,
+            metadata,
+            output,
+            progress,
+        };
+        self.run_agentic_loop(
+            messages,
+            prompt,
+            Vec::new(),
+            self.max_tool_iterations,
+            options,
+        )
+        .await
+    }
+
+    /// Run the tool calls the user confirmed and continue the agentic loop
+    ///
+    /// `messages` is the history up to the paused response and `prompt` the
+    /// user's reply (e.g. "Yes"). The confirmed calls are recorded as a new
+    /// tool round, so the model sees their results.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn resume_confirmed(
+        &self,
+        messages: &[Message],
+        prompt: &str,
+        confirmations: &[PendingConfirmation],
+        recorder: &TurnRecorder,
+        model: Option<&str>,
+        metadata: Option<&ConversationMetadata>,
+        output: Option<&ToolOutputSink>,
+        progress: Option<&ToolProgressSink>,
+    ) -> Result<AgenticResponse> {
+        let calls: Vec<ToolCall> = confirmations.iter().map(|c| c.call.clone()).collect();
+        for call in &calls {
+            info!("Running confirmed tool call: {}", call.function.name);
+        }
+        ToolProgress::new(1, &calls).report(progress);
+        let assistant_turn = ConversationTurn::assistant_with_tools(None, calls.clone());
+        Self::record_turn(Some(recorder), &assistant_turn);
+        let mut turns = vec![assistant_turn];
+
+        let results = self
+            .run_tool_calls(
+                &calls,
+                output,
+                metadata.and_then(|m| m.chat_id),
+                &mut HashMap::new(),
+                None,
+            )
+            .await;
+        let mut attachments = Vec::new();
+        let mut usage = TokenUsage::default();
+        for mut result in results {
+            attachments.append(&mut result.attachments);
+            usage += self
+                .feed_result(result, Some(recorder), &mut turns, model, None)
+                .await;
+        }
+
+        let options = LoopOptions {
+            recorder: Some(recorder),
+            model,
+            metadata,
+            output,
+            progress,
+        };
+        let mut response = self
+            .run_agentic_loop(messages, prompt, turns, self.max_tool_iterations, options)
+            .await
+            .map_err(|e| UsageError::wrap(e, usage))?;
+        response.usage += usage;
+        Ok(response.with_attachments(attachments))
+    }
+
+    /// Complete with automatic tool execution (agentic loop)
+    pub async fn complete_agentic(
+        &self,
+        messages: &[Message],
+        prompt: &str,
+        max_iterations: usize,
+    ) -> Result<AgenticResponse> {
+        self.run_agentic_loop(
+            messages,
+            prompt,
+            Vec::new(),
+            max_iterations,
+            LoopOptions::default(),
+        )
+        .await
+    }
+
+    async fn run_agentic_loop(
+        &self,
+        messages: &[Message],
+        prompt: &str,
+        mut turns: Vec<ConversationTurn>,
+        max_iterations: usize,
+        options: LoopOptions<'_>,
+    ) -> Result<AgenticResponse> {
+        let LoopOptions {
+            recorder,
+            model,
+            metadata,
+            output,
+            progress,
+        } = options;
+        let messages = self.fit_history(messages, prompt, model)?;
+
+        // `turns` holds the assistant tool-call turns and tool results
+        // produced by this loop
+        let mut last_tool_output: Option<String> = None;
+        // Results of every tool call run so far, keyed by call id
+        let mut executed: HashMap<String, ToolResult> = HashMap::new();
+        // Images produced by tools, delivered with the final response
+        let mut attachments = Vec::new();
+        let mut usage = TokenUsage::default();
+        // Rounds with malformed arguments answered with a correction prompt
+        let mut argument_corrections = 0;
+        let deadline = self
+            .max_duration
+            .map(|max| tokio::time::Instant::now() + max);
+
+        for iteration in 0..max_iterations {
+            debug!("Agentic iteration {} of {}", iteration + 1, max_iterations);
+            if deadline.is_some_and(|deadline| tokio::time::Instant::now() >= deadline) {
+                return Ok(self
+                    .timed_out(last_tool_output, usage)
+                    .with_attachments(attachments));
+            }
+
+            let chat_messages =
+                self.build_messages_with_turns(messages, prompt, &turns, metadata)?;
+            let Some(response) =
+                until(deadline, self.send_request(chat_messages, true, model)).await
+            else {
+                return Ok(self
+                    .timed_out(last_tool_output, usage)
+                    .with_attachments(attachments));
+            };
+            let mut response = response.map_err(|e| UsageError::wrap(e, usage))?;
+            usage += response.usage;
+
+            if !response.has_tool_calls() && self.recover_text_tool_calls {
+                if let Some(call) = self.text_tool_call(response.content.as_deref(), iteration) {
+                    warn!(
+                        "Model wrote a {} tool call as text, executing it",
+                        call.function.name
+                    );
+                    response.content = None;
+                    response.tool_calls = vec![call];
+                }
+            }
+
+            if !response.has_tool_calls() {
+                // If LLM returns empty content but we have tool output, use that
+                let content_is_empty = response
+                    .content
+                    .as_ref()
+                    .is_none_or(|c| c.trim().is_empty());
+                if content_is_empty {
+                    if let Some(output) = last_tool_output.take() {
+                        debug!("LLM returned empty content, using tool output directly");
+                        return Ok(AgenticResponse {
+                            content: output,
+                            finish_reason: response.finish_reason,
+                            usage,
+                            confirmations: Vec::new(),
+                            attachments,
+                        });
+                    }
+                }
+                let response = self
+                    .continue_if_truncated(
+                        messages, prompt, &turns, response, usage, options, deadline,
+                    )
+                    .await?;
+                return Ok(response.with_attachments(attachments));
+            }
+
+            let assistant_turn = ConversationTurn::assistant_with_tools(
+                response.content.clone(),
+                response.tool_calls.clone(),
+            );
+            Self::record_turn(recorder, &assistant_turn);
+            turns.push(assistant_turn);
+
+            ToolProgress::new(iteration + 1, &response.tool_calls).report(progress);
+
+            // Execute tool calls
+            let Some(mut results) = until(
+                deadline,
+                self.run_tool_calls(
+                    &response.tool_calls,
+                    output,
+                    metadata.and_then(|m| m.chat_id),
+                    &mut executed,
+                    deadline,
+                ),
+            )
+            .await
+            else {
+                // Answer every call so the recorded transcript stays valid
+                for call in &response.tool_calls {
+                    let error = serde_json::json!({ "error": TOOL_TIMEOUT_ERROR }).to_string();
+                    let tool_turn = ConversationTurn::tool_result(&call.id, error)
+                        .with_name(&call.function.name);
+                    Self::record_turn(recorder, &tool_turn);
+                }
+                return Ok(self
+                    .timed_out(last_tool_output, usage)
+                    .with_attachments(attachments));
+            };
+
+            // Log tool executions and save last output
+            for (call, result) in response.tool_calls.iter().zip(results.iter()) {
+                trace!("Tool output of {}: {}", call.function.name, result.output);
+                let output = self.output_preview(&result.output);
+                if result.is_error() {
+                    match output {
+                        Some(output) => warn!("Tool failed: {} -> {}", call.function.name, output),
+                        None => warn!("Tool failed: {}", call.function.name),
+                    }
+                    continue;
+                }
+                if let Some(output) = output {
+                    info!("Tool executed: {} -> {}", call.function.name, output);
+                }
+                // Save the last successful output in case LLM returns empty
+                last_tool_output = Some(result.output.clone());
+            }
+
+            // Tools that refuse until the user confirms pause the loop
+            let confirmations: Vec<PendingConfirmation> = response
+                .tool_calls
+                .iter()
+                .zip(results.iter())
+                .filter_map(|(call, result)| PendingConfirmation::from_result(call, result))
+                .collect();
+
+            // Malformed arguments are answered with the parse error and the
+            // tool's schema once; after that they stop the run
+            let corrections: Vec<Option<String>> = response
+                .tool_calls
+                .iter()
+                .map(|call| self.argument_correction(call))
+                .collect();
+            let mut invalid_arguments = None;
+            if corrections.iter().any(Option::is_some) {
+                if argument_corrections < MAX_ARGUMENT_CORRECTIONS {
+                    argument_corrections += 1;
+                    info!(
+                        "Asking the model to correct malformed tool arguments ({}/{})",
+                        argument_corrections, MAX_ARGUMENT_CORRECTIONS
+                    );
+                    for (result, correction) in results.iter_mut().zip(corrections) {
+                        if let Some(correction) = correction {
+                            result.output = correction;
+                        }
+                    }
+                } else {
+                    invalid_arguments = response
+                        .tool_calls
+                        .iter()
+                        .zip(&corrections)
+                        .find(|(_, correction)| correction.is_some())
+                        .map(|(call, _)| call.function.name.clone());
+                }
+            }
+
+            // Feed results into the next iteration
+            for mut result in results {
+                attachments.append(&mut result.attachments);
+                usage += self
+                    .feed_result(result, recorder, &mut turns, model, deadline)
+                    .await;
+            }
+
+            if let Some(name) = invalid_arguments {
+                warn!(
+                    "Model sent malformed arguments for {} again, stopping",
+                    name
+                );
+                return Ok(AgenticResponse {
+                    content: format!("[The model sent invalid arguments for {}]", name),
+                    finish_reason: "invalid_arguments".to_string(),
+                    usage,
+                    confirmations: Vec::new(),
+                    attachments,
+                });
+            }
+
+            if !confirmations.is_empty() {
+                for confirmation in &confirmations {
+                    info!(
+                        "Tool {} needs confirmation ({}), pausing",
+                        confirmation.call.function.name, confirmation.confirmation_type
+                    );
+                }
+                let reasons: Vec<&str> = confirmations.iter().map(|c| c.reason.as_str()).collect();
+                return Ok(AgenticResponse {
+                    content: reasons.join("\n"),
+                    finish_reason: "needs_confirmation".to_string(),
+                    usage,
+                    confirmations,
+                    attachments,
+                });
+            }
+        }
+
+        warn!("Max tool iterations reached without final response");
+        Ok(AgenticResponse {
+            content: "[Max tool iterations reached]".to_string(),
+            finish_reason: "max_iterations".to_string(),
+            usage,
+            confirmations: Vec::new(),
+            attachments,
+        })
+    }
+
+    /// Correction prompt for a call to a registered tool whose arguments are
+    /// not valid JSON: the parse error and the schema the arguments must match
+    fn argument_correction(&self, call: &ToolCall) -> Option<String> {
+        let error = serde_json::from_str::<serde_json::Value>(&call.function.arguments).err()?;
+        let tool = self.tools.get_definition(&call.function.name)?;
+        Some(format!(
+            "Failed to parse arguments: {}\n\nThe arguments of {} must be a JSON object \
+             matching this schema:\n{}\n\nCall {} again with valid JSON arguments.",
+            error, call.function.name, tool.function.parameters, call.function.name
+        ))
+    }
+
+    /// Record a tool result in full and add it to `turns`, summarized if it
+    /// is over the summary threshold
+    ///
+    /// Returns the tokens the summary used.
+    async fn feed_result(
+        &self,
+        mut result: ToolResult,
+        recorder: Option<&TurnRecorder>,
+        turns: &mut Vec<ConversationTurn>,
+        model: Option<&str>,
+        deadline: Option<tokio::time::Instant>,
+    ) -> TokenUsage {
+        let turn = ConversationTurn::from_result(result.clone());
+        Self::record_turn(recorder, &turn);
+
+        let mut usage = TokenUsage::default();
+        if let Some(Some((summary, summary_usage))) =
+            until(deadline, self.summarize_tool_result(&result, model)).await
+        {
+            usage = summary_usage;
+            result.output = summary;
+            result.items.clear();
+            turns.push(ConversationTurn::from_result(result));
+        } else {
+            turns.push(turn);
+        }
+        usage
+    }
+
+    /// Summarize a successful tool result over the summary threshold
+    ///
+    /// Returns the summary and the tokens it used, or `None` if the result
+    /// is kept as it is (also when summarizing fails).
+    async fn summarize_tool_result(
+        &self,
+        result: &ToolResult,
+        model: Option<&str>,
+    ) -> Option<(String, TokenUsage)> {
+        let threshold = self.summarize_tool_results_at?;
+        let tokens = context::estimate_tokens(&result.output);
+        if result.is_error || tokens <= threshold {
+            return None;
+        }
+
+        // The configured summary model is used even if it can't be selected
+        // per chat
+        let name = result.tool_name.as_deref().unwrap_or("tool");
+        let model = match &self.tool_summary_model {
+            Some(model) => model.as_str(),
+            None => self.resolve_model(model),
+        };
+
+        // Leave a quarter of the model's context window for the prompt and
+        // the summary
+        let max_tokens = self.context_window(model) / 4 * 3;
+        let output = truncate_utf8(&result.output, max_tokens * 4);
+        if output.len() < result.output.len() {
+            warn!(
+                "{} output is too long for {}, summarizing its first {} tokens",
+                name, model, max_tokens
+            );
+        }
+        let prompt = format!(
+            "{}\n\nTool: {}\nOutput:\n{}",
+            TOOL_SUMMARY_PROMPT, name, output
+        );
+        let request = ChatCompletionRequestUserMessageArgs::default()
+            .content(prompt)
+            .build()
+            .ok()?;
+        let response = match self
+            .send_request_to(vec![request.into()], false, model)
+            .await
+        {
+            Ok(response) => response,
+            Err(e) => {
+                warn!(
+                    "Failed to summarize {} output, keeping it whole: {}",
+                    name, e
+                );
+                return None;
+            }
+        };
+        let summary = response.content.filter(|c| !c.trim().is_empty())?;
+        let summary = format!(
+            "[Summary of {} output of about {} tokens]\n{}",
+            name, tokens, summary
+        );
+        info!(
+            "Summarized {} output: about {} -> {} tokens",
+            name,
+            tokens,
+            context::estimate_tokens(&summary)
+        );
+        Some((summary, response.usage))
+    }
+
+    /// The part of a tool output logged at `info` level, `None` when tool
+    /// output is not logged there
+    fn output_preview<'a>(&self, output: &'a str) -> Option<Cow<'a, str>> {
+        (self.tool_output_preview > 0).then(|| preview(output, self.tool_output_preview))
+    }
+
+    /// Partial result of a run that reached its deadline: the last tool
+    /// output, if any, followed by a note
+    fn timed_out(&self, last_tool_output: Option<String>, usage: TokenUsage) -> AgenticResponse {
+        let max = self.max_duration.unwrap_or_default();
+        warn!("Agentic loop timed out after {:?}", max);
+        let note = format!("⏱️ [Timed out after {}s]", max.as_secs());
+        AgenticResponse {
+            content: match last_tool_output {
+                Some(output) => format!("{}\n\n{}", output, note),
+                None => note,
+            },
+            finish_reason: "timeout".to_string(),
+            usage,
+            confirmations: Vec::new(),
+            attachments: Vec::new(),
+        }
+    }
+
+    /// Keep asking the model to continue while its response is cut off by the
+    /// output token limit, up to the configured number of continuations
+    ///
+    /// Continuing stops at `deadline`, leaving the response truncated.
+    #[allow(clippy::too_many_arguments)]
+    async fn continue_if_truncated(
+        &self,
+        messages: &[Message],
+        prompt: &str,
+        turns: &[ConversationTurn],
+        response: CompletionResponse,
+        mut usage: TokenUsage,
+        options: LoopOptions<'_>,
+        deadline: Option<tokio::time::Instant>,
+    ) -> Result<AgenticResponse> {
+        let LoopOptions {
+            model, metadata, ..
+        } = options;
+        let mut content = response.content.clone().unwrap_or_default();
+        let reasoning = response.reasoning.clone();
+        let mut last = response;
+        let mut continuations = 0;
+
+        while last.is_truncated() && continuations < self.max_continuations {
+            continuations += 1;
+            info!(
+                "Response truncated, requesting continuation {} of {}",
+                continuations, self.max_continuations
+            );
+
+            let mut chat_messages =
+                self.build_messages_with_turns(messages, prompt, turns, metadata)?;
+            chat_messages.push(
+                ChatCompletionRequestAssistantMessageArgs::default()
+                    .content(content.clone())
+                    .build()?
+                    .into(),
+            );
+            chat_messages.push(
+                ChatCompletionRequestUserMessageArgs::default()
+                    .content(CONTINUE_PROMPT)
+                    .build()?
+                    .into(),
+            );
+
+            let Some(next) = until(deadline, self.send_request(chat_messages, false, model)).await
+            else {
+                warn!("Run reached its deadline, stopping continuations");
+                break;
+            };
+            last = next.map_err(|e| UsageError::wrap(e, usage))?;
+            usage += last.usage;
+            content.push_str(last.content.as_deref().unwrap_or_default());
+        }
+
+        if last.is_truncated() {
+            warn!(
+                "Response still truncated after {} continuation(s)",
+                continuations
+            );
+        }
+
+        if let Some(reasoning) = reasoning.filter(|_| self.show_reasoning) {
+            content = format!("💭 {}\n\n{}", reasoning, content);
+        }
+
+        Ok(AgenticResponse {
+            content,
+            finish_reason: last.finish_reason,
+            usage,
+            confirmations: Vec::new(),
+            attachments: Vec::new(),
+        })
+    }
+
+    // ========================================================================
+    // Private helpers
+    // ========================================================================
+
+    /// Build an API client for a request to `model`
+    ///
+    /// Azure OpenAI routes requests by deployment rather than by the model
+    /// field, so the model name is used as the deployment.
+    fn create_client(&self, model: &str) -> Result<Client<Box<dyn Config>>> {
+        let (api_key, base_url) = match &self.provider {
+            Provider::OpenAI {
+                api_key, base_url, ..
+            } => (api_key.clone(), base_url.clone()),
+            Provider::Ollama { base_url, .. } => {
+                (None, Some(format!("{}/v1", ollama::server_url(base_url))))
+            }
+            Provider::Gemini { api_key, .. } => {
+                (Some(api_key.clone()), Some(GEMINI_BASE_URL.to_string()))
+            }
+            Provider::AzureOpenAI {
+                endpoint,
+                api_version,
+                api_key,
+                ..
+            } => {
+                debug!("Using Azure deployment: {}", model);
+                let config = AzureConfig::new()
+                    .with_api_base(endpoint)
+                    .with_api_version(api_version)
+                    .with_deployment_id(model)
+                    .with_api_key(api_key);
+                return Ok(Client::with_config(Box::new(config)));
+            }
+        };
+
+        // Build config with API key and optional base URL
+        let mut config = OpenAIConfig::new();
+
+        if let Some(key) = api_key {
+            debug!("Using API key: {}...", truncate_utf8(&key, 20));
+            config = config.with_api_key(key);
+        }
+
+        if let Some(url) = base_url {
+            debug!("Using API base URL: {}", url);
+            config = config.with_api_base(url);
+        }
+
+        if let Provider::OpenAI { headers, .. } = &self.provider {
+            for (name, value) in headers {
+                let header = reqwest::header::HeaderName::from_bytes(name.as_bytes())
+                    .map_err(|e| anyhow!("Invalid header name '{}': {}", name, e))?;
+                debug!("Using custom header: {}", name);
+                config = config.with_header(header, value.as_str())?;
+            }
+        }
+
+        let client = Client::with_config(Box::new(config) as Box<dyn Config>);
+        Ok(client)
+    }
+
+    /// Backend answering requests to `model`: the one set with
+    /// [`Self::with_backend`], Ollama's native API or the provider's API
+    fn chat_backend(&self, model: &str) -> Result<Arc<dyn ChatBackend>> {
+        if let Some(backend) = &self.backend {
+            return Ok(Arc::clone(backend));
+        }
+        if let Some(ollama) = &self.ollama {
+            return Ok(Arc::new(ollama.clone()));
+        }
+        Ok(Arc::new(backend::ApiBackend {
+            client: self.create_client(model)?,
+        }))
+    }
+
+    fn model_name(&self) -> &str {
+        match &self.provider {
+            Provider::OpenAI { model, .. } => model,
+            Provider::Ollama { model, .. } => model,
+            Provider::Gemini { model, .. } => model,
+            Provider::AzureOpenAI { deployment, .. } => deployment,
+        }
+    }
+
+    /// Pick the model for a request, falling back to the configured one
+    /// when the override is not allowed
+    fn resolve_model<'a>(&'a self, model: Option<&'a str>) -> &'a str {
+        match model {
+            Some(m) if self.is_model_allowed(m) => m,
+            Some(m) => {
+                warn!("Model '{}' is not allowed, using {}", m, self.model_name());
+                self.model_name()
+            }
+            None => self.model_name(),
+        }
+    }
+
+    /// Drop the oldest history messages until the request fits the model's
+    /// context window
+    ///
+    /// Fails with a "message too long" error when the system prompt, tool
+    /// definitions and prompt alone exceed the limit.
+    fn fit_history<'m>(
+        &self,
+        messages: &'m [Message],
+        prompt: &str,
+        model: Option<&str>,
+    ) -> Result<&'m [Message]> {
+        let model = self.resolve_model(model);
+        let limit = self.context_window(model);
+
+        let tools = serde_json::to_string(&self.tools.get_tools()).unwrap_or_default();
+        let system = context::estimate_tokens(&self.system_prompt)
+            + self
+                .skills
+                .read()
+                .unwrap_or_else(PoisonError::into_inner)
+                .iter()
+                .map(|skill| context::estimate_tokens(skill))
+                .sum::<usize>();
+        // Sections are dropped when the system prompt is assembled over budget
+        let system = self
+            .system_prompt_budget
+            .map_or(system, |budget| system.min(budget));
+        let fixed = system + context::estimate_tokens(&tools) + context::estimate_tokens(prompt);
+        if fixed > limit {
+            return Err(anyhow!(
+                "Message too long: about {} tokens, but {} accepts at most {}",
+                fixed,
+                model,
+                limit
+            ));
+        }
+
+        let mut history = ContextManager::new()
+            .with_strategy(ContextStrategy::None)
+            .with_max_tokens(limit);
+        for msg in messages {
+            history.add_turn(ConversationTurn::from(msg));
+        }
+
+        let removed = history.truncate_oldest(limit - fixed);
+        if removed > 0 {
+            warn!(
+                "Prompt exceeds the {}-token limit for {}, dropped {} oldest message(s)",
+                limit, model, removed
+            );
+        }
+        Ok(&messages[removed..])
+    }
+
+    fn provider_name(&self) -> &str {
+        match &self.provider {
+            Provider::OpenAI { .. } => "OpenAI",
+            Provider::Ollama { .. } => "Ollama",
+            Provider::Gemini { .. } => "Gemini",
+            Provider::AzureOpenAI { .. } => "Azure OpenAI",
+        }
+    }
+
+    /// Send a turn to the recorder, if any
+    fn record_turn(recorder: Option<&TurnRecorder>, turn: &ConversationTurn) {
+        if let Some(recorder) = recorder {
+            if recorder.send(turn.clone()).is_err() {
+                debug!("Turn recorder closed, dropping turn {}", turn.id);
+            }
+        }
+    }
+
+    /// Build request messages: history, prompt, then the loop's own turns
+    fn build_messages_with_turns(
+        &self,
+        messages: &[Message],
+        prompt: &str,
+        turns: &[ConversationTurn],
+        metadata: Option<&ConversationMetadata>,
+    ) -> Result<Vec<ChatCompletionRequestMessage>> {
+        let mut chat_messages = self.build_messages(messages, prompt, None, metadata)?;
+        for turn in turns {
+            chat_messages.push(Self::to_request_message(&turn.to_chat_message())?);
+        }
+        Ok(chat_messages)
+    }
+
+    /// Convert a `ChatMessage` into the API request representation
+    fn to_request_message(message: &ChatMessage) -> Result<ChatCompletionRequestMessage> {
+        let content = message.content.clone().unwrap_or_default();
+
+        let request_message = match message.role {
+            Role::System => ChatCompletionRequestSystemMessageArgs::default()
+                .content(content)
+                .build()?
+                .into(),
+            Role::User => ChatCompletionRequestUserMessageArgs::default()
+                .content(content)
+                .build()?
+                .into(),
+            Role::Assistant => {
+                let mut args = ChatCompletionRequestAssistantMessageArgs::default();
+                if !content.is_empty() {
+                    args.content(content);
+                }
+                if let Some(calls) = &message.tool_calls {
+                    args.tool_calls(
+                        calls
+                            .iter()
+                            .map(|call| {
+                                ChatCompletionMessageToolCalls::Function(
+                                    ChatCompletionMessageToolCall {
+                                        id: call.id.clone(),
+                                        function: async_openai::types::chat::FunctionCall {
+                                            name: call.function.name.clone(),
+                                            arguments: call.function.arguments.clone(),
+                                        },
+                                    },
+                                )
+                            })
+                            .collect::<Vec<_>>(),
+                    );
+                }
+                args.build()?.into()
+            }
+            // The tool message type has no `name` field; the tool is
+            // identified through `tool_call_id`
+            Role::Tool => {
+                let mut args = ChatCompletionRequestToolMessageArgs::default();
+                if message.parts.is_empty() {
+                    args.content(content);
+                } else {
+                    // Items of a list-like result go out as separate parts
+                    args.content(ChatCompletionRequestToolMessageContent::Array(
+                        message
+                            .parts
+                            .iter()
+                            .map(|text| {
+                                ChatCompletionRequestToolMessageContentPart::Text(
+                                    ChatCompletionRequestMessageContentPartText {
+                                        text: text.clone(),
+                                    },
+                                )
+                            })
+                            .collect(),
+                    ));
+                }
+                args.tool_call_id(message.tool_call_id.clone().unwrap_or_default())
+                    .build()?
+                    .into()
+            }
+        };
+
+        Ok(request_message)
+    }
+
+    /// The system prompt with the skills and the conversation's metadata,
+    /// within the system prompt budget
+    fn assemble_system_prompt(&self, metadata: Option<&ConversationMetadata>) -> String {
+        prompt::assemble(
+            &self.system_prompt,
+            &self.skills.read().unwrap_or_else(PoisonError::into_inner),
+            metadata,
+            self.system_prompt_budget,
+            &self.prompt_drop_order,
+        )
+    }
+
+    fn build_messages(
+        &self,
+        messages: &[Message],
+        prompt: &str,
+        tool_results: Option<Vec<ToolResult>>,
+        metadata: Option<&ConversationMetadata>,
+    ) -> Result<Vec<ChatCompletionRequestMessage>> {
+        let mut chat_messages = vec![ChatCompletionRequestSystemMessageArgs::default()
+            .content(self.assemble_system_prompt(metadata))
+            .build()?
+            .into()];
+
+        // Add conversation history, replies as the assistant's own, with the
+        // tool rounds that led to them
+        let rounds = metadata.map_or(&[][..], |m| &m.tool_rounds[..]);
+        let mut rounds = replayable_rounds(rounds, messages).into_iter().peekable();
+        for msg in messages {
+            while let Some(round) = rounds.next_if(|r| r.timestamp < msg.timestamp) {
+                chat_messages.push(Self::to_request_message(&round.to_chat_message())?);
+            }
+            let turn = ConversationTurn::from(msg);
+            chat_messages.push(Self::to_request_message(&turn.to_chat_message())?);
+        }
+
+        // Add current prompt if provided
+        if !prompt.is_empty() {
+            chat_messages.push(
+                ChatCompletionRequestUserMessageArgs::default()
+                    .content(prompt)
+                    .build()?
+                    .into(),
+            );
+        }
+
+        // Add tool results if provided
+        if let Some(results) = tool_results {
+            for result in results {
+                chat_messages.push(
+                    ChatCompletionRequestToolMessageArgs::default()
+                        .content(result.output.clone())
+                        .tool_call_id(result.tool_call_id.clone())
+                        .build()?
+                        .into(),
+                );
+            }
+        }
+
+        Ok(chat_messages)
+    }
+
+    /// The tool definitions sent to the API with every request, as JSON
+    ///
+    /// Includes built-in and MCP tools, with the schema adjustments made for
+    /// the configured provider (e.g. Gemini).
+    pub fn tool_manifest(&self) -> Result<serde_json::Value> {
+        Ok(serde_json::to_value(self.build_tools_for_api()?)?)
+    }
+
+    fn build_tools_for_api(&self) -> Result<Vec<ChatCompletionTools>> {
+        let is_gemini = matches!(self.provider, Provider::Gemini { .. });
+
+        self.tools
+            .get_tools()
+            .into_iter()
+            .map(|tool| {
+                let (parameters, strict) = if is_gemini {
+                    // Gemini's compatible endpoint rejects `strict` and `additionalProperties`
+                    (gemini_compatible_schema(tool.function.parameters), None)
+                } else {
+                    (tool.function.parameters, tool.function.strict)
+                };
+
+                Ok(ChatCompletionTools::Function(ChatCompletionTool {
+                    function: FunctionObject {
+                        name: tool.function.name,
+                        description: Some(tool.function.description),
+                        parameters: Some(parameters),
+                        strict,
+                    },
+                }))
+            })
+            .collect()
+    }
+
+    fn parse_response(&self, choice: &ChatChoice) -> Result<CompletionResponse> {
+        let message = &choice.message;
+
+        let (reasoning, content) = match message.content.as_deref() {
+            Some(content) => {
+                let (reasoning, answer) = split_reasoning(content);
+                (reasoning, Some(answer))
+            }
+            None => (None, None),
+        };
+
+        let tool_calls: Vec<ToolCall> = message
+            .tool_calls
+            .as_ref()
+            .map(|calls| {
+                calls
+                    .iter()
+                    .filter_map(|tc| match tc {
+                        ChatCompletionMessageToolCalls::Function(func_call) => Some(ToolCall {
+                            id: func_call.id.clone(),
+                            call_type: "function".to_string(),
+                            function: rustclaw_types::FunctionCall {
+                                name: func_call.function.name.clone(),
+                                arguments: func_call.function.arguments.clone(),
+                            },
+                        }),
+                        ChatCompletionMessageToolCalls::Custom(_) => None,
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let finish_reason = choice
+            .finish_reason
+            .as_ref()
+            .and_then(|r| serde_json::to_value(r).ok())
+            .and_then(|v| v.as_str().map(str::to_string))
+            .unwrap_or_else(|| "unknown".to_string());
+
+        debug!(
+            "Response parsed: content={}, tool_calls={}, finish_reason={}",
+            content.as_deref().unwrap_or("none"),
+            tool_calls.len(),
+            finish_reason
+        );
+
+        Ok(CompletionResponse {
+            content,
+            tool_calls,
+            finish_reason,
+            usage: TokenUsage::default(),
+            reasoning,
+        })
+    }
+}
+
+/// Whether a model is a reasoning model with restricted sampling parameters
+///
+/// Matching ignores case and any provider prefix such as `openai/`.
+pub fn is_reasoning_model(model: &str) -> bool {
+    let name = model.rsplit('/').next().unwrap_or(model).to_lowercase();
+    REASONING_MODEL_PREFIXES.iter().any(|prefix| {
+        name.strip_prefix(prefix)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with('-'))
+    })
+}
+
+/// The longest prefix of `text` of at most `max_bytes` bytes that doesn't
+/// split a character
+///
+/// Slicing with `&text[..max_bytes]` panics when the cut falls inside a
+/// multi-byte character, which non-ASCII tool output makes likely.
+pub fn truncate_utf8(text: &str, max_bytes: usize) -> &str {
+    if text.len() <= max_bytes {
+        return text;
+    }
+    let mut end = max_bytes;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    &text[..end]
+}
+
+/// Decode tool output as UTF-8, replacing invalid bytes with U+FFFD
+///
+/// Also returns whether any bytes were invalid, so tools can flag the result
+/// with `"invalid_utf8": true` and the model knows the text may be binary or
+/// corrupted.
+pub fn lossy_utf8(bytes: &[u8]) -> (String, bool) {
+    match String::from_utf8_lossy(bytes) {
+        Cow::Borrowed(text) => (text.to_string(), false),
+        Cow::Owned(text) => (text, true),
+    }
+}
+
+/// The first `max_chars` characters of `text`, with `...` if it is longer
+fn preview(text: &str, max_chars: usize) -> Cow<'_, str> {
+    match text.char_indices().nth(max_chars) {
+        Some((end, _)) => Cow::Owned(format!("{}...", &text[..end])),
+        None => Cow::Borrowed(text),
+    }
+}
+
+/// Parse text that consists only of a JSON tool call into `(name, arguments)`
+///
+/// Accepts `{"name": .., "arguments": ..}` (or `parameters`/`args`) and the
+/// API's own `{"function": {"name": .., "arguments": ..}}`, optionally in a
+/// code fence.
+fn parse_text_tool_call(content: &str) -> Option<(String, String)> {
+    let text = content.trim();
+    let text = text
+        .strip_prefix("```json")
+        .or_else(|| text.strip_prefix("```"))
+        .and_then(|rest| rest.trim_end().strip_suffix("```"))
+        .unwrap_or(text);
+
+    let value: serde_json::Value = serde_json::from_str(text.trim()).ok()?;
+    let call = value.get("function").unwrap_or(&value);
+    let name = call.get("name")?.as_str()?.to_string();
+    let arguments = match ["arguments", "parameters", "args"]
+        .iter()
+        .find_map(|key| call.get(key))
+    {
+        None => "{}".to_string(),
+        Some(serde_json::Value::String(args)) => args.clone(),
+        Some(args @ serde_json::Value::Object(_)) => args.to_string(),
+        Some(_) => return None,
+    };
+    Some((name, arguments))
+}
+
+/// Split leading `<think>` reasoning off a response into `(reasoning, answer)`
+fn split_reasoning(content: &str) -> (Option<String>, String) {
+    let trimmed = content.trim_start();
+    let Some(rest) = trimmed.strip_prefix(THINK_OPEN) else {
+        return (None, content.to_string());
+    };
+    let Some((reasoning, answer)) = rest.split_once(THINK_CLOSE) else {
+        // Cut off while still reasoning: there is no answer yet
+        return (Some(rest.trim().to_string()), String::new());
+    };
+
+    let reasoning = reasoning.trim();
+    (
+        (!reasoning.is_empty()).then(|| reasoning.to_string()),
+        answer.trim_start().to_string(),
+    )
+}
+
+/// Strip JSON Schema keywords that Gemini's OpenAI-compatible API does not accept
+fn gemini_compatible_schema(schema: serde_json::Value) -> serde_json::Value {
+    match schema {
+        serde_json::Value::Object(map) => map
+            .into_iter()
+            .filter(|(key, _)| key != "additionalProperties")
+            .map(|(key, value)| (key, gemini_compatible_schema(value)))
+            .collect(),
+        serde_json::Value::Array(items) => {
+            items.into_iter().map(gemini_compatible_schema).collect()
+        }
+        other => other,
+    }
+}
+
+/// Render a history message as text for the model
+fn message_text(msg: &Message) -> String {
+    match &msg.content {
+        MessageContent::Text(text) => text.clone(),
+        MessageContent::Image(img) => {
+            // Include image context in the conversation
+            let caption = img.caption.as_deref().unwrap_or("[Image]");
+            format!(
+                "[Image: {}x{}, caption: {}]",
+                img.width, img.height, caption
+            )
+        }
+        MessageContent::Document(doc) => {
+            // Include document context in the conversation
+            let name = doc.file_name.as_deref().unwrap_or("Unknown");
+            format!("[Document: {}, {} bytes]", name, doc.file_size.unwrap_or(0))
+        }
+    }
+}
+
+/// The saved tool rounds that can be replayed among `messages`, oldest first
+///
+/// Only rounds between the first and last history message are kept, so
+/// rounds of answers no longer in the history are left out. An assistant
+/// turn is kept only together with a result for each of its calls, since
+/// the API rejects calls left unanswered, and results only with their call.
+fn replayable_rounds<'r>(
+    rounds: &'r [ConversationTurn],
+    messages: &[Message],
+) -> Vec<&'r ConversationTurn> {
+    let (Some(first), Some(last)) = (messages.first(), messages.last()) else {
+        return Vec::new();
+    };
+    let within: Vec<_> = rounds
+        .iter()
+        .filter(|r| r.timestamp > first.timestamp && r.timestamp < last.timestamp)
+        .collect();
+
+    let answered: HashSet<&str> = within
+        .iter()
+        .filter_map(|r| r.tool_call_id.as_deref())
+        .collect();
+    let complete: HashSet<&str> = within
+        .iter()
+        .filter_map(|r| r.tool_calls.as_deref())
+        .filter(|calls| !calls.is_empty() && calls.iter().all(|c| answered.contains(c.id.as_str())))
+        .flatten()
+        .map(|c| c.id.as_str())
+        .collect();
+
+    within
+        .into_iter()
+        .filter(|r| match (&r.tool_calls, &r.tool_call_id) {
+            (Some(calls), _) => calls.iter().any(|c| complete.contains(c.id.as_str())),
+            (None, Some(id)) => complete.contains(id.as_str()),
+            (None, None) => false,
+        })
+        .collect()
+}
+
+/// Pair every tool call with its result, in call order
+///
+/// Fresh results are matched to calls by `tool_call_id`, so the order in
+/// which they finished does not matter; only results of calls without an
+/// id are taken in order. Calls already in `executed` get their earlier
+/// result, and fresh results are added to it.
+fn correlate_results(
+    tool_calls: &[ToolCall],
+    fresh: Vec<ToolResult>,
+    executed: &mut HashMap<String, ToolResult>,
+) -> Vec<ToolResult> {
+    let mut by_id = HashMap::new();
+    let mut anonymous = Vec::new();
+    for result in fresh {
+        if result.tool_call_id.is_empty() {
+            anonymous.push(result);
+        } else {
+            by_id.insert(result.tool_call_id.clone(), result);
+        }
+    }
+    let mut anonymous = anonymous.into_iter();
+
+    tool_calls
+        .iter()
+        .map(|call| {
+            let result = if call.id.is_empty() {
+                anonymous.next()
+            } else if let Some(result) = executed.get(&call.id) {
+                return result.clone();
+            } else {
+                by_id.remove(&call.id)
+            };
+            let result = result.unwrap_or_else(|| {
+                ToolResult {
+                    is_error: true,
+                    ..ToolResult::new(call.id.clone(), "Error: tool not executed")
+                }
+                .with_tool_name(&call.function.name)
+            });
+            if !call.id.is_empty() {
+                // A repeated call must not deliver the same attachments again
+                let earlier = ToolResult {
+                    attachments: Vec::new(),
+                    ..result.clone()
+                };
+                executed.insert(call.id.clone(), earlier);
+            }
+            result
+        })
+        .collect()
+}
+
+/// Cap the [timeout argument](ToolFunction::timeout_argument) of a call to
+/// `tool` to `time_left`, rounded up to whole seconds
+///
+/// A call without the argument gets it unless the tool's default is shorter.
+fn cap_timeout(tool: &dyn ToolFunction, args: &mut serde_json::Value, time_left: Duration) {
+    let Some(name) = tool.timeout_argument() else {
+        return;
+    };
+    let Some(args) = args.as_object_mut() else {
+        return;
+    };
+    let left = time_left.as_secs_f64().ceil().max(1.0) as u64;
+    let timeout = args
+        .get(name)
+        .and_then(|t| t.as_u64())
+        .or_else(|| tool.definition().function.parameters["properties"][name]["default"].as_u64());
+    if timeout.is_none_or(|timeout| timeout > left) {
+        debug!(
+            "Capping {} of {} to {}s",
+            name,
+            tool.definition().function.name,
+            left
+        );
+        args.insert(name.to_string(), left.into());
+    }
+}
+
+/// Run `future` until `deadline`, returning `None` if the deadline passes first
+async fn until<F: std::future::Future>(
+    deadline: Option<tokio::time::Instant>,
+    future: F,
+) -> Option<F::Output> {
+    match deadline {
+        Some(deadline) => tokio::time::timeout_at(deadline, future).await.ok(),
+        None => Some(future.await),
+    }
+}
+
+// ============================================================================
+// Built-in Example Tools
+// ============================================================================
+
+/// A simple echo tool for testing
+pub struct EchoTool;
+
+impl ToolFunction for EchoTool {
+    fn definition(&self) -> Tool {
+        Tool::function(
+            "echo",
+            "Echo back the input message",
+            serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "message": {
+                        "type": "string",
+                        "description": "The message to echo back"
+                    }
+                },
+                "required": ["message"],
+                "additionalProperties": false
+            }),
+        )
+    }
+
+    fn execute(&self, args: serde_json::Value) -> Result<serde_json::Value> {
+        let message = args
+            .get("message")
+            .and_then(|m| m.as_str())
+            .ok_or_else(|| anyhow!("Missing 'message' argument"))?;
+        Ok(serde_json::json!({ "echoed": message }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rustclaw_types::ResponseImage;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Tool that records how many executions overlap
+    struct ConcurrencyProbeTool {
+        running: Arc<AtomicUsize>,
+        max_seen: Arc<AtomicUsize>,
+    }
+
+    impl ToolFunction for ConcurrencyProbeTool {
+        fn definition(&self) -> Tool {
+            Tool::function("probe", "Concurrency probe", serde_json::json!({}))
+        }
+
+        fn execute(&self, _args: serde_json::Value) -> Result<serde_json::Value> {
+            let now = self.running.fetch_add(1, Ordering::SeqCst) + 1;
+            self.max_seen.fetch_max(now, Ordering::SeqCst);
+            std::thread::sleep(Duration::from_millis(50));
+            self.running.fetch_sub(1, Ordering::SeqCst);
+            Ok(serde_json::json!({ "ok": true }))
+        }
+    }
+
+    /// Cacheable tool that counts its executions
+    struct CountingTool {
+        calls: Arc<AtomicUsize>,
+    }
+
+    impl ToolFunction for CountingTool {
+        fn definition(&self) -> Tool {
+            Tool::function(
+                "count",
+                "Counting tool",
+                serde_json::json!({
+                    "type": "object",
+                    "properties": { "a": { "type": "integer" } }
+                }),
+            )
+        }
+
+        fn is_cacheable(&self) -> bool {
+            true
+        }
+
+        fn execute(&self, _args: serde_json::Value) -> Result<serde_json::Value> {
+            let n = self.calls.fetch_add(1, Ordering::SeqCst) + 1;
+            Ok(serde_json::json!({ "n": n }))
+        }
+    }
+
+    #[test]
+    fn test_tool_cache() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let registry = ToolRegistry::new().with_cache_ttl(Duration::from_mins(1));
+        registry.register(Box::new(CountingTool {
+            calls: Arc::clone(&calls),
+        }));
+        registry.register(Box::new(EchoTool));
+        registry.register(Box::new(TouchTool));
+
+        let call = |id: &str, name: &str, args: &str| ToolCall {
+            id: id.to_string(),
+            call_type: "function".to_string(),
+            function: rustclaw_types::FunctionCall {
+                name: name.to_string(),
+                arguments: args.to_string(),
+            },
+        };
+        registry.execute_call(&call("1", "count", r#"{"a":1,"b":2}"#));
+        let cached = registry.execute_call(&call("2", "count", r#"{"b":2,"a":1}"#));
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        assert_eq!(cached.tool_call_id, "2");
+
+        // Arguments are canonicalized against the schema before the lookup
+        registry.execute_call(&call("2b", "count", r#"{"b":2,"a":" 1"}"#));
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        // Tools without side effects leave the cache alone, tools that may
+        // change files invalidate it
+        registry.execute_call(&call("3", "echo", r#"{"message":"hi"}"#));
+        registry.execute_call(&call("4", "count", r#"{"a":1,"b":2}"#));
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        registry.execute_call(&call("5", "touch", "{}"));
+        registry.execute_call(&call("6", "count", r#"{"a":1,"b":2}"#));
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    /// Tool that claims to write files
+    struct TouchTool;
+
+    impl ToolFunction for TouchTool {
+        fn definition(&self) -> Tool {
+            Tool::function("touch", "Touch tool", serde_json::json!({}))
+        }
+
+        fn capabilities(&self) -> ToolCapabilities {
+            ToolCapabilities {
+                writes_files: true,
+                ..ToolCapabilities::default()
+            }
+        }
+
+        fn execute(&self, _args: serde_json::Value) -> Result<serde_json::Value> {
+            Ok(serde_json::json!({ "ok": true }))
+        }
+    }
+
+    /// Deletes nothing, but refuses until `confirm_delete` is set
+    struct DeleteTool;
+
+    impl ToolFunction for DeleteTool {
+        fn definition(&self) -> Tool {
+            Tool::function("delete", "Delete tool", serde_json::json!({}))
+        }
+
+        fn execute(&self, args: serde_json::Value) -> Result<serde_json::Value> {
+            if args["confirm_delete"] == true {
+                return Ok(serde_json::json!({ "deleted": args["path"] }));
+            }
+            Ok(serde_json::json!({
+                "success": false,
+                "needs_confirmation": true,
+                "confirmation_type": "delete",
+                "confirm_arg": "confirm_delete",
+                "error": format!("This will delete {}", args["path"]),
+            }))
+        }
+    }
+
+    struct StreamingTool;
+
+    impl ToolFunction for StreamingTool {
+        fn definition(&self) -> Tool {
+            Tool::function("stream", "Streaming tool", serde_json::json!({}))
+        }
+
+        fn execute(&self, _args: serde_json::Value) -> Result<serde_json::Value> {
+            Ok(serde_json::json!("done"))
+        }
+
+        fn execute_streaming(
+            &self,
+            args: serde_json::Value,
+            output: &ToolOutputSink,
+        ) -> Result<serde_json::Value> {
+            for line in ["one", "two"] {
+                output.send(line.to_string())?;
+            }
+            self.execute(args)
+        }
+    }
+
+    /// Returns its arguments, taking a `timeout` of 30s by default
+    struct TimeoutTool;
+
+    impl ToolFunction for TimeoutTool {
+        fn definition(&self) -> Tool {
+            Tool::function(
+                "wait",
+                "Timeout tool",
+                serde_json::json!({
+                    "type": "object",
+                    "properties": { "timeout": { "type": "integer", "default": 30 } }
+                }),
+            )
+        }
+
+        fn timeout_argument(&self) -> Option<&str> {
+            Some("timeout")
+        }
+
+        fn execute(&self, args: serde_json::Value) -> Result<serde_json::Value> {
+            Ok(args)
+        }
+    }
+
+    /// Returns its `value` argument unchanged
+    struct ValueTool;
+
+    impl ToolFunction for ValueTool {
+        fn definition(&self) -> Tool {
+            Tool::function("value", "Value tool", serde_json::json!({}))
+        }
+
+        fn execute(&self, args: serde_json::Value) -> Result<serde_json::Value> {
+            Ok(args["value"].clone())
+        }
+    }
+
+    #[test]
+    fn test_tool_result_format() {
+        let call = |value: serde_json::Value| ToolCall {
+            id: "1".to_string(),
+            call_type: "function".to_string(),
+            function: rustclaw_types::FunctionCall {
+                name: "value".to_string(),
+                arguments: serde_json::json!({ "value": value }).to_string(),
+            },
+        };
+
+        // Bare strings are passed as plain text, structured results as JSON
+        let registry = ToolRegistry::new();
+        registry.register(Box::new(ValueTool));
+        let text = registry.execute_call(&call(serde_json::json!("say \"hi\"")));
+        assert_eq!(text.output, r#"say "hi""#);
+        assert_eq!(text.tool_name.as_deref(), Some("value"));
+        let structured = registry.execute_call(&call(serde_json::json!({ "n": 1 })));
+        assert_eq!(structured.output, r#"{"n":1}"#);
+
+        let registry = ToolRegistry::new().with_result_format(ToolResultFormat::Json);
+        registry.register(Box::new(ValueTool));
+        let text = registry.execute_call(&call(serde_json::json!("hi")));
+        assert_eq!(text.output, r#""hi""#);
+        let structured = registry.execute_call(&call(serde_json::json!({ "n": 1 })));
+        assert_eq!(structured.output, r#"{"n":1}"#);
+    }
+
+    #[test]
+    fn test_tool_errors() {
+        let call = |name: &str, arguments: &str| ToolCall {
+            id: "1".to_string(),
+            call_type: "function".to_string(),
+            function: rustclaw_types::FunctionCall {
+                name: name.to_string(),
+                arguments: arguments.to_string(),
+            },
+        };
+        let registry = ToolRegistry::new();
+        registry.register(Box::new(ValueTool));
+
+        // A result with an `error` field is still a result
+        let result = registry.execute_call(&call("value", r#"{"value":{"error":"none"}}"#));
+        assert!(!result.is_error());
+        assert_eq!(result.output, r#"{"error":"none"}"#);
+
+        let unknown = registry.execute_call(&call("missing", "{}"));
+        assert!(unknown.is_error());
+        assert_eq!(unknown.output, r#"{"error":"Unknown tool: missing"}"#);
+        assert!(registry.execute_call(&call("value", "{")).is_error());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_tool_allowlist() {
+        let call = |name: &str| ToolCall {
+            id: "1".to_string(),
+            call_type: "function".to_string(),
+            function: rustclaw_types::FunctionCall {
+                name: name.to_string(),
+                arguments: r#"{"value":1}"#.to_string(),
+            },
+        };
+        let registry = ToolRegistry::new().with_allowlist(["value".to_string()]);
+        registry.register(Box::new(ValueTool));
+        registry.register(Box::new(ItemsTool));
+
+        assert_eq!(registry.execute_call(&call("value")).output, "1");
+        // Registered and offered, but not allowed to run
+        assert_eq!(registry.get_tools().len(), 2);
+        for result in [
+            registry.execute_call(&call("items")),
+            registry.execute_call_blocking(&call("items")).await,
+        ] {
+            assert!(result.is_error());
+            assert_eq!(
+                result.output,
+                r#"{"error":"Tool 'items' is not permitted to run"}"#
+            );
+        }
+        assert!(registry.execute("items", serde_json::json!({})).is_err());
+    }
+
+    /// Returns its `value` argument as a list of items
+    struct ItemsTool;
+
+    impl ToolFunction for ItemsTool {
+        fn definition(&self) -> Tool {
+            Tool::function("items", "Items tool", serde_json::json!({}))
+        }
+
+        fn returns_items(&self) -> bool {
+            true
+        }
+
+        fn execute(&self, args: serde_json::Value) -> Result<serde_json::Value> {
+            Ok(args["value"].clone())
+        }
+    }
+
+    #[test]
+    fn test_items_fed_back_as_separate_parts() {
+        let call = |name: &str, value: serde_json::Value| ToolCall {
+            id: "1".to_string(),
+            call_type: "function".to_string(),
+            function: rustclaw_types::FunctionCall {
+                name: name.to_string(),
+                arguments: serde_json::json!({ "value": value }).to_string(),
+            },
+        };
+        let registry = ToolRegistry::new();
+        registry.register(Box::new(ItemsTool));
+        registry.register(Box::new(ValueTool));
+
+        let hits = serde_json::json!(["first hit", { "title": "second hit" }]);
+        let result = registry.execute_call(&call("items", hits.clone()));
+        assert_eq!(result.items, ["first hit", r#"{"title":"second hit"}"#]);
+        assert_eq!(result.output, hits.to_string());
+
+        // Arrays from other tools stay a single blob
+        assert!(registry.execute_call(&call("value", hits)).items.is_empty());
+        // So do non-array results of item tools
+        let single = registry.execute_call(&call("items", serde_json::json!("only")));
+        assert!(single.items.is_empty());
+
+        let message = ConversationTurn::from_result(result).to_chat_message();
+        let request = ProviderService::to_request_message(&message).unwrap();
+        let json = serde_json::to_value(&request).unwrap();
+        assert_eq!(json["role"], "tool");
+        assert_eq!(json["content"][0]["type"], "text");
+        assert_eq!(json["content"][0]["text"], "first hit");
+        assert_eq!(json["content"][1]["text"], r#"{"title":"second hit"}"#);
+    }
+
+    #[tokio::test]
+    async fn test_repeated_tool_call_id_runs_once() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let registry = ToolRegistry::new();
+        registry.register(Box::new(CountingTool {
+            calls: Arc::clone(&calls),
+        }));
+        let service = ProviderService::new(Provider::default()).with_tool_registry(registry);
+
+        let call = |id: &str| ToolCall {
+            id: id.to_string(),
+            call_type: "function".to_string(),
+            function: rustclaw_types::FunctionCall {
+                name: "count".to_string(),
+                arguments: "{}".to_string(),
+            },
+        };
+
+        let mut executed = HashMap::new();
+        let first = service
+            .run_tool_calls(&[call("a")], None, None, &mut executed, None)
+            .await;
+        assert_eq!(first[0].output, r#"{"n":1}"#);
+
+        // A retried iteration repeats call "a" alongside a new call
+        let retried = service
+            .run_tool_calls(&[call("a"), call("b")], None, None, &mut executed, None)
+            .await;
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+        assert_eq!(retried[0].tool_call_id, "a");
+        assert_eq!(retried[0].output, r#"{"n":1}"#);
+        assert_eq!(retried[1].tool_call_id, "b");
+        assert_eq!(retried[1].output, r#"{"n":2}"#);
+    }
+
+    #[test]
+    fn test_correlate_shuffled_results() {
+        let call = |id: &str, name: &str| ToolCall {
+            id: id.to_string(),
+            call_type: "function".to_string(),
+            function: rustclaw_types::FunctionCall {
+                name: name.to_string(),
+                arguments: "{}".to_string(),
+            },
+        };
+        let calls = [
+            call("a", "first"),
+            call("b", "second"),
+            call("", "anonymous"),
+            call("c", "third"),
+            call("d", "lost"),
+        ];
+
+        // Results arrive in a different order than the calls were made
+        let fresh = vec![
+            ToolResult::new("c".to_string(), "third result"),
+            ToolResult::new(String::new(), "anonymous result"),
+            ToolResult::new("a".to_string(), "first result"),
+            ToolResult::new("b".to_string(), "second result"),
+        ];
+        let mut executed = HashMap::new();
+        let results = correlate_results(&calls, fresh, &mut executed);
+
+        let pairs: Vec<(&str, &str)> = results
+            .iter()
+            .map(|r| (r.tool_call_id.as_str(), r.output.as_str()))
+            .collect();
+        assert_eq!(
+            pairs,
+            [
+                ("a", "first result"),
+                ("b", "second result"),
+                ("", "anonymous result"),
+                ("c", "third result"),
+                ("d", "Error: tool not executed"),
+            ]
+        );
+        assert_eq!(executed["c"].output, "third result");
+        assert_eq!(results[4].tool_name.as_deref(), Some("lost"));
+    }
+
+    #[tokio::test]
+    async fn test_execute_call_streaming() {
+        let registry = ToolRegistry::new();
+        registry.register(Box::new(StreamingTool));
+        let call = ToolCall {
+            id: "1".to_string(),
+            call_type: "function".to_string(),
+            function: rustclaw_types::FunctionCall {
+                name: "stream".to_string(),
+                arguments: "{}".to_string(),
+            },
+        };
+
+        let (output, mut lines) = tokio::sync::mpsc::unbounded_channel();
+        let result = registry
+            .execute_call_streaming(&call, Some(output), None, None)
+            .await;
+        assert_eq!(result.output, "done");
+        assert_eq!(lines.recv().await.as_deref(), Some("one"));
+        assert_eq!(lines.recv().await.as_deref(), Some("two"));
+        assert_eq!(lines.recv().await, None);
+
+        // Without a sink the tool runs normally
+        let result = registry.execute_call_blocking(&call).await;
+        assert_eq!(result.output, "done");
+    }
+
+    /// Async tool that waits before echoing its `message` argument
+    struct AsyncTool;
+
+    impl ToolFunction for AsyncTool {
+        fn definition(&self) -> Tool {
+            Tool::function("wait", "Async tool", serde_json::json!({}))
+        }
+
+        fn execute(&self, _args: serde_json::Value) -> Result<serde_json::Value> {
+            Err(anyhow!("ran synchronously"))
+        }
+
+        fn is_async(&self) -> bool {
+            true
+        }
+
+        fn execute_async(
+            &self,
+            args: serde_json::Value,
+        ) -> BoxFuture<'_, Result<serde_json::Value>> {
+            Box::pin(async move {
+                tokio::time::sleep(Duration::from_millis(200)).await;
+                Ok(args["message"].clone())
+            })
+        }
+    }
+
+    async fn run_async_tools() {
+        let service = ProviderService::new(Provider::default());
+        service.add_tool(Box::new(AsyncTool));
+        let calls: Vec<ToolCall> = (0..4)
+            .map(|i| ToolCall {
+                id: i.to_string(),
+                call_type: "function".to_string(),
+                function: rustclaw_types::FunctionCall {
+                    name: "wait".to_string(),
+                    arguments: format!(r#"{{"message":"call {}"}}"#, i),
+                },
+            })
+            .collect();
+
+        let started = std::time::Instant::now();
+        let results = service.execute_tool_calls(&calls).await;
+        let outputs: Vec<_> = results.iter().map(|r| r.output.as_str()).collect();
+        assert_eq!(outputs, ["call 0", "call 1", "call 2", "call 3"]);
+        // The calls wait together, not one after another
+        assert!(started.elapsed() < Duration::from_millis(600));
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn test_async_tool_current_thread() {
+        run_async_tools().await;
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_async_tool_multi_thread() {
+        run_async_tools().await;
+    }
+
+    /// Chat-scoped tool that reports the chat it ran in
+    struct ChatTool;
+
+    impl ToolFunction for ChatTool {
+        fn definition(&self) -> Tool {
+            Tool::function("chat", "Chat probe", serde_json::json!({}))
+        }
+
+        fn is_chat_scoped(&self) -> bool {
+            true
+        }
+
+        fn execute(&self, _args: serde_json::Value) -> Result<serde_json::Value> {
+            Err(anyhow!("needs a chat"))
+        }
+
+        fn execute_in_chat(
+            &self,
+            _args: serde_json::Value,
+            chat_id: i64,
+        ) -> Result<serde_json::Value> {
+            Ok(serde_json::json!(chat_id))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_chat_scoped_tool() {
+        let registry = ToolRegistry::new();
+        registry.register(Box::new(ChatTool));
+        let call = ToolCall {
+            id: "1".to_string(),
+            call_type: "function".to_string(),
+            function: rustclaw_types::FunctionCall {
+                name: "chat".to_string(),
+                arguments: "{}".to_string(),
+            },
+        };
+
+        let result = registry
+            .execute_call_streaming(&call, None, Some(42), None)
+            .await;
+        assert_eq!(result.output, "42");
+        let result = registry.execute_call_blocking(&call).await;
+        assert!(result.output.contains("can only be used in a chat"));
+    }
+
+    #[test]
+    fn test_cap_timeout() {
+        let capped = |args: serde_json::Value, left: Duration| {
+            let mut args = args;
+            cap_timeout(&TimeoutTool, &mut args, left);
+            args["timeout"].clone()
+        };
+        let left = Duration::from_millis(9500);
+        assert_eq!(capped(serde_json::json!({ "timeout": 60 }), left), 10);
+        assert_eq!(capped(serde_json::json!({ "timeout": 5 }), left), 5);
+        // Without the argument, the tool's default is capped
+        assert_eq!(capped(serde_json::json!({}), left), 10);
+        assert!(capped(serde_json::json!({}), Duration::from_secs(60)).is_null());
+        assert_eq!(capped(serde_json::json!({}), Duration::ZERO), 1);
+
+        // Tools without a timeout are left alone
+        let mut args = serde_json::json!({ "timeout": 60 });
+        cap_timeout(&StreamingTool, &mut args, left);
+        assert_eq!(args["timeout"], 60);
+    }
+
+    #[tokio::test]
+    async fn test_max_duration() {
+        // An expired deadline stops the run before any model request
+        let service = ProviderService::new(Provider::default()).with_max_duration(Duration::ZERO);
+        let response = service.complete_agentic(&[], "hello", 5).await.unwrap();
+        assert_eq!(response.finish_reason, "timeout");
+        assert!(response.content.contains("Timed out"));
+
+        let deadline = tokio::time::Instant::now() + Duration::from_millis(20);
+        let slow = tokio::time::sleep(Duration::from_secs(5));
+        assert!(until(Some(deadline), slow).await.is_none());
+        assert_eq!(until(Some(deadline), async { 1 }).await, Some(1));
+        assert_eq!(until(None, async { 2 }).await, Some(2));
+    }
+
+    #[tokio::test]
+    async fn test_failed_run_keeps_usage() {
+        // The second request fails after the first one used tokens
+        let backend = Arc::new(backend::MockBackend::new().tool_call(
+            "call_1",
+            "echo",
+            serde_json::json!({ "message": "one" }),
+        ));
+        let service = ProviderService::new(Provider::default()).with_backend(backend);
+        service.add_tool(Box::new(EchoTool));
+
+        let error = service.complete_agentic(&[], "hello", 5).await.unwrap_err();
+        assert_eq!(UsageError::usage_of(&error).total_tokens(), 15);
+    }
+
+    #[tokio::test]
+    async fn test_mock_backend_tool_round_trip() {
+        let backend = Arc::new(
+            backend::MockBackend::new()
+                .tool_call("call_1", "echo", serde_json::json!({ "message": "one" }))
+                .tool_call("call_2", "echo", serde_json::json!({ "message": "two" }))
+                .text("done"),
+        );
+        let service = ProviderService::new(Provider::default()).with_backend(backend.clone());
+        service.add_tool(Box::new(EchoTool));
+
+        let response = service.complete_agentic(&[], "hello", 5).await.unwrap();
+        assert_eq!(response.content, "done");
+        assert_eq!(response.finish_reason, "stop");
+        assert_eq!(response.usage.total_tokens(), 45);
+
+        // Each round's request carries the results of the calls before it
+        let requests = backend.requests();
+        assert_eq!(requests.len(), 3);
+        let second = serde_json::to_string(&requests[1].messages).unwrap();
+        assert!(second.contains("call_1") && second.contains(r#"\"echoed\":\"one\""#));
+        assert!(!second.contains("call_2"));
+        let third = serde_json::to_string(&requests[2].messages).unwrap();
+        assert!(third.contains(r#"\"echoed\":\"two\""#));
+    }
+
+    #[tokio::test]
+    async fn test_continue_if_truncated() {
+        let backend = Arc::new(
+            backend::MockBackend::new()
+                .truncated_text("one, ")
+                .truncated_text("two, ")
+                .text("three"),
+        );
+        let service = ProviderService::new(Provider::default())
+            .with_max_continuations(2)
+            .with_backend(backend.clone());
+
+        let response = service.complete_agentic(&[], "count", 5).await.unwrap();
+        assert_eq!(response.content, "one, two, three");
+        assert_eq!(response.finish_reason, "stop");
+        assert!(!response.is_truncated());
+        assert_eq!(response.usage.total_tokens(), 45);
+
+        // Each continuation replays the text so far, then asks to go on
+        let requests = backend.requests();
+        assert_eq!(requests.len(), 3);
+        let last = serde_json::to_string(&requests[2].messages).unwrap();
+        assert!(last.contains("one, two, ") && last.contains(CONTINUE_PROMPT));
+
+        // Past the limit, the response is returned truncated
+        let backend = Arc::new(
+            backend::MockBackend::new()
+                .truncated_text("one, ")
+                .truncated_text("two, "),
+        );
+        let service = ProviderService::new(Provider::default())
+            .with_max_continuations(1)
+            .with_backend(backend.clone());
+        let response = service.complete_agentic(&[], "count", 5).await.unwrap();
+        assert_eq!(response.content, "one, two, ");
+        assert!(response.is_truncated());
+        assert_eq!(backend.requests().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_complete_stream_whole_response() {
+        use futures::StreamExt;
+
+        // A backend that can't stream sends each response as one chunk
+        let backend = Arc::new(backend::MockBackend::new().text("all at once").tool_call(
+            "call_1",
+            "echo",
+            serde_json::json!({ "message": "hi" }),
+        ));
+        let service = ProviderService::new(Provider::default())
+            .with_allowed_models(vec!["gpt-4o".to_string()])
+            .with_backend(backend.clone());
+        service.add_tool(Box::new(EchoTool));
+
+        let deltas: Vec<_> = service
+            .complete_stream(&[], "hello", None)
+            .await
+            .unwrap()
+            .collect()
+            .await;
+        assert_eq!(deltas.len(), 2);
+        assert!(
+            matches!(&deltas[0], Ok(stream::StreamDelta::Content(text)) if text == "all at once")
+        );
+        assert!(
+            matches!(&deltas[1], Ok(stream::StreamDelta::Usage(usage)) if usage.total_tokens() == 15)
+        );
+
+        let deltas: Vec<_> = service
+            .complete_stream(&[], "hello", Some("gpt-4o"))
+            .await
+            .unwrap()
+            .collect()
+            .await;
+        let Some(Ok(stream::StreamDelta::ToolCall(call))) = deltas.first() else {
+            panic!("expected a tool call, got {:?}", deltas);
+        };
+        assert_eq!(call.id, "call_1");
+        assert_eq!(call.function.arguments, r#"{"message":"hi"}"#);
+        // The call is only reported, not executed
+        let requests = backend.requests();
+        assert_eq!(requests.len(), 2);
+        assert!(requests[0].tools.is_some());
+        assert_eq!(requests[0].model, "gpt-4o-mini");
+        assert_eq!(requests[1].model, "gpt-4o");
+        // The provider is asked for the tokens used
+        let options = requests[0].stream_options.unwrap();
+        assert_eq!(options.include_usage, Some(true));
+    }
+
+    #[tokio::test]
+    async fn test_malformed_arguments_reprompt() {
+        let backend = Arc::new(
+            backend::MockBackend::new()
+                .raw_tool_call("call_1", "echo", r#"{"message": "hi""#)
+                .tool_call("call_2", "echo", serde_json::json!({ "message": "hi" }))
+                .text("done"),
+        );
+        let service = ProviderService::new(Provider::default()).with_backend(backend.clone());
+        service.add_tool(Box::new(EchoTool));
+
+        let response = service.complete_agentic(&[], "hello", 5).await.unwrap();
+        assert_eq!(response.content, "done");
+
+        // The re-prompt has the parse error and the tool's schema
+        let requests = backend.requests();
+        let correction = serde_json::to_string(&requests[1].messages).unwrap();
+        assert!(correction.contains("Failed to parse arguments: EOF while parsing an object"));
+        assert!(correction.contains("must be a JSON object matching this schema"));
+        assert!(correction.contains(r#"\"required\":[\"message\"]"#));
+        assert!(correction.contains("Call echo again"));
+
+        // Arguments still malformed after the correction stop the run
+        let backend = Arc::new(
+            backend::MockBackend::new()
+                .raw_tool_call("call_1", "echo", "message=hi")
+                .raw_tool_call("call_2", "echo", "{message: hi}")
+                .text("never sent"),
+        );
+        let service = ProviderService::new(Provider::default()).with_backend(backend.clone());
+        service.add_tool(Box::new(EchoTool));
+
+        let response = service.complete_agentic(&[], "hello", 5).await.unwrap();
+        assert_eq!(response.finish_reason, "invalid_arguments");
+        assert_eq!(
+            response.content,
+            "[The model sent invalid arguments for echo]"
+        );
+        assert_eq!(backend.requests().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_summarize_tool_results() {
+        let long = "x".repeat(2000);
+        let backend = Arc::new(
+            backend::MockBackend::new()
+                .tool_call("call_1", "echo", serde_json::json!({ "message": long }))
+                .text("Echoed 2000 x's.")
+                .text("done"),
+        );
+        let service = ProviderService::new(Provider::default())
+            .with_backend(backend.clone())
+            .with_summarize_tool_results_at(100)
+            .with_tool_summary_model("cheap-model");
+        service.add_tool(Box::new(EchoTool));
+
+        let (recorder, mut recorded) = tokio::sync::mpsc::unbounded_channel();
+        let response = service
+            .complete_agentic_recorded(&[], "hello", &recorder, None, None, None, None)
+            .await
+            .unwrap();
+        assert_eq!(response.content, "done");
+        // The summary's tokens count towards the run
+        assert_eq!(response.usage.total_tokens(), 45);
+
+        // The output is summarized with the summary model, without tools
+        let requests = backend.requests();
+        assert_eq!(requests.len(), 3);
+        assert_eq!(requests[1].model, "cheap-model");
+        assert!(requests[1].tools.is_none());
+        let summarized = serde_json::to_string(&requests[1].messages).unwrap();
+        assert!(summarized.contains(&long));
+
+        // The model gets the summary, the recorder the full output
+        let last = serde_json::to_string(&requests[2].messages).unwrap();
+        assert!(last.contains("[Summary of echo output of about"));
+        assert!(last.contains("Echoed 2000 x's."));
+        // Left only in the call's arguments
+        assert_eq!(last.matches(&long).count(), 1);
+        drop(recorder);
+        let mut full = false;
+        while let Some(turn) = recorded.recv().await {
+            full |= turn.content.is_some_and(|content| content.contains(&long));
+        }
+        assert!(full);
+        // The summary model is not selectable per chat
+        assert_eq!(service.resolve_model(Some("cheap-model")), "gpt-4o-mini");
+
+        // Output beyond the summary model's context window is left out
+        let backend = Arc::new(
+            backend::MockBackend::new()
+                .tool_call("call_1", "echo", serde_json::json!({ "message": long }))
+                .text("Echoed many x's.")
+                .text("done"),
+        );
+        let service = ProviderService::new(Provider::default())
+            .with_backend(backend.clone())
+            .with_summarize_tool_results_at(100)
+            .with_tool_summary_model("cheap-model")
+            .with_model_context_windows(HashMap::from([("cheap-model".to_string(), 200)]));
+        service.add_tool(Box::new(EchoTool));
+        service.complete_agentic(&[], "hello", 5).await.unwrap();
+        let summarized = serde_json::to_string(&backend.requests()[1].messages).unwrap();
+        assert!(summarized.contains(&"x".repeat(500)));
+        assert!(!summarized.contains(&"x".repeat(600)));
+
+        // Results within the threshold are fed back as they are
+        let backend = Arc::new(
+            backend::MockBackend::new()
+                .tool_call("call_1", "echo", serde_json::json!({ "message": "hi" }))
+                .text("done"),
+        );
+        let service = ProviderService::new(Provider::default())
+            .with_backend(backend.clone())
+            .with_summarize_tool_results_at(100);
+        service.add_tool(Box::new(EchoTool));
+        service.complete_agentic(&[], "hello", 5).await.unwrap();
+        assert_eq!(backend.requests().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_mock_backend_reports_tool_progress() {
+        let backend = Arc::new(
+            backend::MockBackend::new()
+                .tool_call("call_1", "echo", serde_json::json!({ "message": "one" }))
+                .text("done"),
+        );
+        let service = ProviderService::new(Provider::default()).with_backend(backend);
+        service.add_tool(Box::new(EchoTool));
+
+        let (recorder, _turns) = tokio::sync::mpsc::unbounded_channel();
+        let (progress, mut events) = tokio::sync::mpsc::unbounded_channel();
+        let response = service
+            .complete_agentic_recorded(&[], "hello", &recorder, None, None, None, Some(&progress))
+            .await
+            .unwrap();
+        assert_eq!(response.content, "done");
+
+        drop(progress);
+        assert_eq!(
+            events.recv().await,
+            Some(ToolProgress {
+                iteration: 1,
+                tools: vec!["echo".to_string()],
+            })
+        );
+        assert_eq!(events.recv().await, None);
+    }
+
+    #[tokio::test]
+    async fn test_mock_backend_max_iterations() {
+        let backend = Arc::new(
+            backend::MockBackend::new().repeat("echo", serde_json::json!({ "message": "again" })),
+        );
+        let service = ProviderService::new(Provider::default()).with_backend(backend.clone());
+        service.add_tool(Box::new(EchoTool));
+
+        let response = service.complete_agentic(&[], "hello", 3).await.unwrap();
+        assert_eq!(response.finish_reason, "max_iterations");
+        assert_eq!(backend.requests().len(), 3);
+
+        // An exhausted script surfaces as an error
+        let service = ProviderService::new(Provider::default())
+            .with_backend(Arc::new(backend::MockBackend::new()));
+        assert!(service.complete_agentic(&[], "hello", 3).await.is_err());
+    }
+
+    #[test]
+    fn test_tool_manifest() {
+        let service = ProviderService::new(Provider::default());
+        service.add_tool(Box::new(EchoTool));
+
+        let manifest = service.tool_manifest().unwrap();
+        let tools = manifest.as_array().unwrap();
+        assert_eq!(tools.len(), 1);
+        assert_eq!(tools[0]["type"], "function");
+        assert_eq!(tools[0]["function"]["name"], "echo");
+        assert_eq!(
+            tools[0]["function"]["parameters"],
+            EchoTool.definition().function.parameters
+        );
+    }
+
+    #[tokio::test]
+    async fn test_add_and_remove_tools_at_runtime() {
+        let service = Arc::new(ProviderService::new(Provider::default()));
+        let shared = service.tools().clone();
+
+        // Tools added through a shared handle are visible to the service
+        let writer = tokio::spawn(async move { shared.register(Box::new(EchoTool)) });
+        assert!(!writer.await.unwrap());
+        assert!(service.tools().contains("echo"));
+        assert_eq!(service.tools().get_tools().len(), 1);
+
+        assert!(service.add_tool(Box::new(EchoTool)));
+        assert!(service.remove_tool("echo"));
+        assert!(!service.remove_tool("echo"));
+        assert!(service.tools().is_empty());
+    }
+
+    #[test]
+    fn test_tool_registry() {
+        let registry = ToolRegistry::new();
+        registry.register(Box::new(EchoTool));
+
+        assert!(!registry.is_empty());
+        assert_eq!(registry.get_tools().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_max_concurrent_tools() {
+        let max_seen = Arc::new(AtomicUsize::new(0));
+        let registry = ToolRegistry::new();
+        registry.register(Box::new(ConcurrencyProbeTool {
+            running: Arc::new(AtomicUsize::new(0)),
+            max_seen: Arc::clone(&max_seen),
+        }));
+
+        let service = ProviderService::new(Provider::default())
+            .with_tool_registry(registry)
+            .with_max_concurrent_tools(2);
+
+        let calls: Vec<ToolCall> = (0..6)
+            .map(|i| ToolCall {
+                id: format!("call_{}", i),
+                call_type: "function".to_string(),
+                function: rustclaw_types::FunctionCall {
+                    name: "probe".to_string(),
+                    arguments: "{}".to_string(),
+                },
+            })
+            .collect();
+
+        let results = service.execute_tool_calls(&calls).await;
+
+        assert_eq!(results.len(), 6);
+        assert_eq!(results[3].tool_call_id, "call_3");
+        assert_eq!(max_seen.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_gemini_compatible_schema() {
+        let schema = EchoTool.definition().function.parameters;
+        let cleaned = gemini_compatible_schema(schema);
+
+        assert!(cleaned.get("additionalProperties").is_none());
+        assert_eq!(cleaned["properties"]["message"]["type"], "string");
+        assert_eq!(cleaned["required"][0], "message");
+    }
+
+    #[test]
+    fn test_to_request_message_assistant_with_tools() {
+        let call = ToolCall {
+            id: "call_1".to_string(),
+            call_type: "function".to_string(),
+            function: rustclaw_types::FunctionCall {
+                name: "echo".to_string(),
+                arguments: r#"{"message":"hi"}"#.to_string(),
+            },
+        };
+        let message = ChatMessage::assistant_with_tools(None, vec![call]);
+
+        let request = ProviderService::to_request_message(&message).unwrap();
+        let json = serde_json::to_value(&request).unwrap();
+
+        assert_eq!(json["role"], "assistant");
+        assert_eq!(json["tool_calls"][0]["id"], "call_1");
+        assert_eq!(json["tool_calls"][0]["function"]["name"], "echo");
+        assert!(json.get("content").is_none());
+    }
+
+    #[test]
+    fn test_build_messages_roles() {
+        let service = ProviderService::new(Provider::openai("gpt-4o")).with_system_prompt("Hi.");
+        let history = [
+            Message::new(
+                1,
+                rustclaw_types::User::new(42),
+                MessageContent::Text("Who are you?".to_string()),
+            ),
+            Message::new(
+                1,
+                rustclaw_types::User::new(0),
+                MessageContent::Text("An assistant.".to_string()),
+            ),
+        ];
+
+        let messages = service
+            .build_messages(&history, "Thanks", None, None)
+            .unwrap();
+        let roles: Vec<_> = messages
+            .iter()
+            .map(|message| serde_json::to_value(message).unwrap()["role"].clone())
+            .collect();
+        assert_eq!(roles, ["system", "user", "assistant", "user"]);
+    }
+
+    #[test]
+    fn test_build_messages_replays_tool_rounds() {
+        let service = ProviderService::new(Provider::openai("gpt-4o"));
+        let start = chrono::Utc::now() - chrono::Duration::minutes(1);
+        let at = |seconds| start + chrono::Duration::seconds(seconds);
+        let message = |user, text: &str, seconds| {
+            let mut message = Message::new(
+                1,
+                rustclaw_types::User::new(user),
+                MessageContent::Text(text.to_string()),
+            );
+            message.timestamp = at(seconds);
+            message
+        };
+        let call = |id: &str| ToolCall {
+            id: id.to_string(),
+            call_type: "function".to_string(),
+            function: rustclaw_types::FunctionCall {
+                name: "time".to_string(),
+                arguments: "{}".to_string(),
+            },
+        };
+        let round = |turn: ConversationTurn, seconds| {
+            ConversationTurn::from_chat_message(turn.to_chat_message(), at(seconds))
+        };
+
+        let history = [
+            message(42, "What time is it?", 0),
+            message(0, "It is noon.", 3),
+            message(42, "Thanks", 10),
+        ];
+        let metadata = ConversationMetadata {
+            tool_rounds: vec![
+                round(
+                    ConversationTurn::assistant_with_tools(None, vec![call("c0")]),
+                    -5,
+                ),
+                round(
+                    ConversationTurn::assistant_with_tools(None, vec![call("c1")]),
+                    1,
+                ),
+                round(ConversationTurn::tool_result("c1", "12:00"), 2),
+                // Never answered, e.g. cut short by a restart
+                round(
+                    ConversationTurn::assistant_with_tools(None, vec![call("c2")]),
+                    4,
+                ),
+                round(ConversationTurn::tool_result("c3", "orphan"), 5),
+            ],
+            ..Default::default()
+        };
+
+        let messages = service
+            .build_messages(&history, "", None, Some(&metadata))
+            .unwrap();
+        let messages: Vec<_> = messages
+            .iter()
+            .map(|message| serde_json::to_value(message).unwrap())
+            .collect();
+        let roles: Vec<_> = messages.iter().map(|m| m["role"].clone()).collect();
+        assert_eq!(
+            roles,
+            ["system", "user", "assistant", "tool", "assistant", "user"]
+        );
+        assert_eq!(messages[2]["tool_calls"][0]["id"], "c1");
+        assert_eq!(messages[3]["tool_call_id"], "c1");
+        assert_eq!(messages[4]["content"], "It is noon.");
+    }
+
+    #[test]
+    fn test_preview() {
+        assert_eq!(preview("hello", 5), "hello");
+        assert_eq!(preview("hello!", 5), "hello...");
+        // Counts characters, not bytes
+        assert_eq!(preview("äöü", 3), "äöü");
+        assert_eq!(preview("äöüß", 3), "äöü...");
+        assert_eq!(preview("", 0), "");
+        assert_eq!(preview("x", 0), "...");
+    }
+
+    #[test]
+    fn test_skill_summaries_shared_with_clones() {
+        let service = ProviderService::new(Provider::default())
+            .with_skill_summaries(vec!["- first: The first skill".to_string()]);
+        let clone = service.clone();
+        assert!(clone.assemble_system_prompt(None).contains("- first"));
+
+        // A new ranking reaches every clone, e.g. one made for a retry
+        service.set_skill_summaries(vec!["- second: The second skill".to_string()]);
+        let prompt = clone.assemble_system_prompt(None);
+        assert!(prompt.contains("- second") && !prompt.contains("- first"));
+    }
+
+    #[test]
+    fn test_output_preview() {
+        let service = ProviderService::new(Provider::default()).with_tool_output_preview(5);
+        assert_eq!(
+            service.output_preview("hello!").as_deref(),
+            Some("hello...")
+        );
+        let service = service.with_tool_output_preview(0);
+        assert_eq!(service.output_preview("hello!"), None);
+    }
+
+    #[test]
+    fn test_lossy_utf8() {
+        assert_eq!(lossy_utf8("grüß".as_bytes()), ("grüß".to_string(), false));
+        assert_eq!(
+            lossy_utf8(b"ok \xff\xfe done"),
+            ("ok \u{FFFD}\u{FFFD} done".to_string(), true)
+        );
+        // A character cut off at the end is invalid too
+        assert_eq!(lossy_utf8(b"gr\xc3"), ("gr\u{FFFD}".to_string(), true));
+    }
+
+    #[test]
+    fn test_truncate_utf8() {
+        assert_eq!(truncate_utf8("short", 10), "short");
+        assert_eq!(truncate_utf8("grüß", 3), "gr");
+        assert_eq!(truncate_utf8("grüß", 4), "grü");
+        assert_eq!(truncate_utf8("日本", 0), "");
+
+        // Every cut of CJK text falls back to a character boundary
+        let text = "日本語のテキスト".repeat(2000);
+        for max_bytes in 14990..15010 {
+            let cut = truncate_utf8(&text, max_bytes);
+            assert!(cut.len() <= max_bytes && cut.len() > max_bytes - 3);
+            assert!(text.starts_with(cut));
+        }
+    }
+
+    #[test]
+    fn test_reasoning_models() {
+        assert!(is_reasoning_model("o1"));
+        assert!(is_reasoning_model("o3-mini"));
+        assert!(is_reasoning_model("openai/O4-mini"));
+        assert!(!is_reasoning_model("gpt-4o"));
+        assert!(!is_reasoning_model("o1x"));
+
+        let service = ProviderService::new(Provider::default())
+            .with_temperature(0.2)
+            .with_max_output_tokens(500);
+        let request = |model: &str| {
+            let mut args = CreateChatCompletionRequestArgs::default();
+            args.model(model).messages(Vec::new());
+            service.apply_sampling(&mut args, model);
+            serde_json::to_value(args.build().unwrap()).unwrap()
+        };
+
+        let regular = request("gpt-4o");
+        assert!((regular["temperature"].as_f64().unwrap() - 0.2).abs() < 1e-6);
+        assert_eq!(regular["max_tokens"], 500);
+        assert!(regular.get("max_completion_tokens").is_none());
+
+        let reasoning = request("o3-mini");
+        assert!(reasoning.get("temperature").is_none());
+        assert!(reasoning.get("max_tokens").is_none());
+        assert_eq!(reasoning["max_completion_tokens"], 500);
+    }
+
+    #[test]
+    fn test_text_tool_call() {
+        assert_eq!(
+            parse_text_tool_call(r#"{"name": "echo", "arguments": {"message": "hi"}}"#),
+            Some(("echo".to_string(), r#"{"message":"hi"}"#.to_string()))
+        );
+        assert_eq!(
+            parse_text_tool_call(
+                "```json\n{\"type\": \"function\", \"function\": {\"name\": \"echo\", \"arguments\": \"{}\"}}\n```"
+            ),
+            Some(("echo".to_string(), "{}".to_string()))
+        );
+        assert_eq!(parse_text_tool_call("Hello there"), None);
+        assert_eq!(parse_text_tool_call(r#"{"result": 42}"#), None);
+
+        // Only calls to registered tools are recovered
+        let registry = ToolRegistry::new();
+        registry.register(Box::new(EchoTool));
+        let service = ProviderService::new(Provider::default()).with_tool_registry(registry);
+        let call = service
+            .text_tool_call(
+                Some(r#"{"name": "echo", "parameters": {"message": "hi"}}"#),
+                2,
+            )
+            .unwrap();
+        assert_eq!(call.id, "text_call_2");
+        assert_eq!(call.function.name, "echo");
+        assert!(service
+            .text_tool_call(Some(r#"{"name": "launch_rockets"}"#), 0)
+            .is_none());
+    }
+
+    #[test]
+    fn test_split_reasoning() {
+        assert_eq!(split_reasoning("Hello"), (None, "Hello".to_string()));
+        assert_eq!(
+            split_reasoning("<think>\nUser greets me.\n</think>\n\nHello!"),
+            (Some("User greets me.".to_string()), "Hello!".to_string())
+        );
+        assert_eq!(
+            split_reasoning("<think></think>Hi"),
+            (None, "Hi".to_string())
+        );
+        assert_eq!(
+            split_reasoning("<think>Still going"),
+            (Some("Still going".to_string()), String::new())
+        );
+    }
+
+    #[test]
+    fn test_agentic_response_truncated() {
+        let response = AgenticResponse {
+            content: "partial".to_string(),
+            finish_reason: "length".to_string(),
+            usage: TokenUsage::default(),
+            confirmations: Vec::new(),
+            attachments: Vec::new(),
+        };
+        assert!(response.is_truncated());
+
+        let response = AgenticResponse {
+            content: "done".to_string(),
+            finish_reason: "stop".to_string(),
+            usage: TokenUsage::default(),
+            confirmations: Vec::new(),
+            attachments: Vec::new(),
+        };
+        assert!(!response.is_truncated());
+    }
+
+    #[test]
+    fn test_agentic_response_parts() {
+        let image = ResponsePart::Image(ResponseImage {
+            data: b"hi".to_vec(),
+            mime_type: "image/png".to_string(),
+        });
+        let response = AgenticResponse {
+            content: "Here is the chart".to_string(),
+            finish_reason: "stop".to_string(),
+            usage: TokenUsage::default(),
+            confirmations: Vec::new(),
+            attachments: vec![image.clone()],
+        };
+        assert_eq!(
+            response.parts(),
+            vec![ResponsePart::Text("Here is the chart".to_string()), image]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_confirmation_pauses_and_resumes() {
+        let backend = Arc::new(
+            backend::MockBackend::new()
+                .tool_calls(&[
+                    ("call_1", "delete", serde_json::json!({ "path": "a.txt" })),
+                    ("call_2", "echo", serde_json::json!({ "message": "hi" })),
+                    ("call_3", "delete", serde_json::json!({ "path": "b.txt" })),
+                ])
+                .text("Deleted both"),
+        );
+        let service = ProviderService::new(Provider::default()).with_backend(backend.clone());
+        service.add_tool(Box::new(EchoTool));
+        service.add_tool(Box::new(DeleteTool));
+
+        // Every refused call of the round is kept, and the loop stops
+        let paused = service.complete_agentic(&[], "clean up", 5).await.unwrap();
+        assert!(paused.needs_confirmation());
+        assert_eq!(paused.finish_reason, "needs_confirmation");
+        let calls: Vec<&str> = paused
+            .confirmations
+            .iter()
+            .map(|c| c.call.id.as_str())
+            .collect();
+        assert_eq!(calls, ["call_1_confirmed", "call_3_confirmed"]);
+        assert_eq!(backend.requests().len(), 1);
+
+        let (recorder, mut recorded) = tokio::sync::mpsc::unbounded_channel();
+        let resumed = service
+            .resume_confirmed(
+                &[],
+                "Yes, go ahead.",
+                &paused.confirmations,
+                &recorder,
+                None,
+                None,
+                None,
+                None,
+            )
+            .await
+            .unwrap();
+        assert_eq!(resumed.content, "Deleted both");
+        assert!(!resumed.needs_confirmation());
+
+        // The confirmed calls ran and the model saw their results
+        let resumed_request = serde_json::to_string(&backend.requests()[1].messages).unwrap();
+        assert!(resumed_request.contains("call_1_confirmed"));
+        assert!(resumed_request.contains(r#"\"deleted\":\"b.txt\""#));
+        drop(recorder);
+        let mut turns = Vec::new();
+        while let Some(turn) = recorded.recv().await {
+            turns.push(turn);
+        }
+        assert_eq!(turns.len(), 3);
+    }
+
+    #[test]
+    fn test_pending_confirmation() {
+        let call = ToolCall {
+            id: "call_1".to_string(),
+            call_type: "function".to_string(),
+            function: rustclaw_types::FunctionCall {
+                name: "bash".to_string(),
+                arguments: r#"{"command":"rm notes.txt"}"#.to_string(),
+            },
+        };
+        let refusal = ToolResult::from_json(
+            "call_1".to_string(),
+            &serde_json::json!({
+                "success": false,
+                "needs_confirmation": true,
+                "confirmation_type": "destructive",
+                "confirm_arg": "confirm_destructive",
+                "error": "This will delete files"
+            }),
+        );
+
+        let pending = PendingConfirmation::from_result(&call, &refusal).unwrap();
+        assert_eq!(pending.confirmation_type, "destructive");
+        assert_eq!(pending.reason, "This will delete files");
+        assert_eq!(pending.call.id, "call_1_confirmed");
+        let args: serde_json::Value =
+            serde_json::from_str(&pending.call.function.arguments).unwrap();
+        assert_eq!(args["command"], "rm notes.txt");
+        assert_eq!(args["confirm_destructive"], true);
+
+        // Ordinary results and requests without a confirmation argument
+        // are left to the model
+        let ok = ToolResult::new("call_1".to_string(), r#"{"success":true}"#);
+        assert!(PendingConfirmation::from_result(&call, &ok).is_none());
+        let no_arg = ToolResult::from_json(
+            "call_1".to_string(),
+            &serde_json::json!({"needs_confirmation": true}),
+        );
+        assert!(PendingConfirmation::from_result(&call, &no_arg).is_none());
+    }
+
+    #[test]
+    fn test_allowed_models() {
+        let service = ProviderService::new(Provider::ollama("llama3", "http://localhost:11434"))
+            .with_allowed_models(vec!["qwen3".to_string(), "llama3".to_string()]);
+
+        assert!(service.is_model_allowed("llama3"));
+        assert!(service.is_model_allowed("qwen3"));
+        assert!(!service.is_model_allowed("gpt-4o"));
+        assert_eq!(service.allowed_models(), vec!["llama3", "qwen3"]);
+    }
+
+    #[test]
+    fn test_alternative_providers() {
+        let service = ProviderService::new(Provider::openai("gpt-4o"))
+            .with_allowed_models(vec!["gpt-4o-mini".to_string()])
+            .with_alternative_providers(vec![
+                Provider::ollama("llama3", "http://localhost:11434"),
+                Provider::openai("gpt-4.1"),
+            ]);
+
+        assert_eq!(service.providers(), vec!["openai", "ollama"]);
+        assert!(service.is_provider_allowed("ollama"));
+        assert!(!service.is_provider_allowed("gemini"));
+
+        let ollama = service.for_provider(Some("ollama"));
+        assert_eq!(ollama.provider_name(), "Ollama");
+        assert_eq!(ollama.allowed_models(), vec!["llama3"]);
+        assert!(!ollama.is_model_allowed("gpt-4o-mini"));
+
+        // Unknown providers fall back to the configured one
+        assert_eq!(
+            service.for_provider(Some("gemini")).default_model(),
+            "gpt-4o"
+        );
+        assert!(matches!(service.for_provider(None), Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn test_fit_history() {
+        let mut windows = HashMap::new();
+        windows.insert("llama3".to_string(), 100);
+        let service = ProviderService::new(Provider::ollama("llama3", "http://localhost:11434"))
+            .with_system_prompt("")
+            .with_model_context_windows(windows);
+
+        let history: Vec<Message> = (0..5)
+            .map(|i| {
+                Message::new(
+                    1,
+                    rustclaw_types::User::new(1),
+                    MessageContent::Text(format!("{}: {}", i, "x".repeat(78))),
+                )
+            })
+            .collect();
+
+        // Each message is ~20 tokens; the prompt leaves room for three
+        let fitted = service
+            .fit_history(&history, &"y".repeat(160), None)
+            .unwrap();
+        assert_eq!(fitted.len(), 3);
+        assert_eq!(fitted[0].id, history[2].id);
+
+        let err = service
+            .fit_history(&history, &"y".repeat(800), None)
+            .unwrap_err();
+        assert!(err.to_string().starts_with("Message too long"));
+        assert_eq!(service.context_window("qwen3"), DEFAULT_CONTEXT_WINDOW);
+    }
+
+    #[test]
+    fn test_context_window_lookup() {
+        let mut windows = HashMap::new();
+        windows.insert("llama3".to_string(), 4_096);
+        let service = ProviderService::new(Provider::openai("gpt-4o-mini"))
+            .with_context_window(32_000)
+            .with_model_context_windows(windows);
+
+        // Configured windows win over known ones
+        assert_eq!(service.context_window("llama3"), 4_096);
+        // Known models, including dated and provider-prefixed names
+        assert_eq!(service.context_window("gpt-4o-mini-2024-07-18"), 128_000);
+        assert_eq!(service.context_window("gpt-4"), 8_192);
+        assert_eq!(service.context_window("llama3.1:8b"), 128_000);
+        assert_eq!(
+            service.context_window("anthropic/claude-3.5-sonnet"),
+            200_000
+        );
+        // Unknown models fall back to the configured default
+        assert_eq!(service.context_window("my-finetune"), 32_000);
+
+        // The configured default never overrides a known window
+        let service = service.with_context_window(1_000_000);
+        assert_eq!(service.context_window("gpt-4"), 8_192);
+        assert_eq!(service.context_window("my-finetune"), 1_000_000);
+    }
+
+    #[test]
+    fn test_conversation_metadata_prompt() {
+        assert_eq!(ConversationMetadata::default().to_prompt(), None);
+
+        let metadata = ConversationMetadata {
+            user_name: Some("Alice".to_string()),
+            username: Some("alice".to_string()),
+            locale: Some("de".to_string()),
+            summary: Some("Alice is planning a trip.".to_string()),
+            facts: vec![("units".to_string(), "metric".to_string())],
+            chat_id: Some(1),
+            ..Default::default()
+        };
+        let prompt = metadata.to_prompt().unwrap();
+        assert!(prompt.starts_with("You are talking to Alice (@alice)."));
+        assert!(prompt.contains("'de'"));
+        assert!(prompt.contains("earlier conversation:\nAlice is planning a trip."));
+        assert!(prompt.ends_with("about this chat:\n- units: metric"));
+    }
+
+    #[test]
+    fn test_needs_summary() {
+        let message = |text: &str| {
+            Message::new(
+                1,
+                rustclaw_types::User::new(1),
+                MessageContent::Text(text.to_string()),
+            )
+        };
+        let history = vec![message(&"word ".repeat(100)), message(&"word ".repeat(100))];
+
+        let service = ProviderService::new(Provider::default());
+        assert!(!service.needs_summary(&history));
+
+        let service = service.with_summarize_at_tokens(100);
+        assert!(service.needs_summary(&history));
+        assert!(!service.needs_summary(&history[..0]));
+
+        let service = service.with_summarize_at_tokens(0);
+        assert!(!service.needs_summary(&history));
+    }
+
+    #[test]
+    fn test_custom_headers() {
+        let mut headers = HashMap::new();
+        headers.insert(
+            "HTTP-Referer".to_string(),
+            "https://example.com".to_string(),
+        );
+        let service = ProviderService::new(Provider::openai("gpt-4o-mini").with_headers(headers));
+        assert!(service.create_client("gpt-4o-mini").is_ok());
+
+        let mut invalid = HashMap::new();
+        invalid.insert("bad header".to_string(), "x".to_string());
+        let service = ProviderService::new(Provider::openai("gpt-4o-mini").with_headers(invalid));
+        assert!(service.create_client("gpt-4o-mini").is_err());
+    }
+
+    #[test]
+    fn test_azure_deployment_routing() {
+        let provider = Provider::azure_openai(
+            "https://example.openai.azure.com/",
+            "gpt4o-prod",
+            "2024-10-21",
+            "secret",
+        );
+        let service =
+            ProviderService::new(provider).with_allowed_models(vec!["gpt4o-mini-prod".to_string()]);
+
+        assert_eq!(service.provider_name(), "Azure OpenAI");
+        assert_eq!(service.resolve_model(None), "gpt4o-prod");
+        assert_eq!(
+            service.resolve_model(Some("gpt4o-mini-prod")),
+            "gpt4o-mini-prod"
+        );
+
+        let client = service.create_client("gpt4o-mini-prod").unwrap();
+        assert_eq!(
+            client.config().url("/chat/completions"),
+            "https://example.openai.azure.com/openai/deployments/gpt4o-mini-prod/chat/completions"
+        );
+        assert_eq!(client.config().query(), vec![("api-version", "2024-10-21")]);
+    }
+
+    #[test]
+    fn test_tool_lookup() {
+        let registry = ToolRegistry::new();
+        registry.register(Box::new(EchoTool));
+
+        assert!(registry.contains("echo"));
+        assert!(!registry.contains("bash"));
+        assert_eq!(
+            registry.get_definition("echo").unwrap().function.name,
+            "echo"
+        );
+        assert!(registry.get_definition("bash").is_none());
+    }
+
+    #[test]
+    fn test_register_replace_and_unregister() {
+        let registry = ToolRegistry::new();
+        assert!(!registry.register(Box::new(EchoTool)));
+        assert!(registry.register(Box::new(EchoTool)));
         assert_eq!(registry.get_tools().len(), 1);
+
+        assert!(registry.unregister("echo"));
+        assert!(!registry.unregister("echo"));
+        assert!(registry.is_empty());
     }
 
     #[test]
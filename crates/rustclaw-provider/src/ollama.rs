@@ -0,0 +1,410 @@
+//! Native Ollama API
+//!
+//! Ollama serves an OpenAI-compatible API under `/v1`, but some versions only
+//! support tool calls on the native `/api/chat` endpoint. [`OllamaBackend`]
+//! answers chat completion requests through that endpoint, translating them
+//! from and to the OpenAI format the agentic loop works with:
+//!
+//! - Tool call arguments are JSON objects rather than encoded strings
+//! - Tool calls carry no ids; unique ids are made up, and tool results name
+//!   the tool they answer instead
+//! - Sampling settings go in `options` (`max_tokens` becomes `num_predict`)
+//! - Reasoning returned as `thinking` is wrapped in `<think>` tags
+
+use crate::backend::ChatBackend;
+use anyhow::Result;
+use async_openai::error::{ApiError, OpenAIError};
+use async_openai::types::chat::{CreateChatCompletionRequest, CreateChatCompletionResponse};
+use futures::future::BoxFuture;
+use rustclaw_types::{OllamaApi, Provider};
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+use std::time::Duration;
+use tracing::{debug, warn};
+use uuid::Uuid;
+
+/// Limit on one request, long enough for a local model to write a long
+/// answer
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Backend sending chat requests to Ollama's native `/api/chat`
+///
+/// Errors are reported as [`OpenAIError`]s, so
+/// [`ProviderErrorKind::of`](crate::error::ProviderErrorKind::of) classifies
+/// them like those of the OpenAI-compatible API.
+#[derive(Clone)]
+pub struct OllamaBackend {
+    url: String,
+    client: reqwest::Client,
+}
+
+impl OllamaBackend {
+    /// Create a backend for the Ollama server at `base_url`
+    ///
+    /// A trailing `/v1` (the OpenAI-compatible API) is ignored.
+    pub fn new(base_url: &str) -> Self {
+        let client = reqwest::Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .build()
+            .unwrap_or_else(|e| {
+                warn!("Failed to build Ollama client with a timeout: {}", e);
+                reqwest::Client::new()
+            });
+        Self {
+            url: format!("{}/api/chat", server_url(base_url)),
+            client,
+        }
+    }
+
+    /// The backend for `provider` if it is an Ollama server using the
+    /// native API
+    pub(crate) fn for_provider(provider: &Provider) -> Option<Self> {
+        match provider {
+            Provider::Ollama { base_url, api, .. }
+                if api.resolve(base_url) == OllamaApi::Native =>
+            {
+                Some(Self::new(base_url))
+            }
+            _ => None,
+        }
+    }
+}
+
+impl ChatBackend for OllamaBackend {
+    fn create(
+        &self,
+        request: CreateChatCompletionRequest,
+    ) -> BoxFuture<'_, Result<CreateChatCompletionResponse>> {
+        Box::pin(async move {
+            let body = to_native_request(serde_json::to_value(&request)?);
+            debug!("Sending native Ollama request to {}", self.url);
+            let response = self
+                .client
+                .post(&self.url)
+                .header(reqwest::header::CONTENT_TYPE, "application/json")
+                .body(body.to_string())
+                .send()
+                .await
+                .map_err(OpenAIError::Reqwest)?;
+            let status = response.status();
+            let text = response.text().await.map_err(OpenAIError::Reqwest)?;
+            if !status.is_success() {
+                return Err(status_error(status, text).into());
+            }
+            let native =
+                serde_json::from_str(&text).map_err(|e| OpenAIError::JSONDeserialize(e, text))?;
+            Ok(serde_json::from_value(from_native_response(native))?)
+        })
+    }
+}
+
+/// The error for a failed request, with the status as its code
+fn status_error(status: reqwest::StatusCode, body: String) -> OpenAIError {
+    let message = serde_json::from_str::<Value>(&body)
+        .ok()
+        .and_then(|body| body["error"].as_str().map(str::to_string))
+        .unwrap_or(body);
+    OpenAIError::ApiError(ApiError {
+        message: format!("Ollama returned {}: {}", status, message),
+        r#type: None,
+        param: None,
+        code: Some(status.as_u16().to_string()),
+    })
+}
+
+/// URL of the Ollama server, without a trailing `/` or `/v1`
+pub(crate) fn server_url(base_url: &str) -> &str {
+    let url = base_url.trim_end_matches('/');
+    url.strip_suffix("/v1").unwrap_or(url)
+}
+
+/// Translate an OpenAI chat completion request to an `/api/chat` request
+fn to_native_request(request: Value) -> Value {
+    // Tool results name their tool rather than the call id
+    let mut tool_names = HashMap::new();
+    let mut messages = Vec::new();
+    for message in request["messages"].as_array().into_iter().flatten() {
+        let mut native = Map::new();
+        native.insert("role".into(), message["role"].clone());
+        native.insert("content".into(), Value::String(text(&message["content"])));
+        if let Some(calls) = message["tool_calls"].as_array() {
+            let calls: Vec<Value> = calls
+                .iter()
+                .map(|call| {
+                    let function = &call["function"];
+                    if let Some(id) = call["id"].as_str() {
+                        tool_names.insert(id.to_string(), function["name"].clone());
+                    }
+                    let arguments = function["arguments"]
+                        .as_str()
+                        .and_then(|arguments| serde_json::from_str(arguments).ok())
+                        .unwrap_or_else(|| json!({}));
+                    json!({ "function": { "name": function["name"], "arguments": arguments } })
+                })
+                .collect();
+            native.insert("tool_calls".into(), Value::Array(calls));
+        }
+        if let Some(name) = message["tool_call_id"]
+            .as_str()
+            .and_then(|id| tool_names.get(id))
+        {
+            native.insert("tool_name".into(), name.clone());
+        }
+        let images = images(&message["content"]);
+        if !images.is_empty() {
+            native.insert("images".into(), Value::Array(images));
+        }
+        messages.push(Value::Object(native));
+    }
+
+    let mut options = Map::new();
+    for (openai, native) in [
+        ("temperature", "temperature"),
+        ("top_p", "top_p"),
+        ("seed", "seed"),
+        ("stop", "stop"),
+        ("presence_penalty", "presence_penalty"),
+        ("frequency_penalty", "frequency_penalty"),
+        ("max_tokens", "num_predict"),
+        ("max_completion_tokens", "num_predict"),
+    ] {
+        if let Some(value) = request.get(openai).filter(|value| !value.is_null()) {
+            options.insert(native.into(), value.clone());
+        }
+    }
+
+    let mut native = json!({
+        "model": request["model"],
+        "messages": messages,
+        "stream": false,
+    });
+    if let Some(tools) = request["tools"].as_array() {
+        let tools: Vec<Value> = tools
+            .iter()
+            .map(|tool| {
+                let mut tool = tool.clone();
+                if let Some(function) = tool["function"].as_object_mut() {
+                    function.remove("strict");
+                }
+                tool
+            })
+            .collect();
+        native["tools"] = Value::Array(tools);
+    }
+    if !options.is_empty() {
+        native["options"] = Value::Object(options);
+    }
+    native
+}
+
+/// Text of a message's content, joining the text parts of a multi-part one
+fn text(content: &Value) -> String {
+    match content {
+        Value::String(text) => text.clone(),
+        Value::Array(parts) => parts
+            .iter()
+            .filter_map(|part| part["text"].as_str())
+            .collect::<Vec<_>>()
+            .join("\n"),
+        _ => String::new(),
+    }
+}
+
+/// Base64 data of the images in a multi-part content, which Ollama takes
+/// separately from the text
+fn images(content: &Value) -> Vec<Value> {
+    content
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|part| part["image_url"]["url"].as_str())
+        .filter_map(|url| url.split_once(";base64,").map(|(_, data)| data))
+        .map(|data| Value::String(data.to_string()))
+        .collect()
+}
+
+/// Translate an `/api/chat` response to an OpenAI chat completion response
+fn from_native_response(response: Value) -> Value {
+    let message = &response["message"];
+    let mut content = message["content"].as_str().unwrap_or_default().to_string();
+    if let Some(thinking) = message["thinking"].as_str().filter(|t| !t.is_empty()) {
+        content = format!("<think>{}</think>{}", thinking, content);
+    }
+
+    let calls: Vec<Value> = message["tool_calls"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .map(|call| {
+            let function = &call["function"];
+            let arguments = match &function["arguments"] {
+                Value::String(arguments) => arguments.clone(),
+                Value::Null => "{}".to_string(),
+                arguments => arguments.to_string(),
+            };
+            json!({
+                "id": call["id"]
+                    .as_str()
+                    .map_or_else(|| format!("call_{}", Uuid::new_v4().simple()), str::to_string),
+                "type": "function",
+                "function": { "name": function["name"], "arguments": arguments },
+            })
+        })
+        .collect();
+
+    let finish_reason = if !calls.is_empty() {
+        "tool_calls"
+    } else if response["done_reason"] == "length" {
+        "length"
+    } else {
+        "stop"
+    };
+    let mut message = json!({ "role": "assistant", "content": content });
+    if !calls.is_empty() {
+        message["tool_calls"] = Value::Array(calls);
+    }
+    let prompt_tokens = response["prompt_eval_count"].as_u64().unwrap_or(0);
+    let completion_tokens = response["eval_count"].as_u64().unwrap_or(0);
+    json!({
+        "id": "ollama",
+        "object": "chat.completion",
+        "created": 0,
+        "model": response["model"],
+        "choices": [{
+            "index": 0,
+            "message": message,
+            "finish_reason": finish_reason,
+        }],
+        "usage": {
+            "prompt_tokens": prompt_tokens,
+            "completion_tokens": completion_tokens,
+            "total_tokens": prompt_tokens + completion_tokens,
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_server_url() {
+        assert_eq!(
+            server_url("http://localhost:11434"),
+            "http://localhost:11434"
+        );
+        assert_eq!(
+            server_url("http://localhost:11434/"),
+            "http://localhost:11434"
+        );
+        assert_eq!(
+            server_url("http://localhost:11434/v1/"),
+            "http://localhost:11434"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_errors_are_classified() {
+        use crate::error::ProviderErrorKind;
+
+        let kind = |status: u16| {
+            let status = reqwest::StatusCode::from_u16(status).unwrap();
+            let error = status_error(status, r#"{"error":"denied"}"#.to_string());
+            assert!(error.to_string().contains("denied"));
+            ProviderErrorKind::of(&error.into())
+        };
+        assert_eq!(kind(401), ProviderErrorKind::Authentication);
+        assert_eq!(kind(429), ProviderErrorKind::RateLimited);
+        assert_eq!(kind(404), ProviderErrorKind::Other);
+
+        // Nothing listens on port 1
+        let request = serde_json::from_value(json!({
+            "model": "llama3",
+            "messages": [{ "role": "user", "content": "Hi" }],
+        }))
+        .unwrap();
+        let error = OllamaBackend::new("http://127.0.0.1:1")
+            .create(request)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            ProviderErrorKind::of(&error),
+            ProviderErrorKind::Unavailable
+        );
+    }
+
+    #[test]
+    fn test_native_request() {
+        let request = json!({
+            "model": "llama3",
+            "messages": [
+                { "role": "system", "content": "Be brief." },
+                { "role": "user", "content": [
+                    { "type": "text", "text": "What is this?" },
+                    { "type": "image_url", "image_url": { "url": "data:image/png;base64,aGk=" } },
+                ] },
+                { "role": "assistant", "tool_calls": [{
+                    "id": "call_1",
+                    "type": "function",
+                    "function": { "name": "lookup", "arguments": "{\"q\":\"x\"}" },
+                }] },
+                { "role": "tool", "tool_call_id": "call_1", "content": "found" },
+            ],
+            "tools": [{ "type": "function", "function": {
+                "name": "lookup", "description": "Look up", "parameters": {}, "strict": true,
+            } }],
+            "temperature": 0.2,
+            "max_tokens": 100,
+        });
+
+        let native = to_native_request(request);
+        assert_eq!(native["stream"], false);
+        assert_eq!(native["messages"][1]["content"], "What is this?");
+        assert_eq!(native["messages"][1]["images"], json!(["aGk="]));
+        assert_eq!(
+            native["messages"][2]["tool_calls"],
+            json!([{ "function": { "name": "lookup", "arguments": { "q": "x" } } }])
+        );
+        assert_eq!(native["messages"][3]["tool_name"], "lookup");
+        assert!(native["tools"][0]["function"].get("strict").is_none());
+        assert_eq!(
+            native["options"],
+            json!({ "temperature": 0.2, "num_predict": 100 })
+        );
+    }
+
+    #[test]
+    fn test_native_response() {
+        let response = from_native_response(json!({
+            "model": "llama3",
+            "message": {
+                "role": "assistant",
+                "content": "",
+                "tool_calls": [{ "function": { "name": "lookup", "arguments": { "q": "x" } } }],
+            },
+            "done": true,
+            "done_reason": "stop",
+            "prompt_eval_count": 26,
+            "eval_count": 9,
+        }));
+        let response: CreateChatCompletionResponse = serde_json::from_value(response).unwrap();
+        let choice = &response.choices[0];
+        let calls = choice.message.tool_calls.as_ref().unwrap();
+        assert_eq!(calls.len(), 1);
+        let call = serde_json::to_value(&calls[0]).unwrap();
+        assert!(call["id"].as_str().unwrap().starts_with("call_"));
+        assert_eq!(call["function"]["arguments"], "{\"q\":\"x\"}");
+        assert_eq!(response.usage.unwrap().total_tokens, 35);
+
+        let response = from_native_response(json!({
+            "model": "qwen3",
+            "message": { "role": "assistant", "content": "Hi", "thinking": "Greet." },
+            "done_reason": "length",
+        }));
+        assert_eq!(
+            response["choices"][0]["message"]["content"],
+            "<think>Greet.</think>Hi"
+        );
+        assert_eq!(response["choices"][0]["finish_reason"], "length");
+    }
+}
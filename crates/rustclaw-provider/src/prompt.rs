@@ -0,0 +1,206 @@
+//! System Prompt Budget
+//!
+//! The system prompt is assembled from the configured base prompt, the list
+//! of available skills and the chat's context: who the user is, the summary
+//! of older messages and the facts remembered about the chat. Skills and
+//! facts accumulate over time, so the assembled prompt can be held to a token
+//! budget. Over budget, the droppable sections are shortened one entry at a
+//! time in a configurable order until the prompt fits; the base prompt and
+//! the user's identity are never dropped.
+
+use crate::context::estimate_tokens;
+use crate::ConversationMetadata;
+use serde::Deserialize;
+use tracing::warn;
+
+/// A part of the system prompt that may be dropped to stay within budget
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PromptSection {
+    /// Available skills, dropped from the lowest ranked (least used) up
+    Skills,
+    /// Remembered facts, dropped from the last key up
+    Facts,
+    /// Summary of the earlier conversation, dropped as a whole
+    Summary,
+}
+
+/// Order sections are dropped in when the system prompt is over budget
+pub const DEFAULT_PROMPT_DROP_ORDER: [PromptSection; 3] = [
+    PromptSection::Skills,
+    PromptSection::Facts,
+    PromptSection::Summary,
+];
+
+/// How much of each droppable section is still in the prompt
+struct Kept {
+    skills: usize,
+    facts: usize,
+    summary: bool,
+}
+
+impl Kept {
+    /// Drop one entry of `section`, returning false if none is left
+    fn drop_one(&mut self, section: PromptSection) -> bool {
+        match section {
+            PromptSection::Skills if self.skills > 0 => self.skills -= 1,
+            PromptSection::Facts if self.facts > 0 => self.facts -= 1,
+            PromptSection::Summary if self.summary => self.summary = false,
+            _ => return false,
+        }
+        true
+    }
+}
+
+/// Assemble the system prompt, dropping sections in `drop_order` until it
+/// is at most `budget` tokens
+pub(crate) fn assemble(
+    base: &str,
+    skills: &[String],
+    metadata: Option<&ConversationMetadata>,
+    budget: Option<usize>,
+    drop_order: &[PromptSection],
+) -> String {
+    let metadata = metadata.cloned().unwrap_or_default();
+    let mut kept = Kept {
+        skills: skills.len(),
+        facts: metadata.facts.len(),
+        summary: metadata.summary.is_some(),
+    };
+    let mut prompt = render(base, skills, &metadata, &kept);
+    let Some(budget) = budget else {
+        return prompt;
+    };
+
+    while estimate_tokens(&prompt) > budget {
+        if !drop_order.iter().any(|section| kept.drop_one(*section)) {
+            warn!(
+                "System prompt is about {} tokens, over its budget of {}, with nothing left to drop",
+                estimate_tokens(&prompt),
+                budget
+            );
+            return prompt;
+        }
+        prompt = render(base, skills, &metadata, &kept);
+    }
+
+    let mut dropped = Vec::new();
+    if kept.skills < skills.len() {
+        dropped.push(format!("{} skill(s)", skills.len() - kept.skills));
+    }
+    if kept.facts < metadata.facts.len() {
+        dropped.push(format!("{} fact(s)", metadata.facts.len() - kept.facts));
+    }
+    if metadata.summary.is_some() && !kept.summary {
+        dropped.push("the summary".to_string());
+    }
+    if !dropped.is_empty() {
+        warn!(
+            "System prompt over its budget of {} tokens, dropped {}",
+            budget,
+            dropped.join(", ")
+        );
+    }
+    prompt
+}
+
+/// The system prompt with the kept part of each section
+fn render(base: &str, skills: &[String], metadata: &ConversationMetadata, kept: &Kept) -> String {
+    let mut prompt = base.to_string();
+    if kept.skills > 0 {
+        prompt.push_str("\n\nAvailable skills (use /{skill-name} to activate):\n");
+        for skill in &skills[..kept.skills] {
+            prompt.push_str(skill);
+            prompt.push('\n');
+        }
+    }
+
+    let metadata = ConversationMetadata {
+        summary: metadata.summary.clone().filter(|_| kept.summary),
+        facts: metadata.facts[..kept.facts].to_vec(),
+        ..metadata.clone()
+    };
+    if let Some(context) = metadata.to_prompt() {
+        prompt.push_str("\n\n");
+        prompt.push_str(&context);
+    }
+    prompt
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn skills() -> Vec<String> {
+        (0..4)
+            .map(|i| format!("- skill-{}: {}", i, "x".repeat(60)))
+            .collect()
+    }
+
+    fn metadata() -> ConversationMetadata {
+        ConversationMetadata {
+            user_name: Some("Alice".to_string()),
+            summary: Some("s".repeat(200)),
+            facts: vec![
+                ("city".to_string(), "Berlin".to_string()),
+                ("units".to_string(), "metric".to_string()),
+            ],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_without_budget() {
+        let prompt = assemble("Base.", &skills(), Some(&metadata()), None, &[]);
+        assert!(prompt.starts_with("Base.\n\nAvailable skills"));
+        assert!(prompt.contains("- skill-3:"));
+        assert!(prompt.contains(&"s".repeat(200)));
+        assert!(prompt.ends_with("- units: metric"));
+    }
+
+    #[test]
+    fn test_drops_in_order() {
+        let skills = skills();
+        let metadata = metadata();
+        let full = assemble("Base.", &skills, Some(&metadata), None, &[]);
+        let budget = estimate_tokens(&full) - 20;
+
+        // Two lowest-ranked skills make room
+        let prompt = assemble(
+            "Base.",
+            &skills,
+            Some(&metadata),
+            Some(budget),
+            &DEFAULT_PROMPT_DROP_ORDER,
+        );
+        assert!(estimate_tokens(&prompt) <= budget);
+        assert!(prompt.contains("- skill-1:"));
+        assert!(!prompt.contains("- skill-2:"));
+        assert!(prompt.contains(&"s".repeat(200)));
+
+        // The summary goes first when it comes first in the order
+        let prompt = assemble(
+            "Base.",
+            &skills,
+            Some(&metadata),
+            Some(budget),
+            &[PromptSection::Summary, PromptSection::Skills],
+        );
+        assert!(!prompt.contains(&"s".repeat(200)));
+        assert!(prompt.contains("- skill-3:"));
+
+        // Sections missing from the order are kept even over budget
+        let prompt = assemble("Base.", &skills, Some(&metadata), Some(1), &[]);
+        assert_eq!(prompt, full);
+        let prompt = assemble(
+            "Base.",
+            &skills,
+            Some(&metadata),
+            Some(1),
+            &[PromptSection::Skills, PromptSection::Facts],
+        );
+        assert!(prompt.starts_with("Base.\n\nYou are talking to Alice."));
+        assert!(prompt.contains(&"s".repeat(200)));
+        assert!(!prompt.contains("Facts"));
+    }
+}
@@ -0,0 +1,265 @@
+//! Streamed Completions
+//!
+//! [`ProviderService::complete_stream`](crate::ProviderService::complete_stream)
+//! hands out a response while it is generated. Text arrives as
+//! [`StreamDelta::Content`] pieces to show right away; tool calls arrive in
+//! fragments (an id and name, then the arguments a few characters at a time)
+//! and are buffered until the model finishes, then sent whole as
+//! [`StreamDelta::ToolCall`]. The tokens used arrive last as
+//! [`StreamDelta::Usage`], if the provider reports them.
+
+use crate::backend::ChunkStream;
+use anyhow::Result;
+use async_openai::types::chat::{
+    ChatCompletionMessageToolCallChunk, CreateChatCompletionStreamResponse,
+};
+use futures::stream::{self, Stream, StreamExt};
+use rustclaw_types::{FunctionCall, TokenUsage, ToolCall};
+use std::collections::{BTreeMap, VecDeque};
+use tracing::warn;
+
+/// A piece of a streamed completion
+#[derive(Debug, Clone)]
+pub enum StreamDelta {
+    /// More of the response text
+    Content(String),
+    /// A tool call the model requested, once all of its fragments arrived
+    ToolCall(ToolCall),
+    /// Tokens used by the whole completion
+    Usage(TokenUsage),
+}
+
+/// A tool call whose fragments are still arriving
+#[derive(Debug, Default)]
+struct PartialToolCall {
+    id: Option<String>,
+    name: String,
+    arguments: String,
+}
+
+/// Turns chunks into deltas, holding back tool calls until they are complete
+struct Assembler {
+    chunks: ChunkStream,
+    /// Tool calls being assembled, by their index in the response
+    calls: BTreeMap<u32, PartialToolCall>,
+    ready: VecDeque<StreamDelta>,
+    done: bool,
+}
+
+impl Assembler {
+    /// Take in one chunk, queueing the deltas it completes
+    fn push(&mut self, chunk: CreateChatCompletionStreamResponse) {
+        // Only the first choice is used, as with whole responses
+        if let Some(choice) = chunk.choices.into_iter().find(|c| c.index == 0) {
+            if let Some(content) = choice.delta.content.filter(|c| !c.is_empty()) {
+                self.ready.push_back(StreamDelta::Content(content));
+            }
+            for fragment in choice.delta.tool_calls.unwrap_or_default() {
+                self.add_fragment(fragment);
+            }
+            if choice.finish_reason.is_some() {
+                self.flush();
+            }
+        }
+        // Sent with the last chunk, which has no choices
+        if let Some(usage) = chunk.usage {
+            self.ready.push_back(StreamDelta::Usage(TokenUsage::new(
+                u64::from(usage.prompt_tokens),
+                u64::from(usage.completion_tokens),
+            )));
+        }
+    }
+
+    fn add_fragment(&mut self, fragment: ChatCompletionMessageToolCallChunk) {
+        let call = self.calls.entry(fragment.index).or_default();
+        if let Some(id) = fragment.id.filter(|id| !id.is_empty()) {
+            call.id = Some(id);
+        }
+        if let Some(function) = fragment.function {
+            call.name
+                .push_str(function.name.as_deref().unwrap_or_default());
+            call.arguments
+                .push_str(function.arguments.as_deref().unwrap_or_default());
+        }
+    }
+
+    /// Queue the buffered tool calls, in the order the model made them
+    fn flush(&mut self) {
+        for (index, call) in std::mem::take(&mut self.calls) {
+            if call.name.is_empty() {
+                warn!("Dropping streamed tool call {} without a name", index);
+                continue;
+            }
+            self.ready.push_back(StreamDelta::ToolCall(ToolCall {
+                id: call.id.unwrap_or_else(|| format!("stream_call_{}", index)),
+                call_type: "function".to_string(),
+                function: FunctionCall {
+                    name: call.name,
+                    arguments: call.arguments,
+                },
+            }));
+        }
+    }
+}
+
+/// The deltas of a streamed completion
+///
+/// Tool calls are sent when the model finishes, or when the stream ends
+/// without saying so. The stream stops after the first error.
+pub(crate) fn deltas(chunks: ChunkStream) -> impl Stream<Item = Result<StreamDelta>> + Send {
+    let assembler = Assembler {
+        chunks,
+        calls: BTreeMap::new(),
+        ready: VecDeque::new(),
+        done: false,
+    };
+    stream::unfold(assembler, |mut assembler| async move {
+        loop {
+            if let Some(delta) = assembler.ready.pop_front() {
+                return Some((Ok(delta), assembler));
+            }
+            if assembler.done {
+                return None;
+            }
+            match assembler.chunks.next().await {
+                Some(Ok(chunk)) => assembler.push(chunk),
+                Some(Err(e)) => {
+                    assembler.done = true;
+                    return Some((Err(e), assembler));
+                }
+                None => {
+                    assembler.done = true;
+                    assembler.flush();
+                }
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+
+    fn chunk(
+        delta: Value,
+        finish_reason: Option<&str>,
+    ) -> Result<CreateChatCompletionStreamResponse> {
+        Ok(serde_json::from_value(json!({
+            "id": "chunk",
+            "object": "chat.completion.chunk",
+            "created": 0,
+            "model": "mock",
+            "choices": [{ "index": 0, "delta": delta, "finish_reason": finish_reason }],
+        }))?)
+    }
+
+    async fn collect(chunks: Vec<Result<CreateChatCompletionStreamResponse>>) -> Vec<StreamDelta> {
+        deltas(stream::iter(chunks).boxed())
+            .map(|delta| delta.unwrap())
+            .collect()
+            .await
+    }
+
+    #[tokio::test]
+    async fn test_content_deltas() {
+        let deltas = collect(vec![
+            chunk(json!({ "role": "assistant", "content": "" }), None),
+            chunk(json!({ "content": "Hel" }), None),
+            chunk(json!({ "content": "lo, " }), None),
+            chunk(json!({ "content": "world" }), None),
+            chunk(json!({}), Some("stop")),
+        ])
+        .await;
+
+        let content: String = deltas
+            .iter()
+            .map(|delta| match delta {
+                StreamDelta::Content(text) => text.as_str(),
+                other => panic!("unexpected delta {:?}", other),
+            })
+            .collect();
+        assert_eq!(deltas.len(), 3);
+        assert_eq!(content, "Hello, world");
+    }
+
+    #[tokio::test]
+    async fn test_tool_call_fragments() {
+        let deltas = collect(vec![
+            chunk(json!({ "content": "Checking" }), None),
+            chunk(
+                json!({ "tool_calls": [{
+                    "index": 0, "id": "call_1", "type": "function",
+                    "function": { "name": "echo", "arguments": "" }
+                }] }),
+                None,
+            ),
+            chunk(
+                json!({ "tool_calls": [{ "index": 0, "function": { "arguments": "{\"message\":" } }] }),
+                None,
+            ),
+            chunk(
+                json!({ "tool_calls": [{
+                    "index": 1, "id": "call_2", "type": "function",
+                    "function": { "name": "time", "arguments": "{}" }
+                }] }),
+                None,
+            ),
+            chunk(
+                json!({ "tool_calls": [{ "index": 0, "function": { "arguments": "\"hi\"}" } }] }),
+                None,
+            ),
+            chunk(json!({}), Some("tool_calls")),
+        ])
+        .await;
+
+        assert_eq!(deltas.len(), 3);
+        assert!(matches!(&deltas[0], StreamDelta::Content(text) if text == "Checking"));
+        let StreamDelta::ToolCall(first) = &deltas[1] else {
+            panic!("expected a tool call, got {:?}", deltas[1]);
+        };
+        assert_eq!(first.id, "call_1");
+        assert_eq!(first.function.name, "echo");
+        assert_eq!(first.function.arguments, r#"{"message":"hi"}"#);
+        let StreamDelta::ToolCall(second) = &deltas[2] else {
+            panic!("expected a tool call, got {:?}", deltas[2]);
+        };
+        assert_eq!(second.id, "call_2");
+        assert_eq!(second.function.name, "time");
+    }
+
+    #[tokio::test]
+    async fn test_usage_chunk() {
+        let mut usage = chunk(json!({}), None).unwrap();
+        usage.choices.clear();
+        usage.usage = serde_json::from_value(json!({
+            "prompt_tokens": 12, "completion_tokens": 3, "total_tokens": 15,
+        }))
+        .unwrap();
+        let deltas = collect(vec![
+            chunk(json!({ "content": "Hi" }), None),
+            chunk(json!({}), Some("stop")),
+            Ok(usage),
+        ])
+        .await;
+
+        assert_eq!(deltas.len(), 2);
+        let StreamDelta::Usage(usage) = &deltas[1] else {
+            panic!("expected the usage, got {:?}", deltas[1]);
+        };
+        assert_eq!(usage.prompt_tokens, 12);
+        assert_eq!(usage.completion_tokens, 3);
+    }
+
+    #[tokio::test]
+    async fn test_stream_ends_without_finish_reason() {
+        let deltas = collect(vec![chunk(
+            json!({ "tool_calls": [{ "index": 0, "function": { "name": "echo", "arguments": "{}" } }] }),
+            None,
+        )])
+        .await;
+
+        assert_eq!(deltas.len(), 1);
+        assert!(matches!(&deltas[0], StreamDelta::ToolCall(call) if call.id == "stream_call_0"));
+    }
+}
@@ -23,8 +23,8 @@
 pub mod registry;
 pub mod skill;
 
-pub use registry::SkillsRegistry;
-pub use skill::Skill;
+pub use registry::{SkillLoadError, SkillsRegistry};
+pub use skill::{FrontmatterError, Skill};
 
 /// Prelude for convenient imports
 pub mod prelude {
@@ -9,7 +9,70 @@ use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 use tracing::{debug, info, warn};
 
-use crate::skill::Skill;
+use crate::skill::{FrontmatterError, Skill};
+
+/// A SKILL.md that failed to load, and why
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SkillLoadError {
+    /// Path of the SKILL.md
+    pub path: PathBuf,
+    /// Line of the error (1-based), for malformed frontmatter
+    pub line: Option<usize>,
+    /// Column of the error (1-based), for malformed frontmatter
+    pub column: Option<usize>,
+    /// What is wrong
+    pub reason: String,
+}
+
+impl SkillLoadError {
+    fn new(path: PathBuf, error: &anyhow::Error) -> Self {
+        match error
+            .chain()
+            .find_map(|e| e.downcast_ref::<FrontmatterError>())
+        {
+            Some(e) => Self {
+                path,
+                line: e.line,
+                column: e.column,
+                reason: format!("invalid frontmatter: {}", e.message),
+            },
+            None => Self {
+                path,
+                line: None,
+                column: None,
+                reason: error.root_cause().to_string(),
+            },
+        }
+    }
+}
+
+impl std::fmt::Display for SkillLoadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.path.display())?;
+        if let Some(line) = self.line {
+            write!(f, ":{}", line)?;
+            if let Some(column) = self.column {
+                write!(f, ":{}", column)?;
+            }
+        }
+        write!(f, ": {}", self.reason)
+    }
+}
+
+/// How often a skill was activated and how often that succeeded
+#[derive(Debug, Clone, Copy, Default)]
+struct Usage {
+    uses: u64,
+    successes: u64,
+}
+
+impl Usage {
+    /// Ranking score: successful runs minus failed runs
+    fn score(self) -> i64 {
+        let failures = self.uses.saturating_sub(self.successes);
+        self.successes as i64 - failures as i64
+    }
+}
 
 /// Skills registry managing all available skills
 pub struct SkillsRegistry {
@@ -17,6 +80,12 @@ pub struct SkillsRegistry {
     skills: HashMap<String, Skill>,
     /// Skills directories to scan
     directories: Vec<PathBuf>,
+    /// Whether activations are tracked to rank skills
+    track_usage: bool,
+    /// Activation counts by skill name
+    usage: HashMap<String, Usage>,
+    /// SKILL.md files that failed to load in the last discovery
+    failures: Vec<SkillLoadError>,
 }
 
 impl SkillsRegistry {
@@ -25,6 +94,9 @@ impl SkillsRegistry {
         Self {
             skills: HashMap::new(),
             directories: Vec::new(),
+            track_usage: false,
+            usage: HashMap::new(),
+            failures: Vec::new(),
         }
     }
 
@@ -34,6 +106,32 @@ impl SkillsRegistry {
         self
     }
 
+    /// Track skill activations and list frequently successful skills first
+    pub fn with_usage_tracking(mut self) -> Self {
+        self.track_usage = true;
+        self
+    }
+
+    /// Whether skill activations should be recorded
+    pub fn tracks_usage(&self) -> bool {
+        self.track_usage
+    }
+
+    /// Set the recorded usage of a skill (e.g. loaded from the database)
+    pub fn set_usage(&mut self, name: &str, uses: u64, successes: u64) {
+        self.usage
+            .insert(name.to_string(), Usage { uses, successes });
+    }
+
+    /// Count an activation of a skill and whether it succeeded
+    pub fn record_use(&mut self, name: &str, success: bool) {
+        let usage = self.usage.entry(name.to_string()).or_default();
+        usage.uses += 1;
+        if success {
+            usage.successes += 1;
+        }
+    }
+
     /// Add personal skills directory: ~/.rustclaw/skills/
     pub fn with_personal_skills(self) -> Self {
         if let Some(home) = dirs::home_dir() {
@@ -76,6 +174,108 @@ impl SkillsRegistry {
         Ok(())
     }
 
+    /// Scan all configured directories like [`discover`](Self::discover),
+    /// using `tokio::fs` so slow filesystems don't stall the async runtime
+    pub async fn discover_async(&mut self) -> Result<()> {
+        info!(
+            "Starting skills discovery in {} directories",
+            self.directories.len()
+        );
+
+        let directories = self.directories.clone();
+
+        for dir in &directories {
+            match tokio::fs::metadata(dir).await {
+                Err(_) => {
+                    debug!("Skills directory does not exist: {:?}", dir);
+                    continue;
+                }
+                Ok(metadata) if !metadata.is_dir() => {
+                    warn!("Skills path is not a directory: {:?}", dir);
+                    continue;
+                }
+                Ok(_) => {}
+            }
+
+            self.scan_directory_async(dir).await?;
+        }
+
+        info!("Discovered {} skills", self.skills.len());
+        Ok(())
+    }
+
+    /// Discover the skills again, picking up added, changed and removed ones
+    ///
+    /// Recorded usage is kept. If discovery fails, the skills discovered
+    /// before stay available.
+    pub async fn reload(&mut self) -> Result<()> {
+        let mut fresh = Self {
+            directories: self.directories.clone(),
+            ..Self::new()
+        };
+        fresh.discover_async().await?;
+        self.skills = fresh.skills;
+        self.failures = fresh.failures;
+        Ok(())
+    }
+
+    /// SKILL.md files that failed to load in the last discovery
+    pub fn failures(&self) -> &[SkillLoadError] {
+        &self.failures
+    }
+
+    /// Note a skill directory whose SKILL.md could not be loaded
+    fn record_failure(&mut self, dir: &Path, error: anyhow::Error) {
+        let failure = SkillLoadError::new(dir.join("SKILL.md"), &error);
+        warn!("Skill not loaded: {}", failure);
+        self.failures.push(failure);
+    }
+
+    /// Scan a directory and its subdirectories for skills without blocking
+    ///
+    /// Walks the tree with an explicit stack, visiting directories in the
+    /// same order as [`scan_directory_recursive`](Self::scan_directory_recursive).
+    async fn scan_directory_async(&mut self, root: &Path) -> Result<()> {
+        let mut pending: Vec<(PathBuf, usize)> = subdirectories(root)
+            .await?
+            .into_iter()
+            .rev()
+            .map(|path| (path, 1))
+            .collect();
+
+        while let Some((path, depth)) = pending.pop() {
+            // Try to load skill metadata from this directory
+            match Skill::metadata_from_dir_async(&path).await {
+                Ok(skill) => {
+                    let name = skill.name().to_string();
+                    debug!("Discovered skill: {} at {:?}", name, path);
+                    self.skills.insert(name, skill);
+                }
+                Err(e) => {
+                    if tokio::fs::try_exists(path.join("SKILL.md"))
+                        .await
+                        .unwrap_or(false)
+                    {
+                        self.record_failure(&path, e);
+                    } else {
+                        debug!("No skill in {:?}: {}", path, e);
+                    }
+                }
+            }
+
+            // Safety limit to prevent infinite recursion
+            if depth > 10 {
+                warn!("Maximum directory depth (10) reached at {:?}", path);
+                continue;
+            }
+
+            let children = subdirectories(&path).await?;
+            pending.extend(children.into_iter().rev().map(|child| (child, depth + 1)));
+        }
+
+        Ok(())
+    }
+
     /// Scan a single directory for skills (recursively scans subdirectories)
     fn scan_directory(&mut self, dir: &Path) -> Result<()> {
         self.scan_directory_recursive(dir, 0)
@@ -107,6 +307,7 @@ impl SkillsRegistry {
                     debug!("Discovered skill: {} at {:?}", name, path);
                     self.skills.insert(name, skill);
                 }
+                Err(e) if path.join("SKILL.md").exists() => self.record_failure(&path, e),
                 Err(e) => {
                     // This directory doesn't have a SKILL.md, but might contain subdirectories that do
                     debug!("No skill in {:?}: {}", path, e);
@@ -156,28 +357,13 @@ impl SkillsRegistry {
         self.skills.is_empty()
     }
 
-    /// Generate skills list for LLM system prompt
-    /// Format:
-    /// Available skills (use /{skill-name} or call the Skill tool):
-    /// - skill-name: Description of what this skill does and when to use it
-    /// - another-skill: Another description...
-    pub fn generate_system_prompt(&self) -> String {
-        if self.skills.is_empty() {
-            return String::new();
-        }
-
-        let mut prompt = String::from("\n\nAvailable skills (use /{skill-name} to activate):\n");
-
-        // Sort skills by name for consistent ordering
-        let mut sorted_skills: Vec<_> = self.skills.values().collect();
-        sorted_skills.sort_by_key(|s| s.name());
-
-        for skill in sorted_skills {
-            prompt.push_str(&skill.to_summary());
-            prompt.push('\n');
-        }
-
-        prompt
+    /// Summary line of each skill (`- skill-name: Description`), in prompt
+    /// order
+    pub fn summaries(&self) -> Vec<String> {
+        self.ranked()
+            .iter()
+            .map(|skill| skill.to_summary())
+            .collect()
     }
 
     /// Generate a concise skills list for embedding in tool descriptions
@@ -187,10 +373,7 @@ impl SkillsRegistry {
         }
 
         let mut list = String::new();
-        let mut sorted_skills: Vec<_> = self.skills.values().collect();
-        sorted_skills.sort_by_key(|s| s.name());
-
-        for (i, skill) in sorted_skills.iter().enumerate() {
+        for (i, skill) in self.ranked().iter().enumerate() {
             if i > 0 {
                 list.push_str(", ");
             }
@@ -199,6 +382,45 @@ impl SkillsRegistry {
 
         list
     }
+
+    /// Skills in prompt order: by name, or with usage tracking, frequently
+    /// successful skills first and skills that keep failing last
+    fn ranked(&self) -> Vec<&Skill> {
+        let mut skills: Vec<_> = self.skills.values().collect();
+        skills.sort_by_key(|s| s.name());
+        if self.track_usage {
+            // Stable sort keeps equally ranked skills in name order
+            skills.sort_by_key(|s| {
+                std::cmp::Reverse(
+                    self.usage
+                        .get(s.name())
+                        .copied()
+                        .unwrap_or_default()
+                        .score(),
+                )
+            });
+        }
+        skills
+    }
+}
+
+/// Subdirectories of `dir`, in directory listing order
+async fn subdirectories(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut entries = tokio::fs::read_dir(dir)
+        .await
+        .with_context(|| format!("Failed to read directory {:?}", dir))?;
+
+    let mut subdirectories = Vec::new();
+    while let Some(entry) = entries.next_entry().await? {
+        let path = entry.path();
+        if tokio::fs::metadata(&path)
+            .await
+            .is_ok_and(|metadata| metadata.is_dir())
+        {
+            subdirectories.push(path);
+        }
+    }
+    Ok(subdirectories)
 }
 
 impl Default for SkillsRegistry {
@@ -219,10 +441,124 @@ mod tests {
     }
 
     #[test]
-    fn test_generate_system_prompt_empty() {
+    fn test_summaries_empty() {
         let registry = SkillsRegistry::new();
-        let prompt = registry.generate_system_prompt();
-        assert!(prompt.is_empty());
+        assert!(registry.summaries().is_empty());
+    }
+
+    fn skill(name: &str) -> Skill {
+        Skill {
+            metadata: crate::skill::SkillMetadata {
+                name: name.to_string(),
+                description: format!("The {name} skill"),
+                output_schema: None,
+            },
+            path: PathBuf::from(name),
+            content: None,
+            modified: None,
+        }
+    }
+
+    #[test]
+    fn test_rank_by_usage() {
+        let mut registry = SkillsRegistry::new();
+        for name in ["alpha", "beta", "gamma", "delta"] {
+            registry.skills.insert(name.to_string(), skill(name));
+        }
+        registry.set_usage("gamma", 10, 9);
+        registry.record_use("delta", false);
+        registry.record_use("beta", true);
+
+        // Without tracking, usage does not affect the order
+        let names = |registry: &SkillsRegistry| -> Vec<String> {
+            registry
+                .ranked()
+                .iter()
+                .map(|s| s.name().to_string())
+                .collect()
+        };
+        assert_eq!(names(&registry), ["alpha", "beta", "delta", "gamma"]);
+
+        let registry = registry.with_usage_tracking();
+        assert_eq!(names(&registry), ["gamma", "beta", "alpha", "delta"]);
+        assert!(registry
+            .generate_skills_list()
+            .starts_with("gamma: The gamma skill, beta:"));
+    }
+
+    #[tokio::test]
+    async fn test_discover_async() {
+        let root = std::env::temp_dir().join(format!("rustclaw-skills-{}", std::process::id()));
+        let write_skill = |dir: &Path, name: &str| {
+            std::fs::create_dir_all(dir).unwrap();
+            std::fs::write(
+                dir.join("SKILL.md"),
+                format!("---\nname: {name}\ndescription: The {name} skill\n---\n\n# {name}\n"),
+            )
+            .unwrap();
+        };
+        write_skill(&root.join("alpha"), "alpha");
+        write_skill(&root.join("group").join("beta"), "beta");
+        std::fs::create_dir_all(root.join("empty")).unwrap();
+        std::fs::write(root.join("notes.txt"), "not a skill").unwrap();
+
+        let mut registry = SkillsRegistry::new()
+            .add_directory(&root)
+            .add_directory(root.join("missing"));
+        registry.discover_async().await.unwrap();
+
+        let mut sync_registry = SkillsRegistry::new().add_directory(&root);
+        sync_registry.discover().unwrap();
+
+        let mut names: Vec<_> = registry.skill_names().cloned().collect();
+        names.sort();
+        assert_eq!(names, ["alpha", "beta"]);
+        assert_eq!(registry.len(), sync_registry.len());
+        assert!(registry.get("beta").unwrap().content.is_none());
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_reload() {
+        let root = std::env::temp_dir().join(format!("rustclaw-reload-{}", std::process::id()));
+        let write_skill = |name: &str, frontmatter: &str| {
+            let dir = root.join(name);
+            std::fs::create_dir_all(&dir).unwrap();
+            std::fs::write(dir.join("SKILL.md"), format!("---\n{frontmatter}---\n")).unwrap();
+        };
+        write_skill("alpha", "name: alpha\ndescription: The alpha skill\n");
+
+        let mut registry = SkillsRegistry::new().add_directory(&root);
+        registry.discover_async().await.unwrap();
+        registry.record_use("alpha", true);
+        assert_eq!(registry.len(), 1);
+
+        // Added skills are picked up, broken ones reported
+        write_skill("beta", "name: beta\ndescription: The beta skill\n");
+        write_skill("broken", "name: Not Valid!\n");
+        registry.reload().await.unwrap();
+        assert_eq!(registry.len(), 2);
+        assert_eq!(registry.failures().len(), 1);
+        let failure = &registry.failures()[0];
+        assert_eq!(failure.path, root.join("broken").join("SKILL.md"));
+        assert_eq!(
+            failure.to_string(),
+            format!(
+                "{}:2:1: invalid frontmatter: missing field `description`",
+                failure.path.display()
+            )
+        );
+        assert_eq!(registry.usage["alpha"].uses, 1);
+
+        // Removed skills disappear
+        std::fs::remove_dir_all(root.join("alpha")).unwrap();
+        std::fs::remove_dir_all(root.join("broken")).unwrap();
+        registry.reload().await.unwrap();
+        assert_eq!(registry.skill_names().collect::<Vec<_>>(), ["beta"]);
+        assert!(registry.failures().is_empty());
+
+        std::fs::remove_dir_all(&root).unwrap();
     }
 
     #[test]
@@ -5,8 +5,10 @@
 use anyhow::{anyhow, Context, Result};
 use regex::Regex;
 use serde::Deserialize;
+use serde_json::Value;
 use std::fs;
 use std::path::{Path, PathBuf};
+use std::time::SystemTime;
 use tracing::warn;
 
 /// Maximum allowed name length (from Anthropic spec)
@@ -21,8 +23,60 @@ pub struct SkillMetadata {
     pub name: String,
     /// Skill description (max 1024 chars, describes WHAT and WHEN)
     pub description: String,
+    /// Optional JSON schema the final response must satisfy when the skill is active
+    #[serde(default)]
+    pub output_schema: Option<Value>,
 }
 
+/// Malformed frontmatter of a SKILL.md
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FrontmatterError {
+    /// Line in SKILL.md (1-based), if the error has a location
+    pub line: Option<usize>,
+    /// Column in that line (1-based)
+    pub column: Option<usize>,
+    /// What is wrong, without the location
+    pub message: String,
+}
+
+impl FrontmatterError {
+    /// A YAML error in frontmatter that starts on line 2 of the file
+    fn from_yaml(error: &serde_yaml::Error) -> Self {
+        let mut message = error.to_string();
+        let location = error.location();
+        if let Some(location) = &location {
+            let suffix = format!(" at line {} column {}", location.line(), location.column());
+            if let Some(stripped) = message.strip_suffix(&suffix) {
+                message = stripped.to_string();
+            }
+        }
+        Self {
+            // The opening `---` is line 1
+            line: location.as_ref().map(|l| l.line() + 1),
+            column: location.as_ref().map(serde_yaml::Location::column),
+            message,
+        }
+    }
+}
+
+impl std::fmt::Display for FrontmatterError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (self.line, self.column) {
+            (Some(line), Some(column)) => write!(
+                f,
+                "Invalid frontmatter at line {}, column {}: {}",
+                line, column, self.message
+            ),
+            (Some(line), None) => {
+                write!(f, "Invalid frontmatter at line {}: {}", line, self.message)
+            }
+            _ => write!(f, "Invalid frontmatter: {}", self.message),
+        }
+    }
+}
+
+impl std::error::Error for FrontmatterError {}
+
 /// A complete skill with metadata and content
 #[derive(Debug, Clone)]
 pub struct Skill {
@@ -32,6 +86,8 @@ pub struct Skill {
     pub path: PathBuf,
     /// Full SKILL.md content (loaded on demand)
     pub content: Option<String>,
+    /// Modification time of SKILL.md when `content` was read
+    pub modified: Option<SystemTime>,
 }
 
 impl Skill {
@@ -43,6 +99,7 @@ impl Skill {
             return Err(anyhow!("SKILL.md not found in {:?}", dir));
         }
 
+        let modified = modified_time(&skill_file);
         let content = fs::read_to_string(&skill_file)
             .with_context(|| format!("Failed to read {:?}", skill_file))?;
 
@@ -56,6 +113,7 @@ impl Skill {
             metadata,
             path: dir.to_path_buf(),
             content: Some(content),
+            modified,
         })
     }
 
@@ -70,7 +128,28 @@ impl Skill {
         let content = fs::read_to_string(&skill_file)
             .with_context(|| format!("Failed to read {:?}", skill_file))?;
 
-        let (metadata, _) = parse_skill_content(&content)
+        Self::metadata_from_content(dir, &content)
+    }
+
+    /// Load only metadata from a directory without blocking the async runtime
+    pub async fn metadata_from_dir_async(dir: &Path) -> Result<Self> {
+        let skill_file = dir.join("SKILL.md");
+
+        if !tokio::fs::try_exists(&skill_file).await.unwrap_or(false) {
+            return Err(anyhow!("SKILL.md not found in {:?}", dir));
+        }
+
+        let content = tokio::fs::read_to_string(&skill_file)
+            .await
+            .with_context(|| format!("Failed to read {:?}", skill_file))?;
+
+        Self::metadata_from_content(dir, &content)
+    }
+
+    /// Build a metadata-only skill from the SKILL.md content of `dir`
+    fn metadata_from_content(dir: &Path, content: &str) -> Result<Self> {
+        let skill_file = dir.join("SKILL.md");
+        let (metadata, _) = parse_skill_content(content)
             .with_context(|| format!("Failed to parse skill from {:?}", skill_file))?;
 
         // Validate metadata
@@ -80,20 +159,28 @@ impl Skill {
             metadata,
             path: dir.to_path_buf(),
             content: None, // Don't load full content yet
+            modified: None,
         })
     }
 
     /// Load full content if not already loaded (Phase 2: Activation)
+    ///
+    /// Cached content is reused while SKILL.md keeps the modification time
+    /// it had when read; a modified file is read again.
     pub fn load_content(&mut self) -> Result<()> {
-        if self.content.is_some() {
+        let skill_file = self.path.join("SKILL.md");
+        let modified = modified_time(&skill_file);
+
+        // Keep the cache when the file is unchanged or cannot be checked
+        if self.content.is_some() && (modified.is_none() || modified == self.modified) {
             return Ok(());
         }
 
-        let skill_file = self.path.join("SKILL.md");
         let content = fs::read_to_string(&skill_file)
             .with_context(|| format!("Failed to read {:?}", skill_file))?;
 
         self.content = Some(content);
+        self.modified = modified;
         Ok(())
     }
 
@@ -107,6 +194,48 @@ impl Skill {
         &self.metadata.description
     }
 
+    /// Get the skill instructions (SKILL.md without frontmatter), if loaded
+    pub fn instructions(&self) -> Option<String> {
+        let content = self.content.as_ref()?;
+        parse_skill_content(content)
+            .map(|(_, body)| body.trim().to_string())
+            .ok()
+    }
+
+    /// Get the declared output schema, if any
+    pub fn output_schema(&self) -> Option<&Value> {
+        self.metadata.output_schema.as_ref()
+    }
+
+    /// Validate a model response against the skill's output schema
+    ///
+    /// The response may be bare JSON or wrapped in a Markdown code block.
+    /// Skills without a schema accept any response.
+    pub fn validate_output(&self, response: &str) -> Result<Value> {
+        let Some(schema) = self.output_schema() else {
+            return Ok(Value::String(response.to_string()));
+        };
+
+        let json = extract_json(response).ok_or_else(|| anyhow!("Response is not valid JSON"))?;
+
+        let validator = jsonschema::validator_for(schema)
+            .map_err(|e| anyhow!("Invalid output schema: {}", e))?;
+
+        let errors: Vec<String> = validator
+            .iter_errors(&json)
+            .map(|e| format!("{} (at '{}')", e, e.instance_path()))
+            .collect();
+
+        if errors.is_empty() {
+            Ok(json)
+        } else {
+            Err(anyhow!(
+                "Response does not match the output schema: {}",
+                errors.join("; ")
+            ))
+        }
+    }
+
     /// Generate a concise summary for LLM system prompt
     /// Format: "- {name}: {description}"
     pub fn to_summary(&self) -> String {
@@ -114,15 +243,22 @@ impl Skill {
     }
 }
 
+/// Modification time of `path`, if the filesystem reports one
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
 /// Parse skill content to extract frontmatter metadata and body
 fn parse_skill_content(content: &str) -> Result<(SkillMetadata, String)> {
     // Extract YAML frontmatter
     let frontmatter_re = Regex::new(r"^---\s*\n([\s\S]*?)\n---\s*\n([\s\S]*)$")
         .map_err(|e| anyhow!("Failed to compile regex: {}", e))?;
 
-    let captures = frontmatter_re
-        .captures(content)
-        .ok_or_else(|| anyhow!("No valid YAML frontmatter found"))?;
+    let captures = frontmatter_re.captures(content).ok_or(FrontmatterError {
+        line: Some(1),
+        column: None,
+        message: "expected YAML between `---` lines at the top of the file".to_string(),
+    })?;
 
     let yaml_str = captures
         .get(1)
@@ -132,11 +268,25 @@ fn parse_skill_content(content: &str) -> Result<(SkillMetadata, String)> {
     let body = captures.get(2).map(|m| m.as_str()).unwrap_or("");
 
     let metadata: SkillMetadata =
-        serde_yaml::from_str(yaml_str).with_context(|| "Failed to parse YAML frontmatter")?;
+        serde_yaml::from_str(yaml_str).map_err(|e| FrontmatterError::from_yaml(&e))?;
 
     Ok((metadata, body.to_string()))
 }
 
+/// Parse JSON from a response, allowing a surrounding Markdown code block
+fn extract_json(response: &str) -> Option<Value> {
+    let trimmed = response.trim();
+    if let Ok(json) = serde_json::from_str(trimmed) {
+        return Some(json);
+    }
+
+    let fenced = trimmed
+        .strip_prefix("```json")
+        .or_else(|| trimmed.strip_prefix("```"))?
+        .strip_suffix("```")?;
+    serde_json::from_str(fenced.trim()).ok()
+}
+
 /// Validate skill metadata according to Anthropic specification
 fn validate_metadata(metadata: &SkillMetadata) -> Result<()> {
     // Validate name
@@ -178,6 +328,17 @@ fn validate_metadata(metadata: &SkillMetadata) -> Result<()> {
         );
     }
 
+    // Reject schemas that can't be compiled up front rather than on activation
+    if let Some(schema) = &metadata.output_schema {
+        jsonschema::validator_for(schema).map_err(|e| {
+            anyhow!(
+                "Skill '{}' has an invalid output_schema: {}",
+                metadata.name,
+                e
+            )
+        })?;
+    }
+
     Ok(())
 }
 
@@ -206,18 +367,131 @@ This skill helps review code.
         assert!(body.contains("# Code Reviewer"));
     }
 
+    #[test]
+    fn test_frontmatter_errors() {
+        let error = |content: &str| {
+            parse_skill_content(content)
+                .unwrap_err()
+                .downcast::<FrontmatterError>()
+                .unwrap()
+        };
+
+        // Located in the file, not in the frontmatter
+        let e = error("---\nname: broken\ndescription: [unclosed\n---\nBody\n");
+        assert_eq!(e.line, Some(3));
+        assert!(!e.message.contains(" at line "), "{}", e.message);
+        assert!(e
+            .to_string()
+            .starts_with("Invalid frontmatter at line 3, column "));
+
+        let e = error("---\nname: no-description\n---\n");
+        assert_eq!((e.line, e.column), (Some(2), Some(1)));
+        assert!(
+            e.message.contains("missing field `description`"),
+            "{}",
+            e.message
+        );
+
+        let e = error("# No frontmatter\n");
+        assert_eq!((e.line, e.column), (Some(1), None));
+    }
+
+    #[test]
+    fn test_content_cache() {
+        let dir = std::env::temp_dir().join(format!("rustclaw-skill-cache-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let skill_file = dir.join("SKILL.md");
+        let write = |body: &str, modified: SystemTime| {
+            fs::write(
+                &skill_file,
+                format!("---\nname: cached\ndescription: A cached skill\n---\n\n{body}\n"),
+            )
+            .unwrap();
+            fs::File::options()
+                .write(true)
+                .open(&skill_file)
+                .unwrap()
+                .set_modified(modified)
+                .unwrap();
+        };
+        let first = SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1_000_000);
+        write("First version", first);
+
+        let mut skill = Skill::metadata_from_dir(&dir).unwrap();
+        assert!(skill.content.is_none());
+        skill.load_content().unwrap();
+        assert!(skill.content.as_deref().unwrap().contains("First version"));
+        assert_eq!(skill.modified, Some(first));
+
+        // An unchanged file is not read again
+        skill.content = Some("cached".to_string());
+        skill.load_content().unwrap();
+        assert_eq!(skill.content.as_deref(), Some("cached"));
+
+        // A modified file is reloaded
+        let second = first + std::time::Duration::from_secs(60);
+        write("Second version", second);
+        skill.load_content().unwrap();
+        assert!(skill.content.as_deref().unwrap().contains("Second version"));
+        assert_eq!(skill.modified, Some(second));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
     #[test]
     fn test_validate_metadata() {
         let valid = SkillMetadata {
             name: "valid-skill-name".to_string(),
             description: "A valid description".to_string(),
+            output_schema: None,
         };
         assert!(validate_metadata(&valid).is_ok());
 
         let invalid_name = SkillMetadata {
             name: "Invalid_Name".to_string(),
             description: "A description".to_string(),
+            output_schema: None,
         };
         assert!(validate_metadata(&invalid_name).is_err());
     }
+
+    #[test]
+    fn test_validate_output_against_schema() {
+        let content = r#"---
+name: release-notes
+description: Generate release notes as JSON.
+output_schema:
+  type: object
+  properties:
+    version:
+      type: string
+    changes:
+      type: array
+      items:
+        type: string
+  required: [version, changes]
+---
+
+Produce release notes.
+"#;
+
+        let (metadata, _) = parse_skill_content(content).unwrap();
+        assert!(validate_metadata(&metadata).is_ok());
+        let skill = Skill {
+            metadata,
+            path: PathBuf::from("release-notes"),
+            content: Some(content.to_string()),
+            modified: None,
+        };
+
+        assert_eq!(
+            skill.instructions().as_deref(),
+            Some("Produce release notes.")
+        );
+        assert!(skill
+            .validate_output("```json\n{\"version\": \"1.0\", \"changes\": [\"fix\"]}\n```")
+            .is_ok());
+        assert!(skill.validate_output("{\"version\": \"1.0\"}").is_err());
+        assert!(skill.validate_output("not json").is_err());
+    }
 }
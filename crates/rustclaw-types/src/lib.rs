@@ -4,6 +4,7 @@
 
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::path::PathBuf;
 use uuid::Uuid;
 
@@ -71,6 +72,37 @@ pub struct ImageContent {
     pub local_path: Option<PathBuf>,
 }
 
+/// Image produced while answering, to be sent to the user as a photo
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResponseImage {
+    /// Raw image bytes
+    pub data: Vec<u8>,
+    /// MIME type of the image (e.g. `image/png`)
+    pub mime_type: String,
+}
+
+/// File produced while answering, to be sent to the user as a document
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResponseDocument {
+    /// Raw file bytes
+    pub data: Vec<u8>,
+    /// Name the file is sent under
+    pub file_name: String,
+}
+
+/// One part of a multi-part assistant response
+#[derive(Debug, Clone, PartialEq)]
+pub enum ResponsePart {
+    Text(String),
+    Image(ResponseImage),
+    Document(ResponseDocument),
+    /// A point on the map, in degrees
+    Location {
+        latitude: f64,
+        longitude: f64,
+    },
+}
+
 /// Document content received from Telegram
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DocumentContent {
@@ -110,6 +142,16 @@ impl Message {
             timestamp: Utc::now(),
         }
     }
+
+    /// Role of the sender: the assistant's own replies are saved as sent by
+    /// the AI user (id 0), everything else by the user
+    pub fn role(&self) -> Role {
+        if self.sender.id == 0 {
+            Role::Assistant
+        } else {
+            Role::User
+        }
+    }
 }
 
 /// LLM Provider configuration
@@ -119,10 +161,29 @@ pub enum Provider {
         model: String,
         api_key: Option<String>,
         base_url: Option<String>,
+        /// Extra headers sent with every request (e.g. `HTTP-Referer` for OpenRouter)
+        headers: HashMap<String, String>,
     },
     Ollama {
         model: String,
         base_url: String,
+        /// API requests are sent to (see [`OllamaApi`])
+        api: OllamaApi,
+    },
+    /// Google Gemini via its OpenAI-compatible endpoint
+    Gemini {
+        /// Model name, e.g. `gemini-2.0-flash`
+        model: String,
+        api_key: String,
+    },
+    /// Azure OpenAI, where each model is served by a named deployment
+    AzureOpenAI {
+        /// Resource endpoint, e.g. `https://my-resource.openai.azure.com`
+        endpoint: String,
+        /// Deployment used when no other model is selected
+        deployment: String,
+        api_version: String,
+        api_key: String,
     },
 }
 
@@ -132,6 +193,7 @@ impl Default for Provider {
             model: "gpt-4o-mini".to_string(),
             api_key: None,
             base_url: None,
+            headers: HashMap::new(),
         }
     }
 }
@@ -142,6 +204,7 @@ impl Provider {
             model: model.to_string(),
             api_key: None,
             base_url: None,
+            headers: HashMap::new(),
         }
     }
 
@@ -150,6 +213,7 @@ impl Provider {
             model: model.to_string(),
             api_key: None,
             base_url: Some(base_url.to_string()),
+            headers: HashMap::new(),
         }
     }
 
@@ -158,6 +222,7 @@ impl Provider {
             model: model.to_string(),
             api_key: Some(api_key.to_string()),
             base_url: None,
+            headers: HashMap::new(),
         }
     }
 
@@ -166,17 +231,267 @@ impl Provider {
             model: model.to_string(),
             api_key: Some(api_key.to_string()),
             base_url: Some(base_url.to_string()),
+            headers: HashMap::new(),
+        }
+    }
+
+    /// Send extra headers with every request (OpenAI-compatible providers only)
+    pub fn with_headers(mut self, extra: HashMap<String, String>) -> Self {
+        if let Provider::OpenAI { headers, .. } = &mut self {
+            headers.extend(extra);
         }
+        self
     }
 
     pub fn ollama(model: &str, base_url: &str) -> Self {
         Provider::Ollama {
             model: model.to_string(),
             base_url: base_url.to_string(),
+            api: OllamaApi::Auto,
+        }
+    }
+
+    /// Select the API of an Ollama provider; ignored by other providers
+    pub fn with_ollama_api(mut self, selected: OllamaApi) -> Self {
+        if let Provider::Ollama { api, .. } = &mut self {
+            *api = selected;
+        }
+        self
+    }
+
+    pub fn gemini(model: &str, api_key: &str) -> Self {
+        Provider::Gemini {
+            model: model.to_string(),
+            api_key: api_key.to_string(),
+        }
+    }
+
+    pub fn azure_openai(
+        endpoint: &str,
+        deployment: &str,
+        api_version: &str,
+        api_key: &str,
+    ) -> Self {
+        Provider::AzureOpenAI {
+            endpoint: endpoint.trim_end_matches('/').to_string(),
+            deployment: deployment.to_string(),
+            api_version: api_version.to_string(),
+            api_key: api_key.to_string(),
         }
     }
 }
 
+// ============================================================================
+// Provider Configuration
+// ============================================================================
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct OpenAIConfig {
+    pub api_key: Option<String>,
+    pub model: String,
+    pub base_url: Option<String>,
+    /// Extra headers sent with every request (e.g. for OpenRouter or LiteLLM)
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct OllamaConfig {
+    pub base_url: String,
+    pub model: String,
+    #[serde(default)]
+    pub api: OllamaApi,
+}
+
+/// API of an Ollama server that chat requests are sent to
+///
+/// Ollama serves its native API under `/api` and an OpenAI-compatible one
+/// under `/v1`. Some versions only support tool calls on the native
+/// `/api/chat` endpoint.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OllamaApi {
+    /// OpenAI-compatible if `base_url` ends in `/v1`, native otherwise
+    #[default]
+    Auto,
+    /// Native `/api/chat`
+    Native,
+    /// OpenAI-compatible `/v1/chat/completions`
+    OpenAI,
+}
+
+impl OllamaApi {
+    /// The API used with `base_url`, resolving [`OllamaApi::Auto`]
+    pub fn resolve(self, base_url: &str) -> Self {
+        match self {
+            OllamaApi::Auto if base_url.trim_end_matches('/').ends_with("/v1") => OllamaApi::OpenAI,
+            OllamaApi::Auto => OllamaApi::Native,
+            api => api,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct GeminiConfig {
+    pub api_key: Option<String>,
+    #[serde(default = "default_gemini_model")]
+    pub model: String,
+}
+
+fn default_gemini_model() -> String {
+    "gemini-2.0-flash".to_string()
+}
+
+impl Default for GeminiConfig {
+    fn default() -> Self {
+        Self {
+            api_key: None,
+            model: default_gemini_model(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct AzureOpenAIConfig {
+    /// Resource endpoint, e.g. `https://my-resource.openai.azure.com`
+    #[serde(default)]
+    pub endpoint: String,
+    /// Deployment that serves the default model
+    #[serde(default)]
+    pub deployment: String,
+    #[serde(default = "default_azure_api_version")]
+    pub api_version: String,
+    pub api_key: Option<String>,
+}
+
+fn default_azure_api_version() -> String {
+    "2024-10-21".to_string()
+}
+
+impl Default for AzureOpenAIConfig {
+    fn default() -> Self {
+        Self {
+            endpoint: String::new(),
+            deployment: String::new(),
+            api_version: default_azure_api_version(),
+            api_key: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ProvidersConfig {
+    pub default: String,
+    /// Extra models users may pick per chat with `/model`
+    #[serde(default)]
+    pub allowed_models: Vec<String>,
+    /// Other providers users may pick per chat with `/provider`
+    #[serde(default)]
+    pub allowed_providers: Vec<String>,
+    pub openai: OpenAIConfig,
+    pub ollama: OllamaConfig,
+    #[serde(default)]
+    pub gemini: GeminiConfig,
+    #[serde(default)]
+    pub azure: AzureOpenAIConfig,
+}
+
+/// Why a [`Provider`] could not be built from [`ProvidersConfig`]
+#[derive(Debug, thiserror::Error)]
+pub enum ProviderConfigError {
+    #[error("Unknown provider '{0}'")]
+    UnknownProvider(String),
+    #[error("{0}")]
+    Missing(String),
+}
+
+impl Provider {
+    /// Build the provider selected by `providers.default`
+    ///
+    /// Empty API keys and base URLs count as unset, so config files can leave
+    /// them blank and supply them through environment variables instead.
+    pub fn from_config(config: &ProvidersConfig) -> Result<Self, ProviderConfigError> {
+        Self::from_config_named(config, &config.default)
+    }
+
+    /// Build the provider configured under `name` (e.g. `ollama`)
+    pub fn from_config_named(
+        config: &ProvidersConfig,
+        name: &str,
+    ) -> Result<Self, ProviderConfigError> {
+        match name {
+            "openai" => {
+                let openai = &config.openai;
+                let model = &openai.model;
+                let api_key = non_empty(&openai.api_key);
+                let base_url = non_empty(&openai.base_url);
+
+                // Use full constructor if we have API key and/or base URL
+                let provider = match (api_key, base_url) {
+                    (Some(key), Some(url)) => Provider::openai_full(model, key, url),
+                    (Some(key), None) => Provider::openai_with_api_key(model, key),
+                    (None, Some(url)) => Provider::openai_with_base_url(model, url),
+                    (None, None) => Provider::openai(model),
+                };
+                Ok(provider.with_headers(openai.headers.clone()))
+            }
+            "ollama" => Ok(
+                Provider::ollama(&config.ollama.model, &config.ollama.base_url)
+                    .with_ollama_api(config.ollama.api),
+            ),
+            "gemini" => {
+                let gemini = &config.gemini;
+                let api_key = non_empty(&gemini.api_key).ok_or_else(|| {
+                    ProviderConfigError::Missing(
+                        "Gemini provider selected but no API key configured. \
+                         Set GEMINI_API_KEY or providers.gemini.api_key"
+                            .to_string(),
+                    )
+                })?;
+                Ok(Provider::gemini(&gemini.model, api_key))
+            }
+            "azure" => {
+                let azure = &config.azure;
+                let api_key = non_empty(&azure.api_key).ok_or_else(|| {
+                    ProviderConfigError::Missing(
+                        "Azure provider selected but no API key configured. \
+                         Set AZURE_OPENAI_API_KEY or providers.azure.api_key"
+                            .to_string(),
+                    )
+                })?;
+                if azure.endpoint.is_empty() || azure.deployment.is_empty() {
+                    return Err(ProviderConfigError::Missing(
+                        "Azure provider selected but providers.azure.endpoint or \
+                         providers.azure.deployment is not set"
+                            .to_string(),
+                    ));
+                }
+                Ok(Provider::azure_openai(
+                    &azure.endpoint,
+                    &azure.deployment,
+                    &azure.api_version,
+                    api_key,
+                ))
+            }
+            other => Err(ProviderConfigError::UnknownProvider(other.to_string())),
+        }
+    }
+
+    /// Name of the provider's section in the config, e.g. `ollama`
+    pub fn config_name(&self) -> &'static str {
+        match self {
+            Provider::OpenAI { .. } => "openai",
+            Provider::Ollama { .. } => "ollama",
+            Provider::Gemini { .. } => "gemini",
+            Provider::AzureOpenAI { .. } => "azure",
+        }
+    }
+}
+
+fn non_empty(value: &Option<String>) -> Option<&String> {
+    value.as_ref().filter(|v| !v.is_empty())
+}
+
 // ============================================================================
 // Tool Calling Types (OpenAI-compatible)
 // ============================================================================
@@ -241,6 +556,26 @@ impl ToolCall {
 pub struct ToolResult {
     pub tool_call_id: String,
     pub output: String,
+    /// Name of the tool that produced the result
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_name: Option<String>,
+    /// Discrete items of a list-like result, each rendered on its own
+    ///
+    /// When set, the items are fed back to the model as separate content
+    /// parts; `output` still holds the whole result.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub items: Vec<String>,
+    /// Images, files and other parts the tool produced, delivered to the
+    /// user instead of the model
+    #[serde(skip)]
+    pub attachments: Vec<ResponsePart>,
+    /// Whether the tool failed rather than returning a result
+    ///
+    /// Set for errors the tool raised (including MCP results flagged
+    /// `isError`), unknown tools and unparsable arguments, not for results
+    /// that merely contain an `error` field.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub is_error: bool,
 }
 
 impl ToolResult {
@@ -248,6 +583,19 @@ impl ToolResult {
         Self {
             tool_call_id,
             output: output.into(),
+            tool_name: None,
+            items: Vec::new(),
+            attachments: Vec::new(),
+            is_error: false,
+        }
+    }
+
+    /// Result of a failed tool call, given to the model as `{"error": ...}`
+    pub fn error(tool_call_id: String, message: impl Into<String>) -> Self {
+        let output = serde_json::json!({ "error": message.into() }).to_string();
+        Self {
+            is_error: true,
+            ..Self::new(tool_call_id, output)
         }
     }
 
@@ -255,8 +603,35 @@ impl ToolResult {
         Self {
             tool_call_id,
             output: serde_json::to_string(value).unwrap_or_else(|_| "{}".to_string()),
+            tool_name: None,
+            items: Vec::new(),
+            attachments: Vec::new(),
+            is_error: false,
         }
     }
+
+    /// Whether the tool failed rather than returning a result
+    pub fn is_error(&self) -> bool {
+        self.is_error
+    }
+
+    /// Attach the name of the tool that produced the result
+    pub fn with_tool_name(mut self, name: impl Into<String>) -> Self {
+        self.tool_name = Some(name.into());
+        self
+    }
+
+    /// Attach the separately rendered items of a list-like result
+    pub fn with_items(mut self, items: Vec<String>) -> Self {
+        self.items = items;
+        self
+    }
+
+    /// Attach the parts the tool produced for the user
+    pub fn with_attachments(mut self, attachments: Vec<ResponsePart>) -> Self {
+        self.attachments = attachments;
+        self
+    }
 }
 
 /// Response from a completion that may include tool calls
@@ -265,6 +640,10 @@ pub struct CompletionResponse {
     pub content: Option<String>,
     pub tool_calls: Vec<ToolCall>,
     pub finish_reason: String,
+    /// Tokens billed for the request, if the API reported them
+    pub usage: TokenUsage,
+    /// Reasoning the model returned separately from its answer
+    pub reasoning: Option<String>,
 }
 
 impl CompletionResponse {
@@ -273,6 +652,8 @@ impl CompletionResponse {
             content: Some(content),
             tool_calls: vec![],
             finish_reason: "stop".to_string(),
+            usage: TokenUsage::default(),
+            reasoning: None,
         }
     }
 
@@ -281,12 +662,65 @@ impl CompletionResponse {
             content: None,
             tool_calls: calls,
             finish_reason: "tool_calls".to_string(),
+            usage: TokenUsage::default(),
+            reasoning: None,
         }
     }
 
     pub fn has_tool_calls(&self) -> bool {
         !self.tool_calls.is_empty()
     }
+
+    /// Whether the model stopped because it hit its output token limit
+    pub fn is_truncated(&self) -> bool {
+        self.finish_reason == "length"
+    }
+}
+
+/// Token counts reported by the API for one or more requests
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TokenUsage {
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+}
+
+impl TokenUsage {
+    pub fn new(prompt_tokens: u64, completion_tokens: u64) -> Self {
+        Self {
+            prompt_tokens,
+            completion_tokens,
+        }
+    }
+
+    pub fn total_tokens(&self) -> u64 {
+        self.prompt_tokens + self.completion_tokens
+    }
+}
+
+impl std::ops::AddAssign for TokenUsage {
+    fn add_assign(&mut self, other: Self) {
+        self.prompt_tokens += other.prompt_tokens;
+        self.completion_tokens += other.completion_tokens;
+    }
+}
+
+/// Price of a model in USD per million tokens
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct ModelPrice {
+    /// Price per million prompt (input) tokens
+    #[serde(default)]
+    pub input: f64,
+    /// Price per million completion (output) tokens
+    #[serde(default)]
+    pub output: f64,
+}
+
+impl ModelPrice {
+    /// Cost in USD of the given token usage
+    pub fn cost(&self, usage: &TokenUsage) -> f64 {
+        (usage.prompt_tokens as f64 * self.input + usage.completion_tokens as f64 * self.output)
+            / 1_000_000.0
+    }
 }
 
 /// Chat role for messages
@@ -299,6 +733,32 @@ pub enum Role {
     Tool,
 }
 
+impl Role {
+    /// Lowercase name of the role as used by the chat API
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::System => "system",
+            Role::User => "user",
+            Role::Assistant => "assistant",
+            Role::Tool => "tool",
+        }
+    }
+}
+
+impl std::str::FromStr for Role {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "system" => Ok(Role::System),
+            "user" => Ok(Role::User),
+            "assistant" => Ok(Role::Assistant),
+            "tool" => Ok(Role::Tool),
+            other => Err(format!("Unknown role: {}", other)),
+        }
+    }
+}
+
 /// A chat message for the API
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ChatMessage {
@@ -310,6 +770,9 @@ pub struct ChatMessage {
     pub tool_calls: Option<Vec<ToolCall>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub tool_call_id: Option<String>,
+    /// Separate content parts of a tool message; `content` holds them whole
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub parts: Vec<String>,
 }
 
 impl ChatMessage {
@@ -320,6 +783,7 @@ impl ChatMessage {
             name: None,
             tool_calls: None,
             tool_call_id: None,
+            parts: Vec::new(),
         }
     }
 
@@ -330,6 +794,7 @@ impl ChatMessage {
             name: None,
             tool_calls: None,
             tool_call_id: None,
+            parts: Vec::new(),
         }
     }
 
@@ -340,6 +805,7 @@ impl ChatMessage {
             name: None,
             tool_calls: None,
             tool_call_id: None,
+            parts: Vec::new(),
         }
     }
 
@@ -350,6 +816,7 @@ impl ChatMessage {
             name: None,
             tool_calls: Some(tool_calls),
             tool_call_id: None,
+            parts: Vec::new(),
         }
     }
 
@@ -360,6 +827,136 @@ impl ChatMessage {
             name: None,
             tool_calls: None,
             tool_call_id: Some(tool_call_id.into()),
+            parts: Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// Providers config with `default` selected and `extra` merged in
+    fn config(default: &str, extra: serde_json::Value) -> ProvidersConfig {
+        let mut config = json!({
+            "default": default,
+            "openai": { "model": "gpt-4o-mini" },
+            "ollama": { "base_url": "http://localhost:11434", "model": "llama3" },
+        });
+        for (key, value) in extra.as_object().unwrap() {
+            config[key] = value.clone();
+        }
+        serde_json::from_value(config).unwrap()
+    }
+
+    #[test]
+    fn test_openai_from_config() {
+        let provider = Provider::from_config(&config(
+            "openai",
+            json!({ "openai": {
+                "model": "gpt-4o",
+                "api_key": "sk-test",
+                "base_url": "",
+                "headers": { "HTTP-Referer": "https://example.com" },
+            } }),
+        ))
+        .unwrap();
+        let Provider::OpenAI {
+            model,
+            api_key,
+            base_url,
+            headers,
+        } = provider
+        else {
+            panic!("expected OpenAI, got {:?}", provider);
+        };
+        assert_eq!(model, "gpt-4o");
+        assert_eq!(api_key.as_deref(), Some("sk-test"));
+        // Empty values count as unset
+        assert_eq!(base_url, None);
+        assert_eq!(headers["HTTP-Referer"], "https://example.com");
+    }
+
+    #[test]
+    fn test_ollama_from_config() {
+        let provider = Provider::from_config(&config("ollama", json!({}))).unwrap();
+        assert!(matches!(
+            &provider,
+            Provider::Ollama { model, base_url, api: OllamaApi::Auto }
+                if model == "llama3" && base_url == "http://localhost:11434"
+        ));
+
+        let provider = Provider::from_config(&config(
+            "ollama",
+            json!({ "ollama": { "base_url": "http://gpu:11434", "model": "qwen3", "api": "openai" } }),
+        ))
+        .unwrap();
+        assert!(matches!(
+            provider,
+            Provider::Ollama {
+                api: OllamaApi::OpenAI,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn test_gemini_from_config() {
+        let provider =
+            Provider::from_config(&config("gemini", json!({ "gemini": { "api_key": "key" } })))
+                .unwrap();
+        assert!(matches!(
+            &provider,
+            Provider::Gemini { model, api_key } if model == "gemini-2.0-flash" && api_key == "key"
+        ));
+
+        for gemini in [json!({}), json!({ "api_key": "" })] {
+            let error = Provider::from_config(&config("gemini", json!({ "gemini": gemini })));
+            assert!(
+                matches!(error, Err(ProviderConfigError::Missing(message)) if message.contains("GEMINI_API_KEY"))
+            );
         }
     }
+
+    #[test]
+    fn test_azure_from_config() {
+        let azure = json!({
+            "endpoint": "https://my-resource.openai.azure.com",
+            "deployment": "gpt-4o",
+            "api_key": "key",
+        });
+        let provider =
+            Provider::from_config(&config("azure", json!({ "azure": azure.clone() }))).unwrap();
+        assert!(matches!(
+            &provider,
+            Provider::AzureOpenAI { deployment, api_version, .. }
+                if deployment == "gpt-4o" && api_version == "2024-10-21"
+        ));
+
+        let mut no_key = azure.clone();
+        no_key["api_key"] = json!("");
+        let error = Provider::from_config(&config("azure", json!({ "azure": no_key })));
+        assert!(
+            matches!(error, Err(ProviderConfigError::Missing(message)) if message.contains("AZURE_OPENAI_API_KEY"))
+        );
+
+        let mut no_deployment = azure;
+        no_deployment["deployment"] = json!("");
+        let error = Provider::from_config(&config("azure", json!({ "azure": no_deployment })));
+        assert!(matches!(error, Err(ProviderConfigError::Missing(_))));
+    }
+
+    #[test]
+    fn test_unknown_provider() {
+        let error = Provider::from_config(&config("anthropic", json!({})));
+        assert!(
+            matches!(error, Err(ProviderConfigError::UnknownProvider(name)) if name == "anthropic")
+        );
+
+        // Other providers are built by name, whatever the default
+        let config = config("openai", json!({}));
+        let provider = Provider::from_config_named(&config, "ollama").unwrap();
+        assert_eq!(provider.config_name(), "ollama");
+    }
 }